@@ -0,0 +1,339 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::Serialize;
+
+use crate::traits::StatusReport;
+
+/// A numeric `StatusReport` field that alert rules can watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StatusField {
+    OpenPositions,
+    RealizedPnl,
+    UnrealizedPnl,
+    TotalPnl,
+}
+
+impl StatusField {
+    /// The field's value in `report`; `TotalPnl` is realized plus unrealized
+    pub fn value(self, report: &StatusReport) -> f64 {
+        match self {
+            StatusField::OpenPositions => report.open_positions as f64,
+            StatusField::RealizedPnl => report.realized_pnl,
+            StatusField::UnrealizedPnl => report.unrealized_pnl,
+            StatusField::TotalPnl => report.realized_pnl + report.unrealized_pnl,
+        }
+    }
+}
+
+impl fmt::Display for StatusField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StatusField::OpenPositions => "open_positions",
+            StatusField::RealizedPnl => "realized_pnl",
+            StatusField::UnrealizedPnl => "unrealized_pnl",
+            StatusField::TotalPnl => "total_pnl",
+        })
+    }
+}
+
+impl FromStr for StatusField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "open_positions" => Ok(StatusField::OpenPositions),
+            "realized_pnl" => Ok(StatusField::RealizedPnl),
+            "unrealized_pnl" => Ok(StatusField::UnrealizedPnl),
+            "total_pnl" => Ok(StatusField::TotalPnl),
+            _ => Err(format!(
+                "Unknown field '{}'. Use: open_positions, realized_pnl, unrealized_pnl or \
+                 total_pnl",
+                s
+            )),
+        }
+    }
+}
+
+/// How an alert rule compares a field with its threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// Whether `left` compares to `right` this way
+    pub fn holds(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        })
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessOrEqual),
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterOrEqual),
+            "=" | "==" => Ok(Comparison::Equal),
+            "!=" => Ok(Comparison::NotEqual),
+            _ => Err(format!(
+                "Unknown operator '{}'. Use: <, <=, >, >=, == or !=",
+                s
+            )),
+        }
+    }
+}
+
+/// What an alert rule watches: a `StatusReport` field, or any other name
+/// for one of the strategy's `status_columns`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AlertField {
+    Status(StatusField),
+    Metric(String),
+}
+
+impl AlertField {
+    /// The watched value; a metric missing from `columns` has none
+    pub fn value(&self, report: &StatusReport, columns: &BTreeMap<String, f64>) -> Option<f64> {
+        match self {
+            AlertField::Status(field) => Some(field.value(report)),
+            AlertField::Metric(metric) => columns.get(metric).copied(),
+        }
+    }
+}
+
+impl fmt::Display for AlertField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertField::Status(field) => write!(f, "{}", field),
+            AlertField::Metric(metric) => f.write_str(metric),
+        }
+    }
+}
+
+impl FromStr for AlertField {
+    type Err = String;
+
+    /// Status field names are matched case-insensitively; anything else
+    /// names a metric exactly
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map(AlertField::Status)
+            .unwrap_or_else(|_| AlertField::Metric(s.to_string())))
+    }
+}
+
+/// An alert such as `unrealized_pnl < -100` or `drawdown > 5`, checked
+/// against the status report and the strategy's metrics after every tick
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertRule {
+    pub field: AlertField,
+    pub comparison: Comparison,
+    pub threshold: f64,
+}
+
+impl AlertRule {
+    /// The watched value when the rule fires for `report` and `columns`
+    pub fn breach(&self, report: &StatusReport, columns: &BTreeMap<String, f64>) -> Option<f64> {
+        self.field
+            .value(report, columns)
+            .filter(|value| self.comparison.holds(*value, self.threshold))
+    }
+
+    /// Whether the rule fires for `report` and `columns`
+    pub fn matches(&self, report: &StatusReport, columns: &BTreeMap<String, f64>) -> bool {
+        self.breach(report, columns).is_some()
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.field, self.comparison, self.threshold)
+    }
+}
+
+impl AlertRule {
+    fn from_parts(field: &str, comparison: &str, threshold: &str) -> Result<Self, String> {
+        if field.is_empty() {
+            return Err("Field name must not be empty".to_string());
+        }
+        let threshold = threshold
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("'{}' is not a number", threshold))?;
+
+        Ok(AlertRule {
+            field: field.parse()?,
+            comparison: comparison.parse()?,
+            threshold,
+        })
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    /// Parse `FIELD OP VALUE`; the parts must be separated by whitespace
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let [field, comparison, threshold] = parts[..] else {
+            return Err(format!(
+                "Expected FIELD OP VALUE, e.g. 'unrealized_pnl < -100', got '{}'",
+                s.trim()
+            ));
+        };
+        AlertRule::from_parts(field, comparison, threshold)
+    }
+}
+
+/// Parse a `/threshold` argument, `METRIC,OP,VALUE` such as `drawdown,>,5`,
+/// into the alert rule that watches it
+pub fn parse_threshold(input: &str) -> Result<AlertRule, String> {
+    let parts: Vec<&str> = input.split(',').map(str::trim).collect();
+    let [metric, comparison, threshold] = parts[..] else {
+        return Err(format!(
+            "Expected METRIC,OP,VALUE, e.g. 'drawdown,>,5', got '{}'",
+            input.trim()
+        ));
+    };
+    AlertRule::from_parts(metric, comparison, threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(realized_pnl: f64, unrealized_pnl: f64) -> StatusReport {
+        StatusReport {
+            open_positions: 2,
+            realized_pnl,
+            unrealized_pnl,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn alert_rules_parse_field_operator_and_value() {
+        let rule: AlertRule = " Unrealized_PnL  <  -100 ".parse().unwrap();
+        assert_eq!(
+            rule,
+            AlertRule {
+                field: AlertField::Status(StatusField::UnrealizedPnl),
+                comparison: Comparison::Less,
+                threshold: -100.0,
+            }
+        );
+        assert_eq!(rule.to_string(), "unrealized_pnl < -100");
+        assert_eq!(
+            "total_pnl = 0".parse::<AlertRule>().unwrap().comparison,
+            Comparison::Equal
+        );
+    }
+
+    #[test]
+    fn alert_rules_reject_malformed_input() {
+        assert!("unrealized_pnl<-100"
+            .parse::<AlertRule>()
+            .unwrap_err()
+            .starts_with("Expected FIELD OP VALUE"));
+        assert!("total_pnl ~ 5"
+            .parse::<AlertRule>()
+            .unwrap_err()
+            .starts_with("Unknown operator '~'"));
+        assert_eq!(
+            "total_pnl < NaN".parse::<AlertRule>(),
+            Err("'NaN' is not a number".to_string())
+        );
+    }
+
+    #[test]
+    fn thresholds_parse_metric_operator_and_value() {
+        let rule = parse_threshold(" drawdown , >= , 5.5 ").unwrap();
+        assert_eq!(
+            rule,
+            AlertRule {
+                field: AlertField::Metric("drawdown".to_string()),
+                comparison: Comparison::GreaterOrEqual,
+                threshold: 5.5,
+            }
+        );
+        assert_eq!(rule, "drawdown >= 5.5".parse().unwrap());
+
+        assert!(parse_threshold("drawdown > 5")
+            .unwrap_err()
+            .starts_with("Expected METRIC,OP,VALUE"));
+        assert_eq!(
+            parse_threshold(",>,5"),
+            Err("Field name must not be empty".to_string())
+        );
+        assert_eq!(
+            parse_threshold("drawdown,>,lots"),
+            Err("'lots' is not a number".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_fields_name_strategy_metrics() {
+        let rule: AlertRule = "Drawdown > 5".parse().unwrap();
+        assert_eq!(rule.field, AlertField::Metric("Drawdown".to_string()));
+        assert_eq!(rule.to_string(), "Drawdown > 5");
+        assert_eq!(
+            "TOTAL_PNL < 0".parse::<AlertRule>().unwrap().field,
+            AlertField::Status(StatusField::TotalPnl)
+        );
+    }
+
+    #[test]
+    fn alert_rules_match_against_the_report() {
+        let none = BTreeMap::new();
+        let losing: AlertRule = "unrealized_pnl < -100".parse().unwrap();
+        assert!(losing.matches(&report(0.0, -150.0), &none));
+        assert!(!losing.matches(&report(0.0, -100.0), &none));
+
+        let total: AlertRule = "total_pnl >= 10".parse().unwrap();
+        assert!(total.matches(&report(15.0, -5.0), &none));
+        assert!(!total.matches(&report(5.0, 4.0), &none));
+
+        let positions: AlertRule = "open_positions != 2".parse().unwrap();
+        assert!(!positions.matches(&report(0.0, 0.0), &none));
+    }
+
+    #[test]
+    fn metric_rules_match_against_the_strategys_columns() {
+        let rule: AlertRule = "drawdown > 5".parse().unwrap();
+        let columns = |drawdown: f64| BTreeMap::from([("drawdown".to_string(), drawdown)]);
+
+        assert_eq!(rule.breach(&report(0.0, 0.0), &columns(7.5)), Some(7.5));
+        assert_eq!(rule.breach(&report(0.0, 0.0), &columns(5.0)), None);
+        // A metric the strategy stopped reporting never fires
+        assert_eq!(rule.breach(&report(0.0, 0.0), &BTreeMap::new()), None);
+    }
+}
@@ -1,765 +1,16150 @@
-use std::{error::Error, path::PathBuf, sync::Arc};
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    error::Error,
+    future::{Future, IntoFuture},
+    io,
+    panic::AssertUnwindSafe,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Once,
+    },
+};
 
-use prettytable::{row, Table};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+use futures::FutureExt;
+use prettytable::{row, Cell, Row, Table};
+use serde::Serialize;
 use teloxide::{
+    net::Download,
     prelude::*,
-    types::{ChatId, ParseMode},
-    utils::command::BotCommands,
+    types::{ChatId, Document, InputFile, ParseMode, ReactionType, ReplyParameters},
+    utils::{command::BotCommands, html, markdown},
+    RequestError,
 };
 use tokio::{
-    sync::{mpsc, oneshot, Mutex},
-    time::Duration,
+    sync::{broadcast, mpsc, oneshot, Mutex, Semaphore},
+    time::{Duration, Instant},
 };
+use tokio_util::sync::CancellationToken;
 
 /// Requests that can be sent to the live trading bot runner.
 pub enum BotRequest {
-    GetStatus(oneshot::Sender<Result<String, String>>),
+    GetStatus(oneshot::Sender<Result<(StatusReport, String), String>>),
+    GetPositions(oneshot::Sender<Result<Vec<PositionInfo>, String>>),
+    GetOrders(oneshot::Sender<Result<Vec<OrderInfo>, String>>),
+    /// Switch the strategy to another API endpoint
+    SetEndpoint(String, oneshot::Sender<Result<(), String>>),
+    /// Cancel every open order, answering with how many were cancelled
+    CancelAll(oneshot::Sender<Result<usize, String>>),
+    SetInterval(u64, oneshot::Sender<Result<(), String>>),
+    GetLastError(oneshot::Sender<Result<Option<ErrorRecord>, String>>),
+    GetLastPanic(oneshot::Sender<Result<Option<PanicRecord>, String>>),
+    GetCapabilities(oneshot::Sender<Result<Capabilities, String>>),
+    GetAccount(oneshot::Sender<Result<AccountInfo, String>>),
+    GetStatusColumns(oneshot::Sender<Result<BTreeMap<String, f64>, String>>),
+    GetRiskParams(oneshot::Sender<Result<Vec<RiskParam>, String>>),
+    /// Measure the exchange API latency
+    PingExchange(oneshot::Sender<Result<Duration, String>>),
+    /// Change a risk parameter the strategy declares
+    SetRiskParam(String, f64, oneshot::Sender<Result<(), String>>),
+    /// Run one dry-run tick against recorded data
+    Replay(MarketData, oneshot::Sender<Result<Option<String>, String>>),
+    /// The in-memory symbols used instead of the file while `/persist` is off
+    GetSymbolOverride(oneshot::Sender<Result<Option<Vec<SymbolConfig>>, String>>),
+    SetSymbolOverride(
+        Option<Vec<SymbolConfig>>,
+        oneshot::Sender<Result<(), String>>,
+    ),
+    /// Receive the events the runner's bot publishes from now on
+    Subscribe(oneshot::Sender<Result<broadcast::Receiver<BotEvent>, String>>),
+}
+
+/// The most recent error seen by the runner
+#[derive(Debug, Clone)]
+pub struct ErrorRecord {
+    pub at: DateTime<Utc>,
+    pub message: String,
+}
+
+/// A panic caught while running the strategy
+#[derive(Debug, Clone)]
+pub struct PanicRecord {
+    pub at: DateTime<Utc>,
+    pub message: String,
+    /// Only captured when `RUST_BACKTRACE` (or `RUST_LIB_BACKTRACE`) enables it
+    pub backtrace: Option<String>,
+}
+
+impl PanicRecord {
+    fn from_payload(payload: Box<dyn Any + Send>, at: DateTime<Utc>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic with a non-string payload".to_string());
+        Self {
+            at,
+            message,
+            backtrace: PANIC_BACKTRACE.with(|slot| slot.borrow_mut().take()),
+        }
+    }
+}
+
+thread_local! {
+    static PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Keep the backtrace of the latest panic on each thread so a caught panic can
+/// report it. Earlier hooks still run afterwards.
+fn install_panic_backtrace_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::capture();
+            let captured =
+                (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string());
+            PANIC_BACKTRACE.with(|slot| *slot.borrow_mut() = captured);
+            previous(info);
+        }));
+    });
+}
+
+fn backtrace_capture_enabled() -> bool {
+    Backtrace::capture().status() == BacktraceStatus::Captured
 }
 
 use crate::{
-    error::BotError,
-    traits::{SymbolConfig, TradingBot},
+    alerts::{parse_threshold, AlertField, AlertRule},
+    error::{classify_error, describe_error, BotError, ErrorClass},
+    schedule::{
+        format_duration, parse_duration, parse_level_schedule, parse_time, render_schedule_info,
+        resolve_level, LevelWindow, TimeWindow,
+    },
+    traits::{
+        validate_import_entry, AccountInfo, Capabilities, MarketData, OrderInfo, PositionInfo,
+        RiskParam, StatusReport, SymbolConfig, TradingBot,
+    },
 };
 
+/// Where the handler and runner read the current time from. Tests swap in a
+/// fake clock to drive time-based behavior without waiting.
 #[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>);
+
+impl Clock {
+    /// A clock reading the time from `now`
+    pub fn new(now: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        Clock(Arc::new(now))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    /// The system clock
+    fn default() -> Self {
+        Clock::new(Utc::now)
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct BotState {
     pub is_running: bool,
     pub notification_level: NotificationLevel,
     pub config_path: Option<String>,
     pub interval_seconds: Option<u64>,
+    pub send_timeout_secs: u64,
+    pub max_positions: Option<usize>,
+    pub notification_targets: Vec<ChatId>,
+    /// Set once any chat has been a notification target, so a chat that
+    /// unsubscribes is not claimed again as the default target
+    pub targets_configured: bool,
+    /// Set once a stop confirmation has been sent, so the runner and the
+    /// command handler don't both announce the same stop
+    pub stop_announced: bool,
+    /// Set while the runner idles after a breaker trip, so /startbot resumes
+    /// ticking instead of leaving the runner to exit
+    pub held_by_breaker: bool,
+    /// Buffer non-critical notifications and send them as one summary per
+    /// tick
+    pub summary_mode: bool,
+    /// The current tick's buffered notifications, sent by
+    /// `flush_notification_summary`
+    pub summary_buffer: Vec<(ChatId, NotificationLevel, String)>,
+    /// Deliver notifications without a sound on the recipient's device
+    pub silent_notifications: bool,
+    /// Time-of-day overrides for `notification_level`, evaluated in
+    /// `time_zone`
+    pub level_schedule: Vec<LevelWindow>,
+    /// How many symbols a strategy may process in parallel per tick, between
+    /// 1 and `MAX_STRATEGY_CONCURRENCY`
+    pub strategy_concurrency: usize,
+    /// How many notification sends may run at once across all chats, between
+    /// 1 and `MAX_SEND_CONCURRENCY`
+    pub send_concurrency: usize,
+    /// Permits for `send_concurrency` and the limit they were sized for
+    #[serde(skip)]
+    send_permits: Option<(usize, Arc<Semaphore>)>,
+    pub notification_stats: NotificationStats,
+    /// Symbol edits made after /stage, written on /apply
+    pub staged_symbols: Option<Vec<SymbolConfig>>,
+    pub staged_changes: usize,
+    /// Skip strategy execution entirely while the runner keeps serving
+    /// requests
+    pub kill_switch: bool,
+    /// Prepended to every notification and reply, e.g. `[PROD]`, so bots
+    /// sharing a channel can be told apart
+    pub instance_tag: Option<String>,
+    /// Write symbol edits to the configuration file; when off they only
+    /// change the runner's in-memory copy
+    pub persist_changes: bool,
+    pub unsaved_changes: bool,
+    /// Identical commands from the same chat within this window are ignored;
+    /// destructive commands wait longer. 0 disables the cooldown.
+    pub command_cooldown_secs: u64,
+    /// How many configuration backups to keep; older ones are deleted after
+    /// each new backup
+    pub backup_retention: usize,
+    /// Whether /stopbot waits for the running tick or cancels it
+    pub stop_mode: StopMode,
+    /// How /startbot and /stopbot confirm that the bot started or stopped
+    pub confirm_style: ConfirmStyle,
+    /// Cancelled when an immediate stop interrupts the running tick.
+    /// Long-running strategies may also watch it to stop cooperatively.
+    #[serde(skip)]
+    pub tick_cancel: Option<CancellationToken>,
+    /// How long after a start only `Critical` notifications are sent
+    pub warmup: Duration,
+    pub warmup_until: Option<DateTime<Utc>>,
+    /// When the runner expects its next strategy tick
+    pub next_tick_at: Option<DateTime<Utc>>,
+    /// Columns shown by /symbols, in order
+    pub symbol_columns: Vec<SymbolColumn>,
+    /// Internal events for programmatic consumers; see
+    /// `TelegramBotHandler::subscribe`
+    #[serde(skip)]
+    events: broadcast::Sender<BotEvent>,
+    /// Where each setting `/effectiveconfig` reports was last set from;
+    /// settings missing here have not been set since the state was created
+    #[serde(skip)]
+    setting_sources: HashMap<&'static str, SettingSource>,
+    /// Strategies must not place real orders while this is set
+    pub dry_run: bool,
+    /// Directory `/replay` reads market data files from; replays are
+    /// disabled while it is unset
+    pub replay_dir: Option<String>,
+    /// Chats allowed to use the bot and what they may do. Empty means every
+    /// chat is an admin.
+    pub chat_roles: HashMap<ChatId, Role>,
+    /// Offset used for the level schedule and the times the bot shows
+    #[serde(serialize_with = "serialize_display")]
+    pub time_zone: FixedOffset,
+    /// Outside this daily window the runner skips strategy ticks
+    pub trading_window: Option<TimeWindow>,
+    pub circuit_breaker: CircuitBreaker,
+    /// Paces notification sends to each chat
+    pub rate_limiter: RateLimiter,
+    /// Until this time no notifications are sent, while trading continues
+    pub quiet_until: Option<DateTime<Utc>>,
+    /// When the runner last finished a tick without stalling; deliberately
+    /// skipped ticks (kill switch, trading window) count too
+    pub last_successful_tick: Option<DateTime<Utc>>,
+    /// Intervals without a successful tick before the watchdog alerts; 0,
+    /// the default, disables it
+    pub watchdog_intervals: u64,
+    /// Whether the watchdog has already alerted about the current stall
+    pub watchdog_alerted: bool,
+    /// Whether /status replies also go to every notification target
+    pub status_broadcast: bool,
+    /// Rules checked against the status report and the strategy's metrics
+    /// after every tick
+    pub alert_rules: Vec<AlertRule>,
+    /// Rules that matched at the last check; they alert again only after
+    /// they stop matching
+    pub firing_alerts: Vec<AlertRule>,
+    /// Longest message sent in one piece; longer text is split into chunks.
+    /// Lower it for relays with a smaller limit than Telegram's.
+    pub max_message_length: usize,
+    /// Notification counts for the tick in progress, if any
+    pub tick_notifications: Option<TickNotifications>,
+    /// Notification counts for the last completed tick
+    pub last_tick_notifications: Option<TickNotifications>,
+    /// Most capital the strategy may deploy between starts; see
+    /// `check_budget`
+    pub session_budget: Option<f64>,
+    /// Capital in open entries since the bot last started: added by
+    /// `check_budget`, given back by `release_budget`
+    pub deployed_capital: f64,
+    /// Status reports saved by `/snapshot`, by label
+    pub status_snapshots: HashMap<String, StatusSnapshot>,
+    /// Local time of day at which the runner backs up the config file
+    pub auto_backup_at: Option<NaiveTime>,
+    /// Local date of the last automatic backup
+    pub last_auto_backup: Option<NaiveDate>,
+    /// Prefix each notification with the time it was sent, in `time_zone`
+    pub timestamp_notifications: bool,
+    /// Report errors with their full chain of causes instead of one line
+    pub verbose_errors: bool,
+    /// Chats that joined the notification targets with /subscribe; they
+    /// only receive `Critical` notifications
+    pub subscribers: Vec<ChatId>,
+    /// Notifications raised per symbol since the runner started, filtered
+    /// or not; see `send_symbol_notification`
+    pub symbol_notifications: HashMap<String, u64>,
+    /// Consecutive ticks on which each symbol had a trade error; see
+    /// `TradingBot::symbol_errors`
+    pub symbol_errors: HashMap<String, usize>,
+    /// Consecutive errors after which a symbol is disabled (0 never does)
+    pub symbol_error_limit: usize,
+    /// Symbols shown per `/symbols` page
+    pub symbols_page_size: usize,
+    /// The latest notifications that passed the level filter, oldest first;
+    /// see `/replaynotify`
+    pub notification_history: VecDeque<NotificationRecord>,
+    /// Most entries per day; see `check_trade_cap` (0 means no cap)
+    pub max_daily_trades: usize,
+    /// UTC hour at which the daily trade count starts over
+    pub trade_reset_hour: u32,
+    /// Entries counted since `trade_day` began
+    pub daily_trades: usize,
+    /// When the day `daily_trades` counts began
+    pub trade_day: Option<DateTime<Utc>>,
+    /// How often a failed strategy execution is retried within the same tick
+    /// before the failure counts towards a restart
+    pub strategy_retries: u32,
+    /// Pause between those retries
+    pub strategy_retry_delay: Duration,
+    /// Quote currency, e.g. `USDT`, that `/symbols` is limited to
+    pub quote_filter: Option<String>,
+    /// A non-critical notification identical to one sent to the same chat
+    /// within this window is dropped; zero disables deduplication
+    pub dedup_window: Duration,
+    /// When each chat last received each deduplicated message
+    #[serde(skip)]
+    pub dedup_sent: HashMap<(ChatId, String), DateTime<Utc>>,
+    /// Level of the progress messages sent while the runner restarts a
+    /// failed strategy; the failure itself is always reported
+    pub restart_level: NotificationLevel,
+    /// Receives a broadcast notification only when it could not be delivered
+    /// to any of the notification targets. Notifications sent to a single
+    /// chat with `send_notification` never fall back.
+    pub fallback_chat: Option<ChatId>,
+    /// Total PnL after each successful tick, oldest first; see `/pnlchart`
+    pub pnl_samples: VecDeque<f64>,
+    /// Source of the current time for the handler and runner
+    #[serde(skip)]
+    pub clock: Clock,
 }
 
-/// Notification levels for the Telegram bot
-#[derive(Clone, PartialEq, Debug)]
-pub enum NotificationLevel {
-    All,       // Send all messages
-    Important, // Only important updates and errors
-    Critical,  // Only critical errors and trade executions
-    None,      // No messages
+/// Schemes `/endpoint` accepts
+const ENDPOINT_SCHEMES: [&str; 4] = ["http", "https", "ws", "wss"];
+
+/// Check that `url` looks like an absolute http(s) or ws(s) URL with a host
+pub fn validate_endpoint(url: &str) -> Result<(), String> {
+    let invalid = || {
+        format!(
+            "Invalid endpoint '{}'. Use an http(s):// or ws(s):// URL, e.g. \
+             https://testnet.example.com",
+            url
+        )
+    };
+    let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+    if !ENDPOINT_SCHEMES.contains(&scheme.to_lowercase().as_str()) {
+        return Err(invalid());
+    }
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if host.is_empty() || host.starts_with(':') || url.chars().any(char::is_whitespace) {
+        return Err(invalid());
+    }
+    Ok(())
 }
 
-impl BotState {
-    pub fn new() -> Self {
-        Self::default()
+/// Shortest strategy interval `/setinterval` accepts
+pub const MIN_INTERVAL_SECS: u64 = 5;
+
+/// Parse a `/setinterval` value such as `3600`, `30m` or `1h` into seconds
+pub fn parse_interval(input: &str) -> Result<u64, String> {
+    let secs = parse_duration(input)?.as_secs();
+    if secs < MIN_INTERVAL_SECS {
+        return Err(format!(
+            "Interval must be at least {} seconds.",
+            MIN_INTERVAL_SECS
+        ));
     }
+    Ok(secs)
 }
 
-impl Default for BotState {
-    fn default() -> Self {
-        Self {
-            is_running: false,
-            notification_level: NotificationLevel::Important,
-            config_path: Some("symbols_config.json".to_string()),
-            interval_seconds: Some(300),
+/// Object keys whose values `state_json` replaces with `REDACTED`
+const SENSITIVE_KEYS: [&str; 4] = ["token", "secret", "password", "api_key"];
+const REDACTED: &str = "[redacted]";
+
+/// `state` as pretty JSON for `/state`, with the values of keys that look
+/// sensitive redacted
+pub fn state_json(state: &BotState) -> Result<String, serde_json::Error> {
+    let mut value = serde_json::to_value(state)?;
+    redact_sensitive(&mut value);
+    serde_json::to_string_pretty(&value)
+}
+
+/// Replace the value of every object key, at any depth, that contains one of
+/// `SENSITIVE_KEYS`
+fn redact_sensitive(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SENSITIVE_KEYS
+                    .iter()
+                    .any(|sensitive| key.contains(sensitive))
+                {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_sensitive(value);
+                }
+            }
         }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_sensitive),
+        _ => {}
     }
 }
 
-#[derive(Debug, BotCommands, Clone)]
-#[command(
-    rename_rule = "lowercase",
-    description = "These commands are supported:"
-)]
-pub enum Command {
-    #[command(description = "display this text.")]
-    Help,
-    #[command(description = "start the trading bot.")]
-    StartBot,
-    #[command(description = "stop the trading bot.")]
-    StopBot,
-    #[command(description = "check bot status.")]
-    Status,
-    #[command(description = "set notification level (all/important/critical/none)")]
-    Notify(String),
-    #[command(description = "request immediate status update")]
-    Update,
-    #[command(description = "display the contents of symbols configuration.")]
-    Symbols,
-    #[command(description = "add a new symbol to configuration.")]
-    AddSymbol(String), // Pass a single JSON string or delimited string
-    #[command(description = "remove a symbol from configuration.")]
-    RemoveSymbol(String),
+/// Serialize a field by its `Display` form, for types without serde support
+fn serialize_display<T: std::fmt::Display, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
 }
 
-pub struct TelegramBotHandler {
-    request_tx: mpsc::UnboundedSender<BotRequest>,
+/// Stops the bot after repeated strategy failures until someone
+/// acknowledges it with /clearbreaker
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CircuitBreaker {
+    pub consecutive_failures: u32,
+    /// Failures in a row that trip the breaker; 0, the default, disables it
+    pub threshold: u32,
+    pub tripped: bool,
+    pub last_ack: Option<BreakerAck>,
+    /// How long after tripping /startbot stays refused, even once cleared
+    pub cooldown: Duration,
+    pub tripped_at: Option<DateTime<Utc>>,
 }
 
-impl TelegramBotHandler {
-    pub fn new() -> (Self, mpsc::UnboundedReceiver<BotRequest>) {
-        let (request_tx, request_rx) = mpsc::unbounded_channel();
-        (Self { request_tx }, request_rx)
+/// Who cleared a tripped breaker, and when
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BreakerAck {
+    pub by: String,
+    pub at: DateTime<Utc>,
+}
+
+impl CircuitBreaker {
+    /// Count a failed tick at `now`, returning whether this failure tripped
+    /// the breaker
+    pub fn record_failure(&mut self, now: DateTime<Utc>) -> bool {
+        self.consecutive_failures += 1;
+        self.threshold > 0 && self.consecutive_failures >= self.threshold && self.trip(now)
     }
 
-    async fn request_status(&self) -> Result<String, String> {
-        let (tx, rx) = oneshot::channel();
-        self.request_tx
-            .send(BotRequest::GetStatus(tx))
-            .map_err(|_| "Bot runner unavailable".to_string())?;
+    /// Trip the breaker at `now`, returning false if it already was
+    pub fn trip(&mut self, now: DateTime<Utc>) -> bool {
+        if self.tripped {
+            return false;
+        }
+        self.tripped = true;
+        self.tripped_at = Some(now);
+        true
+    }
 
-        rx.await
-            .map_err(|_| "Bot runner dropped status channel".to_string())?
+    /// Time left at `now` before the bot may be started after the last trip
+    pub fn cooldown_remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let tripped_at = self.tripped_at?;
+        let cooldown = chrono::Duration::from_std(self.cooldown).ok()?;
+        (tripped_at + cooldown - now)
+            .to_std()
+            .ok()
+            .filter(|remaining| !remaining.is_zero())
     }
 
-    /// Handle incoming Telegram commands
-    pub async fn handle_command(
-        &mut self,
-        bot: Bot,
-        msg: Message,
-        cmd: Command,
-        bot_state: Arc<Mutex<BotState>>,
-    ) -> ResponseResult<()> {
-        match cmd {
-            Command::Help => {
-                bot.send_message(msg.chat.id, Command::descriptions().to_string())
-                    .await?;
-            }
-            Command::StartBot => {
-                let mut state = bot_state.lock().await;
-                if !state.is_running {
-                    state.is_running = true;
-                    drop(state);
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
 
-                    bot.send_message(msg.chat.id, "Trading bot started!")
-                        .await?;
-                } else {
-                    bot.send_message(msg.chat.id, "Bot is already running.")
-                        .await?;
-                }
-            }
-            Command::StopBot => {
-                let mut state = bot_state.lock().await;
-                if state.is_running {
-                    state.is_running = false;
-                    bot.send_message(msg.chat.id, "Trading bot stopped.")
-                        .await?;
-                } else {
-                    bot.send_message(msg.chat.id, "Bot is not running.").await?;
-                }
-            }
-            Command::Status => {
-                let (is_running, notification_level) = {
-                    let state = bot_state.lock().await;
-                    (state.is_running, state.notification_level.clone())
-                };
+    /// Reset a tripped breaker on behalf of `by`, returning whether it was
+    /// tripped
+    pub fn acknowledge(&mut self, by: String, at: DateTime<Utc>) -> bool {
+        if !self.tripped {
+            return false;
+        }
+        self.tripped = false;
+        self.consecutive_failures = 0;
+        self.last_ack = Some(BreakerAck { by, at });
+        true
+    }
+}
 
-                let status_msg = if is_running {
-                    match self.request_status().await {
-                        Ok(status) => format!(
-                            "Bot is running.\nNotification level: {:?}\n\n{}",
-                            notification_level, status
-                        ),
-                        Err(err) => format!(
-                            "Bot is running, but failed to retrieve status: {}",
-                            err
-                        ),
-                    }
-                } else {
-                    format!(
-                        "Bot is stopped.\nNotification level: {:?}",
-                        notification_level
-                    )
-                };
-                bot.send_message(msg.chat.id, status_msg).await?;
-            }
-            Command::Notify(level_str) => {
-                let mut state = bot_state.lock().await;
-                match level_str.to_lowercase().as_str() {
-                    "all" => {
-                        state.notification_level = NotificationLevel::All;
-                        bot.send_message(msg.chat.id, "Notification level set to All")
-                            .await?;
-                    }
-                    "important" => {
-                        state.notification_level = NotificationLevel::Important;
-                        bot.send_message(msg.chat.id, "Notification level set to Important")
-                            .await?;
-                    }
-                    "critical" => {
-                        state.notification_level = NotificationLevel::Critical;
-                        bot.send_message(msg.chat.id, "Notification level set to Critical")
-                            .await?;
-                    }
-                    "none" => {
-                        state.notification_level = NotificationLevel::None;
-                        bot.send_message(msg.chat.id, "Notifications disabled")
-                            .await?;
-                    }
-                    _ => {
-                        bot.send_message(
-                            msg.chat.id,
-                            "Invalid level. Use: all, important, critical, or none",
-                        )
-                        .await?;
-                    }
-                }
-            }
-            Command::AddSymbol(data) => {
-                self.handle_add_symbol(&bot, msg.chat.id, data, Arc::clone(&bot_state))
-                    .await?;
-            }
-            Command::RemoveSymbol(symbol) => {
-                self.handle_remove_symbol(&bot, msg.chat.id, symbol, Arc::clone(&bot_state))
-                    .await?;
-            }
-            Command::Symbols => {
-                self.handle_show_symbols(&bot, msg.chat.id, Arc::clone(&bot_state))
-                    .await?;
-            }
-            Command::Update => {
-                match self.request_status().await {
-                    Ok(status) => {
-                        bot.send_message(msg.chat.id, format!("Current status:\n{}", status))
-                            .await?;
-                    }
-                    Err(err) => {
-                        bot.send_message(
-                            msg.chat.id,
-                            format!(
-                                "Unable to retrieve status from running bot: {}",
-                                err
-                            ),
-                        )
-                        .await?;
-                    }
-                }
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 0,
+            threshold: 0,
+            tripped: false,
+            last_ack: None,
+            cooldown: Duration::ZERO,
+            tripped_at: None,
+        }
+    }
+}
+
+/// Per-chat token bucket that paces notification sends. Each send takes a
+/// token; a chat without one waits until the bucket refills.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RateLimiter {
+    /// Tokens added per second; 0 disables the limiter
+    pub rate: f64,
+    /// Most tokens a chat can save up for a burst of sends
+    pub burst: f64,
+    #[serde(skip)]
+    buckets: HashMap<ChatId, TokenBucket>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TokenBucket {
+    tokens: f64,
+    updated: DateTime<Utc>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Tokens `chat` has at `now`; negative while sends are queued behind
+    /// the limit
+    pub fn tokens(&self, chat: ChatId, now: DateTime<Utc>) -> f64 {
+        match self.buckets.get(&chat) {
+            Some(bucket) => {
+                let elapsed = (now - bucket.updated).num_milliseconds().max(0) as f64 / 1000.0;
+                (bucket.tokens + elapsed * self.rate).min(self.burst)
             }
+            None => self.burst,
         }
+    }
 
-        Ok(())
+    /// Whether a send to `chat` at `now` would have to wait
+    pub fn is_throttled(&self, chat: ChatId, now: DateTime<Utc>) -> bool {
+        self.rate > 0.0 && self.tokens(chat, now) < 1.0
     }
 
-    async fn handle_add_symbol(
-        &self,
-        bot: &Bot,
-        chat_id: ChatId,
-        data: String,
-        bot_state: Arc<Mutex<BotState>>,
-    ) -> ResponseResult<()> {
-        let parts: Vec<&str> = data.split(',').collect();
-        if parts.len() != 5 {
-            bot.send_message(
-                chat_id,
-                "Invalid format. Use: /addsymbol \
-                 SYMBOL,ENTRY_AMOUNT,EXIT_AMOUNT,ENTRY_THRESHOLD,EXIT_THRESHOLD",
-            )
-            .await?;
-            return Ok(());
+    /// Take a token for a send to `chat` at `now`, returning how long the
+    /// send must wait for it
+    pub fn reserve(&mut self, chat: ChatId, now: DateTime<Utc>) -> Duration {
+        if self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let tokens = self.tokens(chat, now) - 1.0;
+        self.buckets.insert(
+            chat,
+            TokenBucket {
+                tokens,
+                updated: now,
+            },
+        );
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.rate)
         }
+    }
+}
 
-        let symbol = parts[0].trim().to_string();
-        let entry_amount: f64 = parts[1].trim().parse().unwrap_or(0.0);
-        let exit_amount: f64 = parts[2].trim().parse().unwrap_or(0.0);
-        let entry_threshold: f64 = parts[3].trim().parse().unwrap_or(0.0);
-        let exit_threshold: f64 = parts[4].trim().parse().unwrap_or(0.0);
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_RATE_LIMIT, DEFAULT_RATE_BURST)
+    }
+}
 
-        let config_path = match bot_state.lock().await.config_path.clone() {
-            Some(path) => PathBuf::from(path),
-            None => {
-                bot.send_message(
-                    chat_id,
-                    "Configuration path is not set. Use /startbot first to initialize.",
-                )
-                .await?;
-                return Ok(());
-            }
-        };
+/// Telegram allows about one message per second in a chat
+pub const DEFAULT_RATE_LIMIT: f64 = 1.0;
+pub const DEFAULT_RATE_BURST: f64 = 20.0;
 
-        // Read the current file content
-        let file_content = tokio::fs::read_to_string(config_path.clone()).await;
+/// The level notifications are sent to /subscribe chats at
+const SUBSCRIBER_LEVEL: NotificationLevel = NotificationLevel::Critical;
 
-        match file_content {
-            Ok(content) => {
-                let mut symbols: Vec<SymbolConfig> = match serde_json::from_str(&content) {
-                    Ok(json) => json,
-                    Err(_) => {
-                        bot.send_message(chat_id, "Failed to parse symbols configuration.")
-                            .await?;
-                        return Ok(());
-                    }
-                };
+/// How often the runner checks whether the daily backup is due
+const AUTO_BACKUP_CHECK_PERIOD: Duration = Duration::from_secs(60);
 
-                // Add the new symbol
-                let new_symbol = SymbolConfig {
-                    symbol: symbol.clone(),
-                    entry_amount,
-                    exit_amount,
-                    entry_threshold,
-                    exit_threshold,
-                };
-                symbols.push(new_symbol);
+/// Largest value accepted by `/watchdog`
+const MAX_WATCHDOG_INTERVALS: u64 = 1000;
 
-                // Write the updated content back to the file
-                if tokio::fs::write(config_path, serde_json::to_string_pretty(&symbols).unwrap())
-                    .await
-                    .is_err()
-                {
-                    bot.send_message(chat_id, "Failed to update symbols configuration.")
-                        .await?;
-                    return Ok(());
-                }
+/// How often the watchdog looks for a stalled runner
+const WATCHDOG_CHECK_PERIOD: Duration = Duration::from_secs(10);
 
-                bot.send_message(chat_id, format!("Symbol '{}' added successfully.", symbol))
-                    .await?;
-            }
-            Err(_) => {
-                bot.send_message(
-                    chat_id,
-                    "Failed to read symbols configuration. Ensure the file exists.",
-                )
-                .await?;
-            }
-        }
+/// What a chat may do once roles are configured
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Role {
+    /// Every command, including config edits and role changes
+    Admin,
+    /// Everything except editing the symbols configuration
+    Operator,
+}
 
-        Ok(())
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "admin" => Ok(Role::Admin),
+            "operator" => Ok(Role::Operator),
+            _ => Err(format!(
+                "Invalid role '{}'. Use: admin or operator",
+                value.trim()
+            )),
+        }
     }
+}
 
-    async fn handle_remove_symbol(
-        &self,
-        bot: &Bot,
+/// Something that happened inside the bot, published alongside Telegram
+/// messages
+#[derive(Clone, Debug, PartialEq)]
+pub enum BotEvent {
+    Started,
+    Stopped,
+    KillSwitch {
+        active: bool,
+    },
+    /// A notification passed the level filter and was sent or queued
+    Notification {
         chat_id: ChatId,
-        symbol: String,
-        bot_state: Arc<Mutex<BotState>>,
-    ) -> ResponseResult<()> {
-        let config_path = match bot_state.lock().await.config_path.clone() {
-            Some(path) => PathBuf::from(path),
-            None => {
-                bot.send_message(
-                    chat_id,
-                    "Configuration path is not set. Use /startbot first to initialize.",
-                )
-                .await?;
-                return Ok(());
-            }
-        };
+        level: NotificationLevel,
+        message: String,
+    },
+    TickCompleted,
+    StrategyError {
+        message: String,
+    },
+}
 
-        // Read the current file content
-        let file_content = tokio::fs::read_to_string(config_path.clone()).await;
+/// How many events a slow subscriber may fall behind before missing some
+const EVENT_CAPACITY: usize = 256;
 
-        match file_content {
-            Ok(content) => {
-                let mut symbols: Vec<SymbolConfig> = match serde_json::from_str(&content) {
-                    Ok(json) => json,
-                    Err(_) => {
-                        bot.send_message(chat_id, "Failed to parse symbols configuration.")
-                            .await?;
-                        return Ok(());
+/// A `SymbolConfig` field that /symbols can display
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum SymbolColumn {
+    Symbol,
+    EntryAmount,
+    ExitAmount,
+    EntryThreshold,
+    ExitThreshold,
+    Enabled,
+    MaxSlippage,
+    SizeStep,
+}
+
+impl SymbolColumn {
+    pub const ALL_COLUMNS: [SymbolColumn; 8] = [
+        SymbolColumn::Symbol,
+        SymbolColumn::EntryAmount,
+        SymbolColumn::ExitAmount,
+        SymbolColumn::EntryThreshold,
+        SymbolColumn::ExitThreshold,
+        SymbolColumn::MaxSlippage,
+        SymbolColumn::SizeStep,
+        SymbolColumn::Enabled,
+    ];
+
+    /// The columns shown when none have been chosen
+    pub fn defaults() -> Vec<SymbolColumn> {
+        Self::ALL_COLUMNS[..7].to_vec()
+    }
+
+    /// The `SymbolConfig` field name, as accepted by /columns
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            SymbolColumn::Symbol => "symbol",
+            SymbolColumn::EntryAmount => "entry_amount",
+            SymbolColumn::ExitAmount => "exit_amount",
+            SymbolColumn::EntryThreshold => "entry_threshold",
+            SymbolColumn::ExitThreshold => "exit_threshold",
+            SymbolColumn::Enabled => "enabled",
+            SymbolColumn::MaxSlippage => "max_slippage",
+            SymbolColumn::SizeStep => "size_step",
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            SymbolColumn::Symbol => "Symbol",
+            SymbolColumn::EntryAmount => "Entry Amount",
+            SymbolColumn::ExitAmount => "Exit Amount",
+            SymbolColumn::EntryThreshold => "Entry Threshold",
+            SymbolColumn::ExitThreshold => "Exit Threshold",
+            SymbolColumn::Enabled => "Enabled",
+            SymbolColumn::MaxSlippage => "Max Slippage",
+            SymbolColumn::SizeStep => "Size Step",
+        }
+    }
+
+    fn value(&self, symbol: &SymbolConfig) -> String {
+        match self {
+            SymbolColumn::Symbol => symbol.symbol.clone(),
+            SymbolColumn::EntryAmount => format!("{:.2}", symbol.entry_amount),
+            SymbolColumn::ExitAmount => format!("{:.2}", symbol.exit_amount),
+            SymbolColumn::EntryThreshold => format!("{:.2}", symbol.entry_threshold),
+            SymbolColumn::ExitThreshold => format!("{:.2}", symbol.exit_threshold),
+            SymbolColumn::Enabled => if symbol.enabled { "yes" } else { "no" }.to_string(),
+            SymbolColumn::MaxSlippage => symbol
+                .max_slippage
+                .map_or_else(|| "-".to_string(), |slippage| format!("{:.4}", slippage)),
+            SymbolColumn::SizeStep => symbol
+                .size_step
+                .map_or_else(|| "-".to_string(), |step| step.to_string()),
+        }
+    }
+}
+
+impl FromStr for SymbolColumn {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        SymbolColumn::ALL_COLUMNS
+            .into_iter()
+            .find(|column| column.field_name() == value)
+            .ok_or_else(|| format!("Unknown field '{}'", value))
+    }
+}
+
+/// Parse a comma-separated column list, reporting every unknown field
+pub fn parse_symbol_columns(input: &str) -> Result<Vec<SymbolColumn>, Vec<String>> {
+    let mut columns = Vec::new();
+    let mut errors = Vec::new();
+    for field in input
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+    {
+        match field.parse::<SymbolColumn>() {
+            Ok(column) if columns.contains(&column) => {
+                errors.push(format!("Field '{}' is listed twice", field))
+            }
+            Ok(column) => columns.push(column),
+            Err(err) => errors.push(err),
+        }
+    }
+    if columns.is_empty() && errors.is_empty() {
+        errors.push("List at least one field".to_string());
+    }
+    if errors.is_empty() {
+        Ok(columns)
+    } else {
+        Err(errors)
+    }
+}
+
+/// How /stopbot treats a strategy tick that is already running
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum StopMode {
+    /// Let the tick finish, then stop
+    Graceful,
+    /// Cancel the tick and stop right away
+    Immediate,
+}
+
+impl FromStr for StopMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "graceful" => Ok(StopMode::Graceful),
+            "immediate" => Ok(StopMode::Immediate),
+            _ => Err(format!(
+                "Invalid stop mode '{}'. Use: graceful or immediate",
+                value.trim()
+            )),
+        }
+    }
+}
+
+/// How /startbot and /stopbot confirm a state change
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ConfirmStyle {
+    /// Reply to the command message
+    Reply,
+    /// Send a new message
+    Message,
+    /// React to the command message
+    Reaction,
+}
+
+impl FromStr for ConfirmStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "reply" => Ok(ConfirmStyle::Reply),
+            "message" => Ok(ConfirmStyle::Message),
+            "reaction" => Ok(ConfirmStyle::Reaction),
+            _ => Err(format!(
+                "Invalid confirmation style '{}'. Use: reply, message or reaction",
+                value.trim()
+            )),
+        }
+    }
+}
+
+/// Notification counters for the current session
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NotificationStats {
+    /// Notifications sent per level, indexed like
+    /// `NotificationLevel::ALL_LEVELS`. Summary-mode notifications count once
+    /// queued.
+    pub sent: [u64; 4],
+    /// Notifications dropped for any reason; `suppressed` breaks them down
+    pub filtered: u64,
+    /// Notifications that Telegram failed to deliver
+    pub failed: u64,
+    pub suppressed: SuppressedCounts,
+}
+
+/// Why notifications were dropped, as reported by `/suppressed`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SuppressedCounts {
+    /// Below the configured or scheduled level, or a subscriber's `Critical`
+    pub level: u64,
+    /// Sent while `/quiet` was on
+    pub quiet: u64,
+    /// Would have passed the level but arrived during the warmup
+    pub warmup: u64,
+    /// Repeated within the dedup window
+    pub dedup: u64,
+    /// Buffered by summary mode and coalesced into one summary message
+    pub coalesced: u64,
+}
+
+impl SuppressedCounts {
+    pub fn total(&self) -> u64 {
+        self.level + self.quiet + self.warmup + self.dedup + self.coalesced
+    }
+
+    pub fn render(&self) -> String {
+        format!(
+            "Notifications suppressed this session: {}\n\nBelow level: {}\nQuiet: {}\nWarmup: \
+             {}\nDuplicates: {}\nCoalesced into summaries: {}",
+            self.total(),
+            self.level,
+            self.quiet,
+            self.warmup,
+            self.dedup,
+            self.coalesced
+        )
+    }
+}
+
+impl NotificationStats {
+    pub fn record_sent(&mut self, level: &NotificationLevel) {
+        if let Some(index) = NotificationLevel::ALL_LEVELS
+            .iter()
+            .position(|candidate| candidate == level)
+        {
+            self.sent[index] += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::from("Notifications this session:\n");
+        for (level, count) in NotificationLevel::ALL_LEVELS.iter().zip(self.sent) {
+            if *level != NotificationLevel::None {
+                text.push_str(&format!("\n{:?}: {}", level, count));
+            }
+        }
+        text.push_str(&format!("\n\nFiltered out: {}", self.filtered));
+        text.push_str(&format!("\nFailed: {}", self.failed));
+        text
+    }
+}
+
+/// A status report saved by `/snapshot` for later comparison
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatusSnapshot {
+    pub at: DateTime<Utc>,
+    pub report: StatusReport,
+}
+
+/// Pause between in-tick strategy retries unless `/strategyretries` sets one
+const DEFAULT_STRATEGY_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Most in-tick retries `/strategyretries` accepts
+const MAX_STRATEGY_RETRIES: u32 = 10;
+/// Longest pause between retries `/strategyretries` accepts
+const MAX_STRATEGY_RETRY_DELAY_SECS: u64 = 30;
+
+/// Parse `/strategyretries COUNT [DELAY_SECS]`; `off` means no retries
+pub fn parse_strategy_retries(input: &str) -> Result<(u32, Option<Duration>), String> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let (count, delay) = match parts[..] {
+        ["off"] => return Ok((0, None)),
+        [count] => (count, None),
+        [count, delay] => (count, Some(delay)),
+        _ => return Err("Usage: /strategyretries COUNT [DELAY_SECS] or off".to_string()),
+    };
+    let count = count
+        .parse::<u32>()
+        .ok()
+        .filter(|count| *count <= MAX_STRATEGY_RETRIES)
+        .ok_or_else(|| {
+            format!(
+                "Invalid retry count '{}'. Use a value between 0 and {}.",
+                count, MAX_STRATEGY_RETRIES
+            )
+        })?;
+    let delay = delay
+        .map(|delay| {
+            delay
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs <= MAX_STRATEGY_RETRY_DELAY_SECS)
+                .map(Duration::from_secs)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid delay '{}'. Use 0 to {} seconds.",
+                        delay, MAX_STRATEGY_RETRY_DELAY_SECS
+                    )
+                })
+        })
+        .transpose()?;
+    Ok((count, delay))
+}
+
+/// Longest window `/dedupwindow` accepts
+const MAX_DEDUP_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How many PnL samples the runner keeps for `/pnlchart`
+pub const MAX_PNL_SAMPLES: usize = 60;
+
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Render `values` as one block character each, scaled between their
+/// minimum and maximum; equal values all get the middle block
+pub fn sparkline(values: &[f64]) -> String {
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+    let range = max - min;
+    values
+        .iter()
+        .map(|value| {
+            if range <= 0.0 || !range.is_finite() {
+                return SPARK_BLOCKS[SPARK_BLOCKS.len() / 2];
+            }
+            let index = ((value - min) / range * (SPARK_BLOCKS.len() - 1) as f64).round();
+            SPARK_BLOCKS[(index as usize).min(SPARK_BLOCKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// A notification kept in `BotState::notification_history`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NotificationRecord {
+    pub at: DateTime<Utc>,
+    pub level: NotificationLevel,
+    pub message: String,
+}
+
+/// How many notifications the history keeps
+pub const MAX_NOTIFICATION_HISTORY: usize = 50;
+
+/// Most snapshots kept at once; existing labels can still be overwritten
+const MAX_STATUS_SNAPSHOTS: usize = 20;
+
+/// Label used when `/snapshot` and `/diffstatus` are given none
+const DEFAULT_SNAPSHOT_LABEL: &str = "latest";
+
+/// Where the value of a setting came from, from lowest to highest
+/// precedence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingSource {
+    /// `BotState::default()`
+    Default,
+    /// Assigned by the application's code rather than through a loader or a
+    /// command
+    Application,
+    /// `BotState::load_settings_file`
+    File,
+    /// `BotState::load_settings_env`
+    Env,
+    /// Changed by a command
+    Runtime,
+}
+
+impl std::fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SettingSource::Default => "default",
+            SettingSource::Application => "application",
+            SettingSource::File => "file",
+            SettingSource::Env => "env",
+            SettingSource::Runtime => "runtime",
+        })
+    }
+}
+
+/// Prefix of the environment variables `BotState::load_settings_env` reads
+pub const SETTINGS_ENV_PREFIX: &str = "TELEGRAM_BOT_";
+
+/// Settings a settings file or the environment may set
+const LOADABLE_SETTINGS: [&str; 16] = [
+    "notification_level",
+    "send_timeout_secs",
+    "summary_mode",
+    "silent_notifications",
+    "strategy_concurrency",
+    "instance_tag",
+    "persist_changes",
+    "command_cooldown_secs",
+    "stop_mode",
+    "confirm_style",
+    "warmup",
+    "dry_run",
+    "watchdog_intervals",
+    "timestamp_notifications",
+    "verbose_errors",
+    "symbols_page_size",
+];
+
+/// Parse `value` the way the matching command parses its argument and
+/// assign it to the setting `name`, one of `LOADABLE_SETTINGS`
+fn apply_setting(state: &mut BotState, name: &str, value: &str) -> Result<(), String> {
+    fn toggle(value: &str) -> Result<bool, String> {
+        parse_toggle(value).ok_or_else(|| format!("'{}' is not on or off", value))
+    }
+    fn number<T>(value: &str, min: T, max: T) -> Result<T, String>
+    where
+        T: FromStr + PartialOrd + std::fmt::Display,
+    {
+        value
+            .parse()
+            .ok()
+            .filter(|number| *number >= min && *number <= max)
+            .ok_or_else(|| format!("'{}' is not a number between {} and {}", value, min, max))
+    }
+
+    let value = value.trim();
+    match name {
+        "notification_level" => state.notification_level = value.parse()?,
+        "send_timeout_secs" => state.send_timeout_secs = number(value, 1, MAX_SEND_TIMEOUT_SECS)?,
+        "summary_mode" => state.summary_mode = toggle(value)?,
+        "silent_notifications" => state.silent_notifications = toggle(value)?,
+        "strategy_concurrency" => {
+            state.strategy_concurrency = number(value, 1, MAX_STRATEGY_CONCURRENCY)?
+        }
+        "instance_tag" => {
+            if value.chars().count() > MAX_TAG_LENGTH {
+                return Err(format!("Tags are at most {} characters", MAX_TAG_LENGTH));
+            }
+            state.instance_tag = Some(value.to_string()).filter(|tag| !tag.is_empty());
+        }
+        "persist_changes" => state.persist_changes = toggle(value)?,
+        "command_cooldown_secs" => {
+            state.command_cooldown_secs = number(value, 0, MAX_COMMAND_COOLDOWN_SECS)?
+        }
+        "stop_mode" => state.stop_mode = value.parse()?,
+        "confirm_style" => state.confirm_style = value.parse()?,
+        "warmup" => state.warmup = parse_duration(value)?,
+        "dry_run" => state.dry_run = toggle(value)?,
+        "watchdog_intervals" => {
+            state.watchdog_intervals = number(value, 0, MAX_WATCHDOG_INTERVALS)?
+        }
+        "timestamp_notifications" => state.timestamp_notifications = toggle(value)?,
+        "verbose_errors" => state.verbose_errors = toggle(value)?,
+        "symbols_page_size" => state.symbols_page_size = number(value, 1, MAX_SYMBOLS_PAGE_SIZE)?,
+        _ => return Err(format!("Unknown setting '{}'", name)),
+    }
+    Ok(())
+}
+
+/// A setting as reported by `/effectiveconfig`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectiveSetting {
+    pub name: &'static str,
+    pub value: String,
+    pub source: SettingSource,
+}
+
+/// The settings `/effectiveconfig` reports, rendered for display
+fn setting_values(state: &BotState) -> Vec<(&'static str, String)> {
+    fn optional<T: ToString>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map_or_else(|| "-".to_string(), ToString::to_string)
+    }
+    fn list<T: ToString>(values: &[T]) -> String {
+        if values.is_empty() {
+            "-".to_string()
+        } else {
+            values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    }
+
+    vec![
+        (
+            "notification_level",
+            format!("{:?}", state.notification_level),
+        ),
+        ("level_schedule", list(&state.level_schedule)),
+        ("interval_seconds", optional(&state.interval_seconds)),
+        ("send_timeout_secs", state.send_timeout_secs.to_string()),
+        ("max_positions", optional(&state.max_positions)),
+        ("summary_mode", state.summary_mode.to_string()),
+        (
+            "silent_notifications",
+            state.silent_notifications.to_string(),
+        ),
+        (
+            "strategy_concurrency",
+            state.strategy_concurrency.to_string(),
+        ),
+        ("send_concurrency", state.send_concurrency.to_string()),
+        ("instance_tag", optional(&state.instance_tag)),
+        ("fallback_chat", optional(&state.fallback_chat)),
+        ("persist_changes", state.persist_changes.to_string()),
+        (
+            "command_cooldown_secs",
+            state.command_cooldown_secs.to_string(),
+        ),
+        ("backup_retention", state.backup_retention.to_string()),
+        ("stop_mode", format!("{:?}", state.stop_mode)),
+        ("confirm_style", format!("{:?}", state.confirm_style)),
+        ("warmup", format_duration(state.warmup)),
+        (
+            "symbol_columns",
+            list(
+                &state
+                    .symbol_columns
+                    .iter()
+                    .map(SymbolColumn::field_name)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+        ("quote_filter", optional(&state.quote_filter)),
+        ("dry_run", state.dry_run.to_string()),
+        ("time_zone", state.time_zone.to_string()),
+        ("trading_window", optional(&state.trading_window)),
+        (
+            "breaker_threshold",
+            state.circuit_breaker.threshold.to_string(),
+        ),
+        (
+            "breaker_cooldown",
+            format_duration(state.circuit_breaker.cooldown),
+        ),
+        ("rate_limit", format!("{}/s", state.rate_limiter.rate)),
+        ("rate_burst", state.rate_limiter.burst.to_string()),
+        ("watchdog_intervals", state.watchdog_intervals.to_string()),
+        ("status_broadcast", state.status_broadcast.to_string()),
+        ("alert_rules", list(&state.alert_rules)),
+        ("max_message_length", state.max_message_length.to_string()),
+        ("dedup_window", format_duration(state.dedup_window)),
+        ("restart_level", format!("{:?}", state.restart_level)),
+        ("session_budget", optional(&state.session_budget)),
+        ("auto_backup_at", optional(&state.auto_backup_at)),
+        (
+            "timestamp_notifications",
+            state.timestamp_notifications.to_string(),
+        ),
+        ("verbose_errors", state.verbose_errors.to_string()),
+        ("symbol_error_limit", state.symbol_error_limit.to_string()),
+        ("symbols_page_size", state.symbols_page_size.to_string()),
+        ("max_daily_trades", state.max_daily_trades.to_string()),
+        ("trade_reset_hour", state.trade_reset_hour.to_string()),
+        ("strategy_retries", state.strategy_retries.to_string()),
+        (
+            "strategy_retry_delay",
+            format_duration(state.strategy_retry_delay),
+        ),
+    ]
+}
+
+/// Why the effective notification level differs from the configured one
+#[derive(Clone, Debug, PartialEq)]
+pub enum LevelOverride {
+    /// `/quiet` silences everything
+    Quiet { remaining: Duration },
+    /// Only `Critical` notifications are sent while warming up
+    Warmup { remaining: Duration },
+    /// A level schedule window is active
+    Schedule(LevelWindow),
+}
+
+impl LevelOverride {
+    pub fn explain(&self) -> String {
+        match self {
+            LevelOverride::Quiet { remaining } => {
+                format!("/quiet is on for {} more", format_duration(*remaining))
+            }
+            LevelOverride::Warmup { remaining } => format!(
+                "warmup allows only critical notifications for {} more",
+                format_duration(*remaining)
+            ),
+            LevelOverride::Schedule(window) => format!("schedule window {} is active", window),
+        }
+    }
+}
+
+/// What happened to the notifications raised during one strategy tick
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TickNotifications {
+    /// Notifications sent or queued for the summary
+    pub sent: u64,
+    /// Notifications below the active level, including warmup and schedules
+    pub filtered_by_level: u64,
+    /// Notifications dropped while `/quiet` was on
+    pub filtered_by_quiet: u64,
+}
+
+impl TickNotifications {
+    pub fn render(&self) -> String {
+        format!(
+            "Notifications in the last tick:\nSent: {}\nFiltered by level: {}\nFiltered by quiet: \
+             {}",
+            self.sent, self.filtered_by_level, self.filtered_by_quiet
+        )
+    }
+}
+
+/// Notification levels for the Telegram bot
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub enum NotificationLevel {
+    All,       // Send all messages
+    Important, // Only important updates and errors
+    Critical,  // Only critical errors and trade executions
+    None,      // No messages
+}
+
+impl NotificationLevel {
+    /// Every level, from most to least verbose
+    pub const ALL_LEVELS: [NotificationLevel; 4] = [
+        NotificationLevel::All,
+        NotificationLevel::Important,
+        NotificationLevel::Critical,
+        NotificationLevel::None,
+    ];
+
+    /// Describes which messages get through at this setting
+    pub fn description(&self) -> &'static str {
+        match self {
+            NotificationLevel::All => "every message, including routine updates",
+            NotificationLevel::Important => "important updates, errors and trade executions",
+            NotificationLevel::Critical => "only critical errors and trade executions",
+            NotificationLevel::None => "no notifications at all",
+        }
+    }
+}
+
+impl FromStr for NotificationLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "all" => Ok(NotificationLevel::All),
+            "important" => Ok(NotificationLevel::Important),
+            "critical" => Ok(NotificationLevel::Critical),
+            "none" => Ok(NotificationLevel::None),
+            _ => Err(format!(
+                "Invalid level '{}'. Use: all, important, critical, or none",
+                value.trim()
+            )),
+        }
+    }
+}
+
+/// Render every notification level with its meaning, marking the active one
+fn render_levels(active: &NotificationLevel) -> String {
+    let mut text = String::from("Notification levels:\n");
+    for level in NotificationLevel::ALL_LEVELS.iter() {
+        let marker = if level == active { " (active)" } else { "" };
+        text.push_str(&format!(
+            "\n{:?}{} - {}",
+            level,
+            marker,
+            level.description()
+        ));
+    }
+    text
+}
+
+impl BotState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current time according to `clock`
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// The notification level in force at `time`, taking the level schedule
+    /// into account
+    pub fn effective_notification_level(&self, time: NaiveTime) -> NotificationLevel {
+        resolve_level(&self.level_schedule, &self.notification_level, time)
+    }
+
+    /// The notification level in force at `now`, also applying the quiet
+    /// and warmup windows
+    pub fn notification_level_at(&self, now: DateTime<Utc>) -> NotificationLevel {
+        self.resolve_notification_level(now).0
+    }
+
+    /// The notification level in force at `now` and the override that
+    /// produced it, if any.
+    ///
+    /// Precedence, highest first: `/quiet`, the warmup window (which only
+    /// lowers the level to `Critical`), then the level schedule.
+    pub fn resolve_notification_level(
+        &self,
+        now: DateTime<Utc>,
+    ) -> (NotificationLevel, Option<LevelOverride>) {
+        if let Some(remaining) = self.quiet_remaining(now) {
+            return (
+                NotificationLevel::None,
+                Some(LevelOverride::Quiet { remaining }),
+            );
+        }
+
+        let time = self.local_time(now);
+        let window = self
+            .level_schedule
+            .iter()
+            .find(|window| window.contains(time))
+            .cloned();
+        let level = self.effective_notification_level(time);
+
+        match self.warmup_remaining(now) {
+            Some(remaining) if level != NotificationLevel::None => (
+                NotificationLevel::Critical,
+                Some(LevelOverride::Warmup { remaining }),
+            ),
+            _ => (level, window.map(LevelOverride::Schedule)),
+        }
+    }
+
+    /// Begin the warmup window, if one is configured
+    pub fn start_warmup(&mut self, now: DateTime<Utc>) {
+        self.warmup_until = chrono::Duration::from_std(self.warmup)
+            .ok()
+            .filter(|warmup| !warmup.is_zero())
+            .map(|warmup| now + warmup);
+    }
+
+    /// `now` as a time of day in the configured time zone
+    pub fn local_time(&self, now: DateTime<Utc>) -> NaiveTime {
+        now.with_timezone(&self.time_zone).time()
+    }
+
+    /// Back up daily at `at` local time, or stop when `None`. A time already
+    /// passed today first runs tomorrow.
+    pub fn schedule_auto_backup(&mut self, at: Option<NaiveTime>, now: DateTime<Utc>) {
+        self.auto_backup_at = at;
+        let local = now.with_timezone(&self.time_zone);
+        self.last_auto_backup = at
+            .filter(|at| local.time() >= *at)
+            .map(|_| local.date_naive());
+    }
+
+    /// Whether today's automatic backup is due at `now`, marking it done if
+    /// so
+    pub fn take_auto_backup(&mut self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.time_zone);
+        let due = self.auto_backup_at.is_some_and(|at| local.time() >= at)
+            && self.last_auto_backup != Some(local.date_naive());
+        if due {
+            self.last_auto_backup = Some(local.date_naive());
+        }
+        due
+    }
+
+    /// Whether strategy ticks may run at `now`
+    pub fn in_trading_window(&self, now: DateTime<Utc>) -> bool {
+        self.trading_window
+            .is_none_or(|window| window.contains(self.local_time(now)))
+    }
+
+    /// Note that the runner made progress at `now`, ending any stall
+    pub fn record_tick(&mut self, now: DateTime<Utc>) {
+        self.last_successful_tick = Some(now);
+        self.watchdog_alerted = false;
+    }
+
+    /// How long the runner has gone without a successful tick, if that is
+    /// longer than the watchdog allows at `now`
+    pub fn watchdog_stall(&self, now: DateTime<Utc>) -> Option<Duration> {
+        if self.watchdog_intervals == 0 || !self.is_running {
+            return None;
+        }
+        let interval = self.interval_seconds?;
+        let since = (now - self.last_successful_tick?).to_std().ok()?;
+        let allowed = Duration::from_secs(interval.saturating_mul(self.watchdog_intervals));
+        (since > allowed).then_some(since)
+    }
+
+    /// The chats a /status reply from `origin` goes to, `origin` first and
+    /// each chat once
+    pub fn status_recipients(&self, origin: ChatId) -> Vec<ChatId> {
+        let mut recipients = vec![origin];
+        if self.status_broadcast {
+            for &chat_id in &self.notification_targets {
+                if !recipients.contains(&chat_id) {
+                    recipients.push(chat_id);
+                }
+            }
+        }
+        recipients
+    }
+
+    /// Check the alert rules against `report` and the strategy's metrics,
+    /// returning the rules that started matching since the last check with
+    /// the value each watches
+    pub fn check_alert_rules(
+        &mut self,
+        report: &StatusReport,
+        columns: &BTreeMap<String, f64>,
+    ) -> Vec<(AlertRule, f64)> {
+        let matching: Vec<(AlertRule, f64)> = self
+            .alert_rules
+            .iter()
+            .filter_map(|rule| {
+                rule.breach(report, columns)
+                    .map(|value| (rule.clone(), value))
+            })
+            .collect();
+        let fired = matching
+            .iter()
+            .filter(|(rule, _)| !self.firing_alerts.contains(rule))
+            .cloned()
+            .collect();
+        self.firing_alerts = matching.into_iter().map(|(rule, _)| rule).collect();
+        fired
+    }
+
+    /// Return the operational settings to their defaults, keeping the running
+    /// state, targets, roles, kill switch and symbol edits.
+    ///
+    /// The interval is left alone because the runner owns it; `/resetsettings`
+    /// asks the runner to change it.
+    pub fn reset_settings(&mut self) {
+        let defaults = BotState::default();
+        self.notification_level = defaults.notification_level;
+        self.send_timeout_secs = defaults.send_timeout_secs;
+        self.max_positions = defaults.max_positions;
+        self.summary_mode = defaults.summary_mode;
+        self.silent_notifications = defaults.silent_notifications;
+        self.level_schedule = defaults.level_schedule;
+        self.strategy_concurrency = defaults.strategy_concurrency;
+        self.send_concurrency = defaults.send_concurrency;
+        self.command_cooldown_secs = defaults.command_cooldown_secs;
+        self.backup_retention = defaults.backup_retention;
+        self.stop_mode = defaults.stop_mode;
+        self.confirm_style = defaults.confirm_style;
+        self.warmup = defaults.warmup;
+        self.symbol_columns = defaults.symbol_columns;
+        self.dry_run = defaults.dry_run;
+        self.trading_window = defaults.trading_window;
+        self.quiet_until = defaults.quiet_until;
+        self.watchdog_intervals = defaults.watchdog_intervals;
+        self.status_broadcast = defaults.status_broadcast;
+        self.alert_rules = defaults.alert_rules;
+        self.firing_alerts = defaults.firing_alerts;
+        self.max_message_length = defaults.max_message_length;
+        self.session_budget = defaults.session_budget;
+        self.auto_backup_at = defaults.auto_backup_at;
+        self.timestamp_notifications = defaults.timestamp_notifications;
+        self.verbose_errors = defaults.verbose_errors;
+        self.symbol_error_limit = defaults.symbol_error_limit;
+        self.symbols_page_size = defaults.symbols_page_size;
+        self.max_daily_trades = defaults.max_daily_trades;
+        self.trade_reset_hour = defaults.trade_reset_hour;
+        self.strategy_retries = defaults.strategy_retries;
+        self.strategy_retry_delay = defaults.strategy_retry_delay;
+        self.quote_filter = defaults.quote_filter;
+        self.dedup_window = defaults.dedup_window;
+        self.dedup_sent = defaults.dedup_sent;
+        self.restart_level = defaults.restart_level;
+        self.circuit_breaker.threshold = defaults.circuit_breaker.threshold;
+        self.circuit_breaker.cooldown = defaults.circuit_breaker.cooldown;
+        self.setting_sources.clear();
+    }
+
+    /// The operational settings with their current values and where each
+    /// value came from, in the order `/effectiveconfig` lists them
+    ///
+    /// A setting no loader or command has set is `Application` when the
+    /// application's code gave it a value other than the default.
+    pub fn effective_settings(&self) -> Vec<EffectiveSetting> {
+        let defaults = setting_values(&BotState::default());
+        setting_values(self)
+            .into_iter()
+            .zip(defaults)
+            .map(|((name, value), (_, default))| EffectiveSetting {
+                name,
+                source: match self.setting_sources.get(name) {
+                    Some(source) => *source,
+                    None if value == default => SettingSource::Default,
+                    None => SettingSource::Application,
+                },
+                value,
+            })
+            .collect()
+    }
+
+    /// Attribute the settings whose values differ from `before`, as taken
+    /// by `setting_values`, to `source`
+    fn record_setting_changes(&mut self, before: &[(&'static str, String)], source: SettingSource) {
+        for ((name, value), (_, previous)) in setting_values(self).into_iter().zip(before) {
+            if value != *previous {
+                self.setting_sources.insert(name, source);
+            }
+        }
+    }
+
+    /// Apply the settings in the JSON object stored at `path`
+    ///
+    /// Keys name settings as `/effectiveconfig` lists them; values may be
+    /// strings, numbers or booleans and are parsed like the matching
+    /// command's argument. Nothing is applied if any entry is invalid.
+    /// Returns the names of the settings applied.
+    pub fn load_settings_file(&mut self, path: &Path) -> Result<Vec<&'static str>, BotError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| BotError::context("Failed to read settings file", e))?;
+        let entries: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&content)
+            .map_err(|e| BotError::context("Failed to parse settings file", e))?;
+        let mut settings = Vec::new();
+        let mut errors = Vec::new();
+        for (key, value) in entries {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                _ => {
+                    errors.push(format!("{}: expected a string, number or boolean", key));
+                    continue;
+                }
+            };
+            match LOADABLE_SETTINGS.iter().find(|name| **name == key) {
+                Some(name) => settings.push((*name, value)),
+                None => errors.push(format!("{}: unknown setting", key)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(BotError::Message(format!(
+                "Invalid settings file: {}",
+                errors.join("; ")
+            )));
+        }
+        self.load_settings(settings, SettingSource::File)
+    }
+
+    /// Apply settings from `TELEGRAM_BOT_<SETTING>` variables, such as
+    /// `TELEGRAM_BOT_NOTIFICATION_LEVEL=critical`, ignoring any other variable
+    ///
+    /// Pass `std::env::vars()`. Load the settings file first so the
+    /// environment overrides it. Nothing is applied if any value is invalid.
+    /// Returns the names of the settings applied.
+    pub fn load_settings_env(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Vec<&'static str>, BotError> {
+        let settings = vars
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let setting = key.strip_prefix(SETTINGS_ENV_PREFIX)?.to_lowercase();
+                let name = LOADABLE_SETTINGS.iter().find(|name| **name == setting)?;
+                Some((*name, value))
+            })
+            .collect();
+        self.load_settings(settings, SettingSource::Env)
+    }
+
+    /// Apply `settings` and attribute them to `source`, or none of them if
+    /// any value is invalid
+    fn load_settings(
+        &mut self,
+        settings: Vec<(&'static str, String)>,
+        source: SettingSource,
+    ) -> Result<Vec<&'static str>, BotError> {
+        let mut loaded = self.clone();
+        let errors: Vec<String> = settings
+            .iter()
+            .filter_map(|(name, value)| {
+                apply_setting(&mut loaded, name, value)
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(BotError::Message(format!(
+                "Invalid {} settings: {}",
+                source,
+                errors.join("; ")
+            )));
+        }
+        *self = loaded;
+        let names: Vec<&'static str> = settings.into_iter().map(|(name, _)| name).collect();
+        for name in &names {
+            self.setting_sources.insert(name, source);
+        }
+        Ok(names)
+    }
+
+    /// Add a notification to the history. A notification identical to the
+    /// previous one, such as a broadcast to several chats, is kept once.
+    pub fn record_notification(
+        &mut self,
+        at: DateTime<Utc>,
+        level: NotificationLevel,
+        message: &str,
+    ) {
+        let repeated = self
+            .notification_history
+            .back()
+            .is_some_and(|last| last.level == level && last.message == message);
+        if repeated {
+            return;
+        }
+        if self.notification_history.len() >= MAX_NOTIFICATION_HISTORY {
+            self.notification_history.pop_front();
+        }
+        self.notification_history.push_back(NotificationRecord {
+            at,
+            level,
+            message: message.to_string(),
+        });
+    }
+
+    /// The semaphore bounding concurrent sends. After `send_concurrency`
+    /// changes it is resized rather than replaced, so sends holding a permit
+    /// still count against the new limit.
+    pub fn send_permits(&mut self) -> Arc<Semaphore> {
+        let limit = self.send_concurrency.clamp(1, MAX_SEND_CONCURRENCY);
+        let (current, permits) = self
+            .send_permits
+            .get_or_insert_with(|| (limit, Arc::new(Semaphore::new(limit))));
+        if limit > *current {
+            permits.add_permits(limit - *current);
+        } else if limit < *current {
+            let excess = *current - limit;
+            let owed = excess - permits.forget_permits(excess);
+            if owed > 0 {
+                // Retire the rest as in-flight sends return them; the
+                // semaphore is fair, so no waiting send starts first
+                let permits = Arc::clone(permits);
+                tokio::spawn(async move {
+                    if let Ok(retired) = permits.acquire_many_owned(owed as u32).await {
+                        retired.forget();
                     }
-                };
+                });
+            }
+        }
+        *current = limit;
+        Arc::clone(permits)
+    }
+
+    /// Whether `message` was already sent to `chat_id` within the dedup
+    /// window; otherwise remember it as sent at `now`
+    pub fn is_duplicate_notification(
+        &mut self,
+        chat_id: ChatId,
+        message: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Ok(window) = chrono::Duration::from_std(self.dedup_window) else {
+            return false;
+        };
+        if window.is_zero() {
+            return false;
+        }
+        self.dedup_sent.retain(|_, sent_at| now - *sent_at < window);
+        let key = (chat_id, message.to_string());
+        if self.dedup_sent.contains_key(&key) {
+            return true;
+        }
+        self.dedup_sent.insert(key, now);
+        false
+    }
+
+    /// Keep `pnl` as the newest PnL sample, dropping the oldest beyond
+    /// `MAX_PNL_SAMPLES`
+    pub fn record_pnl_sample(&mut self, pnl: f64) {
+        if self.pnl_samples.len() >= MAX_PNL_SAMPLES {
+            self.pnl_samples.pop_front();
+        }
+        self.pnl_samples.push_back(pnl);
+    }
+
+    /// The last `count` notifications, oldest first
+    pub fn recent_notifications(&self, count: usize) -> Vec<NotificationRecord> {
+        let skip = self.notification_history.len().saturating_sub(count);
+        self.notification_history
+            .iter()
+            .skip(skip)
+            .cloned()
+            .collect()
+    }
+
+    /// Describe `e` for a chat, honouring `verbose_errors`
+    pub fn describe_error(&self, e: &(dyn Error + 'static)) -> String {
+        describe_error(e, self.verbose_errors)
+    }
+
+    /// Count the trade errors the strategy reported for a tick, returning
+    /// the symbols that reached `symbol_error_limit` with their last error.
+    /// Symbols without an error this tick, and those returned, start
+    /// counting again.
+    pub fn record_symbol_errors(
+        &mut self,
+        errors: &BTreeMap<String, String>,
+    ) -> Vec<(String, String)> {
+        self.symbol_errors
+            .retain(|symbol, _| errors.contains_key(symbol));
+        let mut reached = Vec::new();
+        for (symbol, error) in errors {
+            let count = self.symbol_errors.entry(symbol.clone()).or_default();
+            *count += 1;
+            if self.symbol_error_limit > 0 && *count >= self.symbol_error_limit {
+                self.symbol_errors.remove(symbol);
+                reached.push((symbol.clone(), error.clone()));
+            }
+        }
+        reached
+    }
+
+    /// Per-symbol notification counts, noisiest first
+    pub fn symbol_noise(&self) -> Vec<(String, u64)> {
+        let mut counts: Vec<(String, u64)> = self
+            .symbol_notifications
+            .iter()
+            .map(|(symbol, count)| (symbol.clone(), *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Start counting the notifications raised by a new tick
+    pub fn begin_tick_notifications(&mut self) {
+        self.tick_notifications = Some(TickNotifications::default());
+    }
+
+    /// Keep the current tick's notification counts for `/lastticknotify`
+    pub fn end_tick_notifications(&mut self) {
+        if let Some(counts) = self.tick_notifications.take() {
+            self.last_tick_notifications = Some(counts);
+        }
+    }
+
+    /// Time left in the quiet window at `now`
+    pub fn quiet_remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.quiet_until
+            .and_then(|until| (until - now).to_std().ok())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Time left in the warmup window at `now`
+    pub fn warmup_remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.warmup_until
+            .and_then(|until| (until - now).to_std().ok())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Publish an event; it is dropped when nobody is subscribed
+    fn publish(&self, event: BotEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// The role of `chat_id`, or `None` if it may not use the bot
+    pub fn role_of(&self, chat_id: ChatId) -> Option<Role> {
+        if self.chat_roles.is_empty() {
+            Some(Role::Admin)
+        } else {
+            self.chat_roles.get(&chat_id).copied()
+        }
+    }
+
+    fn schedule_next_tick(&mut self, interval_seconds: u64) {
+        self.next_tick_at = Some(
+            self.now() + chrono::Duration::seconds(interval_seconds.min(i64::MAX as u64) as i64),
+        );
+    }
+
+    /// Stop sending notifications to `chat_id`, returning whether it was a
+    /// target
+    pub fn remove_notification_target(&mut self, chat_id: ChatId) -> bool {
+        self.subscribers.retain(|subscriber| *subscriber != chat_id);
+        let before = self.notification_targets.len();
+        self.notification_targets
+            .retain(|target| *target != chat_id);
+        self.notification_targets.len() != before
+    }
+
+    /// Add `chat_id` as a notification target, returning false if it
+    /// already is one
+    pub fn add_notification_target(&mut self, chat_id: ChatId) -> bool {
+        self.targets_configured = true;
+        if self.notification_targets.contains(&chat_id) {
+            return false;
+        }
+        self.notification_targets.push(chat_id);
+        true
+    }
+
+    /// Make `chat_id` the notification target if no target has ever been
+    /// set, returning whether it became one
+    pub fn claim_default_target(&mut self, chat_id: ChatId) -> bool {
+        if self.targets_configured || !self.notification_targets.is_empty() {
+            return false;
+        }
+        self.add_notification_target(chat_id)
+    }
+
+    /// Stop the bot because the circuit breaker tripped; the runner idles
+    /// until /startbot and leaves the announcement to `announce_breaker_trip`
+    pub fn stop_for_breaker(&mut self) {
+        self.is_running = false;
+        self.stop_announced = true;
+        self.held_by_breaker = true;
+    }
+
+    /// Add `chat_id` as a `Critical`-only subscriber, returning false if it
+    /// already receives notifications
+    pub fn add_subscriber(&mut self, chat_id: ChatId) -> bool {
+        if !self.add_notification_target(chat_id) {
+            return false;
+        }
+        self.subscribers.push(chat_id);
+        true
+    }
+
+    /// Count `amount` against the session budget, returning false without
+    /// counting it if that would exceed the budget
+    pub fn reserve_budget(&mut self, amount: f64) -> bool {
+        let allowed = self
+            .session_budget
+            .is_none_or(|budget| self.deployed_capital + amount <= budget);
+        if allowed {
+            self.deployed_capital += amount;
+        }
+        allowed
+    }
+
+    /// Give back `amount` of deployed capital when a position closes, so the
+    /// budget can be spent on new entries
+    pub fn release_budget(&mut self, amount: f64) {
+        self.deployed_capital = (self.deployed_capital - amount).max(0.0);
+    }
+
+    /// The trades counted so far in the trading day containing `now`,
+    /// starting the count over when a new day has begun
+    pub fn trades_today(&mut self, now: DateTime<Utc>) -> usize {
+        let day = trading_day_start(now, self.trade_reset_hour);
+        if self.trade_day != Some(day) {
+            self.trade_day = Some(day);
+            self.daily_trades = 0;
+        }
+        self.daily_trades
+    }
+
+    /// Count an entry at `now` against the daily cap, returning false without
+    /// counting it once the cap has been reached
+    pub fn reserve_trade(&mut self, now: DateTime<Utc>) -> bool {
+        let trades = self.trades_today(now);
+        let allowed = self.max_daily_trades == 0 || trades < self.max_daily_trades;
+        if allowed {
+            self.daily_trades += 1;
+        }
+        allowed
+    }
+
+    /// Whether another position may be opened given the current count
+    pub fn can_open_position(&self, open_positions: usize) -> bool {
+        self.max_positions
+            .is_none_or(|max_positions| open_positions < max_positions)
+    }
+}
+
+impl Default for BotState {
+    fn default() -> Self {
+        Self {
+            is_running: false,
+            notification_level: NotificationLevel::Important,
+            config_path: Some("symbols_config.json".to_string()),
+            interval_seconds: Some(300),
+            send_timeout_secs: DEFAULT_SEND_TIMEOUT_SECS,
+            max_positions: None,
+            notification_targets: Vec::new(),
+            targets_configured: false,
+            stop_announced: false,
+            held_by_breaker: false,
+            summary_mode: false,
+            summary_buffer: Vec::new(),
+            silent_notifications: false,
+            level_schedule: Vec::new(),
+            strategy_concurrency: 1,
+            send_concurrency: DEFAULT_SEND_CONCURRENCY,
+            send_permits: None,
+            notification_stats: NotificationStats::default(),
+            staged_symbols: None,
+            staged_changes: 0,
+            kill_switch: false,
+            instance_tag: None,
+            persist_changes: true,
+            unsaved_changes: false,
+            command_cooldown_secs: DEFAULT_COMMAND_COOLDOWN_SECS,
+            backup_retention: DEFAULT_BACKUP_RETENTION,
+            stop_mode: StopMode::Graceful,
+            confirm_style: ConfirmStyle::Message,
+            tick_cancel: None,
+            warmup: Duration::ZERO,
+            warmup_until: None,
+            next_tick_at: None,
+            symbol_columns: SymbolColumn::defaults(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            setting_sources: HashMap::new(),
+            dry_run: false,
+            replay_dir: None,
+            chat_roles: HashMap::new(),
+            time_zone: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            trading_window: None,
+            circuit_breaker: CircuitBreaker::default(),
+            rate_limiter: RateLimiter::default(),
+            quiet_until: None,
+            last_successful_tick: None,
+            watchdog_intervals: 0,
+            watchdog_alerted: false,
+            status_broadcast: false,
+            alert_rules: Vec::new(),
+            firing_alerts: Vec::new(),
+            max_message_length: TELEGRAM_MAX_MESSAGE_LENGTH,
+            tick_notifications: None,
+            last_tick_notifications: None,
+            session_budget: None,
+            deployed_capital: 0.0,
+            status_snapshots: HashMap::new(),
+            auto_backup_at: None,
+            last_auto_backup: None,
+            timestamp_notifications: false,
+            verbose_errors: false,
+            subscribers: Vec::new(),
+            symbol_notifications: HashMap::new(),
+            symbol_errors: HashMap::new(),
+            symbol_error_limit: 0,
+            symbols_page_size: DEFAULT_SYMBOLS_PAGE_SIZE,
+            notification_history: VecDeque::new(),
+            max_daily_trades: 0,
+            trade_reset_hour: 0,
+            daily_trades: 0,
+            trade_day: None,
+            strategy_retries: 0,
+            strategy_retry_delay: DEFAULT_STRATEGY_RETRY_DELAY,
+            quote_filter: None,
+            dedup_window: Duration::ZERO,
+            dedup_sent: HashMap::new(),
+            fallback_chat: None,
+            pnl_samples: VecDeque::new(),
+            restart_level: NotificationLevel::Important,
+            clock: Clock::default(),
+        }
+    }
+}
+
+#[derive(Debug, BotCommands, Clone)]
+#[command(
+    rename_rule = "lowercase",
+    description = "These commands are supported:"
+)]
+pub enum Command {
+    #[command(description = "display this text.")]
+    Help,
+    #[command(description = "start the trading bot.")]
+    StartBot,
+    #[command(description = "stop the trading bot.")]
+    StopBot,
+    #[command(description = "check bot status.")]
+    Status,
+    #[command(
+        description = "set notification level (all/important/critical/none) or settings as \
+                       key=value pairs (level, silent, summary)"
+    )]
+    Notify(String),
+    #[command(description = "request immediate status update")]
+    Update,
+    #[command(description = "display the contents of symbols configuration, one page at a time.")]
+    Symbols(String),
+    #[command(description = "add a new symbol to configuration.")]
+    AddSymbol(String), // Pass a single JSON string or delimited string
+    #[command(description = "remove a symbol from configuration.")]
+    RemoveSymbol(String),
+    #[command(description = "set the timeout in seconds for Telegram sends.")]
+    SendTimeout(u64),
+    #[command(description = "set the strategy interval, e.g. 30m, 1h or 3600 (seconds).")]
+    SetInterval(String),
+    #[command(description = "list notification levels and their meaning.")]
+    Levels,
+    #[command(
+        description = "send the symbols configuration as a document (add 'gzip' to compress)."
+    )]
+    ExportConfig(String),
+    #[command(description = "cap the number of open positions (0 removes the cap).")]
+    MaxPositions(usize),
+    #[command(description = "move a symbol to a position in the configuration (SYMBOL,POSITION).")]
+    MoveSymbol(String),
+    #[command(description = "show where notifications are sent compared to this chat.")]
+    Where,
+    #[command(description = "save current positions to a timestamped JSON file.")]
+    SavePositions,
+    #[command(description = "show which configured symbols are in a position.")]
+    Holdings,
+    #[command(
+        description = "send one notification summary per run instead of per event (on/off)."
+    )]
+    SummaryMode(String),
+    #[command(description = "list commands in the format expected by BotFather.")]
+    BotFather,
+    #[command(description = "remove all disabled symbols (run with 'confirm').")]
+    PurgeDisabled(String),
+    #[command(
+        description = "set notification levels by time of day in the bot's time zone, e.g. \
+                       00:00-08:00=critical,08:00-00:00=important ('off' clears)."
+    )]
+    LevelSchedule(String),
+    #[command(description = "show the last strategy error in full.")]
+    LastError,
+    #[command(description = "set how many symbols the strategy processes in parallel.")]
+    Concurrency(usize),
+    #[command(description = "show notification counts for this session.")]
+    NotifyStats,
+    #[command(description = "stage symbol edits until /apply or /discard.")]
+    Stage,
+    #[command(description = "write all staged symbol edits.")]
+    Apply,
+    #[command(description = "drop all staged symbol edits.")]
+    Discard,
+    #[command(description = "block all trading until /unkill (the runner keeps running).")]
+    Kill,
+    #[command(description = "lift the kill switch.")]
+    Unkill,
+    #[command(description = "send the symbols configuration as symbols.csv.")]
+    ExportCsv,
+    #[command(description = "set a tag prepended to every message, e.g. [PROD] ('off' clears).")]
+    Tag(String),
+    #[command(description = "show the process memory and CPU usage.")]
+    Resources,
+    #[command(
+        description = "write symbol edits to the config file (on) or keep them in memory (off)."
+    )]
+    Persist(String),
+    #[command(description = "check the config, Telegram, the strategy and the runner.")]
+    SelfTest,
+    #[command(description = "show the last caught strategy panic and the backtrace setting.")]
+    LastPanic,
+    #[command(
+        description = "set the seconds before an identical command is accepted again (0 disables)."
+    )]
+    Cooldown(u64),
+    #[command(description = "list config backups, or 'keep N' to set how many are kept.")]
+    Backups(String),
+    #[command(description = "send text rendered with MarkdownV2 and with HTML to compare them.")]
+    FormatTest(String),
+    #[command(
+        description = "choose whether /stopbot waits for the running tick (graceful) or cancels \
+                       it (immediate)."
+    )]
+    StopMode(String),
+    #[command(description = "send batched summary notifications now ('peek' only counts them).")]
+    FlushNotify(String),
+    #[command(description = "show what the running strategy supports, as JSON.")]
+    Capabilities,
+    #[command(description = "set the log level (error, warn, info, debug, trace).")]
+    LogLevel(String),
+    #[command(
+        description = "only send critical notifications for this long after a start, e.g. 5m \
+                       ('off' disables)."
+    )]
+    Warmup(String),
+    #[command(description = "show a compact one-line status.")]
+    S,
+    #[command(
+        description = "choose the /symbols columns, e.g. symbol,entry_threshold ('reset' restores \
+                       the defaults)."
+    )]
+    Columns(String),
+    #[command(
+        description = "run one dry-run tick against a market data file in the replay directory."
+    )]
+    Replay(String),
+    #[command(description = "show how long recent status requests took.")]
+    Latency,
+    #[command(description = "give a chat a role: /role CHAT_ID admin|operator|remove.")]
+    Role(String),
+    #[command(description = "show the trading window, level windows and what happens next.")]
+    ScheduleInfo,
+    #[command(description = "acknowledge a tripped circuit breaker so the bot can start again.")]
+    ClearBreaker,
+    #[command(description = "change one field of a symbol: /editsymbol SYMBOL FIELD VALUE.")]
+    EditSymbol(String),
+    #[command(
+        description = "silence all notifications for a while but keep trading, e.g. 1h ('off' \
+                       ends it)."
+    )]
+    Quiet(String),
+    #[command(description = "send the full status report as a JSON document.")]
+    StatusFile,
+    #[command(
+        description = "alert when no tick succeeds within this many intervals (0 disables)."
+    )]
+    Watchdog(u64),
+    #[command(description = "also send /status replies to every notification target: on or off.")]
+    StatusBroadcast(String),
+    #[command(
+        description = "alert when a status field or strategy metric crosses a value, e.g. \
+                       unrealized_pnl < -100."
+    )]
+    AddRule(String),
+    #[command(description = "list the alert rules.")]
+    ListRules,
+    #[command(description = "remove an alert rule by its number in /listrules.")]
+    RemoveRule(String),
+    #[command(
+        description = "alert when a strategy metric crosses a value: METRIC,OP,VALUE, e.g. \
+                       drawdown,>,5."
+    )]
+    Threshold(String),
+    #[command(description = "reset runtime settings to their defaults (run with 'confirm').")]
+    ResetSettings(String),
+    #[command(description = "show the exchange account the bot trades.")]
+    Account,
+    #[command(description = "set the longest message sent in one piece, in characters.")]
+    MaxLength(String),
+    #[command(description = "show how many notifications the last tick sent and filtered.")]
+    LastTickNotify,
+    #[command(description = "cap the capital deployed until the next start (0 removes the cap).")]
+    Budget(f64),
+    #[command(description = "save the current status report under a label.")]
+    Snapshot(String),
+    #[command(description = "compare a saved snapshot with the current status report.")]
+    DiffStatus(String),
+    #[command(description = "back up the config daily at HH:MM local time ('off' stops it).")]
+    AutoBackup(String),
+    #[command(description = "show the strategy's risk parameters.")]
+    Risk,
+    #[command(description = "change a risk parameter: KEY=VALUE.")]
+    SetRisk(String),
+    #[command(description = "prefix notifications with the time they were sent: on or off.")]
+    Timestamps(String),
+    #[command(description = "show how many commands are being handled or waiting.")]
+    Queue,
+    #[command(description = "report errors with their full chain of causes: on or off.")]
+    VerboseErrors(String),
+    #[command(description = "show when the config file was last modified, its size and symbols.")]
+    ConfigMtime,
+    #[command(description = "receive critical notifications in this chat.")]
+    Subscribe,
+    #[command(description = "stop receiving notifications in this chat.")]
+    Unsubscribe,
+    #[command(description = "rewrite the config file with every field of the latest schema.")]
+    Migrate,
+    #[command(
+        description = "refuse /startbot for this long after the breaker trips, e.g. 15m ('off' \
+                       disables)."
+    )]
+    BreakerCooldown(String),
+    #[command(
+        description = "trip the circuit breaker after this many failed ticks in a row (0 never \
+                       does)."
+    )]
+    BreakerThreshold(u32),
+    #[command(description = "show notification counts per symbol ('reset' clears them).")]
+    SymbolNoise(String),
+    #[command(description = "disable a symbol after this many errors in a row (0 never does).")]
+    SymbolErrorLimit(usize),
+    #[command(
+        description = "show the configured and effective notification level and why they differ."
+    )]
+    EffectiveLevel,
+    #[command(description = "measure the exchange API latency.")]
+    Exchange,
+    #[command(description = "set how many symbols /symbols shows per page.")]
+    PageSize(usize),
+    #[command(description = "show the resolved settings and where each value came from.")]
+    EffectiveConfig,
+    #[command(description = "re-send the last N notifications to this chat.")]
+    ReplayNotify(usize),
+    #[command(description = "cap the entries per day (0 removes the cap).")]
+    MaxTrades(usize),
+    #[command(description = "set the UTC hour at which the daily trade count resets.")]
+    TradeResetHour(u32),
+    #[command(description = "show how text is escaped for MarkdownV2 and HTML.")]
+    Escape(String),
+    #[command(
+        description = "retry a failed strategy run within the tick: COUNT [DELAY_SECS], or off."
+    )]
+    StrategyRetries(String),
+    #[command(description = "list the orders resting on the exchange.")]
+    Orders,
+    #[command(description = "cancel every open order (run with 'confirm').")]
+    CancelAll(String),
+    #[command(description = "limit /symbols to one quote currency, e.g. USDT ('off' shows all).")]
+    Quote(String),
+    #[command(
+        description = "drop repeated notifications within a window, e.g. 5m ('off' disables it)."
+    )]
+    DedupWindow(String),
+    #[command(description = "dump the bot state as JSON for debugging.")]
+    State,
+    #[command(description = "list the symbols with a per-symbol override active.")]
+    Overrides,
+    #[command(description = "switch the strategy to another API endpoint URL.")]
+    Endpoint(String),
+    #[command(description = "chart the total PnL over the last N ticks.")]
+    PnlChart(String),
+    #[command(
+        description = "set the level of restart progress messages (all/important/critical)."
+    )]
+    RestartLevel(String),
+    #[command(description = "send commands to another registered strategy runner.")]
+    Use(String),
+    #[command(description = "show the active strategy runner and the registered ones.")]
+    Active,
+    #[command(description = "show how many notifications were suppressed, by reason.")]
+    Suppressed,
+    #[command(
+        description = "set a chat that gets broadcasts no target could receive (0 clears it)."
+    )]
+    Fallback(i64),
+    #[command(description = "trip the circuit breaker to rehearse incident response.")]
+    TripBreaker,
+    #[command(
+        description = "choose how start and stop are confirmed: reply, message or reaction."
+    )]
+    ConfirmStyle(String),
+    #[command(description = "show each target chat's notification rate-limit budget.")]
+    RateLimit,
+    #[command(description = "set how many notification sends may run at once.")]
+    SendConcurrency(usize),
+    #[command(
+        description = "check the symbols for duplicates, conflicting thresholds and over-budget \
+                       allocation."
+    )]
+    Audit,
+    #[command(
+        description = "share the symbols as a snippet for /importshare (add gzip to shrink it)."
+    )]
+    Share(String),
+    #[command(description = "replace the symbols with a snippet from /share.")]
+    ImportShare(String),
+}
+
+impl Command {
+    /// Commands that change the symbols configuration or access control, or
+    /// read files on the server, reserved for admins
+    fn requires_admin(&self) -> bool {
+        matches!(
+            self,
+            Command::AddSymbol(_)
+                | Command::ImportShare(_)
+                | Command::RemoveSymbol(_)
+                | Command::MoveSymbol(_)
+                | Command::EditSymbol(_)
+                | Command::PurgeDisabled(_)
+                | Command::Stage
+                | Command::Apply
+                | Command::Discard
+                | Command::Persist(_)
+                | Command::Backups(_)
+                | Command::Role(_)
+                | Command::ResetSettings(_)
+                | Command::AutoBackup(_)
+                | Command::SetRisk(_)
+                | Command::Migrate
+                | Command::State
+                | Command::Endpoint(_)
+                | Command::Use(_)
+                | Command::TripBreaker
+                | Command::Replay(_)
+                | Command::FormatTest(_)
+        )
+    }
+
+    /// Commands any chat may use, even without a role
+    fn is_public(&self) -> bool {
+        matches!(self, Command::Subscribe | Command::Unsubscribe)
+    }
+
+    /// Commands that discard or overwrite state get a longer cooldown
+    fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            Command::RemoveSymbol(_)
+                | Command::PurgeDisabled(_)
+                | Command::Apply
+                | Command::Discard
+                | Command::Kill
+                | Command::StopBot
+                | Command::ResetSettings(_)
+                | Command::CancelAll(_)
+        )
+    }
+}
+
+/// Counts of the commands waiting for the handler and being handled,
+/// shared between the handler and its `CommandDispatcher`
+#[derive(Clone, Debug, Default)]
+struct CommandQueue {
+    waiting: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl CommandQueue {
+    /// Count a command as waiting for the handler until the returned guard
+    /// is dropped
+    fn wait(&self) -> QueueGuard {
+        QueueGuard::new(&self.waiting)
+    }
+
+    /// Count a command as being handled until the returned guard is dropped
+    fn start(&self) -> QueueGuard {
+        QueueGuard::new(&self.in_flight)
+    }
+
+    /// Commands waiting for the handler
+    fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Commands being handled right now
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// Keeps a command counted in a `CommandQueue` while alive
+#[derive(Debug)]
+struct QueueGuard {
+    counter: Arc<AtomicUsize>,
+}
+
+impl QueueGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self {
+            counter: Arc::clone(counter),
+        }
+    }
+}
+
+impl Drop for QueueGuard {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Shares a `TelegramBotHandler` between concurrent updates, counting the
+/// commands that wait for it so `/queue` can show a backlog
+pub struct CommandDispatcher {
+    handler: Mutex<TelegramBotHandler>,
+    queue: CommandQueue,
+}
+
+impl CommandDispatcher {
+    pub fn new(handler: TelegramBotHandler) -> Self {
+        Self {
+            queue: handler.command_queue.clone(),
+            handler: Mutex::new(handler),
+        }
+    }
+
+    /// The shared handler, for anything other than commands
+    pub fn handler(&self) -> &Mutex<TelegramBotHandler> {
+        &self.handler
+    }
+
+    /// Wait for the handler and let it handle `cmd`; the command counts as
+    /// waiting until the handler is free
+    pub async fn dispatch(
+        &self,
+        bot: Bot,
+        msg: Message,
+        cmd: Command,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let mut handler = {
+            let _waiting = self.queue.wait();
+            self.handler.lock().await
+        };
+        handler.handle_command(bot, msg, cmd, bot_state).await
+    }
+}
+
+/// Name of the runner created by `TelegramBotHandler::new`
+pub const DEFAULT_RUNNER: &str = "default";
+
+pub struct TelegramBotHandler {
+    /// Request channels of the registered strategy runners, by name
+    runners: BTreeMap<String, mpsc::UnboundedSender<BotRequest>>,
+    /// The runner that commands are sent to; see `/use`
+    active_runner: String,
+    /// When each chat last sent each distinct command, for the cooldown
+    recent_commands: HashMap<(ChatId, String), Instant>,
+    /// Round-trip times of the latest status requests, oldest first
+    status_latencies: std::sync::Mutex<VecDeque<Duration>>,
+    command_queue: CommandQueue,
+}
+
+impl TelegramBotHandler {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<BotRequest>) {
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                runners: BTreeMap::from([(DEFAULT_RUNNER.to_string(), request_tx)]),
+                active_runner: DEFAULT_RUNNER.to_string(),
+                recent_commands: HashMap::new(),
+                status_latencies: std::sync::Mutex::new(VecDeque::new()),
+                command_queue: CommandQueue::default(),
+            },
+            request_rx,
+        )
+    }
+
+    /// Register another strategy runner under `name`, returning the receiver
+    /// to pass to its `init_and_run_bot`
+    pub fn register_runner(
+        &mut self,
+        name: &str,
+    ) -> Result<mpsc::UnboundedReceiver<BotRequest>, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err("Runner names must be non-empty and without spaces".to_string());
+        }
+        if self.runners.contains_key(name) {
+            return Err(format!("A runner named '{}' is already registered", name));
+        }
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        self.runners.insert(name.to_string(), request_tx);
+        Ok(request_rx)
+    }
+
+    /// The runner commands currently go to
+    pub fn active_runner(&self) -> &str {
+        &self.active_runner
+    }
+
+    /// Send commands to the runner registered as `name` from now on
+    pub fn use_runner(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if !self.runners.contains_key(name) {
+            return Err(format!(
+                "Unknown runner '{}'. Registered: {}",
+                name,
+                self.runners.keys().cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        self.active_runner = name.to_string();
+        Ok(())
+    }
+
+    fn request_tx(&self) -> &mpsc::UnboundedSender<BotRequest> {
+        &self.runners[&self.active_runner]
+    }
+
+    /// Record `cmd` and report whether it repeats the same chat's identical
+    /// command within the cooldown window
+    fn is_rapid_repeat(&mut self, chat_id: ChatId, cmd: &Command, cooldown_secs: u64) -> bool {
+        if cooldown_secs == 0 {
+            return false;
+        }
+        let base = Duration::from_secs(cooldown_secs);
+        let window = if cmd.is_destructive() {
+            base * DESTRUCTIVE_COOLDOWN_FACTOR
+        } else {
+            base
+        };
+
+        let now = Instant::now();
+        let longest = base * DESTRUCTIVE_COOLDOWN_FACTOR;
+        self.recent_commands
+            .retain(|_, seen| now.duration_since(*seen) < longest);
+
+        let key = (chat_id, format!("{:?}", cmd));
+        match self.recent_commands.get(&key) {
+            Some(seen) if now.duration_since(*seen) < window => true,
+            _ => {
+                self.recent_commands.insert(key, now);
+                false
+            }
+        }
+    }
+
+    /// Receive every event published by the active runner's bot from now on,
+    /// such as starts, stops, kill switch changes and notifications
+    pub async fn subscribe(&self) -> Result<broadcast::Receiver<BotEvent>, String> {
+        self.request(BotRequest::Subscribe).await
+    }
+
+    /// Send a request to the runner and wait for its response
+    async fn request<R>(
+        &self,
+        make_request: impl FnOnce(oneshot::Sender<Result<R, String>>) -> BotRequest,
+    ) -> Result<R, String> {
+        let (tx, rx) = oneshot::channel();
+        self.request_tx()
+            .send(make_request(tx))
+            .map_err(|_| "Bot runner unavailable".to_string())?;
+
+        rx.await
+            .map_err(|_| "Bot runner dropped response channel".to_string())?
+    }
+
+    async fn request_status(&self) -> Result<(StatusReport, String), String> {
+        let started = Instant::now();
+        let status = self.request(BotRequest::GetStatus).await;
+        if status.is_ok() {
+            self.record_status_latency(started.elapsed());
+        }
+        status
+    }
+
+    fn record_status_latency(&self, latency: Duration) {
+        let mut latencies = self
+            .status_latencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if latencies.len() == MAX_RECORDED_LATENCIES {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// The recorded status-request latencies, oldest first
+    pub fn status_latencies(&self) -> Vec<Duration> {
+        self.status_latencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    async fn request_positions(&self) -> Result<Vec<PositionInfo>, String> {
+        self.request(BotRequest::GetPositions).await
+    }
+
+    /// Handle incoming Telegram commands
+    pub async fn handle_command(
+        &mut self,
+        bot: Bot,
+        msg: Message,
+        cmd: Command,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let _in_flight = self.command_queue.start();
+        let (role, cooldown_secs) = {
+            let state = bot_state.lock().await;
+            (state.role_of(msg.chat.id), state.command_cooldown_secs)
+        };
+        match role {
+            None if cmd.is_public() => {}
+            None => {
+                send_reply(
+                    &bot,
+                    msg.chat.id,
+                    &bot_state,
+                    "You are not authorized to use this bot.",
+                )
+                .await?;
+                return Ok(());
+            }
+            Some(Role::Operator) if cmd.requires_admin() => {
+                send_reply(
+                    &bot,
+                    msg.chat.id,
+                    &bot_state,
+                    "This command requires the admin role.",
+                )
+                .await?;
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+
+        if self.is_rapid_repeat(msg.chat.id, &cmd, cooldown_secs) {
+            send_reply(
+                &bot,
+                msg.chat.id,
+                &bot_state,
+                "Slow down, that command was just run.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        // Until the runner adds its chat, notifications go to the first chat
+        // that uses the bot
+        let claimed = role.is_some() && bot_state.lock().await.claim_default_target(msg.chat.id);
+        if claimed {
+            send_reply(
+                &bot,
+                msg.chat.id,
+                &bot_state,
+                "No notification target was configured, so this chat will receive notifications. \
+                 Use /unsubscribe to stop them.",
+            )
+            .await?;
+        }
+
+        // Settings a command changes are attributed to it, except that
+        // /resetsettings returns them to their defaults
+        let records_changes = !matches!(cmd, Command::ResetSettings(_));
+        let before = setting_values(&*bot_state.lock().await);
+        let result = self
+            .run_command(bot, msg, cmd, Arc::clone(&bot_state))
+            .await;
+        if records_changes {
+            bot_state
+                .lock()
+                .await
+                .record_setting_changes(&before, SettingSource::Runtime);
+        }
+        result
+    }
+
+    /// Carry out a command the chat is allowed to run
+    async fn run_command(
+        &mut self,
+        bot: Bot,
+        msg: Message,
+        cmd: Command,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        match cmd {
+            Command::Help => {
+                send_reply(
+                    &bot,
+                    msg.chat.id,
+                    &bot_state,
+                    Command::descriptions().to_string(),
+                )
+                .await?;
+            }
+            Command::StartBot => {
+                if bot_state.lock().await.circuit_breaker.tripped {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "The circuit breaker is tripped. Use /clearbreaker to acknowledge it \
+                         first.",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let cooldown = {
+                    let state = bot_state.lock().await;
+                    state.circuit_breaker.cooldown_remaining(state.now())
+                };
+                if let Some(remaining) = cooldown {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "The circuit breaker is cooling down, {} remaining.",
+                            format_duration(remaining)
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let was_running = {
+                    let mut state = bot_state.lock().await;
+                    state.stop_announced = false;
+                    state.held_by_breaker = false;
+                    let was_running = std::mem::replace(&mut state.is_running, true);
+                    if !was_running {
+                        let now = state.now();
+                        state.start_warmup(now);
+                        // A stopped bot doesn't tick, so don't count the
+                        // time it was stopped as a stall
+                        state.record_tick(now);
+                        state.deployed_capital = 0.0;
+                        state.publish(BotEvent::Started);
+                    }
+                    was_running
+                };
+                if !was_running {
+                    send_confirmation(&bot, &msg, &bot_state, "Trading bot started!").await?;
+                } else {
+                    send_reply(&bot, msg.chat.id, &bot_state, "Bot is already running.").await?;
+                }
+            }
+            Command::StopBot => {
+                let was_running = {
+                    let mut state = bot_state.lock().await;
+                    let was_running = std::mem::replace(&mut state.is_running, false);
+                    if was_running {
+                        state.stop_announced = true;
+                        state.publish(BotEvent::Stopped);
+                        if state.stop_mode == StopMode::Immediate {
+                            if let Some(tick_cancel) = &state.tick_cancel {
+                                tick_cancel.cancel();
+                            }
+                        }
+                    }
+                    was_running
+                };
+                if was_running {
+                    send_confirmation(&bot, &msg, &bot_state, "Trading bot stopped.").await?;
+                } else {
+                    send_reply(&bot, msg.chat.id, &bot_state, "Bot is not running.").await?;
+                }
+            }
+            Command::Status => {
+                let (
+                    breaker_tripped,
+                    kill_switch,
+                    is_running,
+                    notification_level,
+                    max_positions,
+                    budget,
+                    staged_changes,
+                    unsaved_changes,
+                    warmup_remaining,
+                    quiet_remaining,
+                ) = {
+                    let state = bot_state.lock().await;
+                    (
+                        state.circuit_breaker.tripped,
+                        state.kill_switch,
+                        state.is_running,
+                        state.notification_level.clone(),
+                        state.max_positions,
+                        state
+                            .session_budget
+                            .map(|budget| (budget, state.deployed_capital)),
+                        state.staged_symbols.as_ref().map(|_| state.staged_changes),
+                        state.unsaved_changes,
+                        state.warmup_remaining(state.now()),
+                        state.quiet_remaining(state.now()),
+                    )
+                };
+
+                let mut lines = Vec::new();
+                if breaker_tripped {
+                    lines.push("*** CIRCUIT BREAKER TRIPPED - use /clearbreaker ***".to_string());
+                }
+                if kill_switch {
+                    lines.push("*** KILL SWITCH ACTIVE - all trading is blocked ***".to_string());
+                }
+                lines.extend([
+                    if is_running {
+                        "Bot is running."
+                    } else {
+                        "Bot is stopped."
+                    }
+                    .to_string(),
+                    format!("Notification level: {:?}", notification_level),
+                ]);
+
+                let status = if is_running {
+                    Some(self.request_status().await)
+                } else {
+                    None
+                };
+
+                if let Some(max_positions) = max_positions {
+                    match &status {
+                        Some(Ok((report, _))) => lines.push(format!(
+                            "Max positions: {} (open: {})",
+                            max_positions, report.open_positions
+                        )),
+                        _ => lines.push(format!("Max positions: {}", max_positions)),
+                    }
+                }
+
+                if let Some((budget, deployed)) = budget {
+                    lines.push(format!(
+                        "Session budget: {:.2} deployed of {:.2}",
+                        deployed, budget
+                    ));
+                }
+
+                if let Some(staged_changes) = staged_changes {
+                    lines.push(format!(
+                        "Staged changes: {} (use /apply or /discard)",
+                        staged_changes
+                    ));
+                }
+
+                if let Some(remaining) = quiet_remaining {
+                    lines.push(format!(
+                        "Quiet: {} left (no notifications)",
+                        format_duration(remaining)
+                    ));
+                }
+
+                if let Some(remaining) = warmup_remaining {
+                    lines.push(format!(
+                        "Warmup: {} left (only critical notifications)",
+                        format_duration(remaining)
+                    ));
+                }
+
+                if unsaved_changes {
+                    lines.push(
+                        "Unsaved in-memory symbol changes (use /persist on to return to the file)"
+                            .to_string(),
+                    );
+                }
+
+                match status {
+                    Some(Ok((_, text))) => {
+                        lines.push(String::new());
+                        lines.push(text);
+                    }
+                    Some(Err(err)) => {
+                        lines.push(format!("Failed to retrieve status: {}", err));
+                    }
+                    None => {}
+                }
+
+                let text = lines.join("\n");
+                let recipients = bot_state.lock().await.status_recipients(msg.chat.id);
+                send_chunked(&bot, msg.chat.id, &bot_state, &text).await?;
+                for chat_id in recipients.into_iter().skip(1) {
+                    if let Err(e) = send_chunked(&bot, chat_id, &bot_state, &text).await {
+                        eprintln!("Error broadcasting status to {}: {}", chat_id, e);
+                    }
+                }
+            }
+            Command::Notify(level_str) if level_str.contains('=') => {
+                self.handle_notify_settings(&bot, msg.chat.id, level_str, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Notify(level_str) => {
+                let (level, reply) = match level_str.to_lowercase().as_str() {
+                    "all" => (NotificationLevel::All, "Notification level set to All"),
+                    "important" => (
+                        NotificationLevel::Important,
+                        "Notification level set to Important",
+                    ),
+                    "critical" => (
+                        NotificationLevel::Critical,
+                        "Notification level set to Critical",
+                    ),
+                    "none" => (NotificationLevel::None, "Notifications disabled"),
+                    _ => {
+                        send_reply(
+                            &bot,
+                            msg.chat.id,
+                            &bot_state,
+                            "Invalid level. Use: all, important, critical, or none",
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                };
+                let unchanged = {
+                    let mut state = bot_state.lock().await;
+                    let unchanged = state.notification_level == level;
+                    state.notification_level = level.clone();
+                    unchanged
+                };
+                if unchanged {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!("Notification level already set to {:?}", level),
+                    )
+                    .await?;
+                } else {
+                    send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+                }
+            }
+            Command::Levels => {
+                let active = bot_state.lock().await.notification_level.clone();
+                send_reply(&bot, msg.chat.id, &bot_state, render_levels(&active)).await?;
+            }
+            Command::ExportConfig(args) => {
+                self.handle_export_config(&bot, msg.chat.id, args, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::MaxPositions(max_positions) => {
+                let max_positions = (max_positions > 0).then_some(max_positions);
+                let previous =
+                    std::mem::replace(&mut bot_state.lock().await.max_positions, max_positions);
+                let reply = match (previous == max_positions, max_positions) {
+                    (true, None) => "No position cap is set.".to_string(),
+                    (true, Some(max)) => format!("Max positions already set to {}", max),
+                    (false, None) => "Position cap removed.".to_string(),
+                    (false, Some(max)) => format!("Max positions set to {}", max),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Where => {
+                let targets = bot_state.lock().await.notification_targets.clone();
+                send_reply(
+                    &bot,
+                    msg.chat.id,
+                    &bot_state,
+                    render_where(&targets, msg.chat.id),
+                )
+                .await?;
+            }
+            Command::SavePositions => {
+                self.handle_save_positions(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Holdings => {
+                self.handle_holdings(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::SummaryMode(mode) => {
+                let enabled = match parse_toggle(&mode) {
+                    Some(enabled) => enabled,
+                    None => {
+                        send_reply(&bot, msg.chat.id, &bot_state, "Usage: /summarymode on|off")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let previous = std::mem::replace(&mut bot_state.lock().await.summary_mode, enabled);
+                let state_name = if enabled { "on" } else { "off" };
+                let reply = if previous == enabled {
+                    format!("Summary mode already {}", state_name)
+                } else {
+                    format!("Summary mode turned {}", state_name)
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::BotFather => {
+                send_chunked(&bot, msg.chat.id, &bot_state, &botfather_commands()).await?;
+            }
+            Command::PurgeDisabled(confirm) => {
+                self.handle_purge_disabled(&bot, msg.chat.id, confirm, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::LevelSchedule(schedule) => {
+                let reply = match schedule.trim() {
+                    "" => {
+                        let state = bot_state.lock().await;
+                        render_level_schedule(
+                            &state.level_schedule,
+                            &state.notification_level,
+                            state.time_zone,
+                        )
+                    }
+                    "off" => {
+                        bot_state.lock().await.level_schedule.clear();
+                        "Level schedule cleared.".to_string()
+                    }
+                    schedule => match parse_level_schedule(schedule) {
+                        Ok(windows) => {
+                            let mut state = bot_state.lock().await;
+                            state.level_schedule = windows;
+                            render_level_schedule(
+                                &state.level_schedule,
+                                &state.notification_level,
+                                state.time_zone,
+                            )
+                        }
+                        Err(err) => err,
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::LastError => {
+                let reply = match self.request(BotRequest::GetLastError).await {
+                    Ok(Some(record)) => format!(
+                        "Last error at {}:\n{}",
+                        record.at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        record.message
+                    ),
+                    Ok(None) => "No errors recorded since the runner started.".to_string(),
+                    Err(err) => format!("Unable to retrieve last error: {}", err),
+                };
+                send_chunked(&bot, msg.chat.id, &bot_state, &reply).await?;
+            }
+            Command::Concurrency(limit) => {
+                if !(1..=MAX_STRATEGY_CONCURRENCY).contains(&limit) {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "Usage: /concurrency N, with N between 1 and {}",
+                            MAX_STRATEGY_CONCURRENCY
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let previous =
+                    std::mem::replace(&mut bot_state.lock().await.strategy_concurrency, limit);
+                let reply = if previous == limit {
+                    format!("Strategy concurrency already set to {}", limit)
+                } else {
+                    format!("Strategy concurrency set to {}", limit)
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::NotifyStats => {
+                let stats = bot_state.lock().await.notification_stats.clone();
+                send_reply(&bot, msg.chat.id, &bot_state, stats.render()).await?;
+            }
+            Command::Stage => {
+                self.handle_stage(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Apply => {
+                self.handle_apply(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Discard => {
+                let discarded = {
+                    let mut state = bot_state.lock().await;
+                    state
+                        .staged_symbols
+                        .take()
+                        .map(|_| std::mem::take(&mut state.staged_changes))
+                };
+                let reply = match discarded {
+                    Some(count) => format!("Discarded {} staged change(s).", count),
+                    None => "Nothing is staged.".to_string(),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Kill => {
+                let already_active = {
+                    let mut state = bot_state.lock().await;
+                    let already_active = std::mem::replace(&mut state.kill_switch, true);
+                    if !already_active {
+                        state.publish(BotEvent::KillSwitch { active: true });
+                    }
+                    already_active
+                };
+                if already_active {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "Kill switch is already active.",
+                    )
+                    .await?;
+                } else {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "Kill switch activated. Use /unkill to resume trading.",
+                    )
+                    .await?;
+                    if let Err(e) = broadcast_notification(
+                        &bot,
+                        &bot_state,
+                        NotificationLevel::Critical,
+                        "KILL SWITCH ACTIVATED: all trading is blocked.".to_string(),
+                    )
+                    .await
+                    {
+                        eprintln!(
+                            "Error sending kill switch alert: {}",
+                            describe_error(&*e, true)
+                        );
+                    }
+                }
+            }
+            Command::Unkill => {
+                let was_active = {
+                    let mut state = bot_state.lock().await;
+                    let was_active = std::mem::replace(&mut state.kill_switch, false);
+                    if was_active {
+                        state.publish(BotEvent::KillSwitch { active: false });
+                    }
+                    was_active
+                };
+                let reply = if was_active {
+                    "Kill switch lifted. Trading resumes on the next tick."
+                } else {
+                    "Kill switch is not active."
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::ExportCsv => {
+                self.handle_export_csv(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Tag(tag) => {
+                let reply = match tag.trim() {
+                    "" => match bot_state.lock().await.instance_tag.clone() {
+                        Some(tag) => format!("Instance tag: {}", tag),
+                        None => "No instance tag is set.".to_string(),
+                    },
+                    "off" | "clear" => {
+                        bot_state.lock().await.instance_tag = None;
+                        "Instance tag cleared.".to_string()
+                    }
+                    tag if tag.chars().count() > MAX_TAG_LENGTH => {
+                        format!("Tag is too long (max {} characters).", MAX_TAG_LENGTH)
+                    }
+                    tag => {
+                        bot_state.lock().await.instance_tag = Some(tag.to_string());
+                        "Instance tag set.".to_string()
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Resources => match resources_table() {
+                Ok(table) => send_table(&bot, msg.chat.id, &bot_state, &table).await?,
+                Err(err) => send_reply(&bot, msg.chat.id, &bot_state, err).await?,
+            },
+            Command::Persist(mode) => {
+                self.handle_persist(&bot, msg.chat.id, mode, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::SelfTest => {
+                let results = self.run_self_test(&bot, &bot_state).await;
+                send_table(&bot, msg.chat.id, &bot_state, &render_self_test(&results)).await?;
+            }
+            Command::LastPanic => {
+                let mut reply = format!(
+                    "Backtrace capture: {}\n\n",
+                    if backtrace_capture_enabled() {
+                        "on"
+                    } else {
+                        "off (set RUST_BACKTRACE=1 to enable)"
+                    }
+                );
+                match self.request(BotRequest::GetLastPanic).await {
+                    Ok(Some(record)) => {
+                        reply.push_str(&format!(
+                            "Last panic at {}:\n{}",
+                            record.at.format("%Y-%m-%d %H:%M:%S UTC"),
+                            record.message
+                        ));
+                        if let Some(backtrace) = record.backtrace {
+                            reply.push_str(&format!("\n\nBacktrace:\n{}", backtrace));
+                        }
+                    }
+                    Ok(None) => reply.push_str("No panics caught since the runner started."),
+                    Err(err) => reply.push_str(&format!("Unable to retrieve last panic: {}", err)),
+                }
+                send_chunked(&bot, msg.chat.id, &bot_state, &reply).await?;
+            }
+            Command::Cooldown(secs) => {
+                let reply = if secs > MAX_COMMAND_COOLDOWN_SECS {
+                    format!(
+                        "Invalid cooldown. Use a value between 0 and {} seconds.",
+                        MAX_COMMAND_COOLDOWN_SECS
+                    )
+                } else {
+                    let previous =
+                        std::mem::replace(&mut bot_state.lock().await.command_cooldown_secs, secs);
+                    match (previous == secs, secs) {
+                        (true, 0) => "Command cooldown is already off".to_string(),
+                        (true, _) => format!("Command cooldown already set to {} seconds", secs),
+                        (false, 0) => "Command cooldown turned off".to_string(),
+                        (false, _) => format!("Command cooldown set to {} seconds", secs),
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Backups(args) => {
+                self.handle_backups(&bot, msg.chat.id, args, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::FormatTest(text) => {
+                if text.trim().is_empty() {
+                    send_reply(&bot, msg.chat.id, &bot_state, "Usage: /formattest TEXT").await?;
+                } else {
+                    let failures = send_format_test(&bot, msg.chat.id, &bot_state, &text).await;
+                    if !failures.is_empty() {
+                        send_reply(&bot, msg.chat.id, &bot_state, failures.join("\n")).await?;
+                    }
+                }
+            }
+            Command::StopMode(mode) => {
+                let reply = if mode.trim().is_empty() {
+                    format!("Stop mode: {:?}", bot_state.lock().await.stop_mode)
+                } else {
+                    match mode.parse::<StopMode>() {
+                        Ok(mode) => {
+                            let previous =
+                                std::mem::replace(&mut bot_state.lock().await.stop_mode, mode);
+                            if previous == mode {
+                                format!("Stop mode already set to {:?}", mode)
+                            } else {
+                                format!("Stop mode set to {:?}", mode)
+                            }
+                        }
+                        Err(err) => err,
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::FlushNotify(args) => {
+                let reply = match args.trim() {
+                    "peek" => {
+                        let pending = bot_state.lock().await.summary_buffer.len();
+                        format!("{} notification(s) pending.", pending)
+                    }
+                    "" => match flush_notification_summary(&bot, &bot_state).await {
+                        Ok(0) => "No notifications pending.".to_string(),
+                        Ok(flushed) => format!("Flushed {} notification(s).", flushed),
+                        Err(err) => format!("Failed to flush notifications: {}", err),
+                    },
+                    _ => "Usage: /flushnotify [peek]".to_string(),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Capabilities => {
+                let reply = match self.request(BotRequest::GetCapabilities).await {
+                    Ok(capabilities) => serde_json::to_string_pretty(&capabilities)
+                        .unwrap_or_else(|e| format!("Failed to serialize capabilities: {}", e)),
+                    Err(err) => format!("Unable to retrieve capabilities: {}", err),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::LogLevel(level) => {
+                let reply = change_log_level(&level);
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Warmup(value) => {
+                let reply = match value.trim() {
+                    "" => {
+                        let state = bot_state.lock().await;
+                        match state.warmup_remaining(state.now()) {
+                            Some(remaining) => format!(
+                                "Warmup: {} ({} left)",
+                                format_duration(state.warmup),
+                                format_duration(remaining)
+                            ),
+                            None if state.warmup.is_zero() => "Warmup is off".to_string(),
+                            None => format!("Warmup: {}", format_duration(state.warmup)),
+                        }
+                    }
+                    "off" => {
+                        let mut state = bot_state.lock().await;
+                        state.warmup = Duration::ZERO;
+                        state.warmup_until = None;
+                        "Warmup turned off".to_string()
+                    }
+                    value => match parse_duration(value) {
+                        Ok(warmup) => {
+                            bot_state.lock().await.warmup = warmup;
+                            format!(
+                                "Warmup set to {}; it applies from the next /startbot",
+                                format_duration(warmup)
+                            )
+                        }
+                        Err(err) => err,
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::S => {
+                let (is_running, kill_switch, next_tick_at, clock) = {
+                    let state = bot_state.lock().await;
+                    (
+                        state.is_running,
+                        state.kill_switch,
+                        state.next_tick_at,
+                        state.clock.clone(),
+                    )
+                };
+                let report = if is_running {
+                    self.request_status().await.ok().map(|(report, _)| report)
+                } else {
+                    None
+                };
+                let next_tick = next_tick_at.and_then(|at| (at - clock.now()).to_std().ok());
+                let line = compact_status(is_running, kill_switch, report.as_ref(), next_tick);
+                send_reply(&bot, msg.chat.id, &bot_state, line).await?;
+            }
+            Command::Columns(fields) => {
+                let available = SymbolColumn::ALL_COLUMNS
+                    .iter()
+                    .map(SymbolColumn::field_name)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let columns = match fields.trim() {
+                    "" => None,
+                    "reset" => Some(Ok(SymbolColumn::defaults())),
+                    fields => Some(parse_symbol_columns(fields)),
+                };
+                let reply = match columns {
+                    None => {
+                        let current = bot_state.lock().await.symbol_columns.clone();
+                        format!(
+                            "Columns: {}\nAvailable: {}",
+                            current
+                                .iter()
+                                .map(SymbolColumn::field_name)
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            available
+                        )
+                    }
+                    Some(Ok(columns)) => {
+                        let names = columns
+                            .iter()
+                            .map(SymbolColumn::field_name)
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        bot_state.lock().await.symbol_columns = columns;
+                        format!("Columns set to {}", names)
+                    }
+                    Some(Err(errors)) => {
+                        format!("{}\nAvailable: {}", errors.join("\n"), available)
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Replay(path) => {
+                self.handle_replay(&bot, msg.chat.id, path, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Latency => {
+                let reply = render_latencies(&self.status_latencies());
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Role(args) => {
+                let reply = assign_role(&bot_state, msg.chat.id, &args).await;
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::ScheduleInfo => {
+                let reply = {
+                    let state = bot_state.lock().await;
+                    render_schedule_info(
+                        state.now().with_timezone(&state.time_zone),
+                        state.trading_window.as_ref(),
+                        &state.level_schedule,
+                        &state.notification_level,
+                    )
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::ClearBreaker => {
+                let by = msg
+                    .from
+                    .as_ref()
+                    .map(|user| match &user.username {
+                        Some(username) => format!("@{}", username),
+                        None => format!("user {}", user.id),
+                    })
+                    .unwrap_or_else(|| format!("chat {}", msg.chat.id));
+                let cleared = {
+                    let mut state = bot_state.lock().await;
+                    let now = state.now();
+                    state.circuit_breaker.acknowledge(by.clone(), now)
+                };
+                let reply = if cleared {
+                    println!("Circuit breaker cleared by {}", by);
+                    format!(
+                        "Circuit breaker cleared by {}. Use /startbot to resume trading.",
+                        by
+                    )
+                } else {
+                    "The circuit breaker is not tripped.".to_string()
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::EditSymbol(args) => {
+                self.handle_edit_symbol(&bot, msg.chat.id, args, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Quiet(value) => {
+                let now = bot_state.lock().await.now();
+                let reply = match value.trim() {
+                    "" => match bot_state.lock().await.quiet_remaining(now) {
+                        Some(remaining) => format!("Quiet for {} more", format_duration(remaining)),
+                        None => "Notifications are not silenced.".to_string(),
+                    },
+                    "off" => {
+                        bot_state.lock().await.quiet_until = None;
+                        "Quiet ended; notifications resume.".to_string()
+                    }
+                    value => match parse_duration(value).and_then(|quiet| {
+                        chrono::Duration::from_std(quiet)
+                            .map_err(|_| "Duration is too long".to_string())
+                    }) {
+                        Ok(quiet) if !quiet.is_zero() => {
+                            bot_state.lock().await.quiet_until = Some(now + quiet);
+                            format!(
+                                "Notifications silenced for {}; trading continues.",
+                                format_duration(quiet.to_std().unwrap_or_default())
+                            )
+                        }
+                        Ok(_) => "Quiet duration must be longer than zero.".to_string(),
+                        Err(err) => err,
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::StatusFile => {
+                self.handle_status_file(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Watchdog(intervals) => {
+                let reply = if intervals > MAX_WATCHDOG_INTERVALS {
+                    format!(
+                        "Invalid threshold. Use a value between 0 and {} intervals.",
+                        MAX_WATCHDOG_INTERVALS
+                    )
+                } else {
+                    let previous = std::mem::replace(
+                        &mut bot_state.lock().await.watchdog_intervals,
+                        intervals,
+                    );
+                    match (previous == intervals, intervals) {
+                        (true, 0) => "Watchdog is already off".to_string(),
+                        (true, _) => format!("Watchdog already set to {} intervals", intervals),
+                        (false, 0) => "Watchdog turned off".to_string(),
+                        (false, _) => format!(
+                            "Watchdog will alert after {} intervals without a successful tick",
+                            intervals
+                        ),
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::StatusBroadcast(mode) => {
+                let reply = match parse_toggle(&mode) {
+                    Some(broadcast) => {
+                        let previous = std::mem::replace(
+                            &mut bot_state.lock().await.status_broadcast,
+                            broadcast,
+                        );
+                        match (previous == broadcast, broadcast) {
+                            (true, true) => "Status broadcast is already on",
+                            (true, false) => "Status broadcast is already off",
+                            (false, true) => {
+                                "Status replies now also go to every notification target"
+                            }
+                            (false, false) => "Status replies now only go to the requesting chat",
+                        }
+                    }
+                    None => "Usage: /statusbroadcast on|off",
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::AddRule(rule) => match rule.parse::<AlertRule>() {
+                Ok(rule) => {
+                    self.add_alert_rule(&bot, msg.chat.id, rule, Arc::clone(&bot_state))
+                        .await?
+                }
+                Err(err) => send_reply(&bot, msg.chat.id, &bot_state, err).await?,
+            },
+            Command::Threshold(threshold) => match parse_threshold(&threshold) {
+                Ok(rule) => {
+                    self.add_alert_rule(&bot, msg.chat.id, rule, Arc::clone(&bot_state))
+                        .await?
+                }
+                Err(err) => send_reply(&bot, msg.chat.id, &bot_state, err).await?,
+            },
+            Command::ListRules => {
+                let rules = bot_state.lock().await.alert_rules.clone();
+                let reply = if rules.is_empty() {
+                    "No alert rules. Add one with /addrule FIELD OP VALUE.".to_string()
+                } else {
+                    rules
+                        .iter()
+                        .enumerate()
+                        .map(|(i, rule)| format!("{}. {}", i + 1, rule))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::RemoveRule(number) => {
+                let reply = match number.trim().parse::<usize>() {
+                    Ok(number) => {
+                        let mut state = bot_state.lock().await;
+                        if number == 0 || number > state.alert_rules.len() {
+                            format!("There is no alert rule #{}", number)
+                        } else {
+                            let rule = state.alert_rules.remove(number - 1);
+                            state.firing_alerts.retain(|firing| *firing != rule);
+                            format!("Alert rule #{} removed: {}", number, rule)
+                        }
+                    }
+                    Err(_) => "Usage: /removerule NUMBER (see /listrules)".to_string(),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::ResetSettings(confirm) => {
+                self.handle_reset_settings(&bot, msg.chat.id, confirm, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Account => {
+                let reply = match self.request(BotRequest::GetAccount).await {
+                    Ok(account) => render_account(&account),
+                    Err(err) => format!("Unable to retrieve account: {}", err),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::MaxLength(value) => {
+                let reply = match value.trim() {
+                    "" => format!(
+                        "Maximum message length: {}",
+                        bot_state.lock().await.max_message_length
+                    ),
+                    value => match value.parse::<usize>() {
+                        Ok(length)
+                            if (MIN_MESSAGE_LENGTH..=TELEGRAM_MAX_MESSAGE_LENGTH)
+                                .contains(&length) =>
+                        {
+                            let previous = std::mem::replace(
+                                &mut bot_state.lock().await.max_message_length,
+                                length,
+                            );
+                            if previous == length {
+                                format!("Maximum message length already set to {}", length)
+                            } else {
+                                format!("Maximum message length set to {}", length)
+                            }
+                        }
+                        _ => format!(
+                            "Invalid length. Use a value between {} and {}.",
+                            MIN_MESSAGE_LENGTH, TELEGRAM_MAX_MESSAGE_LENGTH
+                        ),
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::LastTickNotify => {
+                let reply = match bot_state.lock().await.last_tick_notifications {
+                    Some(counts) => counts.render(),
+                    None => "No tick has completed yet.".to_string(),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Budget(budget) => {
+                let reply = if !budget.is_finite() || budget < 0.0 {
+                    "Invalid budget. Use a non-negative amount.".to_string()
+                } else {
+                    let budget = (budget > 0.0).then_some(budget);
+                    let (previous, deployed) = {
+                        let mut state = bot_state.lock().await;
+                        (
+                            std::mem::replace(&mut state.session_budget, budget),
+                            state.deployed_capital,
+                        )
+                    };
+                    match (previous == budget, budget) {
+                        (true, None) => "No session budget is set.".to_string(),
+                        (true, Some(budget)) => {
+                            format!("Session budget already set to {:.2}", budget)
+                        }
+                        (false, None) => "Session budget removed.".to_string(),
+                        (false, Some(budget)) => format!(
+                            "Session budget set to {:.2} ({:.2} already deployed)",
+                            budget, deployed
+                        ),
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Snapshot(label) => {
+                let label = snapshot_label(&label);
+                let reply = match self.request_status().await {
+                    Ok((report, _)) => {
+                        let mut state = bot_state.lock().await;
+                        if !state.status_snapshots.contains_key(&label)
+                            && state.status_snapshots.len() >= MAX_STATUS_SNAPSHOTS
+                        {
+                            format!(
+                                "Snapshot limit of {} reached; reuse a label instead.",
+                                MAX_STATUS_SNAPSHOTS
+                            )
+                        } else {
+                            let snapshot = StatusSnapshot {
+                                at: state.now(),
+                                report,
+                            };
+                            state.status_snapshots.insert(label.clone(), snapshot);
+                            format!("Status saved as snapshot '{}'", label)
+                        }
+                    }
+                    Err(err) => format!("Failed to retrieve status: {}", err),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::DiffStatus(label) => {
+                let label = snapshot_label(&label);
+                let snapshot = bot_state.lock().await.status_snapshots.get(&label).cloned();
+                let reply = match snapshot {
+                    Some(snapshot) => match self.request_status().await {
+                        Ok((report, _)) => {
+                            let now = bot_state.lock().await.now();
+                            render_status_diff(&label, &snapshot, &report, now)
+                        }
+                        Err(err) => format!("Failed to retrieve status: {}", err),
+                    },
+                    None => format!("No snapshot named '{}'. Save one with /snapshot.", label),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::AutoBackup(value) => {
+                let reply = match value.trim() {
+                    "" => match bot_state.lock().await.auto_backup_at {
+                        Some(at) => {
+                            format!("Automatic backups run daily at {}", at.format("%H:%M"))
+                        }
+                        None => "Automatic backups are off.".to_string(),
+                    },
+                    "off" => {
+                        let mut state = bot_state.lock().await;
+                        let now = state.now();
+                        state.schedule_auto_backup(None, now);
+                        "Automatic backups turned off.".to_string()
+                    }
+                    value => match parse_time(value) {
+                        Ok(at) => {
+                            let mut state = bot_state.lock().await;
+                            let now = state.now();
+                            state.schedule_auto_backup(Some(at), now);
+                            format!("Config will be backed up daily at {}", at.format("%H:%M"))
+                        }
+                        Err(err) => err,
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Risk => match self.request(BotRequest::GetRiskParams).await {
+                Ok(params) if params.is_empty() => {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "The strategy declares no risk parameters.",
+                    )
+                    .await?;
+                }
+                Ok(params) => {
+                    send_table(&bot, msg.chat.id, &bot_state, &risk_table(&params)).await?;
+                }
+                Err(err) => {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!("Unable to retrieve risk parameters: {}", err),
+                    )
+                    .await?;
+                }
+            },
+            Command::SetRisk(assignment) => {
+                let reply = match parse_risk_assignment(&assignment) {
+                    Ok((key, value)) => match self
+                        .request(|tx| BotRequest::SetRiskParam(key.clone(), value, tx))
+                        .await
+                    {
+                        Ok(()) => format!("Risk parameter {} set to {}", key, value),
+                        Err(err) => format!("Unable to set {}: {}", key, err),
+                    },
+                    Err(err) => err,
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Timestamps(mode) => {
+                let reply = match parse_toggle(&mode) {
+                    Some(enabled) => {
+                        let previous = std::mem::replace(
+                            &mut bot_state.lock().await.timestamp_notifications,
+                            enabled,
+                        );
+                        match (previous == enabled, enabled) {
+                            (true, true) => "Notification timestamps are already on",
+                            (true, false) => "Notification timestamps are already off",
+                            (false, true) => "Notifications now start with the time they were sent",
+                            (false, false) => "Notification timestamps turned off",
+                        }
+                    }
+                    None => "Usage: /timestamps on|off",
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Queue => {
+                let reply = format!(
+                    "Commands in flight: {} (including this one)\nCommands waiting: {}",
+                    self.command_queue.in_flight(),
+                    self.command_queue.waiting()
+                );
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::VerboseErrors(mode) => {
+                let reply = match parse_toggle(&mode) {
+                    Some(verbose) => {
+                        let previous =
+                            std::mem::replace(&mut bot_state.lock().await.verbose_errors, verbose);
+                        match (previous == verbose, verbose) {
+                            (true, true) => "Verbose errors are already on",
+                            (true, false) => "Verbose errors are already off",
+                            (false, true) => "Errors now include their full chain of causes",
+                            (false, false) => "Errors are now reported on one line",
+                        }
+                    }
+                    None => "Usage: /verboseerrors on|off",
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::ConfigMtime => {
+                let (config_path, time_zone) = {
+                    let state = bot_state.lock().await;
+                    (state.config_path.clone(), state.time_zone)
+                };
+                let reply = match config_path {
+                    Some(config_path) => match config_file_info(Path::new(&config_path)).await {
+                        Ok(Some(info)) => format!(
+                            "{}\nModified: {}\nSize: {} bytes\nSymbols: {}",
+                            config_path,
+                            info.modified
+                                .with_timezone(&time_zone)
+                                .format("%Y-%m-%d %H:%M:%S %:z"),
+                            info.size,
+                            info.symbols
+                                .map(|count| count.to_string())
+                                .unwrap_or_else(|| "unreadable".to_string())
+                        ),
+                        Ok(None) => format!("Config file {} does not exist.", config_path),
+                        Err(err) => bot_state.lock().await.describe_error(&err),
+                    },
+                    None => "Configuration path is not set.".to_string(),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Subscribe => {
+                let reply = if bot_state.lock().await.add_subscriber(msg.chat.id) {
+                    match persist_subscriptions(&bot_state).await {
+                        Ok(()) => "Subscribed. This chat will receive critical notifications.",
+                        Err(err) => {
+                            eprintln!(
+                                "Failed to save subscriptions: {}",
+                                describe_error(&err, true)
+                            );
+                            "Subscribed, but the subscription could not be saved and will not \
+                             survive a restart."
+                        }
+                    }
+                } else {
+                    "This chat already receives notifications."
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Unsubscribe => {
+                let removed = bot_state
+                    .lock()
+                    .await
+                    .remove_notification_target(msg.chat.id);
+                let reply = if removed {
+                    if let Err(err) = persist_subscriptions(&bot_state).await {
+                        eprintln!(
+                            "Failed to save subscriptions: {}",
+                            describe_error(&err, true)
+                        );
+                    }
+                    "Unsubscribed. This chat will no longer receive notifications."
+                } else {
+                    "This chat does not receive notifications."
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Migrate => {
+                self.handle_migrate(&bot, msg.chat.id, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::BreakerCooldown(value) => {
+                let reply = match value.trim() {
+                    "" => {
+                        let (breaker, now) = {
+                            let state = bot_state.lock().await;
+                            (state.circuit_breaker.clone(), state.now())
+                        };
+                        match breaker.cooldown_remaining(now) {
+                            Some(remaining) => format!(
+                                "Breaker cooldown: {} ({} left)",
+                                format_duration(breaker.cooldown),
+                                format_duration(remaining)
+                            ),
+                            None if breaker.cooldown.is_zero() => {
+                                "Breaker cooldown is off".to_string()
+                            }
+                            None => {
+                                format!("Breaker cooldown: {}", format_duration(breaker.cooldown))
+                            }
+                        }
+                    }
+                    value => {
+                        let cooldown = if value == "off" {
+                            Ok(Duration::ZERO)
+                        } else {
+                            parse_duration(value)
+                        };
+                        match cooldown {
+                            Ok(cooldown) => {
+                                let previous = std::mem::replace(
+                                    &mut bot_state.lock().await.circuit_breaker.cooldown,
+                                    cooldown,
+                                );
+                                match (previous == cooldown, cooldown.is_zero()) {
+                                    (true, true) => "Breaker cooldown is already off".to_string(),
+                                    (true, false) => format!(
+                                        "Breaker cooldown already set to {}",
+                                        format_duration(cooldown)
+                                    ),
+                                    (false, true) => "Breaker cooldown turned off".to_string(),
+                                    (false, false) => format!(
+                                        "Breaker cooldown set to {}",
+                                        format_duration(cooldown)
+                                    ),
+                                }
+                            }
+                            Err(err) => err,
+                        }
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::BreakerThreshold(threshold) => {
+                let previous = std::mem::replace(
+                    &mut bot_state.lock().await.circuit_breaker.threshold,
+                    threshold,
+                );
+                let reply = match (previous == threshold, threshold) {
+                    (true, 0) => "The circuit breaker is already off".to_string(),
+                    (true, _) => format!("Breaker threshold already set to {}", threshold),
+                    (false, 0) => "Circuit breaker turned off".to_string(),
+                    (false, _) => format!(
+                        "The circuit breaker will trip after {} consecutive failures",
+                        threshold
+                    ),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::SymbolNoise(args) => match args.trim() {
+                "" => {
+                    let counts = bot_state.lock().await.symbol_noise();
+                    if counts.is_empty() {
+                        send_reply(
+                            &bot,
+                            msg.chat.id,
+                            &bot_state,
+                            "No symbol notifications recorded yet.",
+                        )
+                        .await?;
+                    } else {
+                        let mut table = Table::new();
+                        table.add_row(row!["Symbol", "Notifications"]);
+                        for (symbol, count) in counts {
+                            table.add_row(row![symbol, count]);
+                        }
+                        send_table(&bot, msg.chat.id, &bot_state, &table).await?;
+                    }
+                }
+                "reset" => {
+                    bot_state.lock().await.symbol_notifications.clear();
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "Symbol notification counts reset.",
+                    )
+                    .await?;
+                }
+                _ => {
+                    send_reply(&bot, msg.chat.id, &bot_state, "Usage: /symbolnoise [reset]")
+                        .await?;
+                }
+            },
+            Command::SymbolErrorLimit(limit) => {
+                let previous =
+                    std::mem::replace(&mut bot_state.lock().await.symbol_error_limit, limit);
+                let reply = match (previous == limit, limit) {
+                    (true, 0) => "Symbol auto-disable is already off".to_string(),
+                    (true, _) => format!("Symbol error limit already set to {}", limit),
+                    (false, 0) => "Symbol auto-disable turned off".to_string(),
+                    (false, _) => format!(
+                        "Symbols will be disabled after {} consecutive errors",
+                        limit
+                    ),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::EffectiveLevel => {
+                let reply = {
+                    let state = bot_state.lock().await;
+                    let (effective, level_override) = state.resolve_notification_level(state.now());
+                    let mut reply = format!(
+                        "Configured level: {:?}\nEffective level: {:?}",
+                        state.notification_level, effective
+                    );
+                    match level_override {
+                        Some(level_override) => {
+                            reply.push_str(&format!("\nBecause {}", level_override.explain()))
+                        }
+                        None => reply.push_str("\nNo override is active."),
+                    }
+                    reply
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Exchange => {
+                let reply = match self.request(BotRequest::PingExchange).await {
+                    Ok(latency) => {
+                        format!("Exchange latency: {:.1} ms", latency.as_secs_f64() * 1000.0)
+                    }
+                    Err(err) => format!("Exchange is unreachable: {}", err),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::PageSize(size) => {
+                if size == 0 || size > MAX_SYMBOLS_PAGE_SIZE {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "Invalid page size. Use a value between 1 and {}.",
+                            MAX_SYMBOLS_PAGE_SIZE
+                        ),
+                    )
+                    .await?;
+                } else {
+                    let previous =
+                        std::mem::replace(&mut bot_state.lock().await.symbols_page_size, size);
+                    let reply = if previous == size {
+                        format!("Page size already set to {} symbols", size)
+                    } else {
+                        format!("Page size set to {} symbols", size)
+                    };
+                    send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+                }
+            }
+            Command::EffectiveConfig => {
+                let settings = bot_state.lock().await.effective_settings();
+                let mut table = Table::new();
+                table.add_row(row!["Setting", "Value", "Source"]);
+                for setting in settings {
+                    table.add_row(row![setting.name, setting.value, setting.source]);
+                }
+                send_table(&bot, msg.chat.id, &bot_state, &table).await?;
+            }
+            Command::ReplayNotify(count) => {
+                if count == 0 || count > MAX_NOTIFICATION_HISTORY {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "Invalid count. Use a value between 1 and {}.",
+                            MAX_NOTIFICATION_HISTORY
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let (records, time_zone) = {
+                    let state = bot_state.lock().await;
+                    (state.recent_notifications(count), state.time_zone)
+                };
+                if records.is_empty() {
+                    send_reply(&bot, msg.chat.id, &bot_state, "No notifications sent yet.").await?;
+                    return Ok(());
+                }
+                for record in records {
+                    let text = format!(
+                        "(archived {}) {}",
+                        record
+                            .at
+                            .with_timezone(&time_zone)
+                            .format("%Y-%m-%d %H:%M:%S"),
+                        record.message
+                    );
+                    send_chunked(&bot, msg.chat.id, &bot_state, &text).await?;
+                }
+            }
+            Command::MaxTrades(cap) => {
+                let (previous, trades) = {
+                    let mut state = bot_state.lock().await;
+                    let now = state.now();
+                    let trades = state.trades_today(now);
+                    (std::mem::replace(&mut state.max_daily_trades, cap), trades)
+                };
+                let reply = match (previous == cap, cap) {
+                    (true, 0) => "No daily trade cap is set.".to_string(),
+                    (true, _) => format!("Daily trade cap already set to {}", cap),
+                    (false, 0) => "Daily trade cap removed.".to_string(),
+                    (false, _) => format!(
+                        "Daily trade cap set to {} ({} entries so far today)",
+                        cap, trades
+                    ),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::TradeResetHour(hour) => {
+                let reply = if hour > 23 {
+                    "Invalid hour. Use a value between 0 and 23.".to_string()
+                } else {
+                    let previous =
+                        std::mem::replace(&mut bot_state.lock().await.trade_reset_hour, hour);
+                    if previous == hour {
+                        format!("Daily trade count already resets at {:02}:00 UTC", hour)
+                    } else {
+                        format!("Daily trade count now resets at {:02}:00 UTC", hour)
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Escape(text) => {
+                let reply = if text.trim().is_empty() {
+                    "Usage: /escape TEXT".to_string()
+                } else {
+                    escape_preview(&text)
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::StrategyRetries(args) => {
+                let reply = if args.trim().is_empty() {
+                    let state = bot_state.lock().await;
+                    match state.strategy_retries {
+                        0 => "Strategy retries are off".to_string(),
+                        retries => format!(
+                            "Strategy retries: {} with {} between attempts",
+                            retries,
+                            format_duration(state.strategy_retry_delay)
+                        ),
+                    }
+                } else {
+                    match parse_strategy_retries(&args) {
+                        Ok((retries, delay)) => {
+                            let mut state = bot_state.lock().await;
+                            let previous = (state.strategy_retries, state.strategy_retry_delay);
+                            state.strategy_retries = retries;
+                            if let Some(delay) = delay {
+                                state.strategy_retry_delay = delay;
+                            }
+                            let current = (state.strategy_retries, state.strategy_retry_delay);
+                            match (previous == current, retries) {
+                                (true, 0) => "Strategy retries are already off".to_string(),
+                                (true, _) => format!(
+                                    "Strategy retries already set to {} with {} between attempts",
+                                    retries,
+                                    format_duration(current.1)
+                                ),
+                                (false, 0) => "Strategy retries turned off".to_string(),
+                                (false, _) => format!(
+                                    "Strategy retries set to {} with {} between attempts",
+                                    retries,
+                                    format_duration(current.1)
+                                ),
+                            }
+                        }
+                        Err(err) => err,
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Orders => match self.request(BotRequest::GetOrders).await {
+                Ok(orders) if orders.is_empty() => {
+                    send_reply(&bot, msg.chat.id, &bot_state, "No open orders.").await?;
+                }
+                Ok(orders) => {
+                    send_table(&bot, msg.chat.id, &bot_state, &orders_table(&orders)).await?
+                }
+                Err(err) => {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!("Unable to retrieve orders from running bot: {}", err),
+                    )
+                    .await?;
+                }
+            },
+            Command::CancelAll(confirm) => {
+                let reply = if !bot_state.lock().await.is_running {
+                    "Bot is not running.".to_string()
+                } else if !confirm.trim().eq_ignore_ascii_case("confirm") {
+                    "This cancels every open order on the exchange. Run /cancelall confirm to \
+                     proceed."
+                        .to_string()
+                } else {
+                    match self.request(BotRequest::CancelAll).await {
+                        Ok(0) => "No open orders to cancel.".to_string(),
+                        Ok(1) => "Cancelled 1 order.".to_string(),
+                        Ok(cancelled) => format!("Cancelled {} orders.", cancelled),
+                        Err(err) => format!("Unable to cancel orders: {}", err),
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Quote(quote) => {
+                let reply = match quote.trim() {
+                    "" => match bot_state.lock().await.quote_filter.clone() {
+                        Some(quote) => format!("/symbols shows only symbols quoted in {}", quote),
+                        None => "/symbols shows all symbols".to_string(),
+                    },
+                    quote => {
+                        let quote = (!matches!(quote.to_lowercase().as_str(), "off" | "all"))
+                            .then(|| quote.to_uppercase());
+                        let previous = std::mem::replace(
+                            &mut bot_state.lock().await.quote_filter,
+                            quote.clone(),
+                        );
+                        match (previous == quote, quote) {
+                            (true, None) => "No quote filter is set.".to_string(),
+                            (true, Some(quote)) => format!("Quote filter already set to {}", quote),
+                            (false, None) => "Quote filter removed.".to_string(),
+                            (false, Some(quote)) => format!("Quote filter set to {}", quote),
+                        }
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::DedupWindow(value) => {
+                let window = match value.trim() {
+                    "" => {
+                        let window = bot_state.lock().await.dedup_window;
+                        let reply = if window.is_zero() {
+                            "Notification deduplication is off".to_string()
+                        } else {
+                            format!("Dedup window: {}", format_duration(window))
+                        };
+                        send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+                        return Ok(());
+                    }
+                    "off" => Ok(Duration::ZERO),
+                    value => parse_duration(value).and_then(|window| {
+                        if window <= MAX_DEDUP_WINDOW {
+                            Ok(window)
+                        } else {
+                            Err(format!(
+                                "Dedup window must be at most {}.",
+                                format_duration(MAX_DEDUP_WINDOW)
+                            ))
+                        }
+                    }),
+                };
+                let reply = match window {
+                    Ok(window) => {
+                        let previous = {
+                            let mut state = bot_state.lock().await;
+                            state.dedup_sent.clear();
+                            std::mem::replace(&mut state.dedup_window, window)
+                        };
+                        match (previous == window, window.is_zero()) {
+                            (true, true) => "Notification deduplication is already off".to_string(),
+                            (true, false) => {
+                                format!("Dedup window already set to {}", format_duration(window))
+                            }
+                            (false, true) => "Notification deduplication turned off".to_string(),
+                            (false, false) => {
+                                format!("Dedup window set to {}", format_duration(window))
+                            }
+                        }
+                    }
+                    Err(err) => err,
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::State => {
+                let (json, options) = {
+                    let state = bot_state.lock().await;
+                    let options = DeliveryOptions {
+                        silent: false,
+                        timestamp_zone: None,
+                        ..DeliveryOptions::from_state(&state)
+                    };
+                    (state_json(&state), options)
+                };
+                let failure = match json {
+                    Ok(json) => {
+                        deliver_notification(&bot, msg.chat.id, &html::escape(&json), &options)
+                            .await
+                            .err()
+                            .map(|e| format!("Unable to send the state: {}", e))
+                    }
+                    Err(e) => Some(format!("Unable to serialize the state: {}", e)),
+                };
+                if let Some(failure) = failure {
+                    send_reply(&bot, msg.chat.id, &bot_state, failure).await?;
+                }
+            }
+            Command::Overrides => {
+                let Some((_, symbols)) = self.load_symbols(&bot, msg.chat.id, &bot_state).await?
+                else {
+                    return Ok(());
+                };
+                let overrides = symbol_overrides(&symbols, &*bot_state.lock().await);
+                if overrides.is_empty() {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "No symbol has an override active.",
+                    )
+                    .await?;
+                } else {
+                    let mut table = Table::new();
+                    table.add_row(row!["Symbol", "Override"]);
+                    for (symbol, active) in overrides {
+                        table.add_row(row![symbol, active.join("\n")]);
+                    }
+                    send_table(&bot, msg.chat.id, &bot_state, &table).await?;
+                }
+            }
+            Command::Endpoint(url) => {
+                let url = url.trim().to_string();
+                let reply = if url.is_empty() {
+                    "Usage: /endpoint URL".to_string()
+                } else if let Err(err) = validate_endpoint(&url) {
+                    err
+                } else {
+                    match self
+                        .request(|tx| BotRequest::SetEndpoint(url.clone(), tx))
+                        .await
+                    {
+                        Ok(()) => format!("Endpoint set to {}; the strategy has reconnected.", url),
+                        Err(err) => format!("Unable to switch endpoint: {}", err),
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::PnlChart(count) => {
+                let count = match count.trim() {
+                    "" => Some(MAX_PNL_SAMPLES),
+                    count => count
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|count| (2..=MAX_PNL_SAMPLES).contains(count)),
+                };
+                let reply = match count {
+                    None => format!(
+                        "Usage: /pnlchart [N] with N between 2 and {}",
+                        MAX_PNL_SAMPLES
+                    ),
+                    Some(count) => {
+                        let samples: Vec<f64> = {
+                            let state = bot_state.lock().await;
+                            let skip = state.pnl_samples.len().saturating_sub(count);
+                            state.pnl_samples.iter().skip(skip).copied().collect()
+                        };
+                        if samples.len() < 2 {
+                            "Not enough PnL samples yet; they are recorded after each tick."
+                                .to_string()
+                        } else {
+                            let (min, max) = samples
+                                .iter()
+                                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                                    (min.min(*value), max.max(*value))
+                                });
+                            format!(
+                                "PnL over the last {} ticks:\n{}\nMin: {:.2}  Max: {:.2}  Now: \
+                                 {:.2}",
+                                samples.len(),
+                                sparkline(&samples),
+                                min,
+                                max,
+                                samples[samples.len() - 1]
+                            )
+                        }
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::RestartLevel(level) => {
+                let reply = match level.trim() {
+                    "" => format!(
+                        "Restart messages are sent at level {:?}",
+                        bot_state.lock().await.restart_level
+                    ),
+                    level => match level.parse::<NotificationLevel>() {
+                        Ok(NotificationLevel::None) | Err(_) => {
+                            "Invalid level. Use: all, important or critical".to_string()
+                        }
+                        Ok(level) => {
+                            let previous = std::mem::replace(
+                                &mut bot_state.lock().await.restart_level,
+                                level.clone(),
+                            );
+                            if previous == level {
+                                format!("Restart level already set to {:?}", level)
+                            } else {
+                                format!("Restart level set to {:?}", level)
+                            }
+                        }
+                    },
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Use(name) => {
+                let reply = if name.trim().is_empty() {
+                    "Usage: /use NAME".to_string()
+                } else {
+                    let previous = self.active_runner.clone();
+                    match self.use_runner(&name) {
+                        Ok(()) if previous == self.active_runner => {
+                            format!("Already using {}", self.active_runner)
+                        }
+                        Ok(()) => format!("Commands now go to {}", self.active_runner),
+                        Err(err) => err,
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Active => {
+                let runners = self
+                    .runners
+                    .keys()
+                    .map(|name| {
+                        if *name == self.active_runner {
+                            format!("{} (active)", name)
+                        } else {
+                            name.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                send_reply(
+                    &bot,
+                    msg.chat.id,
+                    &bot_state,
+                    format!("Strategy runners:\n{}", runners),
+                )
+                .await?;
+            }
+            Command::Suppressed => {
+                let suppressed = bot_state.lock().await.notification_stats.suppressed;
+                send_reply(&bot, msg.chat.id, &bot_state, suppressed.render()).await?;
+            }
+            Command::Fallback(id) => {
+                let fallback = (id != 0).then_some(ChatId(id));
+                let previous =
+                    std::mem::replace(&mut bot_state.lock().await.fallback_chat, fallback);
+                let reply = match (previous == fallback, fallback) {
+                    (true, None) => "No fallback chat is set.".to_string(),
+                    (true, Some(chat)) => format!("Fallback chat already set to {}", chat),
+                    (false, None) => "Fallback chat removed.".to_string(),
+                    (false, Some(chat)) => format!(
+                        "Fallback chat set to {}. It receives broadcasts only when no target can \
+                         be reached.",
+                        chat
+                    ),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::TripBreaker => {
+                let tripped = {
+                    let mut state = bot_state.lock().await;
+                    let now = state.now();
+                    let tripped = state.circuit_breaker.trip(now);
+                    if tripped {
+                        state.stop_for_breaker();
+                    }
+                    tripped
+                };
+                if tripped {
+                    if let Err(e) = announce_breaker_trip(&bot, &bot_state, "by /tripbreaker").await
+                    {
+                        eprintln!(
+                            "Error sending circuit breaker message: {}",
+                            describe_error(&*e, true)
+                        );
+                    }
+                    // The alert went to the targets; tell a caller outside them
+                    let is_target = bot_state
+                        .lock()
+                        .await
+                        .notification_targets
+                        .contains(&msg.chat.id);
+                    if !is_target {
+                        send_reply(
+                            &bot,
+                            msg.chat.id,
+                            &bot_state,
+                            "Circuit breaker tripped. The notification targets were alerted.",
+                        )
+                        .await?;
+                    }
+                } else {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        "The circuit breaker is already tripped.",
+                    )
+                    .await?;
+                }
+            }
+            Command::ConfirmStyle(style) => {
+                let reply = if style.trim().is_empty() {
+                    format!(
+                        "Confirmation style: {:?}",
+                        bot_state.lock().await.confirm_style
+                    )
+                } else {
+                    match style.parse::<ConfirmStyle>() {
+                        Ok(style) => {
+                            let previous =
+                                std::mem::replace(&mut bot_state.lock().await.confirm_style, style);
+                            if previous == style {
+                                format!("Confirmation style already set to {:?}", style)
+                            } else {
+                                format!("Confirmation style set to {:?}", style)
+                            }
+                        }
+                        Err(err) => err,
+                    }
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::RateLimit => {
+                let (table, throttled) = {
+                    let state = bot_state.lock().await;
+                    let now = state.now();
+                    let throttled = state
+                        .notification_targets
+                        .iter()
+                        .filter(|chat| state.rate_limiter.is_throttled(**chat, now))
+                        .count();
+                    (
+                        rate_limit_table(&state.rate_limiter, &state.notification_targets, now),
+                        throttled,
+                    )
+                };
+                send_table(&bot, msg.chat.id, &bot_state, &table).await?;
+                let reply = match throttled {
+                    0 => "No chat is throttled.".to_string(),
+                    count => format!("{} chat(s) throttled.", count),
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::SendConcurrency(limit) => {
+                if !(1..=MAX_SEND_CONCURRENCY).contains(&limit) {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "Usage: /sendconcurrency N, with N between 1 and {}",
+                            MAX_SEND_CONCURRENCY
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let previous =
+                    std::mem::replace(&mut bot_state.lock().await.send_concurrency, limit);
+                let reply = if previous == limit {
+                    format!("Send concurrency already set to {}", limit)
+                } else {
+                    format!("Send concurrency set to {}", limit)
+                };
+                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+            }
+            Command::Audit => {
+                let Some((_, symbols)) = self.load_symbols(&bot, msg.chat.id, &bot_state).await?
+                else {
+                    return Ok(());
+                };
+                let budget = bot_state.lock().await.session_budget;
+                let findings = audit_symbols(&symbols, budget);
+                if findings.is_empty() {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!("No findings across {} symbol(s).", symbols.len()),
+                    )
+                    .await?;
+                } else {
+                    send_table(&bot, msg.chat.id, &bot_state, &render_audit(&findings)).await?;
+                }
+            }
+            Command::Share(args) => {
+                let gzip = match args.trim().to_lowercase().as_str() {
+                    "" => false,
+                    "gzip" => true,
+                    _ => {
+                        send_reply(&bot, msg.chat.id, &bot_state, "Usage: /share [gzip]").await?;
+                        return Ok(());
+                    }
+                };
+                let Some((_, symbols)) = self.load_symbols(&bot, msg.chat.id, &bot_state).await?
+                else {
+                    return Ok(());
+                };
+                let snippet = match encode_share(&symbols, gzip) {
+                    Ok(snippet) => snippet,
+                    Err(err) => {
+                        send_reply(&bot, msg.chat.id, &bot_state, err).await?;
+                        return Ok(());
+                    }
+                };
+                let (tag, max_message_length) = {
+                    let state = bot_state.lock().await;
+                    (state.instance_tag.clone(), state.max_message_length)
+                };
+                // Room for the code fences
+                let room = max_message_length.saturating_sub(tag_overhead(tag.as_deref()) + 8);
+                if snippet.len() > room {
+                    let hint = if gzip {
+                        "Use /exportconfig instead."
+                    } else {
+                        "Try /share gzip or /exportconfig."
+                    };
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "The snippet is {} characters, too long for one message. {}",
+                            snippet.len(),
+                            hint
+                        ),
+                    )
+                    .await?;
+                } else {
+                    send_code_block(&bot, msg.chat.id, &bot_state, &snippet).await?;
+                }
+            }
+            Command::ImportShare(snippet) => {
+                let Some((config_path, _)) =
+                    self.load_symbols(&bot, msg.chat.id, &bot_state).await?
+                else {
+                    return Ok(());
+                };
+                match decode_share(&snippet) {
+                    Ok(symbols) => {
+                        let note = match backup_before_save(&bot_state, &config_path).await {
+                            Ok(note) => note,
+                            Err(err) => {
+                                let reply = format!(
+                                    "Import aborted: {}",
+                                    bot_state.lock().await.describe_error(&err)
+                                );
+                                send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+                                return Ok(());
+                            }
+                        };
+                        if self
+                            .save_symbols(&bot, msg.chat.id, &bot_state, &config_path, &symbols)
+                            .await?
+                        {
+                            send_reply(
+                                &bot,
+                                msg.chat.id,
+                                &bot_state,
+                                format!("Imported {} symbol(s).\n{}", symbols.len(), note),
+                            )
+                            .await?;
+                        }
+                    }
+                    Err(errors) => {
+                        let reply = format!("Import failed:\n{}", errors.join("\n"));
+                        send_chunked(&bot, msg.chat.id, &bot_state, &reply).await?;
+                    }
+                }
+            }
+            Command::AddSymbol(data) => {
+                self.handle_add_symbol(&bot, msg.chat.id, data, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::RemoveSymbol(symbol) => {
+                self.handle_remove_symbol(&bot, msg.chat.id, symbol, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::MoveSymbol(data) => {
+                self.handle_move_symbol(&bot, msg.chat.id, data, Arc::clone(&bot_state))
+                    .await?;
+            }
+            Command::Symbols(page) => {
+                let page = match page.trim() {
+                    "" => Some(1),
+                    page => page.parse::<usize>().ok().filter(|page| *page > 0),
+                };
+                match page {
+                    Some(page) => {
+                        self.handle_show_symbols(&bot, msg.chat.id, page, Arc::clone(&bot_state))
+                            .await?
+                    }
+                    None => {
+                        send_reply(&bot, msg.chat.id, &bot_state, "Usage: /symbols [PAGE]").await?
+                    }
+                }
+            }
+            Command::SendTimeout(secs) => {
+                if secs == 0 || secs > MAX_SEND_TIMEOUT_SECS {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!(
+                            "Invalid timeout. Use a value between 1 and {} seconds.",
+                            MAX_SEND_TIMEOUT_SECS
+                        ),
+                    )
+                    .await?;
+                } else {
+                    let previous =
+                        std::mem::replace(&mut bot_state.lock().await.send_timeout_secs, secs);
+                    let reply = if previous == secs {
+                        format!("Send timeout already set to {} seconds", secs)
+                    } else {
+                        format!("Send timeout set to {} seconds", secs)
+                    };
+                    send_reply(&bot, msg.chat.id, &bot_state, reply).await?;
+                }
+            }
+            Command::SetInterval(value) => match parse_interval(&value) {
+                Ok(secs) => {
+                    self.handle_set_interval(&bot, msg.chat.id, secs, Arc::clone(&bot_state))
+                        .await?
+                }
+                Err(err) => send_reply(&bot, msg.chat.id, &bot_state, err).await?,
+            },
+            Command::Update => match self.request_status().await {
+                Ok((_, status)) => {
+                    send_chunked(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        &format!("Current status:\n{}", status),
+                    )
+                    .await?;
+                }
+                Err(err) => {
+                    send_reply(
+                        &bot,
+                        msg.chat.id,
+                        &bot_state,
+                        format!("Unable to retrieve status from running bot: {}", err),
+                    )
+                    .await?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Load the symbols configuration, replying with the reason when it
+    /// cannot be read
+    async fn load_symbols(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: &Arc<Mutex<BotState>>,
+    ) -> ResponseResult<Option<(PathBuf, Vec<SymbolConfig>)>> {
+        let config_path = bot_state.lock().await.config_path.clone();
+        let config_path = match config_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    bot_state,
+                    "Configuration path is not set. Use /startbot first to initialize.",
+                )
+                .await?;
+                return Ok(None);
+            }
+        };
+
+        let (staged, persist_changes) = {
+            let state = bot_state.lock().await;
+            (state.staged_symbols.clone(), state.persist_changes)
+        };
+        if let Some(symbols) = staged {
+            return Ok(Some((config_path, symbols)));
+        }
+
+        if !persist_changes {
+            match self.request(BotRequest::GetSymbolOverride).await {
+                Ok(Some(symbols)) => return Ok(Some((config_path, symbols))),
+                Ok(None) => {}
+                Err(err) => {
+                    send_reply(
+                        bot,
+                        chat_id,
+                        bot_state,
+                        format!("In-memory symbols are unavailable: {}", err),
+                    )
+                    .await?;
+                    return Ok(None);
+                }
+            }
+        }
+
+        match read_symbols(&config_path).await {
+            Ok(symbols) => Ok(Some((config_path, symbols))),
+            Err(err) => {
+                send_reply(bot, chat_id, bot_state, err.to_string()).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Write the symbols configuration, replying when the write fails
+    ///
+    /// While staging, the edit goes to the staged copy instead, and while
+    /// `/persist` is off it goes to the runner's in-memory copy. Returns
+    /// whether the configuration was saved.
+    async fn save_symbols(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: &Arc<Mutex<BotState>>,
+        config_path: &Path,
+        symbols: &[SymbolConfig],
+    ) -> ResponseResult<bool> {
+        match store_symbols(bot_state, config_path, symbols).await {
+            Ok(StoredSymbols::Staged | StoredSymbols::Written) => Ok(true),
+            Ok(StoredSymbols::InMemory(symbols)) => match self
+                .request(|response_tx| BotRequest::SetSymbolOverride(Some(symbols), response_tx))
+                .await
+            {
+                Ok(()) => {
+                    bot_state.lock().await.unsaved_changes = true;
+                    Ok(true)
+                }
+                Err(err) => {
+                    send_reply(
+                        bot,
+                        chat_id,
+                        bot_state,
+                        format!("Failed to update in-memory symbols: {}", err),
+                    )
+                    .await?;
+                    Ok(false)
+                }
+            },
+            Err(err) => {
+                let reply = bot_state.lock().await.describe_error(&err);
+                send_reply(bot, chat_id, bot_state, reply).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Run every self-test check, in order
+    async fn run_self_test(&self, bot: &Bot, bot_state: &Arc<Mutex<BotState>>) -> Vec<CheckResult> {
+        let (config_path, timeout) = {
+            let state = bot_state.lock().await;
+            (
+                state.config_path.clone(),
+                Duration::from_secs(state.send_timeout_secs),
+            )
+        };
+
+        // A wedged strategy must fail its check rather than hang /selftest
+        let status = tokio::time::timeout(timeout, self.request_status())
+            .await
+            .unwrap_or_else(|_| Err(BotError::Timeout(timeout).to_string()));
+
+        vec![
+            check_config(config_path.as_deref()).await,
+            check_telegram(bot, timeout).await,
+            check_strategy(status),
+            check_runner_channel(self.request_tx()),
+        ]
+    }
+
+    async fn handle_backups(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        args: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        match args.as_slice() {
+            [] => {}
+            ["keep", count] => {
+                let reply = match count.parse::<usize>() {
+                    Ok(count) if count > 0 => {
+                        let config_path = {
+                            let mut state = bot_state.lock().await;
+                            state.backup_retention = count;
+                            state.config_path.clone()
+                        };
+                        // Apply the new limit now rather than at the next backup
+                        let pruned = match config_path {
+                            Some(config_path) => {
+                                prune_backups(Path::new(&config_path), count).await
+                            }
+                            None => Ok(Vec::new()),
+                        };
+                        match pruned {
+                            Ok(removed) if removed.is_empty() => {
+                                format!("Keeping the last {} backup(s).", count)
+                            }
+                            Ok(removed) => format!(
+                                "Keeping the last {} backup(s). Removed {} older backup(s).",
+                                count,
+                                removed.len()
+                            ),
+                            Err(err) => format!(
+                                "Keeping the last {} backup(s), but pruning failed: {}",
+                                count, err
+                            ),
+                        }
+                    }
+                    _ => "Backup retention must be a positive number.".to_string(),
+                };
+                send_reply(bot, chat_id, &bot_state, reply).await?;
+                return Ok(());
+            }
+            _ => {
+                send_reply(bot, chat_id, &bot_state, "Usage: /backups [keep N]").await?;
+                return Ok(());
+            }
+        }
+
+        let (config_path, retention) = {
+            let state = bot_state.lock().await;
+            (state.config_path.clone(), state.backup_retention)
+        };
+        let Some(config_path) = config_path else {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Configuration path is not set. Use /startbot first to initialize.",
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let reply = match list_backups(Path::new(&config_path)).await {
+            Ok(backups) if backups.is_empty() => "No backups found.".to_string(),
+            Ok(backups) => {
+                let mut text = format!(
+                    "{} backup(s), keeping the last {}:\n",
+                    backups.len(),
+                    retention
+                );
+                for backup in backups.iter().rev() {
+                    if let Some(name) = backup.file_name() {
+                        text.push_str(&format!("\n{}", name.to_string_lossy()));
+                    }
+                }
+                text
+            }
+            Err(err) => bot_state.lock().await.describe_error(&err),
+        };
+        send_chunked(bot, chat_id, &bot_state, &reply).await
+    }
+
+    async fn handle_replay(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        name: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            send_reply(bot, chat_id, &bot_state, "Usage: /replay FILE").await?;
+            return Ok(());
+        }
+
+        let replay_dir = bot_state.lock().await.replay_dir.clone();
+        let Some(replay_dir) = replay_dir else {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Replays are disabled: no replay directory is configured.",
+            )
+            .await?;
+            return Ok(());
+        };
+        let path = match replay_data_path(Path::new(&replay_dir), name) {
+            Ok(path) => path,
+            Err(err) => {
+                send_reply(bot, chat_id, &bot_state, err).await?;
+                return Ok(());
+            }
+        };
+
+        // The cause stays in the log so replies don't reveal which files exist
+        let data = match read_market_data(&path).await {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Failed to load replay data: {}", describe_error(&err, true));
+                let reply = format!("Could not load replay data '{}'.", name);
+                send_reply(bot, chat_id, &bot_state, reply).await?;
+                return Ok(());
+            }
+        };
+        let points = data.points.len();
+
+        let reply = match self
+            .request(|response_tx| BotRequest::Replay(data, response_tx))
+            .await
+        {
+            Ok(Some(report)) => format!("Replayed {} data point(s) (dry run):\n{}", points, report),
+            Ok(None) => "The strategy does not support replays.".to_string(),
+            Err(err) => format!("Replay failed: {}", err),
+        };
+        send_chunked(bot, chat_id, &bot_state, &reply).await
+    }
+
+    async fn handle_persist(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        mode: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some(persist) = parse_toggle(&mode) else {
+            send_reply(bot, chat_id, &bot_state, "Usage: /persist on|off").await?;
+            return Ok(());
+        };
+
+        let (previous, unsaved_changes) = {
+            let mut state = bot_state.lock().await;
+            (
+                std::mem::replace(&mut state.persist_changes, persist),
+                state.unsaved_changes,
+            )
+        };
+
+        let reply = if previous == persist {
+            format!("Persistence already {}", if persist { "on" } else { "off" })
+        } else if !persist {
+            "Persistence off. Symbol edits now only change the running bot.".to_string()
+        } else if unsaved_changes {
+            // Drop the runner's copy so the file is authoritative again
+            if let Err(err) = self
+                .request(|response_tx| BotRequest::SetSymbolOverride(None, response_tx))
+                .await
+            {
+                eprintln!("Failed to clear in-memory symbols: {}", err);
+            }
+            bot_state.lock().await.unsaved_changes = false;
+            "Persistence on. Unsaved in-memory changes were discarded.".to_string()
+        } else {
+            "Persistence on. Symbol edits are written to the config file.".to_string()
+        };
+        send_reply(bot, chat_id, &bot_state, reply).await
+    }
+
+    async fn handle_stage(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        if bot_state.lock().await.staged_symbols.is_some() {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Already staging. Use /apply or /discard first.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let Some((_, symbols)) = self.load_symbols(bot, chat_id, &bot_state).await? else {
+            return Ok(());
+        };
+
+        {
+            let mut state = bot_state.lock().await;
+            state.staged_symbols = Some(symbols);
+            state.staged_changes = 0;
+        }
+        send_reply(
+            bot,
+            chat_id,
+            &bot_state,
+            "Staging started. Symbol edits are held until /apply or /discard.",
+        )
+        .await
+    }
+
+    async fn handle_apply(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let (staged, config_path) = {
+            let state = bot_state.lock().await;
+            (state.staged_symbols.clone(), state.config_path.clone())
+        };
+        let (Some(symbols), Some(config_path)) = (staged, config_path) else {
+            send_reply(bot, chat_id, &bot_state, "Nothing is staged.").await?;
+            return Ok(());
+        };
+
+        // Write first so a failed write leaves the staged edits in place
+        let reply = match write_symbols(Path::new(&config_path), &symbols).await {
+            Ok(()) => {
+                let mut state = bot_state.lock().await;
+                state.staged_symbols = None;
+                format!(
+                    "Applied {} staged change(s).",
+                    std::mem::take(&mut state.staged_changes)
+                )
+            }
+            Err(err) => format!("{} Staged changes were kept.", err),
+        };
+        send_reply(bot, chat_id, &bot_state, reply).await
+    }
+
+    async fn handle_add_symbol(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        data: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let parts: Vec<&str> = data.split(',').collect();
+        if parts.len() != 5 {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Invalid format. Use: /addsymbol \
+                 SYMBOL,ENTRY_AMOUNT,EXIT_AMOUNT,ENTRY_THRESHOLD,EXIT_THRESHOLD",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let symbol = parts[0].trim().to_string();
+        let entry_amount: f64 = parts[1].trim().parse().unwrap_or(0.0);
+        let exit_amount: f64 = parts[2].trim().parse().unwrap_or(0.0);
+        let entry_threshold: f64 = parts[3].trim().parse().unwrap_or(0.0);
+        let exit_threshold: f64 = parts[4].trim().parse().unwrap_or(0.0);
+
+        let Some((config_path, mut symbols)) = self.load_symbols(bot, chat_id, &bot_state).await?
+        else {
+            return Ok(());
+        };
+
+        // Add the new symbol
+        let new_symbol = SymbolConfig {
+            symbol: symbol.clone(),
+            entry_amount,
+            exit_amount,
+            entry_threshold,
+            exit_threshold,
+            enabled: true,
+            max_slippage: None,
+            size_step: None,
+        };
+        symbols.push(new_symbol);
+
+        if self
+            .save_symbols(bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!("Symbol '{}' added successfully.", symbol),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_edit_symbol(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        args: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let [symbol, field, value] = parts.as_slice() else {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Usage: /editsymbol SYMBOL FIELD VALUE (use 'none' to clear max_slippage or \
+                 size_step)",
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let Some((config_path, mut symbols)) = self.load_symbols(bot, chat_id, &bot_state).await?
+        else {
+            return Ok(());
+        };
+
+        if let Err(err) = edit_symbol(&mut symbols, symbol, field, value) {
+            send_reply(bot, chat_id, &bot_state, err).await?;
+            return Ok(());
+        }
+
+        if self
+            .save_symbols(bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!("Updated {} of '{}' to {}.", field, symbol, value),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_remove_symbol(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        symbol: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some((config_path, mut symbols)) = self.load_symbols(bot, chat_id, &bot_state).await?
+        else {
+            return Ok(());
+        };
+
+        // Remove the symbol
+        let original_len = symbols.len();
+        symbols.retain(|s| s.symbol != symbol);
+
+        if symbols.len() == original_len {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!("Symbol '{}' not found.", symbol),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if self
+            .save_symbols(bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!("Symbol '{}' removed successfully.", symbol),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_move_symbol(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        data: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let parsed = data.split_once(',').and_then(|(symbol, position)| {
+            position
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .map(|position| (symbol.trim().to_string(), position))
+        });
+        let Some((symbol, position)) = parsed else {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Invalid format. Use: /movesymbol SYMBOL,POSITION",
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let Some((config_path, mut symbols)) = self.load_symbols(bot, chat_id, &bot_state).await?
+        else {
+            return Ok(());
+        };
+
+        if let Err(err) = move_symbol(&mut symbols, &symbol, position) {
+            send_reply(bot, chat_id, &bot_state, err).await?;
+            return Ok(());
+        }
+
+        if self
+            .save_symbols(bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            let order = symbols
+                .iter()
+                .enumerate()
+                .map(|(i, s)| format!("{}. {}", i + 1, s.symbol))
+                .collect::<Vec<_>>()
+                .join("\n");
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "Moved '{}' to position {}. New order:\n{}",
+                    symbol, position, order
+                ),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_reset_settings(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        confirm: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        if !confirm.trim().eq_ignore_ascii_case("confirm") {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "This resets notification levels, schedules, interval, quiet, dry-run, alert \
+                 rules and other runtime settings to their defaults. Symbols and the running \
+                 state are kept. Run /resetsettings confirm to proceed.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let interval_changed = {
+            let mut state = bot_state.lock().await;
+            state.reset_settings();
+            state.interval_seconds != BotState::default().interval_seconds
+        };
+
+        let mut reply = "Runtime settings reset to defaults.".to_string();
+        if let (true, Some(secs)) = (interval_changed, BotState::default().interval_seconds) {
+            if let Err(err) = self.request(|tx| BotRequest::SetInterval(secs, tx)).await {
+                reply.push_str(&format!(" Unable to reset the interval: {}", err));
+            }
+        }
+        send_reply(bot, chat_id, &bot_state, reply).await
+    }
+
+    /// Add `rule` from /addrule or /threshold; the rule engine then checks
+    /// it after every tick and alerts once per breach
+    async fn add_alert_rule(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        rule: AlertRule,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        // Only metrics the strategy reports can ever match
+        if let AlertField::Metric(metric) = &rule.field {
+            let columns = match self.request(BotRequest::GetStatusColumns).await {
+                Ok(columns) => columns,
+                Err(err) => {
+                    return send_reply(
+                        bot,
+                        chat_id,
+                        &bot_state,
+                        format!("Unable to retrieve strategy metrics: {}", err),
+                    )
+                    .await;
+                }
+            };
+            if !columns.contains_key(metric) {
+                let mut reply = format!(
+                    "Unknown field '{}'. Use: open_positions, realized_pnl, unrealized_pnl or \
+                     total_pnl",
+                    metric
+                );
+                if !columns.is_empty() {
+                    reply.push_str(&format!(
+                        ", or a strategy metric: {}",
+                        columns.keys().cloned().collect::<Vec<_>>().join(", ")
+                    ));
+                }
+                return send_reply(bot, chat_id, &bot_state, reply).await;
+            }
+        }
+
+        let reply = {
+            let mut state = bot_state.lock().await;
+            if state.alert_rules.contains(&rule) {
+                format!("Alert rule '{}' already exists", rule)
+            } else {
+                state.alert_rules.push(rule.clone());
+                format!("Alert rule #{} added: {}", state.alert_rules.len(), rule)
+            }
+        };
+        send_reply(bot, chat_id, &bot_state, reply).await
+    }
+
+    async fn handle_migrate(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some(config_path) = bot_state.lock().await.config_path.clone() else {
+            return send_reply(bot, chat_id, &bot_state, "Configuration path is not set.").await;
+        };
+        let config_path = PathBuf::from(config_path);
+
+        let migration = match tokio::fs::read_to_string(&config_path).await {
+            Ok(content) => migrate_config(&content),
+            Err(e) => Err(BotError::context("Failed to read symbols configuration", e)),
+        };
+        let migration = match migration {
+            Ok(migration) => migration,
+            Err(err) => {
+                let reply = bot_state.lock().await.describe_error(&err);
+                return send_reply(bot, chat_id, &bot_state, reply).await;
+            }
+        };
+
+        let invalid: Vec<String> = migration
+            .symbols
+            .iter()
+            .filter_map(|symbol| {
+                symbol
+                    .validate()
+                    .err()
+                    .map(|err| format!("{}: {}", symbol.symbol, err))
+            })
+            .collect();
+        if !invalid.is_empty() {
+            return send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "Migration aborted, fix these entries first:\n{}",
+                    invalid.join("\n")
+                ),
+            )
+            .await;
+        }
+
+        if migration.changes.is_empty() {
+            return send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "The config file already uses the latest schema.",
+            )
+            .await;
+        }
+
+        let retention = bot_state.lock().await.backup_retention;
+        let reply = match backup_config(&config_path, retention).await {
+            Ok(backup_path) => match write_symbols(&config_path, &migration.symbols).await {
+                Ok(()) => format!(
+                    "Migrated {} symbol(s), adding defaults for:\n{}\nBackup saved to {}",
+                    migration.changes.len(),
+                    migration.changes.join("\n"),
+                    backup_path.display()
+                ),
+                Err(err) => format!(
+                    "Migration failed: {}",
+                    bot_state.lock().await.describe_error(&err)
+                ),
+            },
+            Err(err) => format!(
+                "Migration aborted: {}",
+                bot_state.lock().await.describe_error(&err)
+            ),
+        };
+        send_chunked(bot, chat_id, &bot_state, &reply).await
+    }
+
+    async fn handle_purge_disabled(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        confirm: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some((config_path, mut symbols)) = self.load_symbols(bot, chat_id, &bot_state).await?
+        else {
+            return Ok(());
+        };
+
+        let disabled = symbols.iter().filter(|s| !s.enabled).count();
+        if disabled == 0 {
+            send_reply(bot, chat_id, &bot_state, "There are no disabled symbols.").await?;
+            return Ok(());
+        }
+
+        if !confirm.trim().eq_ignore_ascii_case("confirm") {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "This will permanently remove {} disabled symbol(s). Run /purgedisabled \
+                     confirm to proceed.",
+                    disabled
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let note = match backup_before_save(&bot_state, &config_path).await {
+            Ok(note) => note,
+            Err(err) => {
+                let reply = format!(
+                    "Purge aborted: {}",
+                    bot_state.lock().await.describe_error(&err)
+                );
+                send_reply(bot, chat_id, &bot_state, reply).await?;
+                return Ok(());
+            }
+        };
+
+        let removed = purge_disabled(&mut symbols);
+        if self
+            .save_symbols(bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "Removed {} disabled symbol(s): {}\n{}",
+                    removed.len(),
+                    removed.join(", "),
+                    note
+                ),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_show_symbols(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        page: usize,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some((_, symbols)) = self.load_symbols(bot, chat_id, &bot_state).await? else {
+            return Ok(());
+        };
+
+        let (columns, page_size, quote) = {
+            let state = bot_state.lock().await;
+            (
+                state.symbol_columns.clone(),
+                state.symbols_page_size,
+                state.quote_filter.clone(),
+            )
+        };
+        let symbols = match &quote {
+            Some(quote) => filter_by_quote(symbols, quote),
+            None => symbols,
+        };
+        let (entries, pages) = match symbols_page(&symbols, page, page_size) {
+            Ok(found) => found,
+            Err(err) => return send_reply(bot, chat_id, &bot_state, err).await,
+        };
+
+        let mut footer = Vec::new();
+        if let Some(quote) = &quote {
+            footer.push(format!(
+                "Showing only symbols quoted in {} (/quote off shows all)",
+                quote
+            ));
+        }
+        if pages > 1 {
+            footer.push(format!("Page {} of {}", page, pages));
+            if page > 1 {
+                footer.push(format!("Previous: /symbols {}", page - 1));
+            }
+            if page < pages {
+                footer.push(format!("Next: /symbols {}", page + 1));
+            }
+        }
+
+        send_table(bot, chat_id, &bot_state, &symbols_table(entries, &columns)).await?;
+        if !footer.is_empty() {
+            send_reply(bot, chat_id, &bot_state, footer.join("\n")).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_holdings(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        if !bot_state.lock().await.is_running {
+            send_reply(bot, chat_id, &bot_state, "Bot is not running.").await?;
+            return Ok(());
+        }
+
+        let Some((_, symbols)) = self.load_symbols(bot, chat_id, &bot_state).await? else {
+            return Ok(());
+        };
+
+        let positions = match self.request_positions().await {
+            Ok(positions) => positions,
+            Err(err) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!("Unable to retrieve positions from running bot: {}", err),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let mut table = Table::new();
+        table.add_row(row!["Symbol", "State", "Size"]);
+        for (symbol, size) in holdings(&symbols, &positions) {
+            match size {
+                Some(size) => table.add_row(row![symbol, "in position", format!("{:.4}", size)]),
+                None => table.add_row(row![symbol, "flat", "-"]),
+            };
+        }
+
+        send_table(bot, chat_id, &bot_state, &table).await
+    }
+
+    async fn handle_notify_settings(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        args: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let settings = match parse_notify_settings(&args) {
+            Ok(settings) => settings,
+            Err(errors) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!(
+                        "No settings were changed:\n{}\n\nKnown keys: level, silent, summary",
+                        errors.join("\n")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let mut applied = Vec::new();
+        {
+            let mut state = bot_state.lock().await;
+            if let Some(level) = settings.level {
+                applied.push(format!("level={:?}", level));
+                state.notification_level = level;
+            }
+            if let Some(silent) = settings.silent {
+                applied.push(format!("silent={}", silent));
+                state.silent_notifications = silent;
+            }
+            if let Some(summary) = settings.summary {
+                applied.push(format!("summary={}", summary));
+                state.summary_mode = summary;
+            }
+        }
+
+        send_reply(
+            bot,
+            chat_id,
+            &bot_state,
+            format!("Notification settings updated: {}", applied.join(", ")),
+        )
+        .await
+    }
+
+    async fn handle_set_interval(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        secs: u64,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        // Skip the runner round-trip entirely when nothing would change
+        if bot_state.lock().await.interval_seconds == Some(secs) {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "Interval already set to {}",
+                    format_duration(Duration::from_secs(secs))
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        match self.request(|tx| BotRequest::SetInterval(secs, tx)).await {
+            Ok(()) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!(
+                        "Interval set to {}",
+                        format_duration(Duration::from_secs(secs))
+                    ),
+                )
+                .await?;
+            }
+            Err(err) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!("Unable to update interval: {}", err),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_save_positions(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let (is_running, config_path) = {
+            let state = bot_state.lock().await;
+            (state.is_running, state.config_path.clone())
+        };
+        if !is_running {
+            send_reply(bot, chat_id, &bot_state, "Bot is not running.").await?;
+            return Ok(());
+        }
+
+        let positions = match self.request_positions().await {
+            Ok(positions) => positions,
+            Err(err) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!("Unable to retrieve positions from running bot: {}", err),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        // Keep snapshots next to the symbols configuration
+        let directory = config_path
+            .as_deref()
+            .and_then(|path| Path::new(path).parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let now = bot_state.lock().await.now();
+        match save_positions_snapshot(&directory, &positions, now).await {
+            Ok(path) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!(
+                        "Saved {} position(s) to {}",
+                        positions.len(),
+                        path.display()
+                    ),
+                )
+                .await?;
+            }
+            Err(err) => {
+                let reply = bot_state.lock().await.describe_error(&err);
+                send_reply(bot, chat_id, &bot_state, reply).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_export_config(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        args: String,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let compress = match args.trim().to_lowercase().as_str() {
+            "" => false,
+            "gzip" => true,
+            _ => {
+                send_reply(bot, chat_id, &bot_state, "Usage: /exportconfig [gzip]").await?;
+                return Ok(());
+            }
+        };
+
+        let config_path = bot_state.lock().await.config_path.clone();
+        let config_path = match config_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    "Configuration path is not set. Use /startbot first to initialize.",
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let content = match tokio::fs::read(&config_path).await {
+            Ok(content) => content,
+            Err(_) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    "Failed to read symbols configuration. Ensure the file exists.",
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let file_name = config_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "symbols_config.json".to_string());
+
+        let (payload, file_name) = if compress {
+            match compress_payload(&content) {
+                Ok(compressed) => (compressed, format!("{}.gz", file_name)),
+                Err(err) => {
+                    send_reply(bot, chat_id, &bot_state, err).await?;
+                    return Ok(());
+                }
+            }
+        } else {
+            (content, file_name)
+        };
+
+        let timeout = send_timeout(&bot_state).await;
+        with_reply_timeout(
+            timeout,
+            bot.send_document(chat_id, InputFile::memory(payload).file_name(file_name))
+                .into_future(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_status_file(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        if !bot_state.lock().await.is_running {
+            send_reply(
+                bot,
+                chat_id,
+                &bot_state,
+                "Bot is not running; there is no status to export.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let now = bot_state.lock().await.now();
+        let (file_name, content) = match self.request_status().await {
+            Ok((report, _)) => match status_document(&report, now) {
+                Ok(document) => document,
+                Err(err) => {
+                    send_reply(bot, chat_id, &bot_state, err).await?;
+                    return Ok(());
+                }
+            },
+            Err(err) => {
+                send_reply(
+                    bot,
+                    chat_id,
+                    &bot_state,
+                    format!("Failed to retrieve status: {}", err),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let timeout = send_timeout(&bot_state).await;
+        with_reply_timeout(
+            timeout,
+            bot.send_document(chat_id, InputFile::memory(content).file_name(file_name))
+                .into_future(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_export_csv(
+        &self,
+        bot: &Bot,
+        chat_id: ChatId,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some((_, symbols)) = self.load_symbols(bot, chat_id, &bot_state).await? else {
+            return Ok(());
+        };
+
+        let content = match render_csv_symbols(&symbols) {
+            Ok(content) => content,
+            Err(err) => {
+                send_reply(bot, chat_id, &bot_state, err).await?;
+                return Ok(());
+            }
+        };
+
+        let timeout = send_timeout(&bot_state).await;
+        with_reply_timeout(
+            timeout,
+            bot.send_document(
+                chat_id,
+                InputFile::memory(content.into_bytes()).file_name("symbols.csv"),
+            )
+            .into_future(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replace the symbols configuration with an uploaded document
+    ///
+    /// Route messages carrying a document here from the dispatcher. JSON
+    /// documents are accepted as-is; CSV documents (detected by extension or
+    /// content) need the `csv` feature. All entries are validated before
+    /// anything is written.
+    pub async fn handle_document(
+        &self,
+        bot: Bot,
+        msg: Message,
+        bot_state: Arc<Mutex<BotState>>,
+    ) -> ResponseResult<()> {
+        let Some(document) = msg.document() else {
+            return Ok(());
+        };
+        let chat_id = msg.chat.id;
+
+        if bot_state.lock().await.role_of(chat_id) != Some(Role::Admin) {
+            send_reply(
+                &bot,
+                chat_id,
+                &bot_state,
+                "Importing symbols requires the admin role.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if document.file.size > MAX_IMPORT_SIZE {
+            send_reply(
+                &bot,
+                chat_id,
+                &bot_state,
+                format!(
+                    "Document is too large to import (limit {} bytes).",
+                    MAX_IMPORT_SIZE
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let config_path = bot_state.lock().await.config_path.clone();
+        let Some(config_path) = config_path.map(PathBuf::from) else {
+            send_reply(
+                &bot,
+                chat_id,
+                &bot_state,
+                "Configuration path is not set. Use /startbot first to initialize.",
+            )
+            .await?;
+            return Ok(());
+        };
+
+        let content = match download_document(&bot, document).await {
+            Ok(content) => content,
+            Err(err) => {
+                send_reply(&bot, chat_id, &bot_state, err).await?;
+                return Ok(());
+            }
+        };
+
+        let parsed = if looks_like_csv(document.file_name.as_deref(), &content) {
+            parse_csv_symbols(&content)
+        } else {
+            parse_json_symbols(&content)
+        };
+
+        let symbols = match parsed {
+            Ok(symbols) => symbols,
+            Err(errors) => {
+                let reply = format!("Import failed:\n{}", errors.join("\n"));
+                send_chunked(&bot, chat_id, &bot_state, &reply).await?;
+                return Ok(());
+            }
+        };
+
+        let note = match backup_before_save(&bot_state, &config_path).await {
+            Ok(note) => note,
+            Err(err) => {
+                let reply = format!(
+                    "Import aborted: {}",
+                    bot_state.lock().await.describe_error(&err)
+                );
+                send_reply(&bot, chat_id, &bot_state, reply).await?;
+                return Ok(());
+            }
+        };
+
+        if self
+            .save_symbols(&bot, chat_id, &bot_state, &config_path, &symbols)
+            .await?
+        {
+            send_reply(
+                &bot,
+                chat_id,
+                &bot_state,
+                format!("Imported {} symbol(s).\n{}", symbols.len(), note),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Initialize and run the trading bot in a separate thread
+    pub async fn init_and_run_bot<T: TradingBot>(
+        bot_state: Arc<Mutex<BotState>>,
+        bot: Bot,
+        chat_id: ChatId,
+        mut request_rx: mpsc::UnboundedReceiver<BotRequest>,
+        interval_seconds: u64,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let watchdog_state = Arc::clone(&bot_state);
+        let watchdog_bot = bot.clone();
+
+        // Spawn the bot in a new thread to avoid Send issues
+        let runner = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async move {
+                    install_panic_backtrace_hook();
+
+                    // The interval may be changed at runtime via /setinterval
+                    let mut interval_seconds = interval_seconds;
+
+                    // Try to initialize the bot and confirm it is able to trade
+                    match construct_trading_bot::<T>(interval_seconds).await {
+                        Ok(mut trading_bot) => {
+                            {
+                                let mut state = bot_state.lock().await;
+                                state.interval_seconds = Some(interval_seconds);
+                                let now = state.now();
+                                state.start_warmup(now);
+                                state.deployed_capital = 0.0;
+                                state.record_tick(now);
+                                state.symbol_notifications.clear();
+                                state.add_notification_target(chat_id);
+                            }
+
+                            if let Err(e) = restore_subscriptions(&bot_state).await {
+                                eprintln!("Failed to load subscriptions: {}", describe_error(&e, true));
+                            }
+
+                            // Send confirmation message
+                            if let Err(e) = send_reply(
+                                &bot,
+                                chat_id,
+                                &bot_state,
+                                "Trading bot has initialized and is now running.",
+                            )
+                            .await
+                            {
+                                eprintln!("Error sending message: {}", e);
+                            }
+
+                            let mut last_error: Option<ErrorRecord> = None;
+                            let mut last_panic: Option<PanicRecord> = None;
+                            let mut symbol_override: Option<Vec<SymbolConfig>> = None;
+
+                            let mut check_interval =
+                                tokio::time::interval(Duration::from_secs(interval_seconds));
+
+                            let mut auto_backup_check = tokio::time::interval(AUTO_BACKUP_CHECK_PERIOD);
+
+                            // First tick is consumed
+                            check_interval.tick().await;
+                            bot_state.lock().await.schedule_next_tick(interval_seconds);
+
+                            loop {
+                                tokio::select! {
+                                    maybe_request = request_rx.recv() => {
+                                        match maybe_request {
+                                            Some(BotRequest::GetPositions(response_tx)) => {
+                                                let positions = trading_bot
+                                                    .get_positions()
+                                                    .await
+                                                    .map_err(|e| e.to_string());
+                                                let _ = response_tx.send(positions);
+                                            }
+                                            Some(BotRequest::GetOrders(response_tx)) => {
+                                                let orders = if trading_bot.capabilities().orders {
+                                                    trading_bot
+                                                        .get_open_orders()
+                                                        .await
+                                                        .map_err(|e| e.to_string())
+                                                } else {
+                                                    Err("The strategy does not support listing orders".to_string())
+                                                };
+                                                let _ = response_tx.send(orders);
+                                            }
+                                            Some(BotRequest::SetEndpoint(url, response_tx)) => {
+                                                let result = if trading_bot.capabilities().endpoint {
+                                                    trading_bot
+                                                        .set_endpoint(&url)
+                                                        .await
+                                                        .map_err(|e| e.to_string())
+                                                } else {
+                                                    Err("The strategy does not support switching endpoints".to_string())
+                                                };
+                                                let _ = response_tx.send(result);
+                                            }
+                                            Some(BotRequest::CancelAll(response_tx)) => {
+                                                let cancelled = if trading_bot.capabilities().orders {
+                                                    trading_bot
+                                                        .cancel_all_orders()
+                                                        .await
+                                                        .map_err(|e| e.to_string())
+                                                } else {
+                                                    Err("The strategy does not support cancelling orders".to_string())
+                                                };
+                                                let _ = response_tx.send(cancelled);
+                                            }
+                                            Some(BotRequest::SetInterval(secs, response_tx)) => {
+                                                interval_seconds = secs;
+                                                {
+                                                    let mut state = bot_state.lock().await;
+                                                    state.interval_seconds = Some(secs);
+                                                    state.schedule_next_tick(secs);
+                                                }
+                                                check_interval =
+                                                    tokio::time::interval(Duration::from_secs(secs));
+                                                check_interval.tick().await;
+                                                let _ = response_tx.send(Ok(()));
+                                            }
+                                            Some(BotRequest::GetLastError(response_tx)) => {
+                                                let _ = response_tx.send(Ok(last_error.clone()));
+                                            }
+                                            Some(BotRequest::GetLastPanic(response_tx)) => {
+                                                let _ = response_tx.send(Ok(last_panic.clone()));
+                                            }
+                                            Some(BotRequest::GetCapabilities(response_tx)) => {
+                                                let _ = response_tx.send(Ok(trading_bot.capabilities()));
+                                            }
+                                            Some(BotRequest::Subscribe(response_tx)) => {
+                                                let _ = response_tx.send(Ok(bot_state.lock().await.events.subscribe()));
+                                            }
+                                            Some(BotRequest::GetAccount(response_tx)) => {
+                                                let _ = response_tx.send(Ok(trading_bot.account_info()));
+                                            }
+                                            Some(BotRequest::GetStatusColumns(response_tx)) => {
+                                                let _ = response_tx.send(Ok(trading_bot.status_columns()));
+                                            }
+                                            Some(BotRequest::GetRiskParams(response_tx)) => {
+                                                let _ = response_tx.send(Ok(trading_bot.risk_params()));
+                                            }
+                                            Some(BotRequest::PingExchange(response_tx)) => {
+                                                let result = trading_bot
+                                                    .ping_exchange()
+                                                    .await
+                                                    .map_err(|e| e.to_string());
+                                                let _ = response_tx.send(result);
+                                            }
+                                            Some(BotRequest::SetRiskParam(key, value, response_tx)) => {
+                                                let declared = trading_bot
+                                                    .risk_params()
+                                                    .iter()
+                                                    .any(|param| param.key == key);
+                                                let result = if !trading_bot.capabilities().risk {
+                                                    Err("The strategy does not support changing risk parameters"
+                                                        .to_string())
+                                                } else if declared {
+                                                    trading_bot
+                                                        .set_risk_param(&key, value)
+                                                        .await
+                                                        .map_err(|e| e.to_string())
+                                                } else {
+                                                    Err(format!("Unknown risk parameter '{}'", key))
+                                                };
+                                                let _ = response_tx.send(result);
+                                            }
+                                            Some(BotRequest::Replay(data, response_tx)) => {
+                                                let previous_dry_run =
+                                                    std::mem::replace(&mut bot_state.lock().await.dry_run, true);
+                                                let result = trading_bot
+                                                    .execute_strategy_with_data(
+                                                        &data,
+                                                        bot_state.clone(),
+                                                        bot.clone(),
+                                                        chat_id,
+                                                    )
+                                                    .await
+                                                    .map_err(|e| e.to_string());
+                                                bot_state.lock().await.dry_run = previous_dry_run;
+                                                let _ = response_tx.send(result);
+                                            }
+                                            Some(BotRequest::GetSymbolOverride(response_tx)) => {
+                                                let _ = response_tx.send(Ok(symbol_override.clone()));
+                                            }
+                                            Some(BotRequest::SetSymbolOverride(symbols, response_tx)) => {
+                                                // Clearing the override hands the strategy the file's symbols again
+                                                let reload = match &symbols {
+                                                    Some(symbols) => Ok(symbols.clone()),
+                                                    None => {
+                                                        let config_path = bot_state.lock().await.config_path.clone();
+                                                        match config_path {
+                                                            Some(path) => read_symbols(Path::new(&path))
+                                                                .await
+                                                                .map_err(|e| e.to_string()),
+                                                            None => Err("Configuration path is not set".to_string()),
+                                                        }
+                                                    }
+                                                };
+                                                let result = match reload {
+                                                    Ok(reload) => trading_bot
+                                                        .reload_symbols(reload)
+                                                        .await
+                                                        .map_err(|e| e.to_string()),
+                                                    Err(err) => Err(err),
+                                                };
+                                                if result.is_ok() {
+                                                    symbol_override = symbols;
+                                                }
+                                                let _ = response_tx.send(result);
+                                            }
+                                            Some(BotRequest::GetStatus(response_tx)) => {
+                                                let status = trading_bot
+                                                    .get_status()
+                                                    .await
+                                                    .map(|report| {
+                                                        let text = trading_bot.format_status(&report);
+                                                        (report, text)
+                                                    })
+                                                    .map_err(|e| e.to_string());
+                                                let _ = response_tx.send(status);
+                                            }
+                                            None => {
+                                                println!("Request channel closed, shutting down bot runner");
+                                                break;
+                                            }
+                                        }
+                                    }
+                                    _ = auto_backup_check.tick() => {
+                                        let due = {
+                                            let mut state = bot_state.lock().await;
+                                            let now = state.now();
+                                            if state.take_auto_backup(now) {
+                                                state
+                                                    .config_path
+                                                    .clone()
+                                                    .map(|path| (path, state.backup_retention))
+                                            } else {
+                                                None
+                                            }
+                                        };
+                                        if let Some((config_path, retention)) = due {
+                                            match backup_config(Path::new(&config_path), retention).await {
+                                                Ok(path) => println!("Automatic backup written to {}", path.display()),
+                                                Err(e) => {
+                                                    eprintln!("Automatic backup failed: {}", describe_error(&e, true));
+                                                    let error = bot_state.lock().await.describe_error(&e);
+                                                    if let Err(e) = send_notification(
+                                                        &bot,
+                                                        chat_id,
+                                                        &bot_state,
+                                                        NotificationLevel::Important,
+                                                        format!("Automatic config backup failed: {}", error),
+                                                    )
+                                                    .await
+                                                    {
+                                                        eprintln!("Error sending backup alert: {}", describe_error(&*e, true));
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                    _ = check_interval.tick() => {
+                                        bot_state.lock().await.schedule_next_tick(interval_seconds);
+                                        let (should_run, already_announced, held) = {
+                                            let mut state = bot_state.lock().await;
+                                            let already_announced = state.stop_announced;
+                                            if !state.is_running {
+                                                state.stop_announced = true;
+                                            }
+                                            (state.is_running, already_announced, state.held_by_breaker)
+                                        };
+
+                                        // A tripped breaker pauses the runner rather than
+                                        // ending it, so /startbot can resume once cleared
+                                        if !should_run && held {
+                                            continue;
+                                        }
+
+                                        if !should_run {
+                                            println!("Stop flag detected, shutting down bot");
+                                            // /stopbot already confirmed the stop to the operator
+                                            if already_announced {
+                                                break;
+                                            }
+                                            if let Err(e) = send_reply(
+                                                &bot,
+                                                chat_id,
+                                                &bot_state,
+                                                "Trading bot has been stopped.",
+                                            )
+                                            .await
+                                            {
+                                                eprintln!("Error sending stop message: {}", e);
+                                            }
+                                            break;
+                                        }
+
+                                        if bot_state.lock().await.kill_switch {
+                                            println!("Kill switch active, skipping strategy execution");
+                                            let mut state = bot_state.lock().await;
+                                            let now = state.now();
+                                            state.record_tick(now);
+                                            continue;
+                                        }
+
+                                        let in_window = {
+                                            let state = bot_state.lock().await;
+                                            state.in_trading_window(state.now())
+                                        };
+                                        if !in_window {
+                                            let mut state = bot_state.lock().await;
+                                            let now = state.now();
+                                            state.record_tick(now);
+                                            continue;
+                                        }
+
+                                        let tick_cancel = CancellationToken::new();
+                                        {
+                                            let mut state = bot_state.lock().await;
+                                            state.tick_cancel = Some(tick_cancel.clone());
+                                            state.begin_tick_notifications();
+                                        }
+
+                                        // Failed attempts are retried within the tick before
+                                        // the failure counts; panics and timeouts are not
+                                        // retried, and neither is anything once a stop was asked for
+                                        let mut attempt = 0;
+                                        let result = loop {
+                                            let result = tokio::select! {
+                                                result = tokio::time::timeout(
+                                                    Duration::from_secs(60),
+                                                    AssertUnwindSafe(trading_bot.execute_strategy(
+                                                        bot_state.clone(),
+                                                        bot.clone(),
+                                                        chat_id,
+                                                    ))
+                                                    .catch_unwind(),
+                                                ) => result,
+                                                _ = tick_cancel.cancelled() => break None,
+                                            };
+
+                                            let retryable = matches!(result, Ok(Ok(Err(_))));
+                                            // A panicking strategy is restarted like a failing one
+                                            let result = match result {
+                                                Ok(Ok(outcome)) => Ok(outcome.map_err(|e| {
+                                                    BotError::context("Strategy execution failed", e)
+                                                })),
+                                                Ok(Err(payload)) => {
+                                                    let record = PanicRecord::from_payload(
+                                                        payload,
+                                                        bot_state.lock().await.now(),
+                                                    );
+                                                    let error = BotError::Message(format!(
+                                                        "Strategy panicked: {}",
+                                                        record.message
+                                                    ));
+                                                    last_panic = Some(record);
+                                                    Ok(Err(error))
+                                                }
+                                                Err(elapsed) => Err(elapsed),
+                                            };
+
+                                            let (retries, delay, stopping) = {
+                                                let state = bot_state.lock().await;
+                                                (
+                                                    state.strategy_retries,
+                                                    state.strategy_retry_delay,
+                                                    !state.is_running || tick_cancel.is_cancelled(),
+                                                )
+                                            };
+                                            match result {
+                                                Ok(Err(error)) if retryable && !stopping && attempt < retries => {
+                                                    attempt += 1;
+                                                    eprintln!(
+                                                        "{} (retry {} of {})",
+                                                        describe_error(&error, true),
+                                                        attempt,
+                                                        retries
+                                                    );
+                                                    tokio::select! {
+                                                        _ = tokio::time::sleep(delay) => {}
+                                                        _ = tick_cancel.cancelled() => break None,
+                                                    }
+                                                }
+                                                result => break Some(result),
+                                            }
+                                        };
+                                        bot_state.lock().await.tick_cancel = None;
+
+                                        // Only an immediate /stopbot cancels the tick, and it has
+                                        // already confirmed the stop
+                                        let Some(result) = result else {
+                                            println!("Strategy tick cancelled, shutting down bot");
+                                            if let Err(e) =
+                                                flush_notification_summary(&bot, &bot_state).await
+                                            {
+                                                eprintln!("Error sending notification summary: {}", describe_error(&*e, true));
+                                            }
+                                            break;
+                                        };
+
+                                        if let Err(e) =
+                                            flush_notification_summary(&bot, &bot_state).await
+                                        {
+                                            eprintln!("Error sending notification summary: {}", describe_error(&*e, true));
+                                        }
+                                        bot_state.lock().await.end_tick_notifications();
+
+                                        let failing = {
+                                            let errors = trading_bot.symbol_errors();
+                                            bot_state.lock().await.record_symbol_errors(&errors)
+                                        };
+                                        for (symbol, error) in failing {
+                                            disable_failing_symbol(
+                                                &bot,
+                                                chat_id,
+                                                &bot_state,
+                                                &mut trading_bot,
+                                                &mut symbol_override,
+                                                &symbol,
+                                                &error,
+                                            )
+                                            .await;
+                                        }
+
+                                        match result {
+                                            Ok(Ok(_)) => {
+                                                let mut state = bot_state.lock().await;
+                                                state.circuit_breaker.record_success();
+                                                let now = state.now();
+                                                state.record_tick(now);
+                                                state.publish(BotEvent::TickCompleted);
+                                                let check_rules = !state.alert_rules.is_empty();
+                                                drop(state);
+
+                                                // The status after each tick feeds /pnlchart and the
+                                                // alert rules
+                                                match trading_bot.get_status().await {
+                                                    Ok(report) => {
+                                                        let fired = {
+                                                            let mut state = bot_state.lock().await;
+                                                            state.record_pnl_sample(
+                                                                report.realized_pnl + report.unrealized_pnl,
+                                                            );
+                                                            if check_rules {
+                                                                let columns = trading_bot.status_columns();
+                                                                state.check_alert_rules(&report, &columns)
+                                                            } else {
+                                                                Vec::new()
+                                                            }
+                                                        };
+                                                        for (rule, value) in fired {
+                                                            let message =
+                                                                format!("Alert: {} (now {:.2})", rule, value);
+                                                            if let Err(e) = broadcast_notification(
+                                                                &bot,
+                                                                &bot_state,
+                                                                NotificationLevel::Critical,
+                                                                message,
+                                                            )
+                                                            .await
+                                                            {
+                                                                eprintln!("Error sending alert: {}", describe_error(&*e, true));
+                                                            }
+                                                        }
+                                                    }
+                                                    Err(e) => {
+                                                        eprintln!("Failed to get status after tick: {}", e)
+                                                    }
+                                                }
+                                            }
+                                            Ok(Err(error)) => {
+                                                eprintln!("{}", describe_error(&error, true));
+                                                // Described only now so /verboseerrors applies
+                                                let error_msg = bot_state.lock().await.describe_error(&error);
+                                                bot_state.lock().await.publish(BotEvent::StrategyError {
+                                                    message: error_msg.clone(),
+                                                });
+                                                last_error = Some(ErrorRecord {
+                                                    at: bot_state.lock().await.now(),
+                                                    message: error_msg.clone(),
+                                                });
+
+                                                if let Err(e) =
+                                                    send_reply(&bot, chat_id, &bot_state, &error_msg).await
+                                                {
+                                                    eprintln!("Error sending error message: {}", e);
+                                                }
+
+                                                let tripped = {
+                                                    let mut state = bot_state.lock().await;
+                                                    let now = state.now();
+                                                    let tripped = state.circuit_breaker.record_failure(now);
+                                                    if tripped {
+                                                        state.stop_for_breaker();
+                                                    }
+                                                    tripped
+                                                };
+                                                if tripped {
+                                                    let threshold = bot_state.lock().await.circuit_breaker.threshold;
+                                                    let reason = format!("after {} consecutive failures", threshold);
+                                                    if let Err(e) =
+                                                        announce_breaker_trip(&bot, &bot_state, &reason).await
+                                                    {
+                                                        eprintln!(
+                                                            "Error sending circuit breaker message: {}",
+                                                            describe_error(&*e, true)
+                                                        );
+                                                    }
+                                                    continue;
+                                                }
+
+                                                if let Err(e) = send_restart_notification(
+                                                    &bot,
+                                                    chat_id,
+                                                    &bot_state,
+                                                    "Stopping and restarting the bot due to error...",
+                                                )
+                                                .await
+                                                {
+                                                    eprintln!("Error sending restart message: {}", describe_error(&*e, true));
+                                                }
+
+                                                {
+                                                    let mut state = bot_state.lock().await;
+                                                    state.is_running = false;
+                                                }
+
+                                                tokio::time::sleep(Duration::from_secs(5)).await;
+
+                                                {
+                                                    let mut state = bot_state.lock().await;
+                                                    state.is_running = true;
+                                                }
+
+                                                if let Err(e) = send_restart_notification(
+                                                    &bot,
+                                                    chat_id,
+                                                    &bot_state,
+                                                    "Bot has been restarted.",
+                                                )
+                                                .await
+                                                {
+                                                    eprintln!(
+                                                        "Error sending restart confirmation message: {}",
+                                                        describe_error(&*e, true)
+                                                    );
+                                                }
+
+                                                match construct_trading_bot::<T>(interval_seconds).await {
+                                                    Ok(new_bot) => {
+                                                        {
+                                                            let mut state = bot_state.lock().await;
+                                                            state.interval_seconds = Some(interval_seconds);
+                                                            state.schedule_next_tick(interval_seconds);
+                                                        }
+
+                                                        trading_bot = new_bot;
+                                                        check_interval = tokio::time::interval(
+                                                            Duration::from_secs(interval_seconds),
+                                                        );
+                                                        check_interval.tick().await;
+                                                        if let Err(e) = send_restart_notification(
+                                                            &bot,
+                                                            chat_id,
+                                                            &bot_state,
+                                                            "Trading bot has been re-initialized.",
+                                                        )
+                                                        .await
+                                                        {
+                                                            eprintln!(
+                                                                "Error sending re-initialization message: {}",
+                                                                describe_error(&*e, true)
+                                                            );
+                                                        }
+                                                    }
+                                                    Err(e) => {
+                                                        let init_error_msg =
+                                                            format!("Failed to re-initialize bot: {}", e);
+                                                        eprintln!("{}", &init_error_msg);
+                                                        last_error = Some(ErrorRecord {
+                                                            at: bot_state.lock().await.now(),
+                                                            message: init_error_msg.clone(),
+                                                        });
+
+                                                        if let Err(e) = send_reply(
+                                                            &bot,
+                                                            chat_id,
+                                                            &bot_state,
+                                                            &init_error_msg,
+                                                        )
+                                                        .await
+                                                        {
+                                                            eprintln!(
+                                                                "Error sending re-initialization error message: {}",
+                                                                e
+                                                            );
+                                                        }
+
+                                                        let mut state = bot_state.lock().await;
+                                                        state.is_running = false;
+                                                    }
+                                                }
+                                            }
+                                            Err(_) => {
+                                                println!("Strategy execution timed out");
+                                                last_error = Some(ErrorRecord {
+                                                    at: bot_state.lock().await.now(),
+                                                    message: "Strategy execution timed out".to_string(),
+                                                });
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            // Safely handle error
+                            let error_msg = format!("Failed to initialize bot: {}", e);
+                            eprintln!("{}", &error_msg);
+
+                            if let Err(e) = send_reply(&bot, chat_id, &bot_state, &error_msg).await {
+                                eprintln!("Error sending initialization error message: {}", e);
+                            }
+
+                            // Reset the running state
+                            let mut state = bot_state.lock().await;
+                            state.is_running = false;
+                        }
+                    }
+                });
+        });
+
+        // The watchdog runs on the caller's runtime so it keeps going even if
+        // the runner's thread is stuck
+        tokio::spawn(run_watchdog(watchdog_state, watchdog_bot, runner));
+
+        // Don't wait for thread completion - just return success
+        Ok(())
+    }
+}
+
+/// Construct the strategy and confirm it is able to trade, so a restart is
+/// held to the same readiness bar as the first start
+async fn construct_trading_bot<T: TradingBot>(interval_seconds: u64) -> Result<T, String> {
+    let trading_bot = T::new(interval_seconds).await.map_err(|e| e.to_string())?;
+    trading_bot
+        .readiness_check()
+        .await
+        .map_err(|e| format!("trading bot is not ready: {}", e))?;
+    Ok(trading_bot)
+}
+
+/// Alert the notification targets once per stall while the runner thread is
+/// alive
+async fn run_watchdog(
+    bot_state: Arc<Mutex<BotState>>,
+    bot: Bot,
+    runner: std::thread::JoinHandle<()>,
+) {
+    while !runner.is_finished() {
+        tokio::time::sleep(WATCHDOG_CHECK_PERIOD).await;
+
+        let stall = {
+            let mut state = bot_state.lock().await;
+            match state.watchdog_stall(state.now()) {
+                Some(stall) if !state.watchdog_alerted => {
+                    state.watchdog_alerted = true;
+                    Some(stall)
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(stall) = stall {
+            let message = format!(
+                "Runner may be stalled: no successful tick for {}.",
+                format_duration(stall)
+            );
+            if let Err(e) =
+                broadcast_notification(&bot, &bot_state, NotificationLevel::Critical, message).await
+            {
+                eprintln!(
+                    "Error sending watchdog alert: {}",
+                    describe_error(&*e, true)
+                );
+            }
+        }
+    }
+}
+
+/// Create a helper function for sending messages
+const TELEGRAM_MAX_MESSAGE_LENGTH: usize = 4096;
+/// Smallest value accepted by `/maxlength`
+const MIN_MESSAGE_LENGTH: usize = 256;
+const PRE_WRAP_OVERHEAD: usize = "<pre></pre>".len();
+
+fn split_message_chunks(message: &str, max_len: usize) -> Vec<String> {
+    if message.is_empty() {
+        return Vec::new();
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+
+    for segment in message.split_inclusive('\n') {
+        let seg_len = segment.chars().count();
+
+        if current_len + seg_len <= max_len {
+            current.push_str(segment);
+            current_len += seg_len;
+            continue;
+        }
+
+        if !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+
+        if seg_len <= max_len {
+            current.push_str(segment);
+            current_len = seg_len;
+        } else {
+            let mut buffer = String::new();
+            let mut buffer_len = 0usize;
+
+            for ch in segment.chars() {
+                if buffer_len == max_len {
+                    chunks.push(buffer);
+                    buffer = String::new();
+                    buffer_len = 0;
+                }
+
+                buffer.push(ch);
+                buffer_len += 1;
+            }
+
+            if !buffer.is_empty() {
+                current = buffer;
+                current_len = buffer_len;
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Read and parse the symbols configuration file
+pub async fn read_symbols(config_path: &Path) -> Result<Vec<SymbolConfig>, BotError> {
+    let content = tokio::fs::read_to_string(config_path).await.map_err(|_| {
+        BotError::Message(
+            "Failed to read symbols configuration. Ensure the file exists.".to_string(),
+        )
+    })?;
+    serde_json::from_str(&content)
+        .map_err(|_| BotError::Message("Failed to parse symbols configuration.".to_string()))
+}
+
+/// Optional `SymbolConfig` fields and the values older files get for them
+fn optional_symbol_fields() -> [(&'static str, serde_json::Value); 3] {
+    [
+        ("enabled", serde_json::Value::Bool(true)),
+        ("max_slippage", serde_json::Value::Null),
+        ("size_step", serde_json::Value::Null),
+    ]
+}
+
+/// The result of `migrate_config`
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub symbols: Vec<SymbolConfig>,
+    /// One line per symbol that was missing fields, e.g. `BTC: enabled`
+    pub changes: Vec<String>,
+}
+
+/// Bring a symbols configuration written for an older schema up to date by
+/// filling in every optional field it lacks with its default
+pub fn migrate_config(content: &str) -> Result<Migration, BotError> {
+    let entries: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_str(content)
+        .map_err(|e| BotError::context("Failed to parse symbols configuration", e))?;
+
+    let mut symbols = Vec::with_capacity(entries.len());
+    let mut changes = Vec::new();
+    for (index, mut entry) in entries.into_iter().enumerate() {
+        let mut added = Vec::new();
+        for (field, default) in optional_symbol_fields() {
+            if !entry.contains_key(field) {
+                entry.insert(field.to_string(), default);
+                added.push(field);
+            }
+        }
+        let symbol: SymbolConfig = serde_json::from_value(serde_json::Value::Object(entry))
+            .map_err(|e| BotError::context(format!("Invalid entry {}", index + 1), e))?;
+        if !added.is_empty() {
+            changes.push(format!("{}: {}", symbol.symbol, added.join(", ")));
+        }
+        symbols.push(symbol);
+    }
+    Ok(Migration { symbols, changes })
+}
+
+/// What `/configmtime` reports about the symbols configuration file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigFileInfo {
+    pub modified: DateTime<Utc>,
+    pub size: u64,
+    /// Symbols in the file, or `None` if it does not parse
+    pub symbols: Option<usize>,
+}
+
+/// Stat the symbols configuration file, returning `Ok(None)` when it does
+/// not exist
+pub async fn config_file_info(config_path: &Path) -> Result<Option<ConfigFileInfo>, BotError> {
+    let metadata = match tokio::fs::metadata(config_path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(BotError::context("Failed to read config file metadata", e)),
+    };
+    let modified = metadata
+        .modified()
+        .map_err(|e| BotError::context("Failed to read config file modification time", e))?;
+    let symbols = read_symbols(config_path)
+        .await
+        .ok()
+        .map(|symbols| symbols.len());
+
+    Ok(Some(ConfigFileInfo {
+        modified: modified.into(),
+        size: metadata.len(),
+        symbols,
+    }))
+}
+
+/// Write the symbols configuration file as pretty-printed JSON
+pub async fn write_symbols(config_path: &Path, symbols: &[SymbolConfig]) -> Result<(), BotError> {
+    let content = serde_json::to_string_pretty(symbols)
+        .map_err(|e| BotError::Message(format!("Failed to serialize symbols: {}", e)))?;
+    tokio::fs::write(config_path, content)
+        .await
+        .map_err(|_| BotError::Message("Failed to update symbols configuration.".to_string()))
+}
+
+/// Where `store_symbols` saved an edit
+enum StoredSymbols {
+    Staged,
+    Written,
+    /// `/persist` is off, so the runner must keep these in memory
+    InMemory(Vec<SymbolConfig>),
+}
+
+/// Save edited symbols into the staged copy while staging and into the file
+/// while `/persist` is on; otherwise hand them back for the runner
+async fn store_symbols(
+    bot_state: &Arc<Mutex<BotState>>,
+    config_path: &Path,
+    symbols: &[SymbolConfig],
+) -> Result<StoredSymbols, BotError> {
+    {
+        let mut state = bot_state.lock().await;
+        if let Some(staged) = state.staged_symbols.as_mut() {
+            *staged = symbols.to_vec();
+            state.staged_changes += 1;
+            return Ok(StoredSymbols::Staged);
+        }
+        if !state.persist_changes {
+            return Ok(StoredSymbols::InMemory(symbols.to_vec()));
+        }
+    }
+    write_symbols(config_path, symbols).await?;
+    Ok(StoredSymbols::Written)
+}
+
+/// Apply `/role CHAT_ID admin|operator|remove` on behalf of `caller`
+///
+/// The first role assignment also makes the caller an admin, so configuring
+/// roles cannot lock everyone out.
+async fn assign_role(bot_state: &Arc<Mutex<BotState>>, caller: ChatId, args: &str) -> String {
+    let usage = "Usage: /role CHAT_ID admin|operator|remove";
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (target, action) = match parts.as_slice() {
+        [] => {
+            let state = bot_state.lock().await;
+            if state.chat_roles.is_empty() {
+                return "No roles configured; every chat is an admin.".to_string();
+            }
+            let mut roles: Vec<_> = state.chat_roles.iter().collect();
+            roles.sort_by_key(|(chat_id, _)| chat_id.0);
+            let mut text = String::from("Chat roles:\n");
+            for (chat_id, role) in roles {
+                text.push_str(&format!("\n{}: {:?}", chat_id, role));
+            }
+            return text;
+        }
+        [target, action] => (*target, *action),
+        _ => return usage.to_string(),
+    };
+    let Ok(target) = target.parse::<i64>().map(ChatId) else {
+        return usage.to_string();
+    };
+
+    let mut state = bot_state.lock().await;
+    if action.eq_ignore_ascii_case("remove") {
+        if target == caller {
+            return "You cannot remove your own role.".to_string();
+        }
+        return match state.chat_roles.remove(&target) {
+            Some(_) => format!("Removed chat {}", target),
+            None => format!("Chat {} has no role", target),
+        };
+    }
+
+    let role = match action.parse::<Role>() {
+        Ok(role) => role,
+        Err(err) => return err,
+    };
+    if target == caller && role != Role::Admin {
+        return "You cannot demote yourself.".to_string();
+    }
+    let first_assignment = state.chat_roles.is_empty();
+    state.chat_roles.insert(target, role);
+    if first_assignment && target != caller {
+        state.chat_roles.insert(caller, Role::Admin);
+        format!(
+            "Chat {} is now {:?}. Roles are now enforced; you were added as Admin.",
+            target, role
+        )
+    } else {
+        format!("Chat {} is now {:?}", target, role)
+    }
+}
+
+/// Resolve a `/replay` file name inside `replay_dir`
+///
+/// Only relative names without `..` are accepted, so a replay cannot read
+/// files outside the directory.
+pub fn replay_data_path(replay_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Ok(replay_dir.join(relative))
+    } else {
+        Err("Name a file inside the replay directory, without '..' or a leading '/'.".to_string())
+    }
+}
+
+/// Read a recorded market data file for `/replay`
+pub async fn read_market_data(path: &Path) -> Result<MarketData, BotError> {
+    let content = tokio::fs::read_to_string(path).await.map_err(|e| {
+        BotError::context(format!("Failed to read market data {}", path.display()), e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| BotError::context("Failed to parse market data", e))
+}
+
+/// Largest document `handle_document` will download
+const MAX_IMPORT_SIZE: u32 = 1024 * 1024;
+
+async fn download_document(bot: &Bot, document: &Document) -> Result<String, String> {
+    let file = bot
+        .get_file(document.file.id.clone())
+        .await
+        .map_err(|e| format!("Failed to fetch document: {}", e))?;
+    let mut content = Vec::new();
+    bot.download_file(&file.path, &mut content)
+        .await
+        .map_err(|e| format!("Failed to download document: {}", e))?;
+    String::from_utf8(content).map_err(|_| "Document is not valid UTF-8 text.".to_string())
+}
+
+/// The snapshot label to use, `DEFAULT_SNAPSHOT_LABEL` when none was given
+fn snapshot_label(label: &str) -> String {
+    match label.trim() {
+        "" => DEFAULT_SNAPSHOT_LABEL.to_string(),
+        label => label.to_string(),
+    }
+}
+
+/// Show how each status field changed since `snapshot` was taken
+fn render_status_diff(
+    label: &str,
+    snapshot: &StatusSnapshot,
+    current: &StatusReport,
+    now: DateTime<Utc>,
+) -> String {
+    let before = &snapshot.report;
+    let elapsed = (now - snapshot.at).to_std().unwrap_or_default();
+    let total_before = before.realized_pnl + before.unrealized_pnl;
+    let total_now = current.realized_pnl + current.unrealized_pnl;
+    format!(
+        "Changes since snapshot '{}' ({} ago):\nOpen positions: {} -> {} ({:+})\nRealized PnL: \
+         {:.2} -> {:.2} ({:+.2})\nUnrealized PnL: {:.2} -> {:.2} ({:+.2})\nTotal PnL: {:.2} -> \
+         {:.2} ({:+.2})",
+        label,
+        format_duration(elapsed),
+        before.open_positions,
+        current.open_positions,
+        current.open_positions as i64 - before.open_positions as i64,
+        before.realized_pnl,
+        current.realized_pnl,
+        current.realized_pnl - before.realized_pnl,
+        before.unrealized_pnl,
+        current.unrealized_pnl,
+        current.unrealized_pnl - before.unrealized_pnl,
+        total_before,
+        total_now,
+        total_now - total_before
+    )
+}
+
+/// Describe the account for `/account`, flagging whether it trades live funds
+fn render_account(account: &AccountInfo) -> String {
+    if account.exchange.is_empty() {
+        return "The strategy does not report its account.".to_string();
+    }
+    let environment = if account.testnet {
+        "TESTNET (no real funds)"
+    } else {
+        "MAINNET (live funds)"
+    };
+    let label = if account.account_label.is_empty() {
+        "(unlabelled)"
+    } else {
+        &account.account_label
+    };
+    format!(
+        "Exchange: {}\nAccount: {}\nEnvironment: {}",
+        account.exchange, label, environment
+    )
+}
+
+/// Serialize a status report into a timestamped JSON document for
+/// `/statusfile`
+fn status_document(report: &StatusReport, now: DateTime<Utc>) -> Result<(String, Vec<u8>), String> {
+    let content = serde_json::to_vec_pretty(report)
+        .map_err(|err| format!("Failed to serialize status: {}", err))?;
+    let file_name = format!("status_{}.json", now.format("%Y%m%d_%H%M%S"));
+    Ok((file_name, content))
+}
+
+/// Decide whether an uploaded document is CSV, preferring its extension
+fn looks_like_csv(file_name: Option<&str>, content: &str) -> bool {
+    let extension = file_name
+        .and_then(|name| Path::new(name).extension())
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("csv") => true,
+        Some("json") => false,
+        _ => {
+            let content = content.trim_start();
+            !content.starts_with('[')
+                && !content.starts_with('{')
+                && content
+                    .lines()
+                    .next()
+                    .is_some_and(|line| line.contains(','))
+        }
+    }
+}
+
+fn parse_json_symbols(content: &str) -> Result<Vec<SymbolConfig>, Vec<String>> {
+    let symbols: Vec<SymbolConfig> =
+        serde_json::from_str(content).map_err(|e| vec![format!("Invalid symbols JSON: {}", e)])?;
+    let mut seen = HashSet::new();
+    let errors: Vec<String> = symbols
+        .iter()
+        .enumerate()
+        .filter_map(|(index, symbol)| {
+            validate_import_entry(symbol, &mut seen)
+                .err()
+                .map(|reason| format!("entry {}: {}", index + 1, reason))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(symbols)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(feature = "csv")]
+fn parse_csv_symbols(content: &str) -> Result<Vec<SymbolConfig>, Vec<String>> {
+    crate::symbols_csv::parse_symbols_csv(content)
+}
+
+#[cfg(feature = "csv")]
+fn render_csv_symbols(symbols: &[SymbolConfig]) -> Result<String, String> {
+    crate::symbols_csv::symbols_to_csv(symbols)
+}
+
+#[cfg(not(feature = "csv"))]
+fn render_csv_symbols(_symbols: &[SymbolConfig]) -> Result<String, String> {
+    Err("CSV export is not available in this build (enable the 'csv' feature).".to_string())
+}
+
+#[cfg(not(feature = "csv"))]
+fn parse_csv_symbols(_content: &str) -> Result<Vec<SymbolConfig>, Vec<String>> {
+    Err(vec!["CSV import is not available in this build (enable \
+              the 'csv' feature)."
+        .to_string()])
+}
+
+pub const DEFAULT_BACKUP_RETENTION: usize = 10;
+
+/// Where /subscribe chats are saved: `subscriptions.json` next to the
+/// symbols configuration
+fn subscriptions_path(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("subscriptions.json")
+}
+
+/// Write the subscriber list next to the configuration file
+async fn persist_subscriptions(bot_state: &Arc<Mutex<BotState>>) -> Result<(), BotError> {
+    let (config_path, subscribers) = {
+        let state = bot_state.lock().await;
+        (state.config_path.clone(), state.subscribers.clone())
+    };
+    let config_path = config_path
+        .ok_or_else(|| BotError::Message("Configuration path is not set".to_string()))?;
+    let content = serde_json::to_string_pretty(&subscribers)
+        .map_err(|e| BotError::context("Failed to serialize subscriptions", e))?;
+    tokio::fs::write(subscriptions_path(Path::new(&config_path)), content)
+        .await
+        .map_err(|e| BotError::context("Failed to write subscriptions", e))
+}
+
+/// Add the saved subscribers back to the notification targets
+async fn restore_subscriptions(bot_state: &Arc<Mutex<BotState>>) -> Result<(), BotError> {
+    let Some(config_path) = bot_state.lock().await.config_path.clone() else {
+        return Ok(());
+    };
+    let content = match tokio::fs::read_to_string(subscriptions_path(Path::new(&config_path))).await
+    {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(BotError::context("Failed to read subscriptions", e)),
+    };
+    let subscribers: Vec<ChatId> = serde_json::from_str(&content)
+        .map_err(|e| BotError::context("Failed to parse subscriptions", e))?;
+
+    let mut state = bot_state.lock().await;
+    for chat_id in subscribers {
+        state.add_subscriber(chat_id);
+    }
+    Ok(())
+}
+
+fn backup_directory(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("backups")
+}
+
+fn backup_stem(config_path: &Path) -> String {
+    config_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "symbols_config".to_string())
+}
+
+/// Back up the configuration file before an edit replaces it
+///
+/// Staged and in-memory edits leave the file alone, so there is nothing to
+/// back up until it is written. Returns a note for the reply either way.
+async fn backup_before_save(
+    bot_state: &Arc<Mutex<BotState>>,
+    config_path: &Path,
+) -> Result<String, BotError> {
+    let retention = {
+        let state = bot_state.lock().await;
+        if state.staged_symbols.is_some() {
+            return Ok("Staged; use /apply to save it.".to_string());
+        }
+        if !state.persist_changes {
+            return Ok("Kept in memory; the file is unchanged.".to_string());
+        }
+        state.backup_retention
+    };
+    if !tokio::fs::try_exists(config_path).await.unwrap_or(false) {
+        return Ok("No previous configuration to back up.".to_string());
+    }
+    let path = backup_config(config_path, retention).await?;
+    Ok(format!("Backup saved to {}", path.display()))
+}
+
+/// Copy the configuration file into a `backups` directory next to it, then
+/// delete the oldest backups beyond `retention`
+///
+/// Returns the path of the new backup file.
+pub async fn backup_config(config_path: &Path, retention: usize) -> Result<PathBuf, BotError> {
+    let directory = backup_directory(config_path);
+    tokio::fs::create_dir_all(&directory)
+        .await
+        .map_err(|e| BotError::context("Failed to create backup directory", e))?;
+
+    let backup_path = directory.join(format!(
+        "{}_{}.json",
+        backup_stem(config_path),
+        Utc::now().format("%Y%m%d_%H%M%S%.3f")
+    ));
+
+    tokio::fs::copy(config_path, &backup_path)
+        .await
+        .map_err(|e| BotError::context("Failed to back up configuration", e))?;
+
+    // The backup itself succeeded, so a failed prune is only logged
+    if let Err(e) = prune_backups(config_path, retention).await {
+        eprintln!("Failed to prune old backups: {}", describe_error(&e, true));
+    }
+    Ok(backup_path)
+}
+
+/// List the backups of `config_path`, oldest first
+pub async fn list_backups(config_path: &Path) -> Result<Vec<PathBuf>, BotError> {
+    let directory = backup_directory(config_path);
+    let prefix = format!("{}_", backup_stem(config_path));
+
+    let mut entries = match tokio::fs::read_dir(&directory).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(BotError::context("Failed to read backup directory", e)),
+    };
+
+    let mut backups = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| BotError::context("Failed to read backup directory", e))?
+    {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name.ends_with(".json") {
+            backups.push(entry.path());
+        }
+    }
+
+    // Timestamps in the names sort chronologically
+    backups.sort();
+    Ok(backups)
+}
+
+/// Delete all but the newest `retention` backups, returning the removed paths
+pub async fn prune_backups(config_path: &Path, retention: usize) -> Result<Vec<PathBuf>, BotError> {
+    let backups = list_backups(config_path).await?;
+    let excess = backups.len().saturating_sub(retention);
+
+    let mut removed = Vec::new();
+    for backup in backups.into_iter().take(excess) {
+        tokio::fs::remove_file(&backup)
+            .await
+            .map_err(|e| BotError::context("Failed to remove old backup", e))?;
+        removed.push(backup);
+    }
+    Ok(removed)
+}
+
+/// Remove every disabled symbol, returning the names that were removed
+pub fn purge_disabled(symbols: &mut Vec<SymbolConfig>) -> Vec<String> {
+    let removed = symbols
+        .iter()
+        .filter(|s| !s.enabled)
+        .map(|s| s.symbol.clone())
+        .collect();
+    symbols.retain(|s| s.enabled);
+    removed
+}
+
+/// Write `positions` to `positions_<timestamp>.json` inside `directory`
+pub async fn save_positions_snapshot(
+    directory: &Path,
+    positions: &[PositionInfo],
+    timestamp: DateTime<Utc>,
+) -> Result<PathBuf, BotError> {
+    let path = directory.join(format!(
+        "positions_{}.json",
+        timestamp.format("%Y%m%d_%H%M%S")
+    ));
+    let content = serde_json::to_string_pretty(positions)
+        .map_err(|e| BotError::context("Failed to serialize positions", e))?;
+    tokio::fs::write(&path, content)
+        .await
+        .map_err(|e| BotError::context("Failed to write positions snapshot", e))?;
+    Ok(path)
+}
+
+/// Pair each configured symbol with its open position size, or `None` when
+/// the symbol is flat
+pub fn holdings<'a>(
+    symbols: &'a [SymbolConfig],
+    positions: &[PositionInfo],
+) -> Vec<(&'a str, Option<f64>)> {
+    symbols
+        .iter()
+        .map(|config| {
+            let size = positions
+                .iter()
+                .filter(|position| position.symbol == config.symbol && position.size != 0.0)
+                .map(|position| position.size)
+                .reduce(|total, size| total + size);
+            (config.symbol.as_str(), size)
+        })
+        .collect()
+}
+
+/// Move `symbol` to the 1-based `position` in the configuration order
+pub fn move_symbol(
+    symbols: &mut Vec<SymbolConfig>,
+    symbol: &str,
+    position: usize,
+) -> Result<(), String> {
+    if position == 0 || position > symbols.len() {
+        return Err(format!(
+            "Position {} is out of range. Use a value between 1 and {}.",
+            position,
+            symbols.len()
+        ));
+    }
+
+    let current = symbols
+        .iter()
+        .position(|s| s.symbol == symbol)
+        .ok_or_else(|| format!("Symbol '{}' not found.", symbol))?;
+
+    let entry = symbols.remove(current);
+    symbols.insert(position - 1, entry);
+    Ok(())
+}
+
+/// Gzip a document payload, if the crate was built with compression support
+#[cfg(feature = "compression")]
+fn compress_payload(data: &[u8]) -> Result<Vec<u8>, String> {
+    crate::compression::gzip(data).map_err(|e| format!("Failed to compress payload: {}", e))
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress_payload(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Compression is not available. Rebuild with the `compression` feature.".to_string())
+}
+
+#[cfg(feature = "compression")]
+fn decompress_payload(data: &[u8]) -> Result<Vec<u8>, String> {
+    crate::compression::gunzip(data).map_err(|e| format!("Failed to decompress payload: {}", e))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress_payload(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Compressed snippets need the `compression` feature.".to_string())
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Encode `symbols` as a `/share` snippet: compact JSON, optionally gzipped,
+/// in base64
+pub fn encode_share(symbols: &[SymbolConfig], gzip: bool) -> Result<String, String> {
+    let json =
+        serde_json::to_vec(symbols).map_err(|e| format!("Failed to serialize symbols: {}", e))?;
+    let payload = if gzip { compress_payload(&json)? } else { json };
+    Ok(BASE64.encode(payload))
+}
+
+/// Decode and validate a `/share` snippet. Whitespace is ignored, since chat
+/// clients may wrap long snippets; gzipped snippets are detected by their
+/// header.
+pub fn decode_share(snippet: &str) -> Result<Vec<SymbolConfig>, Vec<String>> {
+    let snippet: String = snippet.split_whitespace().collect();
+    if snippet.is_empty() {
+        return Err(vec!["Share snippet is empty".to_string()]);
+    }
+    let mut payload = BASE64
+        .decode(snippet)
+        .map_err(|e| vec![format!("Share snippet is not valid base64: {}", e)])?;
+    if payload.starts_with(&GZIP_MAGIC) {
+        payload = decompress_payload(&payload).map_err(|e| vec![e])?;
+    }
+    let content = String::from_utf8(payload)
+        .map_err(|_| vec!["Share snippet does not contain text".to_string()])?;
+    parse_json_symbols(&content)
+}
+
+#[cfg(feature = "tracing")]
+fn change_log_level(level: &str) -> String {
+    use crate::logging::{current_log_level, parse_log_level, set_log_level};
+
+    if level.trim().is_empty() {
+        return match current_log_level() {
+            Some(level) => format!("Log level: {}", level),
+            None => "Logging was not initialized with init_logging.".to_string(),
+        };
+    }
+    match parse_log_level(level).and_then(|level| set_log_level(level).map(|()| level)) {
+        Ok(level) => format!("Log level set to {}", level),
+        Err(err) => err,
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+fn change_log_level(_level: &str) -> String {
+    "Runtime log levels are not available. Rebuild with the `tracing` feature.".to_string()
+}
+
+#[cfg(feature = "resources")]
+fn resources_table() -> Result<Table, String> {
+    let usage = crate::resources::current_usage();
+    let unavailable = || "unavailable".to_string();
+
+    let mut table = Table::new();
+    table.add_row(row!["Resource", "Value"]);
+    table.add_row(row![
+        "Memory (RSS)",
+        usage.rss_bytes.map_or_else(unavailable, |bytes| format!(
+            "{:.1} MiB",
+            bytes as f64 / (1024.0 * 1024.0)
+        ))
+    ]);
+    table.add_row(row![
+        "CPU time",
+        usage
+            .cpu_time
+            .map_or_else(unavailable, |time| format!("{:.2} s", time.as_secs_f64()))
+    ]);
+    Ok(table)
+}
+
+#[cfg(not(feature = "resources"))]
+fn resources_table() -> Result<Table, String> {
+    Err("Resource reporting is not available. Rebuild with the `resources` feature.".to_string())
+}
+
+/// Outcome of one `/selftest` check
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The symbols configuration can be read and parsed
+pub async fn check_config(config_path: Option<&str>) -> CheckResult {
+    let Some(config_path) = config_path else {
+        return CheckResult::fail("Config", "configuration path is not set");
+    };
+    match read_symbols(Path::new(config_path)).await {
+        Ok(symbols) => CheckResult::pass("Config", format!("{} symbol(s)", symbols.len())),
+        Err(err) => CheckResult::fail("Config", err.to_string()),
+    }
+}
+
+/// The Telegram API answers `getMe` within the send timeout
+pub async fn check_telegram(bot: &Bot, timeout: Duration) -> CheckResult {
+    match with_reply_timeout(timeout, bot.get_me().into_future()).await {
+        Ok(me) => CheckResult::pass("Telegram", format!("reachable as @{}", me.username())),
+        Err(err) => CheckResult::fail("Telegram", err.to_string()),
+    }
+}
+
+/// The strategy reports its status through the runner
+pub fn check_strategy(status: Result<(StatusReport, String), String>) -> CheckResult {
+    match status {
+        Ok((report, _)) => CheckResult::pass(
+            "Strategy",
+            format!("{} open position(s)", report.open_positions),
+        ),
+        Err(err) => CheckResult::fail("Strategy", err),
+    }
+}
+
+/// The runner is still receiving requests
+pub fn check_runner_channel(request_tx: &mpsc::UnboundedSender<BotRequest>) -> CheckResult {
+    if request_tx.is_closed() {
+        CheckResult::fail("Runner", "request channel is closed")
+    } else {
+        CheckResult::pass("Runner", "request channel is open")
+    }
+}
+
+/// Render self-test results as a table with an overall verdict row
+pub fn render_self_test(results: &[CheckResult]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Check", "Result", "Detail"]);
+    for result in results {
+        table.add_row(row![
+            result.name,
+            if result.passed { "PASS" } else { "FAIL" },
+            result.detail
+        ]);
+    }
+
+    let failed = results.iter().filter(|result| !result.passed).count();
+    let verdict = if failed == 0 {
+        "all checks passed".to_string()
+    } else {
+        format!("{} of {} check(s) failed", failed, results.len())
+    };
+    table.add_row(row![
+        "Overall",
+        if failed == 0 { "PASS" } else { "FAIL" },
+        verdict
+    ]);
+    table
+}
+
+/// How serious an `/audit` finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AuditSeverity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for AuditSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            AuditSeverity::Error => "ERROR",
+            AuditSeverity::Warning => "WARN",
+        })
+    }
+}
+
+/// One problem `/audit` found across the symbols configuration
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditFinding {
+    pub severity: AuditSeverity,
+    /// The symbol concerned, or `*` for the configuration as a whole
+    pub symbol: String,
+    pub detail: String,
+}
+
+/// Cross-symbol checks that `SymbolConfig::validate` cannot make on its own:
+/// symbols listed more than once (ignoring case), duplicates that disagree on
+/// thresholds, and enabled entry amounts adding up to more than `budget`.
+/// Errors come first.
+pub fn audit_symbols(symbols: &[SymbolConfig], budget: Option<f64>) -> Vec<AuditFinding> {
+    let mut findings = Vec::new();
+
+    let mut groups: Vec<(String, Vec<&SymbolConfig>)> = Vec::new();
+    for symbol in symbols {
+        let key = symbol.symbol.trim().to_uppercase();
+        match groups.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, group)) => group.push(symbol),
+            None => groups.push((key, vec![symbol])),
+        }
+    }
+    for (name, group) in groups.iter().filter(|(_, group)| group.len() > 1) {
+        let spellings: Vec<&str> = group.iter().map(|symbol| symbol.symbol.as_str()).collect();
+        findings.push(AuditFinding {
+            severity: AuditSeverity::Warning,
+            symbol: name.clone(),
+            detail: format!("listed {} times ({})", group.len(), spellings.join(", ")),
+        });
+        let first = group[0];
+        if group.iter().any(|symbol| {
+            symbol.entry_threshold != first.entry_threshold
+                || symbol.exit_threshold != first.exit_threshold
+        }) {
+            let thresholds: Vec<String> = group
+                .iter()
+                .map(|symbol| format!("{}/{}", symbol.entry_threshold, symbol.exit_threshold))
+                .collect();
+            findings.push(AuditFinding {
+                severity: AuditSeverity::Error,
+                symbol: name.clone(),
+                detail: format!(
+                    "conflicting entry/exit thresholds: {}",
+                    thresholds.join(", ")
+                ),
+            });
+        }
+    }
+
+    if let Some(budget) = budget {
+        let allocated: f64 = symbols
+            .iter()
+            .filter(|symbol| symbol.enabled)
+            .map(|symbol| symbol.entry_amount)
+            .sum();
+        if allocated > budget {
+            findings.push(AuditFinding {
+                severity: AuditSeverity::Error,
+                symbol: "*".to_string(),
+                detail: format!(
+                    "enabled entry amounts total {:.2}, over the session budget of {:.2}",
+                    allocated, budget
+                ),
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| finding.severity);
+    findings
+}
+
+/// Render `/audit` findings, one row each
+pub fn render_audit(findings: &[AuditFinding]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Severity", "Symbol", "Finding"]);
+    for finding in findings {
+        table.add_row(row![finding.severity, finding.symbol, finding.detail]);
+    }
+    table
+}
+
+/// Set one field of `symbol` from its text form, validating the result
+pub fn edit_symbol(
+    symbols: &mut [SymbolConfig],
+    symbol: &str,
+    field: &str,
+    value: &str,
+) -> Result<(), String> {
+    let column: SymbolColumn = field.parse()?;
+    let entry = symbols
+        .iter_mut()
+        .find(|entry| entry.symbol == symbol)
+        .ok_or_else(|| format!("Symbol '{}' not found.", symbol))?;
+
+    let number = || {
+        value
+            .parse::<f64>()
+            .map_err(|_| format!("'{}' is not a number", value))
+    };
+    let mut edited = entry.clone();
+    match column {
+        SymbolColumn::Symbol => return Err("The symbol name cannot be edited.".to_string()),
+        SymbolColumn::EntryAmount => edited.entry_amount = number()?,
+        SymbolColumn::ExitAmount => edited.exit_amount = number()?,
+        SymbolColumn::EntryThreshold => edited.entry_threshold = number()?,
+        SymbolColumn::ExitThreshold => edited.exit_threshold = number()?,
+        SymbolColumn::Enabled => {
+            edited.enabled =
+                parse_toggle(value).ok_or_else(|| "enabled must be on or off".to_string())?
+        }
+        SymbolColumn::MaxSlippage => {
+            edited.max_slippage = match value.to_lowercase().as_str() {
+                "none" | "off" => None,
+                _ => Some(number()?),
+            }
+        }
+        SymbolColumn::SizeStep => {
+            edited.size_step = match value.to_lowercase().as_str() {
+                "none" | "off" => None,
+                _ => Some(number()?),
+            }
+        }
+    }
+    edited.validate()?;
+    *entry = edited;
+    Ok(())
+}
+
+/// Symbols shown per `/symbols` page unless `/pagesize` changes it
+pub const DEFAULT_SYMBOLS_PAGE_SIZE: usize = 20;
+/// Largest page size `/pagesize` accepts
+const MAX_SYMBOLS_PAGE_SIZE: usize = 200;
+
+/// The symbols on 1-based `page` and the number of pages; an empty list still
+/// has one (empty) page
+pub fn symbols_page(
+    symbols: &[SymbolConfig],
+    page: usize,
+    page_size: usize,
+) -> Result<(&[SymbolConfig], usize), String> {
+    let page_size = page_size.max(1);
+    let pages = symbols.len().div_ceil(page_size).max(1);
+    if page == 0 || page > pages {
+        return Err(format!(
+            "Page {} does not exist. There {} {} page{}.",
+            page,
+            if pages == 1 { "is" } else { "are" },
+            pages,
+            if pages == 1 { "" } else { "s" }
+        ));
+    }
+    let start = (page - 1) * page_size;
+    let end = (start + page_size).min(symbols.len());
+    Ok((&symbols[start..end], pages))
+}
+
+/// Render open orders for `/orders`
+pub fn orders_table(orders: &[OrderInfo]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["ID", "Symbol", "Side", "Price", "Qty", "Status"]);
+    for order in orders {
+        table.add_row(row![
+            order.id,
+            order.symbol,
+            order.side,
+            format!("{:.4}", order.price),
+            format!("{:.4}", order.quantity),
+            order.status
+        ]);
+    }
+    table
+}
+
+/// Each chat's token budget under `limiter` at `now`
+pub fn rate_limit_table(limiter: &RateLimiter, chats: &[ChatId], now: DateTime<Utc>) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Chat", "Tokens", "Rate", "Burst", "Throttled"]);
+    for &chat in chats {
+        let tokens = if limiter.rate > 0.0 {
+            format!("{:.1}", limiter.tokens(chat, now))
+        } else {
+            "-".to_string()
+        };
+        table.add_row(row![
+            chat,
+            tokens,
+            format!("{}/s", limiter.rate),
+            limiter.burst,
+            if limiter.is_throttled(chat, now) {
+                "yes"
+            } else {
+                "no"
+            }
+        ]);
+    }
+    table
+}
+
+/// Quote assets recognised at the end of symbols written without a
+/// separator, such as `BTCUSDT`
+const QUOTE_ASSETS: [&str; 17] = [
+    "USDT", "USDC", "BUSD", "FDUSD", "TUSD", "USDP", "DAI", "USD", "EUR", "GBP", "TRY", "BRL",
+    "JPY", "AUD", "BTC", "ETH", "BNB",
+];
+
+/// The quote asset of `symbol` in upper case: what follows the last `/`,
+/// `-`, `_` or `:`, or else the longest of `QUOTE_ASSETS` it ends in, so
+/// `ETHBUSD` is quoted in `BUSD` rather than `USD`
+pub fn quote_asset(symbol: &str) -> Option<String> {
+    let symbol = symbol.trim().to_uppercase();
+    if let Some((base, quote)) = symbol.rsplit_once(['/', '-', '_', ':']) {
+        return (!base.is_empty() && !quote.is_empty()).then(|| quote.to_string());
+    }
+    QUOTE_ASSETS
+        .iter()
+        .filter(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))
+        .max_by_key(|quote| quote.len())
+        .map(|quote| quote.to_string())
+}
+
+/// The symbols quoted in `quote`, ignoring case, e.g. `BTCUSDT` or
+/// `ETH/USDT` for `USDT`; see `quote_asset`
+pub fn filter_by_quote(symbols: Vec<SymbolConfig>, quote: &str) -> Vec<SymbolConfig> {
+    let quote = quote.trim().to_uppercase();
+    symbols
+        .into_iter()
+        .filter(|symbol| quote_asset(&symbol.symbol).is_some_and(|asset| asset == quote))
+        .collect()
+}
+
+/// The per-symbol overrides in effect, by symbol in configuration order;
+/// symbols without any are left out
+pub fn symbol_overrides(symbols: &[SymbolConfig], state: &BotState) -> Vec<(String, Vec<String>)> {
+    symbols
+        .iter()
+        .filter_map(|symbol| {
+            let mut active = Vec::new();
+            if !symbol.enabled {
+                active.push("disabled".to_string());
+            }
+            if let Some(errors) = state.symbol_errors.get(&symbol.symbol).filter(|e| **e > 0) {
+                active.push(match state.symbol_error_limit {
+                    0 => format!("{} consecutive errors", errors),
+                    limit => format!("{} of {} errors before auto-disable", errors, limit),
+                });
+            }
+            if let Some(max_slippage) = symbol.max_slippage {
+                active.push(format!("max slippage {:.4}", max_slippage));
+            }
+            if let Some(size_step) = symbol.size_step {
+                active.push(format!("size step {}", size_step));
+            }
+            (!active.is_empty()).then(|| (symbol.symbol.clone(), active))
+        })
+        .collect()
+}
+
+/// Render the symbols with only the chosen columns
+pub fn symbols_table(symbols: &[SymbolConfig], columns: &[SymbolColumn]) -> Table {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        columns
+            .iter()
+            .map(|column| Cell::new(column.header()))
+            .collect(),
+    ));
+    for symbol in symbols {
+        table.add_row(Row::new(
+            columns
+                .iter()
+                .map(|column| Cell::new(&column.value(symbol)))
+                .collect(),
+        ));
+    }
+    table
+}
+
+/// Render risk parameters for `/risk`
+pub fn risk_table(params: &[RiskParam]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Key", "Value", "Description"]);
+    for param in params {
+        table.add_row(row![param.key, param.value, param.description]);
+    }
+    table
+}
+
+/// Parse the `KEY=VALUE` argument of `/setrisk`
+fn parse_risk_assignment(assignment: &str) -> Result<(String, f64), String> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| "Usage: /setrisk KEY=VALUE".to_string())?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("Usage: /setrisk KEY=VALUE".to_string());
+    }
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| format!("'{}' is not a number", value.trim()))?;
+    Ok((key.to_string(), value))
+}
+
+/// How many status-request latencies `/latency` keeps
+const MAX_RECORDED_LATENCIES: usize = 10;
+
+/// Render recorded latencies, newest first, with their average
+pub fn render_latencies(latencies: &[Duration]) -> String {
+    if latencies.is_empty() {
+        return "No status requests measured yet. Try /status first.".to_string();
+    }
+    let total: Duration = latencies.iter().sum();
+    let average = total / latencies.len() as u32;
+    let mut text = format!(
+        "Status request latency (last {}, newest first):\n",
+        latencies.len()
+    );
+    for latency in latencies.iter().rev() {
+        text.push_str(&format!("\n{:.1} ms", latency.as_secs_f64() * 1000.0));
+    }
+    text.push_str(&format!(
+        "\n\nAverage: {:.1} ms",
+        average.as_secs_f64() * 1000.0
+    ));
+    text
+}
+
+/// One-line status such as `▶ running | 3 pos | pnl +2.40 | next 42s`
+///
+/// Parts that are unknown (no report, no scheduled tick) are left out.
+pub fn compact_status(
+    is_running: bool,
+    kill_switch: bool,
+    report: Option<&StatusReport>,
+    next_tick: Option<Duration>,
+) -> String {
+    let mut parts = vec![match (is_running, kill_switch) {
+        (_, true) => "⛔ killed".to_string(),
+        (true, false) => "▶ running".to_string(),
+        (false, false) => "■ stopped".to_string(),
+    }];
+    if let Some(report) = report {
+        parts.push(format!("{} pos", report.open_positions));
+        parts.push(format!(
+            "pnl {:+.2}",
+            report.realized_pnl + report.unrealized_pnl
+        ));
+    }
+    if let (true, Some(next_tick)) = (is_running, next_tick) {
+        parts.push(format!("next {}", format_duration(next_tick)));
+    }
+    parts.join(" | ")
+}
+
+/// `text` as the crate escapes it for each parse mode, shown unrendered by
+/// `/escape`
+pub fn escape_preview(text: &str) -> String {
+    format!(
+        "MarkdownV2:\n{}\n\nHTML:\n{}",
+        markdown::escape(text),
+        html::escape(text)
+    )
+}
+
+/// Send `text` once as escaped MarkdownV2 and once as HTML `<pre>`
+///
+/// Returns a description of each mode that failed to send.
+async fn send_format_test(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    text: &str,
+) -> Vec<String> {
+    let timeout = send_timeout(bot_state).await;
+    let renderings = [
+        (
+            "MarkdownV2",
+            ParseMode::MarkdownV2,
+            format!("*MarkdownV2:*\n{}", markdown::escape(text)),
+        ),
+        (
+            "HTML",
+            ParseMode::Html,
+            format!("<b>HTML:</b>\n<pre>{}</pre>", html::escape(text)),
+        ),
+    ];
+
+    let mut failures = Vec::new();
+    for (name, parse_mode, rendered) in renderings {
+        let request = bot
+            .send_message(chat_id, rendered)
+            .parse_mode(parse_mode)
+            .into_future();
+        if let Err(e) = with_reply_timeout(timeout, request).await {
+            failures.push(format!("{} rendering failed: {}", name, e));
+        }
+    }
+    failures
+}
+
+/// Send a table as a monospaced MarkdownV2 code block
+async fn send_table(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    table: &Table,
+) -> ResponseResult<()> {
+    send_code_block(bot, chat_id, bot_state, &table.to_string()).await
+}
+
+/// Send `text` as a MarkdownV2 code block
+async fn send_code_block(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    text: &str,
+) -> ResponseResult<()> {
+    let (timeout, tag) = reply_settings(bot_state).await;
+    let block = match tag {
+        Some(tag) => format!("{}\n```\n{}\n```", markdown::escape(&tag), text),
+        None => format!("```\n{}\n```", text),
+    };
+    with_reply_timeout(
+        timeout,
+        bot.send_message(chat_id, block)
+            .parse_mode(ParseMode::MarkdownV2)
+            .into_future(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// How many times a retryable notification send is attempted again
+const MAX_SEND_RETRIES: u32 = 2;
+const SERVER_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+pub const DEFAULT_COMMAND_COOLDOWN_SECS: u64 = 2;
+const MAX_COMMAND_COOLDOWN_SECS: u64 = 60;
+const DESTRUCTIVE_COOLDOWN_FACTOR: u32 = 3;
+
+/// Upper bound for `/concurrency`; larger values are clamped
+pub const MAX_STRATEGY_CONCURRENCY: usize = 32;
+
+pub const DEFAULT_SEND_CONCURRENCY: usize = 4;
+/// Upper bound for `/sendconcurrency`
+pub const MAX_SEND_CONCURRENCY: usize = 32;
+
+/// Default upper bound on how long a single Telegram send may take
+pub const DEFAULT_SEND_TIMEOUT_SECS: u64 = 5;
+const MAX_SEND_TIMEOUT_SECS: u64 = 120;
+
+async fn send_timeout(bot_state: &Arc<Mutex<BotState>>) -> Duration {
+    Duration::from_secs(bot_state.lock().await.send_timeout_secs)
+}
+
+/// Longest accepted `/tag`
+const MAX_TAG_LENGTH: usize = 32;
+
+async fn reply_settings(bot_state: &Arc<Mutex<BotState>>) -> (Duration, Option<String>) {
+    let state = bot_state.lock().await;
+    (
+        Duration::from_secs(state.send_timeout_secs),
+        state.instance_tag.clone(),
+    )
+}
+
+/// Room taken by the instance tag and the space after it
+fn tag_overhead(tag: Option<&str>) -> usize {
+    tag.map_or(0, |tag| tag.chars().count() + 1)
+}
+
+/// Await a Telegram request, turning an expired timeout into a
+/// `RequestError` carrying `BotError::Timeout` so command handlers can keep
+/// using `?`
+async fn with_reply_timeout<T>(
+    timeout: Duration,
+    request: impl Future<Output = ResponseResult<T>>,
+) -> ResponseResult<T> {
+    match tokio::time::timeout(timeout, request).await {
+        Ok(result) => result,
+        Err(_) => Err(RequestError::Io(Arc::new(io::Error::new(
+            io::ErrorKind::TimedOut,
+            BotError::Timeout(timeout),
+        )))),
+    }
+}
+
+/// Send a plain-text reply bounded by the configured send timeout
+async fn send_reply(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    text: impl Into<String>,
+) -> ResponseResult<()> {
+    let (timeout, tag) = reply_settings(bot_state).await;
+    let text = match tag {
+        Some(tag) => format!("{} {}", tag, text.into()),
+        None => text.into(),
+    };
+    with_reply_timeout(timeout, bot.send_message(chat_id, text).into_future()).await?;
+    Ok(())
+}
+
+/// Confirm a start or stop in the style set by /confirmstyle. A reaction
+/// carries no text, so the instance tag only applies to the other styles.
+async fn send_confirmation(
+    bot: &Bot,
+    msg: &Message,
+    bot_state: &Arc<Mutex<BotState>>,
+    text: &str,
+) -> ResponseResult<()> {
+    let style = bot_state.lock().await.confirm_style;
+    match style {
+        ConfirmStyle::Message => send_reply(bot, msg.chat.id, bot_state, text).await,
+        ConfirmStyle::Reply => {
+            let (timeout, tag) = reply_settings(bot_state).await;
+            let text = match tag {
+                Some(tag) => format!("{} {}", tag, text),
+                None => text.to_string(),
+            };
+            with_reply_timeout(
+                timeout,
+                bot.send_message(msg.chat.id, text)
+                    .reply_parameters(ReplyParameters::new(msg.id))
+                    .into_future(),
+            )
+            .await?;
+            Ok(())
+        }
+        ConfirmStyle::Reaction => {
+            let (timeout, _) = reply_settings(bot_state).await;
+            with_reply_timeout(
+                timeout,
+                bot.set_message_reaction(msg.chat.id, msg.id)
+                    .reaction(vec![ReactionType::Emoji {
+                        emoji: "👍".to_string(),
+                    }])
+                    .into_future(),
+            )
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+/// Send a plain-text reply, splitting it into Telegram-sized chunks
+async fn send_chunked(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    text: &str,
+) -> ResponseResult<()> {
+    let (tag, max_message_length) = {
+        let state = bot_state.lock().await;
+        (state.instance_tag.clone(), state.max_message_length)
+    };
+    let max_len = max_message_length.saturating_sub(tag_overhead(tag.as_deref()));
+    for chunk in split_message_chunks(text, max_len) {
+        send_reply(bot, chat_id, bot_state, chunk).await?;
+    }
+    Ok(())
+}
+
+/// Alert every notification target that the circuit breaker tripped
+/// `reason` and stopped the bot. Both organic and forced trips go through
+/// here, so rehearsals reach the same chats as real incidents.
+async fn announce_breaker_trip(
+    bot: &Bot,
+    bot_state: &Arc<Mutex<BotState>>,
+    reason: &str,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    broadcast_notification(
+        bot,
+        bot_state,
+        NotificationLevel::Critical,
+        format!(
+            "Circuit breaker tripped {}. The bot is stopped; use /clearbreaker to acknowledge.",
+            reason
+        ),
+    )
+    .await
+}
+
+/// Send one of the runner's restart progress messages at
+/// `BotState::restart_level`
+async fn send_restart_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    message: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let level = bot_state.lock().await.restart_level.clone();
+    send_notification(bot, chat_id, bot_state, level, message.to_string()).await
+}
+
+/// Send a notification using the level and delivery settings stored in
+/// `BotState`
+///
+/// A failed send is returned to the caller; only `broadcast_notification`
+/// falls back to `BotState::fallback_chat`.
+pub async fn send_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    level: NotificationLevel,
+    message: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    {
+        let mut state = bot_state.lock().await;
+        let now = state.now();
+        let (mut current_level, level_override) = state.resolve_notification_level(now);
+        if state.subscribers.contains(&chat_id) && current_level != NotificationLevel::None {
+            current_level = SUBSCRIBER_LEVEL;
+        }
+        if !level_is_sufficient(level.clone(), current_level) {
+            state.notification_stats.filtered += 1;
+            let quiet = matches!(level_override, Some(LevelOverride::Quiet { .. }));
+            let warmup = matches!(level_override, Some(LevelOverride::Warmup { .. }))
+                && !state.subscribers.contains(&chat_id)
+                && level_is_sufficient(
+                    level.clone(),
+                    state.effective_notification_level(state.local_time(now)),
+                );
+            let suppressed = &mut state.notification_stats.suppressed;
+            match (quiet, warmup) {
+                (true, _) => suppressed.quiet += 1,
+                (false, true) => suppressed.warmup += 1,
+                (false, false) => suppressed.level += 1,
+            }
+            if let Some(counts) = &mut state.tick_notifications {
+                if quiet {
+                    counts.filtered_by_quiet += 1;
+                } else {
+                    counts.filtered_by_level += 1;
+                }
+            }
+            return Ok(());
+        }
+        if level != NotificationLevel::Critical
+            && state.is_duplicate_notification(chat_id, &message, now)
+        {
+            state.notification_stats.filtered += 1;
+            state.notification_stats.suppressed.dedup += 1;
+            return Ok(());
+        }
+        if let Some(counts) = &mut state.tick_notifications {
+            counts.sent += 1;
+        }
+
+        state.publish(BotEvent::Notification {
+            chat_id,
+            level: level.clone(),
+            message: message.clone(),
+        });
+        state.record_notification(now, level.clone(), &message);
+
+        // Only a tick's notifications are summarized, at the end of the tick;
+        // critical messages always go out immediately
+        if state.summary_mode
+            && state.tick_notifications.is_some()
+            && level != NotificationLevel::Critical
+        {
+            state.summary_buffer.push((chat_id, level, message));
+            state.notification_stats.suppressed.coalesced += 1;
+            return Ok(());
+        }
+    }
+
+    deliver_paced(bot, chat_id, bot_state, vec![level], message).await
+}
+
+/// Deliver `message` to `chat_id` through the rate limiter and the send
+/// permits, counting it as one sent notification per entry in `levels`
+async fn deliver_paced(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    levels: Vec<NotificationLevel>,
+    message: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (options, wait, permits) = {
+        let mut state = bot_state.lock().await;
+        let now = state.now();
+        let wait = state.rate_limiter.reserve(chat_id, now);
+        (
+            DeliveryOptions::from_state(&state),
+            wait,
+            state.send_permits(),
+        )
+    };
+
+    // Waiting here rather than in a detached task lets the caller see how
+    // the send went; broadcasts wait for each chat concurrently
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+    deliver_and_count(
+        bot, chat_id, bot_state, &levels, &message, &options, permits,
+    )
+    .await
+}
+
+/// Deliver a message once a send permit is free and count the outcome for
+/// each notification in it, at `levels`
+async fn deliver_and_count(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    levels: &[NotificationLevel],
+    message: &str,
+    options: &DeliveryOptions,
+    permits: Arc<Semaphore>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let result = {
+        let _permit = permits.acquire_owned().await?;
+        deliver_notification(bot, chat_id, message, options).await
+    };
+    let mut state = bot_state.lock().await;
+    match result {
+        Ok(()) => {
+            for level in levels {
+                state.notification_stats.record_sent(level);
+            }
+        }
+        Err(_) => state.notification_stats.failed += levels.len() as u64,
+    }
+    result
+}
+
+/// Send a notification about `symbol`, counting it towards that symbol's
+/// `/symbolnoise` tally
+pub async fn send_symbol_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    symbol: &str,
+    level: NotificationLevel,
+    message: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    *bot_state
+        .lock()
+        .await
+        .symbol_notifications
+        .entry(symbol.to_string())
+        .or_default() += 1;
+    send_notification(bot, chat_id, bot_state, level, message).await
+}
+
+/// Send a notification to every chat in `notification_targets`, at most
+/// `BotState::send_concurrency` at a time
+///
+/// Every target is tried even when some fail. Chats whose send failed with
+/// an error that retrying cannot fix (the bot was blocked, kicked or the
+/// chat was deleted) are removed from the targets instead of being retried on
+/// every send; the remaining failures are returned together as
+/// `BotError::Broadcast`. When no target could be reached, the
+/// notification goes to `BotState::fallback_chat` instead. Returns the number
+/// of chats removed.
+pub async fn broadcast_notification(
+    bot: &Bot,
+    bot_state: &Arc<Mutex<BotState>>,
+    level: NotificationLevel,
+    message: String,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let (targets, fallback) = {
+        let state = bot_state.lock().await;
+        (state.notification_targets.clone(), state.fallback_chat)
+    };
+    let mut removed = 0;
+    let mut failures = 0;
+    let mut errors = Vec::new();
+
+    // Sends run side by side; `send_notification` bounds how many at once
+    let results = futures::future::join_all(targets.iter().map(|&chat_id| {
+        let message = message.clone();
+        let level = level.clone();
+        async move {
+            (
+                chat_id,
+                send_notification(bot, chat_id, bot_state, level, message).await,
+            )
+        }
+    }))
+    .await;
+
+    for (chat_id, result) in results {
+        match result {
+            Ok(()) => {}
+            Err(e) if is_fatal_send_error(e.as_ref()) => {
+                eprintln!(
+                    "Removing chat {} from notification targets: {}",
+                    chat_id,
+                    describe_error(&*e, true)
+                );
+                failures += 1;
+                if bot_state.lock().await.remove_notification_target(chat_id) {
+                    removed += 1;
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                errors.push((chat_id, e));
+            }
+        }
+    }
+
+    // The fallback chat only hears about it when no target could be reached
+    if let (true, Some(fallback)) = (!targets.is_empty() && failures == targets.len(), fallback) {
+        eprintln!(
+            "Delivery failed for every target, using fallback chat {}",
+            fallback
+        );
+        return send_notification(bot, fallback, bot_state, level, message)
+            .await
+            .map(|()| removed);
+    }
+
+    if errors.is_empty() {
+        Ok(removed)
+    } else {
+        Err(Box::new(BotError::Broadcast(errors)))
+    }
+}
+
+fn is_fatal_send_error(e: &(dyn Error + Send + Sync + 'static)) -> bool {
+    matches!(
+        e.downcast_ref::<BotError>(),
+        Some(BotError::Telegram(request_error))
+            if classify_error(request_error) == ErrorClass::Fatal
+    )
+}
+
+/// Send all buffered summary-mode notifications as one message per chat
+///
+/// Returns the number of buffered notifications that were flushed.
+pub async fn flush_notification_summary(
+    bot: &Bot,
+    bot_state: &Arc<Mutex<BotState>>,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let buffered = std::mem::take(&mut bot_state.lock().await.summary_buffer);
+
+    let flushed = buffered.len();
+    let mut by_chat: Vec<(ChatId, Vec<NotificationLevel>, Vec<String>)> = Vec::new();
+    for (chat_id, level, message) in buffered {
+        match by_chat.iter_mut().find(|(id, _, _)| *id == chat_id) {
+            Some((_, levels, messages)) => {
+                levels.push(level);
+                messages.push(message);
+            }
+            None => by_chat.push((chat_id, vec![level], vec![message])),
+        }
+    }
+
+    // Every chat gets its summary even if an earlier one failed
+    let mut first_error = None;
+    for (chat_id, levels, messages) in by_chat {
+        let summary = format!(
+            "Summary of {} notification(s):\n\n{}",
+            messages.len(),
+            messages.join("\n\n")
+        );
+        if let Err(e) = deliver_paced(bot, chat_id, bot_state, levels, summary).await {
+            first_error.get_or_insert(e);
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(flushed),
+    }
+}
+
+/// Send `message` to one chat without any `BotState`, using the default send
+/// timeout and delivery settings
+#[deprecated(
+    note = "use send_notification, which applies the configured send timeout and delivery settings"
+)]
+pub async fn send_telegram_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    level: NotificationLevel,
+    current_level: NotificationLevel,
+    message: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Only send if the message level is important enough
+    if level_is_sufficient(level, current_level) {
+        deliver_notification(bot, chat_id, &message, &DeliveryOptions::default()).await
+    } else {
+        Ok(())
+    }
+}
+
+/// How notifications are delivered, derived from `BotState`
+struct DeliveryOptions {
+    timeout: Duration,
+    silent: bool,
+    tag: Option<String>,
+    max_message_length: usize,
+    /// Zone of the timestamp prefixed to each message, if any
+    timestamp_zone: Option<FixedOffset>,
+    clock: Clock,
+}
+
+impl DeliveryOptions {
+    fn from_state(state: &BotState) -> Self {
+        Self {
+            timeout: Duration::from_secs(state.send_timeout_secs),
+            silent: state.silent_notifications,
+            tag: state.instance_tag.clone(),
+            max_message_length: state.max_message_length,
+            timestamp_zone: state.timestamp_notifications.then_some(state.time_zone),
+            clock: state.clock.clone(),
+        }
+    }
+}
+
+impl Default for DeliveryOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_SEND_TIMEOUT_SECS),
+            silent: false,
+            tag: None,
+            max_message_length: TELEGRAM_MAX_MESSAGE_LENGTH,
+            timestamp_zone: None,
+            clock: Clock::default(),
+        }
+    }
+}
+
+/// The ISO 8601 timestamp prefixed to notifications when
+/// `timestamp_notifications` is on
+fn timestamp_prefix(now: DateTime<Utc>, zone: FixedOffset) -> String {
+    format!(
+        "{} ",
+        now.with_timezone(&zone)
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    )
+}
+
+/// Send a notification as `<pre>` chunks, bounding each send by the
+/// configured timeout
+async fn deliver_notification(
+    bot: &Bot,
+    chat_id: ChatId,
+    message: &str,
+    options: &DeliveryOptions,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let timeout = options.timeout;
+    let timestamp = options
+        .timestamp_zone
+        .map(|zone| timestamp_prefix(options.clock.now(), zone))
+        .unwrap_or_default();
+    let max_payload_len = options
+        .max_message_length
+        .saturating_sub(PRE_WRAP_OVERHEAD)
+        .saturating_sub(tag_overhead(options.tag.as_deref()))
+        .saturating_sub(timestamp.chars().count());
+
+    for chunk in split_message_chunks(message, max_payload_len) {
+        let chunk = format!("{}{}", timestamp, chunk);
+        let mono_message = match &options.tag {
+            Some(tag) => format!("{} <pre>{}</pre>", html::escape(tag), chunk),
+            None => format!("<pre>{}</pre>", chunk),
+        };
+        let mut attempt = 0;
+
+        loop {
+            let request = bot
+                .send_message(chat_id, mono_message.clone())
+                .parse_mode(ParseMode::Html)
+                .disable_notification(options.silent);
+
+            match tokio::time::timeout(timeout, request.into_future()).await {
+                Ok(Ok(_)) => break,
+                Ok(Err(e)) => {
+                    let class = classify_error(&e);
+                    if attempt < MAX_SEND_RETRIES && class.is_retryable() {
+                        attempt += 1;
+                        let delay = match class {
+                            ErrorClass::RetryableRateLimit { after } => after,
+                            _ => SERVER_RETRY_DELAY,
+                        };
+                        eprintln!(
+                            "Retrying Telegram message in {:?} after error: {}",
+                            delay, e
+                        );
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+
+                    eprintln!("Failed to send Telegram message: {}", e);
+                    return Err(Box::new(BotError::Telegram(e)));
+                }
+                Err(_) => {
+                    eprintln!("Timed out sending Telegram message after {:?}", timeout);
+                    return Err(Box::new(BotError::Timeout(timeout)));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Every supported command as `command - description` lines, ready to paste
+/// into BotFather's /setcommands
+pub fn botfather_commands() -> String {
+    Command::bot_commands()
+        .iter()
+        .map(|command| {
+            format!(
+                "{} - {}",
+                command.command.trim_start_matches('/'),
+                command.description
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Notification settings parsed from `/notify key=value ...`
+#[derive(Debug, Default, PartialEq)]
+pub struct NotifySettings {
+    pub level: Option<NotificationLevel>,
+    pub silent: Option<bool>,
+    pub summary: Option<bool>,
+}
+
+/// Parse whitespace-separated `key=value` pairs into `NotifySettings`
+///
+/// Every problem (unknown keys, bad values) is collected so the operator
+/// can fix them all at once.
+pub fn parse_notify_settings(input: &str) -> Result<NotifySettings, Vec<String>> {
+    let mut settings = NotifySettings::default();
+    let mut errors = Vec::new();
+
+    for pair in input.split_whitespace() {
+        let Some((key, value)) = pair.split_once('=') else {
+            errors.push(format!("Expected key=value, got '{}'", pair));
+            continue;
+        };
+
+        match key.to_lowercase().as_str() {
+            "level" => match value.parse::<NotificationLevel>() {
+                Ok(level) => settings.level = Some(level),
+                Err(err) => errors.push(err),
+            },
+            "silent" => match parse_toggle(value) {
+                Some(silent) => settings.silent = Some(silent),
+                None => errors.push(format!("Invalid value for silent: '{}'", value)),
+            },
+            "summary" => match parse_toggle(value) {
+                Some(summary) => settings.summary = Some(summary),
+                None => errors.push(format!("Invalid value for summary: '{}'", value)),
+            },
+            _ => errors.push(format!("Unknown key '{}'", key)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(settings)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Describe the level schedule and the fallback used outside its windows
+fn render_level_schedule(
+    windows: &[LevelWindow],
+    fallback: &NotificationLevel,
+    time_zone: FixedOffset,
+) -> String {
+    if windows.is_empty() {
+        return format!("No level schedule set. Level is always {:?}.", fallback);
+    }
+
+    let lines = windows
+        .iter()
+        .map(|window| window.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "Level schedule (UTC{}):\n{}\nOutside these windows: {:?}",
+        time_zone, lines, fallback
+    )
+}
+
+/// Parse an on/off style argument
+fn parse_toggle(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Describe the configured notification chats and whether `current` is one
+/// of them
+fn render_where(targets: &[ChatId], current: ChatId) -> String {
+    if targets.is_empty() {
+        return format!(
+            "No notification chat is configured yet.\nThis chat: {}",
+            current
+        );
+    }
+
+    let list = targets
+        .iter()
+        .map(|chat_id| chat_id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let verdict = if targets.contains(&current) {
+        "This chat receives notifications."
+    } else {
+        "This chat does NOT receive notifications."
+    };
+
+    format!(
+        "Notification chat(s): {}\nThis chat: {}\n{}",
+        list, current, verdict
+    )
+}
+
+/// Check the position cap before opening a new position, sending an
+/// `Important` alert when the cap blocks the entry
+///
+/// Strategies should call this before entering a trade and skip the entry
+/// when it returns `false`.
+pub async fn check_position_cap(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    symbol: &str,
+    open_positions: usize,
+) -> bool {
+    let (allowed, max_positions) = {
+        let state = bot_state.lock().await;
+        (state.can_open_position(open_positions), state.max_positions)
+    };
+
+    if !allowed {
+        let message = format!(
+            "Entry for {} blocked: {} open positions reached the cap of {}",
+            symbol,
+            open_positions,
+            max_positions.unwrap_or_default()
+        );
+        if let Err(e) = send_symbol_notification(
+            bot,
+            chat_id,
+            bot_state,
+            symbol,
+            NotificationLevel::Important,
+            message,
+        )
+        .await
+        {
+            eprintln!(
+                "Error sending position cap alert: {}",
+                describe_error(&*e, true)
+            );
+        }
+    }
+
+    allowed
+}
+
+/// Disable `symbol` once it reached `symbol_error_limit` and send an
+/// `Important` alert saying so
+async fn disable_failing_symbol<T: TradingBot>(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    trading_bot: &mut T,
+    symbol_override: &mut Option<Vec<SymbolConfig>>,
+    symbol: &str,
+    error: &str,
+) {
+    let limit = bot_state.lock().await.symbol_error_limit;
+    let mut message = format!(
+        "{} disabled after {} consecutive errors. Last error: {}",
+        symbol, limit, error
+    );
+    match disable_symbol(bot_state, trading_bot, symbol_override, symbol).await {
+        Ok(note) => {
+            message.push('\n');
+            message.push_str(&note);
+        }
+        Err(e) => {
+            eprintln!("Failed to disable {}: {}", symbol, describe_error(&e, true));
+            let error = bot_state.lock().await.describe_error(&e);
+            message.push_str(&format!("\nThe symbols could not be updated: {}", error));
+        }
+    }
+    if let Err(e) = send_symbol_notification(
+        bot,
+        chat_id,
+        bot_state,
+        symbol,
+        NotificationLevel::Important,
+        message,
+    )
+    .await
+    {
+        eprintln!(
+            "Error sending symbol disable alert: {}",
+            describe_error(&*e, true)
+        );
+    }
+}
+
+/// Set `enabled = false` for `symbol` and save the symbols like any other
+/// edit: backed up, then staged, kept in memory or written by
+/// `store_symbols`. Returns the backup note.
+async fn disable_symbol<T: TradingBot>(
+    bot_state: &Arc<Mutex<BotState>>,
+    trading_bot: &mut T,
+    symbol_override: &mut Option<Vec<SymbolConfig>>,
+    symbol: &str,
+) -> Result<String, BotError> {
+    let (config_path, staged, persist_changes) = {
+        let state = bot_state.lock().await;
+        (
+            state.config_path.clone(),
+            state.staged_symbols.clone(),
+            state.persist_changes,
+        )
+    };
+    let config_path = PathBuf::from(
+        config_path
+            .ok_or_else(|| BotError::Message("Configuration path is not set".to_string()))?,
+    );
+    let mut symbols = match (staged, persist_changes, symbol_override.clone()) {
+        (Some(staged), ..) => staged,
+        (None, false, Some(symbols)) => symbols,
+        _ => read_symbols(&config_path).await?,
+    };
+    let entry = symbols
+        .iter_mut()
+        .find(|s| s.symbol.eq_ignore_ascii_case(symbol))
+        .ok_or_else(|| BotError::Message(format!("{} is not configured", symbol)))?;
+    entry.enabled = false;
+
+    let note = backup_before_save(bot_state, &config_path).await?;
+    if let StoredSymbols::InMemory(symbols) =
+        store_symbols(bot_state, &config_path, &symbols).await?
+    {
+        trading_bot
+            .reload_symbols(symbols.clone())
+            .await
+            .map_err(|e| BotError::context("Failed to update in-memory symbols", e))?;
+        *symbol_override = Some(symbols);
+        bot_state.lock().await.unsaved_changes = true;
+    }
+    Ok(note)
+}
+
+/// The start of the trading day containing `now` when days begin at
+/// `reset_hour` UTC
+pub fn trading_day_start(now: DateTime<Utc>, reset_hour: u32) -> DateTime<Utc> {
+    let start = now
+        .date_naive()
+        .and_hms_opt(reset_hour.min(23), 0, 0)
+        .unwrap_or_default()
+        .and_utc();
+    if now < start {
+        start - chrono::Duration::days(1)
+    } else {
+        start
+    }
+}
+
+/// Count an entry against the daily trade cap, sending an `Important` alert
+/// when the entry uses up the cap
+///
+/// Strategies should call this before opening a position and skip the entry
+/// when it returns false, then call `BotState::release_budget` with the same
+/// amount once the position is closed.
+pub async fn check_trade_cap(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    symbol: &str,
+) -> bool {
+    let (allowed, cap, trades) = {
+        let mut state = bot_state.lock().await;
+        let now = state.now();
+        (
+            state.reserve_trade(now),
+            state.max_daily_trades,
+            state.daily_trades,
+        )
+    };
+
+    if allowed && cap > 0 && trades == cap {
+        let message = format!(
+            "Daily trade cap of {} reached with {}; further entries are blocked until the count \
+             resets",
+            cap, symbol
+        );
+        if let Err(e) = send_symbol_notification(
+            bot,
+            chat_id,
+            bot_state,
+            symbol,
+            NotificationLevel::Important,
+            message,
+        )
+        .await
+        {
+            eprintln!(
+                "Error sending trade cap alert: {}",
+                describe_error(&*e, true)
+            );
+        }
+    }
+
+    allowed
+}
+
+/// Check an entry of `amount` against the session budget and count it,
+/// sending an `Important` alert when the entry is blocked
+///
+/// Strategies should call this before opening a position and skip the entry
+/// when it returns false.
+pub async fn check_budget(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_state: &Arc<Mutex<BotState>>,
+    symbol: &str,
+    amount: f64,
+) -> bool {
+    let (allowed, budget, deployed) = {
+        let mut state = bot_state.lock().await;
+        (
+            state.reserve_budget(amount),
+            state.session_budget,
+            state.deployed_capital,
+        )
+    };
+
+    if !allowed {
+        let message = format!(
+            "Entry for {} blocked: {:.2} would exceed the session budget of {:.2} ({:.2} deployed)",
+            symbol,
+            amount,
+            budget.unwrap_or_default(),
+            deployed
+        );
+        if let Err(e) = send_symbol_notification(
+            bot,
+            chat_id,
+            bot_state,
+            symbol,
+            NotificationLevel::Important,
+            message,
+        )
+        .await
+        {
+            eprintln!("Error sending budget alert: {}", describe_error(&*e, true));
+        }
+    }
+
+    allowed
+}
+
+/// Helper to check if notification level is sufficient
+fn level_is_sufficient(msg_level: NotificationLevel, current_level: NotificationLevel) -> bool {
+    match current_level {
+        NotificationLevel::None => false,
+        NotificationLevel::Critical => msg_level == NotificationLevel::Critical,
+        NotificationLevel::Important => {
+            matches!(
+                msg_level,
+                NotificationLevel::Critical | NotificationLevel::Important
+            )
+        }
+        NotificationLevel::All => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Mutex as StdMutex};
+
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use serde_json::{json, Value};
+    use teloxide::ApiError;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+    use crate::schedule::parse_time_window;
+
+    const CHAT: ChatId = ChatId(42);
+
+    /// A call the bot made to the fake Telegram API
+    #[derive(Debug, Clone)]
+    struct ApiCall {
+        method: String,
+        /// The JSON payload; `Null` for multipart uploads
+        body: Value,
+        /// The request body as sent, for inspecting multipart uploads
+        raw: String,
+    }
+
+    impl ApiCall {
+        fn is(&self, method: &str) -> bool {
+            self.method.eq_ignore_ascii_case(method)
+        }
+
+        fn chat_id(&self) -> Option<ChatId> {
+            self.body["chat_id"].as_i64().map(ChatId)
+        }
+
+        fn text(&self) -> &str {
+            self.body["text"].as_str().unwrap_or_default()
+        }
+    }
+
+    /// How the fake Telegram API answers a call
+    enum Reply {
+        Ok(Value),
+        /// Never answer, as if the request were stuck in the network
+        Hang,
+        /// Answer with a failed API response such as a 403 or 429
+        Error(Value),
+    }
+
+    type Responder = Arc<dyn Fn(&ApiCall) -> Reply + Send + Sync>;
+
+    /// A local HTTP server standing in for the Telegram Bot API
+    struct FakeTelegram {
+        bot: Bot,
+        calls: Arc<StdMutex<Vec<ApiCall>>>,
+    }
+
+    impl FakeTelegram {
+        async fn start() -> Self {
+            Self::with_responder(|call| Reply::Ok(default_result(call))).await
+        }
+
+        async fn with_responder(
+            responder: impl Fn(&ApiCall) -> Reply + Send + Sync + 'static,
+        ) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let calls = Arc::new(StdMutex::new(Vec::new()));
+            let responder: Responder = Arc::new(responder);
+            let server_calls = Arc::clone(&calls);
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve_connection(
+                        stream,
+                        Arc::clone(&server_calls),
+                        Arc::clone(&responder),
+                    ));
+                }
+            });
+
+            let bot = Bot::new("1234:TEST");
+            let api_url = bot.api_url().join(&format!("http://{}/", address)).unwrap();
+            Self {
+                bot: bot.set_api_url(api_url),
+                calls,
+            }
+        }
+
+        fn calls(&self) -> Vec<ApiCall> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// The texts of every `sendMessage` call, in order
+        fn sent_texts(&self) -> Vec<String> {
+            self.calls()
+                .iter()
+                .filter(|call| call.is("sendMessage"))
+                .map(|call| call.text().to_string())
+                .collect()
+        }
+
+        /// Wait until `predicate` holds for the recorded calls
+        async fn wait_for(&self, predicate: impl Fn(&[ApiCall]) -> bool) -> Vec<ApiCall> {
+            for _ in 0..1000 {
+                let calls = self.calls();
+                if predicate(&calls) {
+                    return calls;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("timed out waiting for API calls, got {:#?}", self.calls());
+        }
+    }
+
+    async fn serve_connection(
+        stream: TcpStream,
+        calls: Arc<StdMutex<Vec<ApiCall>>>,
+        responder: Responder,
+    ) {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            let mut content_length = 0;
+            let mut chunked = false;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                let (name, value) = header.split_once(':').unwrap_or((header, ""));
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                    "transfer-encoding" => chunked = value.contains("chunked"),
+                    _ => {}
+                }
+            }
+            let mut body = Vec::new();
+            if chunked {
+                loop {
+                    let mut size = String::new();
+                    reader.read_line(&mut size).await.unwrap();
+                    let size = usize::from_str_radix(size.trim(), 16).unwrap_or(0);
+                    let mut chunk = vec![0; size + 2];
+                    reader.read_exact(&mut chunk).await.unwrap();
+                    if size == 0 {
+                        break;
+                    }
+                    body.extend_from_slice(&chunk[..size]);
+                }
+            } else {
+                body.resize(content_length, 0);
+                reader.read_exact(&mut body).await.unwrap();
+            }
+
+            let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+            let call = ApiCall {
+                method: path.rsplit('/').next().unwrap_or_default().to_string(),
+                body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+                raw: String::from_utf8_lossy(&body).into_owned(),
+            };
+            calls.lock().unwrap().push(call.clone());
+
+            let (status, payload) = match responder(&call) {
+                Reply::Ok(result) => (200, json!({ "ok": true, "result": result })),
+                Reply::Hang => {
+                    std::future::pending::<()>().await;
+                    return;
+                }
+                Reply::Error(error) => (error["error_code"].as_u64().unwrap_or(400), error),
+            };
+            let payload = payload.to_string();
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                payload.len(),
+                payload
+            );
+            if writer.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// A plausible successful result for `call`
+    fn default_result(call: &ApiCall) -> Value {
+        match call.method.to_ascii_lowercase().as_str() {
+            "getme" => json!({
+                "id": 1,
+                "is_bot": true,
+                "first_name": "Test",
+                "username": "test_bot",
+                "can_join_groups": false,
+                "can_read_all_group_messages": false,
+                "supports_inline_queries": false,
+                "can_connect_to_business": false,
+                "has_main_web_app": false,
+            }),
+            "setmessagereaction" => json!(true),
+            _ => message_json(call.chat_id().unwrap_or(CHAT), call.text()),
+        }
+    }
+
+    fn message_json(chat_id: ChatId, text: &str) -> Value {
+        json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": chat_id.0, "type": "private", "first_name": "Test" },
+            "from": { "id": chat_id.0, "is_bot": false, "first_name": "Test" },
+            "text": text,
+        })
+    }
+
+    /// An incoming command message from `chat_id`
+    fn command_message(chat_id: ChatId, text: &str) -> Message {
+        serde_json::from_value(message_json(chat_id, text)).unwrap()
+    }
+
+    fn shared(state: BotState) -> Arc<Mutex<BotState>> {
+        Arc::new(Mutex::new(state))
+    }
+
+    /// An enabled symbol with placeholder amounts and thresholds
+    fn symbol_config(symbol: &str) -> SymbolConfig {
+        SymbolConfig {
+            symbol: symbol.to_string(),
+            entry_amount: 10.0,
+            exit_amount: 10.0,
+            entry_threshold: 1.0,
+            exit_threshold: 1.0,
+            enabled: true,
+            max_slippage: None,
+            size_step: None,
+        }
+    }
+
+    fn position(symbol: &str, size: f64) -> PositionInfo {
+        PositionInfo {
+            symbol: symbol.to_string(),
+            size,
+            entry_price: 100.0,
+            unrealized_pnl: 0.0,
+        }
+    }
+
+    fn symbol_names(symbols: &[SymbolConfig]) -> Vec<&str> {
+        symbols.iter().map(|s| s.symbol.as_str()).collect()
+    }
+
+    /// A clock tests set and advance by hand
+    #[derive(Clone)]
+    struct FakeClock(Arc<StdMutex<DateTime<Utc>>>);
+
+    impl FakeClock {
+        fn at(now: DateTime<Utc>) -> Self {
+            FakeClock(Arc::new(StdMutex::new(now)))
+        }
+
+        fn clock(&self) -> Clock {
+            let now = Arc::clone(&self.0);
+            Clock::new(move || *now.lock().unwrap())
+        }
+
+        fn set(&self, now: DateTime<Utc>) {
+            *self.0.lock().unwrap() = now;
+        }
+    }
+
+    fn utc(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 9, hour, minute, 0).unwrap()
+    }
+
+    /// A path under the system temp directory unique to this test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("telegram-bot-{}-{}", std::process::id(), name))
+    }
+
+    /// Write `symbols` to a config file in a fresh directory of its own, so
+    /// backups taken next to it don't collide with other tests
+    fn write_config(name: &str, symbols: &[SymbolConfig]) -> PathBuf {
+        let directory = temp_path(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("symbols_config.json");
+        std::fs::write(&path, serde_json::to_string_pretty(symbols).unwrap()).unwrap();
+        path
+    }
+
+    fn read_config(path: &Path) -> Vec<SymbolConfig> {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn remove_config(path: &Path) {
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn state_with_config(path: &Path) -> Arc<Mutex<BotState>> {
+        shared(BotState {
+            config_path: Some(path.to_string_lossy().into_owned()),
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        })
+    }
+
+    /// Start the runner for `T` with `CHAT` as its chat and wait until the
+    /// strategy reports that it initialized
+    async fn start_runner<T: TradingBot>(
+        telegram: &FakeTelegram,
+        state: &Arc<Mutex<BotState>>,
+    ) -> TelegramBotHandler {
+        start_runner_every::<T>(telegram, state, 3600).await
+    }
+
+    /// Like `start_runner`, ticking every `interval_seconds`
+    async fn start_runner_every<T: TradingBot>(
+        telegram: &FakeTelegram,
+        state: &Arc<Mutex<BotState>>,
+        interval_seconds: u64,
+    ) -> TelegramBotHandler {
+        let (handler, request_rx) = TelegramBotHandler::new();
+        TelegramBotHandler::init_and_run_bot::<T>(
+            Arc::clone(state),
+            telegram.bot.clone(),
+            CHAT,
+            request_rx,
+            interval_seconds,
+        )
+        .await
+        .unwrap();
+        telegram
+            .wait_for(|calls| calls.iter().any(|call| call.text().contains("initialized")))
+            .await;
+        handler
+    }
+
+    /// Handle `command` from `CHAT` with a handler that has no runner behind
+    /// it, returning every text sent so far
+    async fn run_command(
+        telegram: &FakeTelegram,
+        state: &Arc<Mutex<BotState>>,
+        text: &str,
+        command: Command,
+    ) -> Vec<String> {
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, text),
+                command,
+                Arc::clone(state),
+            )
+            .await
+            .unwrap();
+        telegram.sent_texts()
+    }
+
+    /// A strategy that renders its status its own way
+    struct CustomFormatBot;
+
+    #[async_trait]
+    impl TradingBot for CustomFormatBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(CustomFormatBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport {
+                summary: "custom".to_string(),
+                open_positions: 2,
+                ..Default::default()
+            })
+        }
+
+        fn format_status(&self, report: &StatusReport) -> String {
+            format!("🚀 {} | {} open", report.summary, report.open_positions)
+        }
+
+        fn account_info(&self) -> AccountInfo {
+            AccountInfo {
+                exchange: "Binance".to_string(),
+                account_label: "main".to_string(),
+                testnet: true,
+            }
+        }
+
+        fn status_columns(&self) -> BTreeMap<String, f64> {
+            BTreeMap::from([("drawdown".to_string(), 7.5)])
+        }
+
+        async fn ping_exchange(&self) -> Result<Duration, Self::Error> {
+            Ok(Duration::from_millis(42))
+        }
+    }
+
+    #[tokio::test]
+    async fn status_sends_the_strategy_formatter_output() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/status"),
+                Command::Status,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert!(
+            texts.iter().any(|text| text.contains("🚀 custom | 2 open")),
+            "{:?}",
+            texts
+        );
+        assert!(!texts.iter().any(|text| text.contains("Realized PnL")));
+    }
+
+    #[tokio::test]
+    async fn reply_timeout_fires_for_a_request_that_never_resolves() {
+        let timeout = Duration::from_millis(20);
+        let result =
+            with_reply_timeout(timeout, std::future::pending::<ResponseResult<()>>()).await;
+
+        match result {
+            Err(RequestError::Io(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+                assert!(matches!(
+                    e.get_ref().and_then(|e| e.downcast_ref::<BotError>()),
+                    Some(BotError::Timeout(t)) if *t == timeout
+                ));
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn notification_gives_up_after_the_configured_send_timeout() {
+        let telegram = FakeTelegram::with_responder(|_| Reply::Hang).await;
+        let state = shared(BotState {
+            send_timeout_secs: 1,
+            ..Default::default()
+        });
+
+        let started = Instant::now();
+        let error = send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "stuck".to_string(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<BotError>(),
+            Some(BotError::Timeout(t)) if *t == Duration::from_secs(1)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(state.lock().await.notification_stats.failed, 1);
+    }
+
+    #[test]
+    fn levels_are_listed_from_most_to_least_verbose() {
+        let text = render_levels(&NotificationLevel::Critical);
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+
+        assert_eq!(lines.len(), NotificationLevel::ALL_LEVELS.len());
+        assert!(lines[0].starts_with("All - "));
+        assert!(lines[2].starts_with("Critical (active) - "));
+        assert!(lines[3].starts_with("None - "));
+        assert_eq!(text.matches("(active)").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn levels_command_marks_the_configured_level() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_level: NotificationLevel::Important,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(&telegram, &state, "/levels", Command::Levels).await;
+
+        assert_eq!(texts.len(), 1, "{:?}", texts);
+        assert!(texts[0].contains("Important (active)"), "{}", texts[0]);
+    }
+
+    #[tokio::test]
+    async fn export_config_rejects_unknown_formats() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/exportconfig zip",
+            Command::ExportConfig("zip".to_string()),
+        )
+        .await;
+
+        assert_eq!(texts, vec!["Usage: /exportconfig [gzip]".to_string()]);
+        assert!(!telegram.calls().iter().any(|call| call.is("sendDocument")));
+    }
+
+    #[tokio::test]
+    async fn export_config_sends_the_config_as_a_document() {
+        let telegram = FakeTelegram::start().await;
+        let path = temp_path("export_config.json");
+        std::fs::write(&path, "[]").unwrap();
+        let state = shared(BotState {
+            config_path: Some(path.to_string_lossy().into_owned()),
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let args = if cfg!(feature = "compression") {
+            "gzip"
+        } else {
+            ""
+        };
+        run_command(
+            &telegram,
+            &state,
+            "/exportconfig",
+            Command::ExportConfig(args.to_string()),
+        )
+        .await;
+        std::fs::remove_file(&path).unwrap();
+
+        let documents = telegram
+            .calls()
+            .iter()
+            .filter(|call| call.is("sendDocument"))
+            .count();
+        assert_eq!(documents, 1);
+    }
+
+    #[test]
+    fn position_cap_blocks_entries_at_the_limit() {
+        let mut state = BotState::default();
+        assert!(state.can_open_position(100));
+
+        state.max_positions = Some(2);
+        assert!(state.can_open_position(1));
+        assert!(!state.can_open_position(2));
+        assert!(!state.can_open_position(3));
+    }
+
+    #[tokio::test]
+    async fn position_cap_alerts_when_it_blocks_an_entry() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            max_positions: Some(1),
+            ..Default::default()
+        });
+
+        assert!(!check_position_cap(&telegram.bot, CHAT, &state, "BTCUSDT", 1).await);
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(
+            texts[0].contains("Entry for BTCUSDT blocked"),
+            "{}",
+            texts[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn max_positions_zero_removes_the_cap() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            max_positions: Some(3),
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/maxpositions 0",
+            Command::MaxPositions(0),
+        )
+        .await;
+
+        assert_eq!(texts, vec!["Position cap removed.".to_string()]);
+        assert_eq!(state.lock().await.max_positions, None);
+    }
+
+    #[test]
+    fn move_symbol_rejects_positions_outside_the_list() {
+        let mut symbols = vec![symbol_config("BTCUSDT"), symbol_config("ETHUSDT")];
+
+        let error = move_symbol(&mut symbols, "BTCUSDT", 0).unwrap_err();
+        assert_eq!(
+            error,
+            "Position 0 is out of range. Use a value between 1 and 2."
+        );
+        assert!(move_symbol(&mut symbols, "BTCUSDT", 3).is_err());
+        assert_eq!(
+            move_symbol(&mut symbols, "SOLUSDT", 1).unwrap_err(),
+            "Symbol 'SOLUSDT' not found."
+        );
+        assert_eq!(symbol_names(&symbols), ["BTCUSDT", "ETHUSDT"]);
+    }
+
+    #[test]
+    fn move_symbol_accepts_the_first_and_last_positions() {
+        let mut symbols = vec![
+            symbol_config("BTCUSDT"),
+            symbol_config("ETHUSDT"),
+            symbol_config("SOLUSDT"),
+        ];
+
+        move_symbol(&mut symbols, "BTCUSDT", 3).unwrap();
+        assert_eq!(symbol_names(&symbols), ["ETHUSDT", "SOLUSDT", "BTCUSDT"]);
+        move_symbol(&mut symbols, "SOLUSDT", 1).unwrap();
+        assert_eq!(symbol_names(&symbols), ["SOLUSDT", "ETHUSDT", "BTCUSDT"]);
+    }
+
+    #[tokio::test]
+    async fn move_symbol_command_saves_the_new_order() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "move_symbol",
+            &[symbol_config("BTCUSDT"), symbol_config("ETHUSDT")],
+        );
+        let state = state_with_config(&path);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/movesymbol ETHUSDT,1",
+            Command::MoveSymbol("ETHUSDT,1".to_string()),
+        )
+        .await;
+        let saved = read_config(&path);
+        remove_config(&path);
+
+        assert!(texts
+            .last()
+            .unwrap()
+            .starts_with("Moved 'ETHUSDT' to position 1."));
+        assert_eq!(symbol_names(&saved), ["ETHUSDT", "BTCUSDT"]);
+    }
+
+    #[test]
+    fn where_compares_the_targets_with_the_current_chat() {
+        assert_eq!(
+            render_where(&[], CHAT),
+            "No notification chat is configured yet.\nThis chat: 42"
+        );
+        assert!(render_where(&[ChatId(7), CHAT], CHAT).ends_with(
+            "Notification chat(s): 7, 42\nThis chat: 42\nThis chat receives notifications."
+        ));
+        assert!(render_where(&[ChatId(7)], CHAT).ends_with("does NOT receive notifications."));
+    }
+
+    #[tokio::test]
+    async fn runner_registers_its_chat_as_a_notification_target() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState::default());
+
+        let _handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        assert_eq!(state.lock().await.notification_targets, vec![CHAT]);
+    }
+
+    #[tokio::test]
+    async fn positions_snapshot_is_named_after_its_timestamp() {
+        let directory = temp_path("positions");
+        std::fs::create_dir_all(&directory).unwrap();
+        let positions = vec![PositionInfo {
+            symbol: "BTCUSDT".to_string(),
+            size: 0.5,
+            entry_price: 30_000.0,
+            unrealized_pnl: 12.5,
+        }];
+        let timestamp = Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap();
+
+        let path = save_positions_snapshot(&directory, &positions, timestamp)
+            .await
+            .unwrap();
+        let saved: Vec<PositionInfo> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(path, directory.join("positions_20240309_140507.json"));
+        assert_eq!(saved, positions);
+    }
+
+    #[tokio::test]
+    async fn save_positions_needs_a_running_bot() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(&telegram, &state, "/savepositions", Command::SavePositions).await;
+
+        assert_eq!(texts, vec!["Bot is not running.".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn set_interval_to_the_current_value_skips_the_runner() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            interval_seconds: Some(300),
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        // No runner is listening, so a round trip would report a failure
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/setinterval 300",
+            Command::SetInterval("300".to_string()),
+        )
+        .await;
+
+        assert_eq!(texts.len(), 1);
+        assert!(
+            texts[0].starts_with("Interval already set to"),
+            "{}",
+            texts[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_setters_report_that_nothing_changed() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            max_positions: Some(4),
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let timeout = state.lock().await.send_timeout_secs;
+
+        run_command(
+            &telegram,
+            &state,
+            "/maxpositions 4",
+            Command::MaxPositions(4),
+        )
+        .await;
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/sendtimeout",
+            Command::SendTimeout(timeout),
+        )
+        .await;
+
+        assert_eq!(
+            texts,
+            vec![
+                "Max positions already set to 4".to_string(),
+                format!("Send timeout already set to {} seconds", timeout),
+            ]
+        );
+    }
+
+    #[test]
+    fn holdings_pairs_each_configured_symbol_with_its_size() {
+        let symbols = vec![
+            symbol_config("BTCUSDT"),
+            symbol_config("ETHUSDT"),
+            symbol_config("SOLUSDT"),
+        ];
+        let positions = vec![
+            position("BTCUSDT", 0.25),
+            position("BTCUSDT", 0.5),
+            position("ETHUSDT", 0.0),
+            position("DOGEUSDT", 100.0),
+        ];
+
+        assert_eq!(
+            holdings(&symbols, &positions),
+            vec![
+                ("BTCUSDT", Some(0.75)),
+                ("ETHUSDT", None),
+                ("SOLUSDT", None)
+            ]
+        );
+    }
+
+    fn count_texts(telegram: &FakeTelegram, text: &str) -> usize {
+        telegram
+            .sent_texts()
+            .iter()
+            .filter(|sent| sent.contains(text))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn stopbot_is_announced_once() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            ..Default::default()
+        });
+        let mut handler = start_runner_every::<CustomFormatBot>(&telegram, &state, 1).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/stopbot"),
+                Command::StopBot,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(count_texts(&telegram, "Trading bot stopped."), 1);
+        assert_eq!(count_texts(&telegram, "Trading bot has been stopped."), 0);
+    }
+
+    #[tokio::test]
+    async fn runner_announces_a_stop_nobody_confirmed() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            ..Default::default()
+        });
+        let _handler = start_runner_every::<CustomFormatBot>(&telegram, &state, 1).await;
+
+        state.lock().await.is_running = false;
+        telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Trading bot has been stopped."))
+            })
+            .await;
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(count_texts(&telegram, "Trading bot has been stopped."), 1);
+        assert!(state.lock().await.stop_announced);
+    }
+
+    #[test]
+    fn parse_toggle_accepts_common_spellings() {
+        assert_eq!(parse_toggle(" ON "), Some(true));
+        assert_eq!(parse_toggle("yes"), Some(true));
+        assert_eq!(parse_toggle("0"), Some(false));
+        assert_eq!(parse_toggle("Off"), Some(false));
+        assert_eq!(parse_toggle("maybe"), None);
+    }
+
+    #[tokio::test]
+    async fn summary_mode_buffers_until_flushed() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            summary_mode: true,
+            notification_level: NotificationLevel::All,
+            ..Default::default()
+        });
+        state.lock().await.begin_tick_notifications();
+        let other = ChatId(7);
+
+        for (chat_id, text) in [(CHAT, "first"), (other, "elsewhere"), (CHAT, "second")] {
+            send_notification(
+                &telegram.bot,
+                chat_id,
+                &state,
+                NotificationLevel::Important,
+                text.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "urgent".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].contains("urgent"));
+        assert_eq!(state.lock().await.notification_stats.sent, [0, 0, 1, 0]);
+
+        let flushed = flush_notification_summary(&telegram.bot, &state)
+            .await
+            .unwrap();
+
+        assert_eq!(flushed, 3);
+        assert_eq!(state.lock().await.notification_stats.sent, [0, 3, 1, 0]);
+        let calls = telegram.calls();
+        let summaries: Vec<&ApiCall> = calls
+            .iter()
+            .filter(|call| call.text().contains("Summary of"))
+            .collect();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].chat_id(), Some(CHAT));
+        assert!(summaries[0].text().contains("Summary of 2 notification(s)"));
+        assert!(summaries[0].text().contains("first\n\nsecond"));
+        assert_eq!(summaries[1].chat_id(), Some(other));
+        assert!(state.lock().await.summary_buffer.is_empty());
+    }
+
+    #[test]
+    fn botfather_commands_fit_botfathers_rules() {
+        let text = botfather_commands();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), Command::bot_commands().len());
+        assert!(lines.contains(&"status - check bot status."), "{}", text);
+        for line in lines {
+            let (name, description) = line.split_once(" - ").unwrap();
+            assert!(
+                (1..=32).contains(&name.len())
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+                "invalid command name {:?}",
+                name
+            );
+            assert!(
+                (3..=256).contains(&description.len()),
+                "invalid description for {}",
+                name
+            );
+        }
+    }
+
+    static FAILING_BOT_CONSTRUCTIONS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A strategy whose ticks always fail and that is only ready the first
+    /// time it is constructed
+    struct FailingBot;
+
+    #[async_trait]
+    impl TradingBot for FailingBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            FAILING_BOT_CONSTRUCTIONS.fetch_add(1, Ordering::SeqCst);
+            Ok(FailingBot)
+        }
+
+        async fn readiness_check(&self) -> Result<(), Self::Error> {
+            if FAILING_BOT_CONSTRUCTIONS.load(Ordering::SeqCst) > 1 {
+                return Err("exchange unreachable".to_string());
+            }
+            Ok(())
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Err("order rejected".to_string())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_runs_the_readiness_check() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            ..Default::default()
+        });
+        let _handler = start_runner_every::<FailingBot>(&telegram, &state, 1).await;
+
+        let calls = telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Failed to re-initialize bot"))
+            })
+            .await;
+
+        let failure = calls
+            .iter()
+            .find(|call| call.text().contains("Failed to re-initialize bot"))
+            .unwrap();
+        assert!(
+            failure.text().contains("not ready: exchange unreachable"),
+            "{}",
+            failure.text()
+        );
+        assert!(!calls
+            .iter()
+            .any(|call| call.text().contains("re-initialized")));
+        // The failure is reported just before the runner clears the flag
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state.lock().await.is_running {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the runner stops after the failed restart");
+    }
+
+    #[test]
+    fn notify_settings_parse_every_key() {
+        assert_eq!(
+            parse_notify_settings("level=critical silent=on SUMMARY=off"),
+            Ok(NotifySettings {
+                level: Some(NotificationLevel::Critical),
+                silent: Some(true),
+                summary: Some(false),
+            })
+        );
+        assert_eq!(
+            parse_notify_settings("silent=no"),
+            Ok(NotifySettings {
+                silent: Some(false),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn notify_settings_report_every_bad_pair() {
+        let errors = parse_notify_settings("level=loud colour=red silent").unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid level 'loud'. Use: all, important, critical, or none".to_string(),
+                "Unknown key 'colour'".to_string(),
+                "Expected key=value, got 'silent'".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn notify_with_settings_applies_none_when_one_is_invalid() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        run_command(
+            &telegram,
+            &state,
+            "/notify level=none silent=maybe",
+            Command::Notify("level=none silent=maybe".to_string()),
+        )
+        .await;
+
+        let state = state.lock().await;
+        assert_eq!(
+            state.notification_level,
+            BotState::default().notification_level
+        );
+        assert!(!state.silent_notifications);
+    }
+
+    fn disabled(symbol: &str) -> SymbolConfig {
+        SymbolConfig {
+            enabled: false,
+            ..symbol_config(symbol)
+        }
+    }
+
+    #[test]
+    fn purge_disabled_keeps_enabled_symbols_in_order() {
+        let mut symbols = vec![
+            disabled("BTCUSDT"),
+            symbol_config("ETHUSDT"),
+            disabled("SOLUSDT"),
+            symbol_config("ADAUSDT"),
+        ];
+
+        assert_eq!(purge_disabled(&mut symbols), ["BTCUSDT", "SOLUSDT"]);
+        assert_eq!(symbol_names(&symbols), ["ETHUSDT", "ADAUSDT"]);
+    }
+
+    #[tokio::test]
+    async fn purge_disabled_needs_confirmation_and_backs_up_first() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "purge_disabled",
+            &[symbol_config("ETHUSDT"), disabled("SOLUSDT")],
+        );
+        let state = state_with_config(&path);
+
+        run_command(
+            &telegram,
+            &state,
+            "/purgedisabled",
+            Command::PurgeDisabled(String::new()),
+        )
+        .await;
+        assert_eq!(read_config(&path).len(), 2);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/purgedisabled confirm",
+            Command::PurgeDisabled("confirm".to_string()),
+        )
+        .await;
+        let saved = read_config(&path);
+        let backups = list_backups(&path).await.unwrap();
+        remove_config(&path);
+
+        assert!(
+            texts[0].contains("Run /purgedisabled confirm"),
+            "{}",
+            texts[0]
+        );
+        assert!(texts[1].contains("Removed 1 disabled symbol(s): SOLUSDT"));
+        assert!(texts[1].contains("Backup saved to"));
+        assert_eq!(symbol_names(&saved), ["ETHUSDT"]);
+        assert_eq!(backups.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn level_schedule_follows_the_clock() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(3, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            level_schedule: parse_level_schedule("00:00-08:00=critical").unwrap(),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let notify = |text: &'static str| {
+            let state = Arc::clone(&state);
+            let bot = telegram.bot.clone();
+            async move {
+                send_notification(
+                    &bot,
+                    CHAT,
+                    &state,
+                    NotificationLevel::Important,
+                    text.to_string(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        notify("overnight").await;
+        clock.set(utc(8, 0));
+        notify("morning").await;
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].contains("morning"));
+        assert_eq!(state.lock().await.notification_stats.suppressed.level, 1);
+    }
+
+    /// A strategy whose ticks always fail with a long, multi-line error
+    struct ErrorBot;
+
+    const ERROR_BOT_MESSAGE: &str =
+        "order rejected by the exchange\nsymbol: BTCUSDT\nreason: insufficient margin";
+
+    #[async_trait]
+    impl TradingBot for ErrorBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(ErrorBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Err(ERROR_BOT_MESSAGE.to_string())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+
+        async fn ping_exchange(&self) -> Result<Duration, Self::Error> {
+            Err("connection refused".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn last_error_shows_the_runners_error_in_full() {
+        let telegram = FakeTelegram::start().await;
+        let mut state = BotState {
+            is_running: true,
+            command_cooldown_secs: 0,
+            ..Default::default()
+        };
+        state.circuit_breaker.threshold = 1;
+        let state = shared(state);
+        let mut handler = start_runner_every::<ErrorBot>(&telegram, &state, 1).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/lasterror"),
+                Command::LastError,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Circuit breaker tripped"))
+            })
+            .await;
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/lasterror"),
+                Command::LastError,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert!(texts.contains(&"No errors recorded since the runner started.".to_string()));
+        let last = texts.last().unwrap();
+        assert!(last.starts_with("Last error at "), "{}", last);
+        assert!(last.ends_with(ERROR_BOT_MESSAGE), "{}", last);
+    }
+
+    struct ChainBot;
+
+    #[async_trait]
+    impl TradingBot for ChainBot {
+        type Error = BotError;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(ChainBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Err(BotError::context("order rejected", "insufficient margin"))
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+    }
+
+    /// The strategy error reported to the chat once its tick has failed
+    async fn reported_strategy_error(verbose_errors: bool) -> String {
+        let telegram = FakeTelegram::start().await;
+        let mut state = BotState {
+            is_running: true,
+            verbose_errors,
+            ..Default::default()
+        };
+        state.circuit_breaker.threshold = 1;
+        let state = shared(state);
+        let _handler = start_runner_every::<ChainBot>(&telegram, &state, 1).await;
+
+        telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Circuit breaker tripped"))
+            })
+            .await;
+        telegram
+            .sent_texts()
+            .into_iter()
+            .find(|text| text.starts_with("Strategy execution failed"))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn strategy_errors_keep_their_cause_chain() {
+        assert_eq!(
+            reported_strategy_error(false).await,
+            "Strategy execution failed: order rejected: insufficient margin"
+        );
+        assert_eq!(
+            reported_strategy_error(true).await,
+            "Strategy execution failed\nCaused by: order rejected\nCaused by: insufficient margin"
+        );
+    }
+
+    fn api_error(code: u16, description: &str) -> Reply {
+        Reply::Error(json!({
+            "ok": false,
+            "error_code": code,
+            "description": description,
+        }))
+    }
+
+    fn sends(calls: &[ApiCall]) -> usize {
+        calls.iter().filter(|call| call.is("sendMessage")).count()
+    }
+
+    #[tokio::test]
+    async fn rate_limited_sends_are_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let telegram = FakeTelegram::with_responder(move |call| {
+            if call.is("sendMessage") && counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                Reply::Error(json!({
+                    "ok": false,
+                    "error_code": 429,
+                    "description": "Too Many Requests: retry after 0",
+                    "parameters": { "retry_after": 0 },
+                }))
+            } else {
+                Reply::Ok(default_result(call))
+            }
+        })
+        .await;
+        let state = shared(BotState::default());
+
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sends(&telegram.calls()), 2);
+    }
+
+    #[tokio::test]
+    async fn fatal_send_errors_are_not_retried() {
+        let telegram = FakeTelegram::with_responder(|_| {
+            api_error(403, "Forbidden: bot was blocked by the user")
+        })
+        .await;
+        let state = shared(BotState::default());
+
+        let error = send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<BotError>(),
+            Some(BotError::Telegram(RequestError::Api(ApiError::BotBlocked)))
+        ));
+        assert_eq!(sends(&telegram.calls()), 1);
+    }
+
+    /// Answers chat 1 as blocked, chat 3 as always rate limited and every
+    /// other chat normally
+    fn blocked_and_limited(call: &ApiCall) -> Reply {
+        match call.chat_id() {
+            Some(ChatId(1)) if call.is("sendMessage") => {
+                api_error(403, "Forbidden: bot was blocked by the user")
+            }
+            Some(ChatId(3)) if call.is("sendMessage") => Reply::Error(json!({
+                "ok": false,
+                "error_code": 429,
+                "description": "Too Many Requests: retry after 0",
+                "parameters": { "retry_after": 0 },
+            })),
+            _ => Reply::Ok(default_result(call)),
+        }
+    }
+
+    #[tokio::test]
+    async fn broadcast_removes_blocked_chats_and_reports_the_rest() {
+        let telegram = FakeTelegram::with_responder(blocked_and_limited).await;
+        let state = shared(BotState {
+            notification_targets: vec![ChatId(1), ChatId(2), ChatId(3)],
+            ..Default::default()
+        });
+
+        let error = broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap_err();
+
+        match error.downcast_ref::<BotError>() {
+            Some(BotError::Broadcast(failures)) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, ChatId(3));
+            }
+            other => panic!("expected a broadcast error, got {:?}", other),
+        }
+        let calls = telegram.calls();
+        assert!(calls
+            .iter()
+            .any(|call| call.chat_id() == Some(ChatId(2)) && call.text().contains("filled")));
+        assert_eq!(
+            state.lock().await.notification_targets,
+            vec![ChatId(2), ChatId(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_reports_how_many_chats_it_removed() {
+        let telegram = FakeTelegram::with_responder(blocked_and_limited).await;
+        let state = shared(BotState {
+            notification_targets: vec![ChatId(1), ChatId(2)],
+            ..Default::default()
+        });
+
+        let removed = broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(removed, 1);
+        assert_eq!(state.lock().await.notification_targets, vec![ChatId(2)]);
+    }
+
+    #[tokio::test]
+    async fn concurrency_rejects_out_of_range_limits() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+
+        for limit in [0, MAX_STRATEGY_CONCURRENCY + 1] {
+            run_command(
+                &telegram,
+                &state,
+                "/concurrency",
+                Command::Concurrency(limit),
+            )
+            .await;
+        }
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/concurrency",
+            Command::Concurrency(MAX_STRATEGY_CONCURRENCY),
+        )
+        .await;
+
+        let usage = format!(
+            "Usage: /concurrency N, with N between 1 and {}",
+            MAX_STRATEGY_CONCURRENCY
+        );
+        assert_eq!(texts[..2], [usage.clone(), usage]);
+        assert_eq!(
+            texts[2],
+            format!("Strategy concurrency set to {}", MAX_STRATEGY_CONCURRENCY)
+        );
+        assert_eq!(
+            state.lock().await.strategy_concurrency,
+            MAX_STRATEGY_CONCURRENCY
+        );
+    }
+
+    #[tokio::test]
+    async fn notification_stats_count_sent_filtered_and_failed() {
+        let telegram = FakeTelegram::with_responder(blocked_and_limited).await;
+        let state = shared(BotState {
+            notification_level: NotificationLevel::Important,
+            ..Default::default()
+        });
+
+        for (chat_id, level) in [
+            (ChatId(2), NotificationLevel::Important),
+            (ChatId(2), NotificationLevel::Critical),
+            (ChatId(2), NotificationLevel::All),
+            (ChatId(1), NotificationLevel::Critical),
+        ] {
+            let _ = send_notification(&telegram.bot, chat_id, &state, level, "x".to_string()).await;
+        }
+
+        let stats = state.lock().await.notification_stats.clone();
+        assert_eq!(stats.filtered, 1);
+        assert_eq!(stats.failed, 1);
+        let rendered = stats.render();
+        assert!(rendered.contains("Important: 1"), "{}", rendered);
+        assert!(rendered.contains("\nAll: 0"), "{}", rendered);
+        assert!(
+            rendered.ends_with("Filtered out: 1\nFailed: 1"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[tokio::test]
+    async fn staged_purge_leaves_the_file_and_backups_alone() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "staged_purge",
+            &[symbol_config("ETHUSDT"), disabled("SOLUSDT")],
+        );
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
+
+        run_command(&telegram, &state, "/stage", Command::Stage).await;
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/purgedisabled confirm",
+            Command::PurgeDisabled("confirm".to_string()),
+        )
+        .await;
+        let saved = read_config(&path);
+        let backups = list_backups(&path).await.unwrap();
+
+        let reply = texts.last().unwrap();
+        assert!(
+            reply.ends_with("Staged; use /apply to save it."),
+            "{}",
+            reply
+        );
+        assert!(!reply.contains("Backup saved"));
+        assert_eq!(saved.len(), 2);
+        assert!(backups.is_empty());
+
+        let texts = run_command(&telegram, &state, "/apply", Command::Apply).await;
+        let saved = read_config(&path);
+        remove_config(&path);
+
+        assert_eq!(texts.last().unwrap(), "Applied 1 staged change(s).");
+        assert_eq!(symbol_names(&saved), ["ETHUSDT"]);
+    }
+
+    static KILL_SWITCH_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A strategy that counts how often it runs
+    struct KillSwitchBot;
+
+    #[async_trait]
+    impl TradingBot for KillSwitchBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(KillSwitchBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            KILL_SWITCH_BOT_TICKS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn kill_switch_blocks_ticks_until_lifted() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            kill_switch: true,
+            ..Default::default()
+        });
+        let mut handler = start_runner_every::<KillSwitchBot>(&telegram, &state, 1).await;
+
+        tokio::time::sleep(Duration::from_millis(2200)).await;
+        assert_eq!(KILL_SWITCH_BOT_TICKS.load(Ordering::SeqCst), 0);
+        assert!(state.lock().await.last_successful_tick.is_some());
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/unkill"),
+                Command::Unkill,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        for _ in 0..300 {
+            if KILL_SWITCH_BOT_TICKS.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(KILL_SWITCH_BOT_TICKS.load(Ordering::SeqCst) > 0);
+        assert!(telegram
+            .sent_texts()
+            .contains(&"Kill switch lifted. Trading resumes on the next tick.".to_string()));
+    }
+
+    static WINDOW_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Counts its ticks, for the trading window test
+    struct WindowBot;
+
+    #[async_trait]
+    impl TradingBot for WindowBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(WindowBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            WINDOW_BOT_TICKS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn ticks_only_run_inside_the_trading_window() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(6, 30));
+        let state = shared(BotState {
+            is_running: true,
+            trading_window: Some(parse_time_window("09:30-16:00").unwrap()),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let _handler = start_runner_every::<WindowBot>(&telegram, &state, 1).await;
+
+        tokio::time::sleep(Duration::from_millis(2200)).await;
+        assert_eq!(WINDOW_BOT_TICKS.load(Ordering::SeqCst), 0);
+        assert!(state.lock().await.last_successful_tick.is_some());
+
+        clock.set(utc(10, 0));
+        for _ in 0..300 {
+            if WINDOW_BOT_TICKS.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(WINDOW_BOT_TICKS.load(Ordering::SeqCst) > 0);
+    }
+
+    #[tokio::test]
+    async fn kill_alerts_every_notification_target() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT, ChatId(7)],
+            ..Default::default()
+        });
+
+        run_command(&telegram, &state, "/kill", Command::Kill).await;
+
+        let alerted: Vec<Option<ChatId>> = telegram
+            .calls()
+            .iter()
+            .filter(|call| call.text().contains("KILL SWITCH ACTIVATED"))
+            .map(ApiCall::chat_id)
+            .collect();
+        assert_eq!(alerted.len(), 2);
+        assert!(alerted.contains(&Some(ChatId(7))));
+        assert!(state.lock().await.kill_switch);
+    }
+
+    #[test]
+    fn json_import_reports_invalid_and_duplicate_entries() {
+        let mut negative = symbol_config("ETHUSDT");
+        negative.entry_amount = -1.0;
+        let content =
+            serde_json::to_string(&[symbol_config("BTCUSDT"), negative, symbol_config("BTCUSDT")])
+                .unwrap();
+
+        let errors = parse_json_symbols(&content).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                "entry 2: entry_amount must be a non-negative number",
+                "entry 3: duplicate symbol BTCUSDT",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_backs_up_the_file_unless_the_edit_is_staged() {
+        let path = write_config("import_backup", &[symbol_config("ETHUSDT")]);
+        let state = state_with_config(&path);
+
+        let note = backup_before_save(&state, &path).await.unwrap();
+        let backups = list_backups(&path).await.unwrap();
+
+        state.lock().await.staged_symbols = Some(Vec::new());
+        let staged_note = backup_before_save(&state, &path).await.unwrap();
+        let staged_backups = list_backups(&path).await.unwrap();
+        remove_config(&path);
+
+        assert!(note.starts_with("Backup saved to"), "{}", note);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(staged_note, "Staged; use /apply to save it.");
+        assert_eq!(staged_backups.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn export_csv_uploads_the_symbols_as_a_document() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "export_csv",
+            &[symbol_config("BTCUSDT"), disabled("ETHUSDT")],
+        );
+        let state = state_with_config(&path);
+
+        let texts = run_command(&telegram, &state, "/exportcsv", Command::ExportCsv).await;
+        remove_config(&path);
+
+        let calls = telegram.calls();
+        let upload = calls.iter().find(|call| call.is("sendDocument"));
+        if cfg!(feature = "csv") {
+            let upload = upload.expect("no document was sent");
+            assert!(texts.is_empty(), "{:?}", texts);
+            assert!(upload.raw.contains("symbols.csv"));
+            assert!(upload.raw.contains("BTCUSDT,10.0,10.0,1.0,1.0,true"));
+            assert!(upload.raw.contains("ETHUSDT,10.0,10.0,1.0,1.0,false"));
+        } else {
+            assert!(upload.is_none());
+            assert!(texts[0].contains("CSV export is not available"));
+        }
+    }
+
+    #[tokio::test]
+    async fn instance_tag_prefixes_replies_and_notifications() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+
+        run_command(
+            &telegram,
+            &state,
+            "/tag [PROD]",
+            Command::Tag("[PROD]".to_string()),
+        )
+        .await;
+        run_command(&telegram, &state, "/tag", Command::Tag(String::new())).await;
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "ok".to_string(),
+        )
+        .await
+        .unwrap();
+        run_command(
+            &telegram,
+            &state,
+            "/tag off",
+            Command::Tag("off".to_string()),
+        )
+        .await;
+        let texts = run_command(&telegram, &state, "/tag", Command::Tag(String::new())).await;
+
+        assert_eq!(
+            texts,
+            [
+                "[PROD] Instance tag set.",
+                "[PROD] Instance tag: [PROD]",
+                "[PROD] <pre>ok</pre>",
+                "Instance tag cleared.",
+                "No instance tag is set.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn tagged_chunks_stay_within_the_message_limit() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            instance_tag: Some("[PROD]".to_string()),
+            ..Default::default()
+        });
+
+        let text = "x".repeat(TELEGRAM_MAX_MESSAGE_LENGTH);
+        send_chunked(&telegram.bot, CHAT, &state, &text)
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts.len(), 2);
+        assert!(texts.iter().all(|text| text.starts_with("[PROD] ")
+            && text.chars().count() <= TELEGRAM_MAX_MESSAGE_LENGTH));
+    }
+
+    #[tokio::test]
+    async fn resources_reports_a_table_or_the_missing_feature() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(&telegram, &state, "/resources", Command::Resources).await;
+
+        if cfg!(feature = "resources") {
+            assert!(texts[0].contains("Memory (RSS)"), "{}", texts[0]);
+            assert!(texts[0].contains("CPU time"), "{}", texts[0]);
+        } else {
+            assert!(texts[0].contains("Rebuild with the `resources` feature"));
+        }
+    }
+
+    /// Symbols handed to `ReloadBot::reload_symbols`, in order
+    static RELOADED_SYMBOLS: StdMutex<Vec<Vec<String>>> = StdMutex::new(Vec::new());
+
+    struct ReloadBot;
+
+    #[async_trait]
+    impl TradingBot for ReloadBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(ReloadBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        async fn reload_symbols(&mut self, symbols: Vec<SymbolConfig>) -> Result<(), Self::Error> {
+            RELOADED_SYMBOLS
+                .lock()
+                .unwrap()
+                .push(symbols.into_iter().map(|symbol| symbol.symbol).collect());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn persist_off_keeps_edits_in_the_runner_until_turned_back_on() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "persist_off",
+            &[symbol_config("ETHUSDT"), symbol_config("SOLUSDT")],
+        );
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
+        let mut handler = start_runner::<ReloadBot>(&telegram, &state).await;
+
+        for (text, command) in [
+            ("/persist off", Command::Persist("off".to_string())),
+            (
+                "/removesymbol SOLUSDT",
+                Command::RemoveSymbol("SOLUSDT".to_string()),
+            ),
+            ("/symbols", Command::Symbols(String::new())),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+        }
+        let unsaved = state.lock().await.unsaved_changes;
+        let file_while_off = read_config(&path);
+        let reloads_while_off = RELOADED_SYMBOLS.lock().unwrap().clone();
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/persist on"),
+                Command::Persist("on".to_string()),
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        remove_config(&path);
+
+        let texts = telegram.sent_texts();
+        assert!(unsaved);
+        assert_eq!(symbol_names(&file_while_off), ["ETHUSDT", "SOLUSDT"]);
+        assert_eq!(reloads_while_off, [vec!["ETHUSDT".to_string()]]);
+        assert!(texts.iter().any(|text| text.contains("SOLUSDT' removed")));
+        assert!(!state.lock().await.unsaved_changes);
+        assert_eq!(
+            RELOADED_SYMBOLS.lock().unwrap().last().unwrap(),
+            &["ETHUSDT", "SOLUSDT"]
+        );
+        assert!(texts
+            .last()
+            .unwrap()
+            .contains("Unsaved in-memory changes were discarded"));
+    }
+
+    struct HangingStatusBot;
+
+    #[async_trait]
+    impl TradingBot for HangingStatusBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(HangingStatusBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn self_test_fails_a_strategy_that_never_answers() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("self_test", &[symbol_config("ETHUSDT")]);
+        let state = state_with_config(&path);
+        state.lock().await.send_timeout_secs = 1;
+        let handler = start_runner::<HangingStatusBot>(&telegram, &state).await;
+
+        let started = Instant::now();
+        let results = handler.run_self_test(&telegram.bot, &state).await;
+        let elapsed = started.elapsed();
+        remove_config(&path);
+
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+        let outcome: Vec<(&str, bool)> = results
+            .iter()
+            .map(|result| (result.name, result.passed))
+            .collect();
+        assert_eq!(
+            outcome,
+            [
+                ("Config", true),
+                ("Telegram", true),
+                ("Strategy", false),
+                ("Runner", true),
+            ]
+        );
+        assert_eq!(results[0].detail, "1 symbol(s)");
+        assert_eq!(
+            results[2].detail,
+            BotError::Timeout(Duration::from_secs(1)).to_string()
+        );
+    }
+
+    #[test]
+    fn self_test_verdict_counts_failed_checks() {
+        let results = [
+            CheckResult::pass("Config", "1 symbol(s)"),
+            CheckResult::fail("Runner", "request channel is closed"),
+        ];
+
+        let rendered = render_self_test(&results).to_string();
+
+        assert!(rendered.contains("FAIL"), "{}", rendered);
+        assert!(rendered.contains("1 of 2 check(s) failed"), "{}", rendered);
+    }
+
+    #[test]
+    fn panic_records_keep_string_payloads() {
+        let at = utc(12, 0);
+        let messages: Vec<String> = [
+            Box::new("static message") as Box<dyn Any + Send>,
+            Box::new(String::from("formatted message")),
+            Box::new(7),
+        ]
+        .into_iter()
+        .map(|payload| PanicRecord::from_payload(payload, at).message)
+        .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "static message",
+                "formatted message",
+                "panic with a non-string payload"
+            ]
+        );
+    }
+
+    static PANIC_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A strategy that panics on its first tick only
+    struct PanicBot;
+
+    #[async_trait]
+    impl TradingBot for PanicBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(PanicBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            if PANIC_BOT_TICKS.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("strategy blew up");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_strategy_panic_is_caught_and_shown_by_last_panic() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let mut handler = start_runner_every::<PanicBot>(&telegram, &state, 1).await;
+        telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Strategy panicked: strategy blew up"))
+            })
+            .await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/lastpanic"),
+                Command::LastPanic,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        let reply = texts
+            .iter()
+            .find(|text| text.starts_with("Backtrace capture:"))
+            .unwrap();
+        assert!(reply.contains("Last panic at"), "{}", reply);
+        assert!(reply.contains("strategy blew up"), "{}", reply);
+    }
+
+    #[test]
+    fn identical_commands_from_one_chat_are_held_back() {
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+        let status = Command::Status;
+
+        assert!(!handler.is_rapid_repeat(CHAT, &status, 2));
+        assert!(handler.is_rapid_repeat(CHAT, &status, 2));
+        assert!(!handler.is_rapid_repeat(ChatId(7), &status, 2));
+        assert!(!handler.is_rapid_repeat(CHAT, &Command::Symbols("BTC".to_string()), 2));
+        assert!(!handler.is_rapid_repeat(CHAT, &status, 0));
+    }
+
+    #[test]
+    fn destructive_commands_wait_longer() {
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+        let remove = Command::RemoveSymbol("BTCUSDT".to_string());
+        let two_seconds_ago = Instant::now() - Duration::from_secs(2);
+        for cmd in [&Command::Status, &remove] {
+            handler
+                .recent_commands
+                .insert((CHAT, format!("{:?}", cmd)), two_seconds_ago);
+        }
+
+        assert!(!handler.is_rapid_repeat(CHAT, &Command::Status, 1));
+        assert!(handler.is_rapid_repeat(CHAT, &remove, 1));
+    }
+
+    #[tokio::test]
+    async fn cooldown_rejects_values_above_the_limit() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        run_command(&telegram, &state, "/cooldown 5", Command::Cooldown(5)).await;
+        let texts = run_command(&telegram, &state, "/cooldown 61", Command::Cooldown(61)).await;
+
+        assert_eq!(texts[0], "Command cooldown set to 5 seconds");
+        assert!(texts[1].starts_with("Invalid cooldown"), "{}", texts[1]);
+        assert_eq!(state.lock().await.command_cooldown_secs, 5);
+    }
+
+    #[tokio::test]
+    async fn lowering_backup_retention_prunes_right_away() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("backup_retention", &[symbol_config("ETHUSDT")]);
+        let state = state_with_config(&path);
+        for _ in 0..3 {
+            backup_config(&path, DEFAULT_BACKUP_RETENTION)
+                .await
+                .unwrap();
+            // Backup names carry millisecond timestamps
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let newest = list_backups(&path).await.unwrap().pop().unwrap();
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/backups keep 1",
+            Command::Backups("keep 1".to_string()),
+        )
+        .await;
+        let backups = list_backups(&path).await.unwrap();
+        remove_config(&path);
+
+        assert_eq!(
+            texts,
+            ["Keeping the last 1 backup(s). Removed 2 older backup(s)."]
+        );
+        assert_eq!(backups, [newest]);
+        assert_eq!(state.lock().await.backup_retention, 1);
+    }
+
+    #[tokio::test]
+    async fn format_test_sends_both_renderings_and_reports_failures() {
+        let telegram = FakeTelegram::with_responder(|call| {
+            if call.body["parse_mode"] == "HTML" {
+                api_error(400, "Bad Request: can't parse entities")
+            } else {
+                Reply::Ok(default_result(call))
+            }
+        })
+        .await;
+        let state = shared(BotState::default());
+
+        let failures = send_format_test(&telegram.bot, CHAT, &state, "a_b <c>").await;
+
+        let texts = telegram.sent_texts();
+        assert_eq!(
+            texts,
+            [
+                "*MarkdownV2:*\na\\_b <c\\>",
+                "<b>HTML:</b>\n<pre>a_b &lt;c&gt;</pre>"
+            ]
+        );
+        assert_eq!(failures.len(), 1);
+        assert!(
+            failures[0].starts_with("HTML rendering failed"),
+            "{}",
+            failures[0]
+        );
+    }
+
+    #[test]
+    fn stop_mode_parses_case_insensitively() {
+        assert_eq!(" Immediate ".parse::<StopMode>(), Ok(StopMode::Immediate));
+        assert_eq!("graceful".parse::<StopMode>(), Ok(StopMode::Graceful));
+        assert!("now".parse::<StopMode>().unwrap_err().contains("'now'"));
+    }
+
+    /// A strategy whose ticks never finish on their own
+    struct StuckTickBot;
+
+    #[async_trait]
+    impl TradingBot for StuckTickBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(StuckTickBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn immediate_stop_cancels_the_running_tick() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            stop_mode: StopMode::Immediate,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let mut handler = start_runner_every::<StuckTickBot>(&telegram, &state, 1).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state.lock().await.tick_cancel.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the first tick never started");
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/stopbot"),
+                Command::StopBot,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        // The runner drops its request channel once it exits
+        tokio::time::timeout(Duration::from_secs(5), handler.request_tx().closed())
+            .await
+            .expect("the runner kept waiting for the tick");
+        assert!(!state.lock().await.is_running);
+    }
+
+    #[tokio::test]
+    async fn flush_notify_peeks_then_sends_one_summary_per_chat() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            summary_buffer: vec![
+                (CHAT, NotificationLevel::All, "first".to_string()),
+                (ChatId(7), NotificationLevel::All, "other".to_string()),
+                (CHAT, NotificationLevel::All, "second".to_string()),
+            ],
+            ..Default::default()
+        });
+
+        run_command(
+            &telegram,
+            &state,
+            "/flushnotify peek",
+            Command::FlushNotify("peek".to_string()),
+        )
+        .await;
+        run_command(
+            &telegram,
+            &state,
+            "/flushnotify",
+            Command::FlushNotify(String::new()),
+        )
+        .await;
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/flushnotify",
+            Command::FlushNotify(String::new()),
+        )
+        .await;
+
+        assert_eq!(texts[0], "3 notification(s) pending.");
+        assert_eq!(
+            texts[1],
+            "<pre>Summary of 2 notification(s):\n\nfirst\n\nsecond</pre>"
+        );
+        assert_eq!(
+            texts[2],
+            "<pre>Summary of 1 notification(s):\n\nother</pre>"
+        );
+        assert_eq!(texts[3], "Flushed 3 notification(s).");
+        assert_eq!(texts[4], "No notifications pending.");
+    }
+
+    #[tokio::test]
+    async fn capabilities_reports_the_strategy_declaration_as_json() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/capabilities"),
+                Command::Capabilities,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        let reply: Value = serde_json::from_str(texts.last().unwrap()).unwrap();
+        assert_eq!(
+            reply,
+            json!({
+                "positions": false,
+                "pnl": false,
+                "params": false,
+                "custom_commands": false,
+                "risk": false,
+                "orders": false,
+                "endpoint": false,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn warmup_holds_back_non_critical_notifications_until_it_ends() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            warmup: Duration::from_secs(5 * 60),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        {
+            let mut state = state.lock().await;
+            let now = state.now();
+            state.start_warmup(now);
+        }
+
+        for level in [NotificationLevel::Important, NotificationLevel::Critical] {
+            send_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                level.clone(),
+                format!("{:?}", level),
+            )
+            .await
+            .unwrap();
+        }
+        clock.set(utc(12, 4));
+        let remaining = state.lock().await.warmup_remaining(utc(12, 4));
+        clock.set(utc(12, 5));
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Important,
+            "after".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(remaining, Some(Duration::from_secs(60)));
+        assert_eq!(
+            telegram.sent_texts(),
+            ["<pre>Critical</pre>", "<pre>after</pre>"]
+        );
+        assert_eq!(state.lock().await.notification_stats.filtered, 1);
+    }
+
+    #[test]
+    fn warmup_keeps_notifications_off_when_the_level_is_none() {
+        let mut state = BotState {
+            notification_level: NotificationLevel::None,
+            warmup: Duration::from_secs(60),
+            ..Default::default()
+        };
+        state.start_warmup(utc(9, 0));
+
+        assert_eq!(
+            state.notification_level_at(utc(9, 0)),
+            NotificationLevel::None
+        );
+        state.warmup = Duration::ZERO;
+        state.start_warmup(utc(9, 0));
+        assert_eq!(state.warmup_until, None);
+    }
+
+    #[test]
+    fn compact_status_leaves_out_unknown_parts() {
+        let report = StatusReport {
+            open_positions: 3,
+            realized_pnl: 2.0,
+            unrealized_pnl: 0.4,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            compact_status(true, false, Some(&report), Some(Duration::from_secs(42))),
+            "▶ running | 3 pos | pnl +2.40 | next 42s"
+        );
+        assert_eq!(
+            compact_status(false, false, None, Some(Duration::from_secs(42))),
+            "■ stopped"
+        );
+        assert_eq!(compact_status(true, true, None, None), "⛔ killed");
+    }
+
+    #[tokio::test]
+    async fn s_reports_the_running_strategy_on_one_line() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/s"),
+                Command::S,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            telegram.sent_texts().last().unwrap(),
+            "▶ running | 2 pos | pnl +0.00 | next 1h 0m"
+        );
+    }
+
+    #[test]
+    fn symbol_columns_parse_in_order_and_report_every_problem() {
+        assert_eq!(
+            parse_symbol_columns(" Enabled , symbol"),
+            Ok(vec![SymbolColumn::Enabled, SymbolColumn::Symbol])
+        );
+        assert_eq!(
+            parse_symbol_columns("symbol,price,symbol"),
+            Err(vec![
+                "Unknown field 'price'".to_string(),
+                "Field 'symbol' is listed twice".to_string(),
+            ])
+        );
+        assert_eq!(
+            parse_symbol_columns(" , "),
+            Err(vec!["List at least one field".to_string()])
+        );
+    }
+
+    #[test]
+    fn symbols_table_shows_only_the_chosen_columns() {
+        let table = symbols_table(
+            &[symbol_config("BTCUSDT"), disabled("ETHUSDT")],
+            &[SymbolColumn::Symbol, SymbolColumn::Enabled],
+        );
+
+        let rows: Vec<Vec<String>> = table
+            .row_iter()
+            .map(|row| row.iter().map(|cell| cell.get_content()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [["Symbol", "Enabled"], ["BTCUSDT", "yes"], ["ETHUSDT", "no"],]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_events_through_the_handler() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+        let mut events = handler.subscribe().await.unwrap();
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/kill"),
+                Command::Kill,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            BotEvent::KillSwitch { active: true }
+        );
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            BotEvent::Notification { message, .. } if message.contains("KILL SWITCH ACTIVATED")
+        ));
+        assert_eq!(
+            events.recv().await.unwrap(),
+            BotEvent::Notification {
+                chat_id: CHAT,
+                level: NotificationLevel::Critical,
+                message: "filled".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn replay_paths_stay_inside_the_replay_directory() {
+        let dir = Path::new("/data/replays");
+
+        assert_eq!(
+            replay_data_path(dir, "2024/march.json"),
+            Ok(dir.join("2024/march.json"))
+        );
+        for name in [
+            "../secrets.json",
+            "2024/../../etc/passwd",
+            "/etc/passwd",
+            "./x.json",
+        ] {
+            assert!(replay_data_path(dir, name).is_err(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_is_admin_only_and_hides_missing_files() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("replay", &[]);
+        let replay_dir = path.parent().unwrap().to_path_buf();
+        std::fs::write(
+            replay_dir.join("ticks.json"),
+            r#"{"points": [{"timestamp": "2024-03-09T12:00:00Z", "symbol": "BTCUSDT", "price": 1.0}]}"#,
+        )
+        .unwrap();
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            chat_roles: HashMap::from([(CHAT, Role::Admin), (ChatId(7), Role::Operator)]),
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+        for (chat_id, name) in [
+            (CHAT, "ticks.json"),
+            (ChatId(7), "ticks.json"),
+            (CHAT, "missing.json"),
+            (CHAT, "../ticks.json"),
+            (CHAT, "ticks.json"),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(chat_id, &format!("/replay {}", name)),
+                    Command::Replay(name.to_string()),
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+            // The first replay runs before a directory is configured
+            state
+                .lock()
+                .await
+                .replay_dir
+                .get_or_insert_with(|| replay_dir.to_string_lossy().into_owned());
+        }
+        remove_config(&path);
+
+        let texts = telegram.sent_texts();
+        let replies = &texts[texts.len() - 5..];
+        assert_eq!(
+            replies[0],
+            "Replays are disabled: no replay directory is configured."
+        );
+        assert!(replies[1].contains("admin"), "{}", replies[1]);
+        assert_eq!(replies[2], "Could not load replay data 'missing.json'.");
+        assert!(replies[3].contains("without '..'"), "{}", replies[3]);
+        assert_eq!(replies[4], "The strategy does not support replays.");
+        assert!(!state.lock().await.dry_run);
+    }
+
+    #[test]
+    fn latencies_render_newest_first_with_their_average() {
+        let latencies = [Duration::from_millis(10), Duration::from_millis(30)];
+
+        assert_eq!(
+            render_latencies(&latencies),
+            "Status request latency (last 2, newest first):\n\n30.0 ms\n10.0 ms\n\nAverage: 20.0 \
+             ms"
+        );
+        assert!(render_latencies(&[]).starts_with("No status requests measured yet"));
+    }
+
+    #[test]
+    fn only_the_latest_latencies_are_kept() {
+        let (handler, _request_rx) = TelegramBotHandler::new();
+        for millis in 0..MAX_RECORDED_LATENCIES as u64 + 2 {
+            handler.record_status_latency(Duration::from_millis(millis));
+        }
+
+        let latencies = handler.status_latencies();
+        assert_eq!(latencies.len(), MAX_RECORDED_LATENCIES);
+        assert_eq!(latencies[0], Duration::from_millis(2));
+    }
+
+    #[tokio::test]
+    async fn status_requests_are_timed() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler.request_status().await.unwrap();
+        handler.request_status().await.unwrap();
+
+        assert_eq!(handler.status_latencies().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn the_first_role_assignment_keeps_the_caller_an_admin() {
+        let state = shared(BotState::default());
+
+        let first = assign_role(&state, CHAT, "7 operator").await;
+        let demote = assign_role(&state, CHAT, "42 operator").await;
+        let remove = assign_role(&state, CHAT, "42 remove").await;
+
+        assert!(first.ends_with("you were added as Admin."), "{}", first);
+        assert_eq!(demote, "You cannot demote yourself.");
+        assert_eq!(remove, "You cannot remove your own role.");
+        let state = state.lock().await;
+        assert_eq!(state.role_of(CHAT), Some(Role::Admin));
+        assert_eq!(state.role_of(ChatId(7)), Some(Role::Operator));
+        assert_eq!(state.role_of(ChatId(8)), None);
+    }
+
+    #[tokio::test]
+    async fn operators_cannot_run_admin_commands() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            chat_roles: HashMap::from([(CHAT, Role::Operator)]),
+            ..Default::default()
+        });
+
+        for (text, command) in [
+            ("/formattest hi", Command::FormatTest("hi".to_string())),
+            (
+                "/replay ticks.json",
+                Command::Replay("ticks.json".to_string()),
+            ),
+            (
+                "/removesymbol BTCUSDT",
+                Command::RemoveSymbol("BTCUSDT".to_string()),
+            ),
+        ] {
+            run_command(&telegram, &state, text, command).await;
+        }
+        state.lock().await.chat_roles = HashMap::from([(ChatId(7), Role::Admin)]);
+        let texts = run_command(&telegram, &state, "/status", Command::Status).await;
+
+        assert_eq!(
+            texts,
+            [
+                "This command requires the admin role.",
+                "This command requires the admin role.",
+                "This command requires the admin role.",
+                "You are not authorized to use this bot.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scheduleinfo_shows_windows_in_the_configured_time_zone() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(6, 30));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            level_schedule: parse_level_schedule("08:00-09:00=critical").unwrap(),
+            time_zone: FixedOffset::east_opt(2 * 3600).unwrap(),
+            trading_window: Some(parse_time_window("08:00-17:00").unwrap()),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+
+        let texts = run_command(&telegram, &state, "/scheduleinfo", Command::ScheduleInfo).await;
+
+        let info = texts.last().unwrap();
+        assert!(info.contains("Time zone: UTC+02:00"), "{}", info);
+        assert!(
+            info.contains("Trading window: 08:00-17:00 (open, closes at 17:00)"),
+            "{}",
+            info
+        );
+        assert!(info.contains("Current time: 2024-03-09 08:30"), "{}", info);
+        assert!(info.contains("08:00-09:00=Critical (active)"), "{}", info);
+        assert!(
+            info.contains("Next change: Critical -> All at 09:00"),
+            "{}",
+            info
+        );
+    }
+
+    #[test]
+    fn circuit_breaker_is_off_until_a_threshold_is_set() {
+        let mut breaker = CircuitBreaker::default();
+        for _ in 0..10 {
+            assert!(!breaker.record_failure(utc(12, 0)));
+        }
+        assert!(!breaker.tripped);
+
+        breaker.threshold = 2;
+        breaker.record_success();
+        assert!(!breaker.record_failure(utc(12, 0)));
+        assert!(breaker.record_failure(utc(12, 1)));
+        assert!(breaker.tripped);
+        assert_eq!(breaker.tripped_at, Some(utc(12, 1)));
+        assert!(!breaker.record_failure(utc(12, 2)), "already tripped");
+    }
+
+    #[test]
+    fn circuit_breaker_cooldown_counts_from_the_trip() {
+        let mut breaker = CircuitBreaker {
+            cooldown: Duration::from_secs(15 * 60),
+            ..Default::default()
+        };
+        assert_eq!(breaker.cooldown_remaining(utc(12, 0)), None);
+
+        assert!(breaker.trip(utc(12, 0)));
+        assert!(breaker.acknowledge("@ops".to_string(), utc(12, 5)));
+        assert!(!breaker.tripped);
+        assert_eq!(breaker.consecutive_failures, 0);
+        assert_eq!(
+            breaker.cooldown_remaining(utc(12, 5)),
+            Some(Duration::from_secs(10 * 60))
+        );
+        assert_eq!(breaker.cooldown_remaining(utc(12, 15)), None);
+        assert!(!breaker.acknowledge("@ops".to_string(), utc(12, 20)));
+    }
+
+    #[tokio::test]
+    async fn breakerthreshold_turns_the_breaker_on_and_off() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        run_command(
+            &telegram,
+            &state,
+            "/breakerthreshold 3",
+            Command::BreakerThreshold(3),
+        )
+        .await;
+        assert_eq!(state.lock().await.circuit_breaker.threshold, 3);
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/breakerthreshold 0",
+            Command::BreakerThreshold(0),
+        )
+        .await;
+
+        assert_eq!(
+            texts,
+            vec![
+                "The circuit breaker will trip after 3 consecutive failures",
+                "Circuit breaker turned off",
+            ]
+        );
+        assert_eq!(state.lock().await.circuit_breaker.threshold, 0);
+    }
+
+    static BREAKER_BOT_FAILING: AtomicBool = AtomicBool::new(true);
+    static BREAKER_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Fails its ticks while `BREAKER_BOT_FAILING` is set and counts the
+    /// ones that succeed
+    struct BreakerBot;
+
+    #[async_trait]
+    impl TradingBot for BreakerBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(BreakerBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            if BREAKER_BOT_FAILING.load(Ordering::SeqCst) {
+                return Err("exchange unavailable".to_string());
+            }
+            BREAKER_BOT_TICKS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn ticks_resume_after_clearing_a_tripped_breaker() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            command_cooldown_secs: 0,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        state.lock().await.circuit_breaker.threshold = 1;
+        let mut handler = start_runner_every::<BreakerBot>(&telegram, &state, 1).await;
+
+        for _ in 0..300 {
+            if state.lock().await.circuit_breaker.tripped {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(state.lock().await.circuit_breaker.tripped);
+        assert!(!state.lock().await.is_running);
+        BREAKER_BOT_FAILING.store(false, Ordering::SeqCst);
+
+        for (text, command) in [
+            ("/clearbreaker", Command::ClearBreaker),
+            ("/startbot", Command::StartBot),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+        }
+        for _ in 0..300 {
+            if BREAKER_BOT_TICKS.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(BREAKER_BOT_TICKS.load(Ordering::SeqCst) > 0);
+        assert!(telegram
+            .sent_texts()
+            .iter()
+            .any(|text| text.ends_with("Use /startbot to resume trading.")));
+
+        // A forced trip pauses the runner the same way
+        for (text, command) in [
+            ("/tripbreaker", Command::TripBreaker),
+            ("/clearbreaker", Command::ClearBreaker),
+            ("/startbot", Command::StartBot),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+        }
+        let ticks = BREAKER_BOT_TICKS.load(Ordering::SeqCst);
+        for _ in 0..300 {
+            if BREAKER_BOT_TICKS.load(Ordering::SeqCst) > ticks {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(BREAKER_BOT_TICKS.load(Ordering::SeqCst) > ticks);
+        assert!(state.lock().await.is_running);
+    }
+
+    #[test]
+    fn edit_symbol_sets_and_clears_max_slippage() {
+        let mut symbols = vec![symbol_config("BTCUSDT")];
+
+        edit_symbol(&mut symbols, "BTCUSDT", "max_slippage", "0.005").unwrap();
+        assert_eq!(symbols[0].max_slippage, Some(0.005));
+        assert_eq!(
+            edit_symbol(&mut symbols, "BTCUSDT", "max_slippage", "-1"),
+            Err("max_slippage must be a non-negative number".to_string())
+        );
+        assert_eq!(symbols[0].max_slippage, Some(0.005));
+        assert_eq!(
+            edit_symbol(&mut symbols, "ETHUSDT", "max_slippage", "0.1"),
+            Err("Symbol 'ETHUSDT' not found.".to_string())
+        );
+
+        edit_symbol(&mut symbols, "BTCUSDT", "max_slippage", "none").unwrap();
+        assert_eq!(symbols[0].max_slippage, None);
+    }
+
+    #[tokio::test]
+    async fn editsymbol_saves_the_new_value() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("editsymbol", &[symbol_config("BTCUSDT")]);
+        let state = state_with_config(&path);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/editsymbol BTCUSDT max_slippage 0.01",
+            Command::EditSymbol("BTCUSDT max_slippage 0.01".to_string()),
+        )
+        .await;
+
+        assert_eq!(
+            texts.last().unwrap(),
+            "Updated max_slippage of 'BTCUSDT' to 0.01."
+        );
+        assert_eq!(read_config(&path)[0].max_slippage, Some(0.01));
+        remove_config(&path);
+    }
+
+    #[test]
+    fn default_symbols_table_shows_max_slippage() {
+        let mut guarded = symbol_config("ETHUSDT");
+        guarded.max_slippage = Some(0.0025);
+        let table = symbols_table(
+            &[symbol_config("BTCUSDT"), guarded],
+            &SymbolColumn::defaults(),
+        );
+
+        let rows: Vec<Vec<String>> = table
+            .row_iter()
+            .map(|row| row.iter().map(|cell| cell.get_content()).collect())
+            .collect();
+        let column = rows[0]
+            .iter()
+            .position(|title| title == "Max Slippage")
+            .unwrap();
+        assert_eq!(rows[1][column], "-");
+        assert_eq!(rows[2][column], "0.0025");
+    }
+
+    #[test]
+    fn size_step_is_editable_and_shown_in_the_table() {
+        let mut symbols = vec![symbol_config("BTCUSDT"), symbol_config("ETHUSDT")];
+
+        edit_symbol(&mut symbols, "ETHUSDT", "size_step", "0.001").unwrap();
+        assert_eq!(symbols[1].size_step, Some(0.001));
+        for step in ["0", "-0.1"] {
+            assert_eq!(
+                edit_symbol(&mut symbols, "ETHUSDT", "size_step", step),
+                Err("size_step must be a positive number".to_string())
+            );
+        }
+        assert_eq!(symbols[1].size_step, Some(0.001));
+
+        let table = symbols_table(&symbols, &SymbolColumn::defaults());
+        let rows: Vec<Vec<String>> = table
+            .row_iter()
+            .map(|row| row.iter().map(|cell| cell.get_content()).collect())
+            .collect();
+        let column = rows[0]
+            .iter()
+            .position(|title| title == "Size Step")
+            .unwrap();
+        assert_eq!(rows[1][column], "-");
+        assert_eq!(rows[2][column], "0.001");
+
+        edit_symbol(&mut symbols, "ETHUSDT", "size_step", "off").unwrap();
+        assert_eq!(symbols[1].size_step, None);
+    }
+
+    static QUIET_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A strategy that counts its ticks and reports each one as a critical
+    /// notification
+    struct QuietBot;
+
+    #[async_trait]
+    impl TradingBot for QuietBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(QuietBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            bot_state: Arc<Mutex<BotState>>,
+            telegram_bot: Bot,
+            chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            let tick = QUIET_BOT_TICKS.fetch_add(1, Ordering::SeqCst) + 1;
+            send_notification(
+                &telegram_bot,
+                chat_id,
+                &bot_state,
+                NotificationLevel::Critical,
+                format!("tick {}", tick),
+            )
+            .await
+            .map_err(|e| e.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn quiet_keeps_trading_but_sends_nothing_until_it_expires() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            is_running: true,
+            notification_level: NotificationLevel::All,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/quiet 1h",
+            Command::Quiet("1h".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Notifications silenced for 1h 0m; trading continues."
+        );
+        let _handler = start_runner_every::<QuietBot>(&telegram, &state, 1).await;
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while QUIET_BOT_TICKS.load(Ordering::SeqCst) < 2 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("the strategy keeps ticking while quiet");
+        assert!(state.lock().await.is_running);
+        assert!(!telegram
+            .sent_texts()
+            .iter()
+            .any(|text| text.contains("tick ")));
+        assert!(state.lock().await.notification_stats.suppressed.quiet >= 2);
+
+        clock.set(utc(13, 1));
+        tokio::time::timeout(Duration::from_secs(10), async {
+            telegram
+                .wait_for(|calls| calls.iter().any(|call| call.text().contains("tick ")))
+                .await
+        })
+        .await
+        .expect("notifications resume after the quiet window");
+        state.lock().await.is_running = false;
+    }
+
+    #[test]
+    fn status_document_round_trips_the_report() {
+        let report = StatusReport {
+            summary: "Running".to_string(),
+            open_positions: 3,
+            realized_pnl: 1.5,
+            unrealized_pnl: -0.25,
+        };
+
+        let (file_name, content) = status_document(&report, utc(9, 5)).unwrap();
+
+        assert_eq!(file_name, "status_20240309_090500.json");
+        assert_eq!(
+            serde_json::from_slice::<StatusReport>(&content).unwrap(),
+            report
+        );
+    }
+
+    #[tokio::test]
+    async fn statusfile_refuses_while_the_bot_is_stopped() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+
+        let texts = run_command(&telegram, &state, "/statusfile", Command::StatusFile).await;
+
+        assert_eq!(
+            texts,
+            vec!["Bot is not running; there is no status to export."]
+        );
+        assert!(!telegram.calls().iter().any(|call| call.is("sendDocument")));
+    }
+
+    #[tokio::test]
+    async fn statusfile_uploads_the_strategy_report_as_json() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 5));
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/statusfile"),
+                Command::StatusFile,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let calls = telegram.calls();
+        let upload = calls
+            .iter()
+            .find(|call| call.is("sendDocument"))
+            .expect("no document was sent");
+        assert!(upload.raw.contains("status_20240309_090500.json"));
+        let json = &upload.raw[upload.raw.find('{').unwrap()..=upload.raw.rfind('}').unwrap()];
+        assert_eq!(
+            serde_json::from_str::<StatusReport>(json).unwrap(),
+            StatusReport {
+                summary: "custom".to_string(),
+                open_positions: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn watchdog_is_off_until_given_a_threshold() {
+        let mut state = BotState {
+            is_running: true,
+            interval_seconds: Some(60),
+            ..Default::default()
+        };
+        state.record_tick(utc(12, 0));
+        assert_eq!(state.watchdog_stall(utc(18, 0)), None);
+
+        state.watchdog_intervals = 3;
+        assert_eq!(state.watchdog_stall(utc(12, 3)), None);
+        assert_eq!(
+            state.watchdog_stall(utc(12, 4)),
+            Some(Duration::from_secs(4 * 60))
+        );
+
+        state.is_running = false;
+        assert_eq!(state.watchdog_stall(utc(12, 4)), None);
+    }
+
+    #[tokio::test]
+    async fn startbot_restarts_the_watchdog_clock() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            interval_seconds: Some(60),
+            watchdog_intervals: 3,
+            last_successful_tick: Some(utc(8, 0)),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+
+        run_command(&telegram, &state, "/startbot", Command::StartBot).await;
+
+        let state = state.lock().await;
+        assert!(state.is_running);
+        assert_eq!(state.last_successful_tick, Some(utc(12, 0)));
+        assert_eq!(state.watchdog_stall(utc(12, 2)), None);
+    }
+
+    /// The chats that received a /status reply from `CustomFormatBot`
+    async fn status_reply_chats(broadcast: bool) -> Vec<ChatId> {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![ChatId(7), CHAT, ChatId(8)],
+            status_broadcast: broadcast,
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/status"),
+                Command::Status,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        telegram
+            .calls()
+            .iter()
+            .filter(|call| call.text().contains("🚀 custom | 2 open"))
+            .filter_map(|call| call.chat_id())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn status_replies_only_to_the_asking_chat_by_default() {
+        assert_eq!(status_reply_chats(false).await, vec![CHAT]);
+    }
+
+    #[tokio::test]
+    async fn status_broadcast_reaches_every_target_once() {
+        assert_eq!(
+            status_reply_chats(true).await,
+            vec![CHAT, ChatId(7), ChatId(8)]
+        );
+    }
+
+    #[test]
+    fn alert_rules_fire_once_until_they_stop_matching() {
+        let rule: AlertRule = "unrealized_pnl < -100".parse().unwrap();
+        let mut state = BotState {
+            alert_rules: vec![rule.clone()],
+            ..Default::default()
+        };
+        let losing = StatusReport {
+            unrealized_pnl: -150.0,
+            ..Default::default()
+        };
+
+        let none = BTreeMap::new();
+
+        assert_eq!(
+            state.check_alert_rules(&losing, &none),
+            vec![(rule.clone(), -150.0)]
+        );
+        assert!(state.check_alert_rules(&losing, &none).is_empty());
+        assert!(state
+            .check_alert_rules(&StatusReport::default(), &none)
+            .is_empty());
+        assert_eq!(
+            state.check_alert_rules(&losing, &none),
+            vec![(rule, -150.0)]
+        );
+    }
+
+    #[test]
+    fn metric_rules_fire_once_across_ticks_until_they_recover() {
+        let rule: AlertRule = "drawdown > 5".parse().unwrap();
+        let mut state = BotState {
+            alert_rules: vec![rule.clone()],
+            ..Default::default()
+        };
+        let report = StatusReport::default();
+        let ticks = [3.0, 6.0, 8.0, 4.0, 9.0];
+
+        let fired: Vec<Vec<(AlertRule, f64)>> = ticks
+            .iter()
+            .map(|drawdown| {
+                let columns = BTreeMap::from([("drawdown".to_string(), *drawdown)]);
+                state.check_alert_rules(&report, &columns)
+            })
+            .collect();
+
+        assert_eq!(
+            fired,
+            vec![
+                vec![],
+                vec![(rule.clone(), 6.0)],
+                vec![],
+                vec![],
+                vec![(rule, 9.0)],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn alert_rules_alert_the_targets_after_a_tick() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/addrule open_positions >= 2",
+            Command::AddRule("open_positions >= 2".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Alert rule #1 added: open_positions >= 2"
+        );
+
+        let _handler = start_runner_every::<CustomFormatBot>(&telegram, &state, 1).await;
+        tokio::time::timeout(Duration::from_secs(10), async {
+            telegram
+                .wait_for(|calls| {
+                    calls.iter().any(|call| {
+                        call.text()
+                            .contains("Alert: open_positions >= 2 (now 2.00)")
+                    })
+                })
+                .await
+        })
+        .await
+        .expect("the rule fired after a tick");
+        state.lock().await.is_running = false;
+    }
+
+    #[tokio::test]
+    async fn addrule_accepts_only_metrics_the_strategy_reports() {
+        let replies = run_commands::<CustomFormatBot>(vec![
+            (
+                "/addrule drawdown > 5",
+                Command::AddRule("drawdown > 5".to_string()),
+            ),
+            (
+                "/addrule equity < 5",
+                Command::AddRule("equity < 5".to_string()),
+            ),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            vec![
+                "Alert rule #1 added: drawdown > 5".to_string(),
+                "Unknown field 'equity'. Use: open_positions, realized_pnl, unrealized_pnl or \
+                 total_pnl, or a strategy metric: drawdown"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn metric_rules_alert_the_targets_after_a_tick() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            alert_rules: vec!["drawdown > 5".parse().unwrap()],
+            ..Default::default()
+        });
+
+        let _handler = start_runner_every::<CustomFormatBot>(&telegram, &state, 1).await;
+        tokio::time::timeout(Duration::from_secs(10), async {
+            telegram
+                .wait_for(|calls| {
+                    calls
+                        .iter()
+                        .any(|call| call.text().contains("Alert: drawdown > 5 (now 7.50)"))
+                })
+                .await
+        })
+        .await
+        .expect("the metric rule fired after a tick");
+        state.lock().await.is_running = false;
+    }
+
+    #[tokio::test]
+    async fn threshold_accepts_only_metrics_the_strategy_reports() {
+        let replies = run_commands::<CustomFormatBot>(vec![
+            (
+                "/threshold drawdown,>,5",
+                Command::Threshold("drawdown,>,5".to_string()),
+            ),
+            (
+                "/threshold equity,<,5",
+                Command::Threshold("equity,<,5".to_string()),
+            ),
+            (
+                "/threshold drawdown > 5",
+                Command::Threshold("drawdown > 5".to_string()),
+            ),
+            ("/listrules", Command::ListRules),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            vec![
+                "Alert rule #1 added: drawdown > 5".to_string(),
+                "Unknown field 'equity'. Use: open_positions, realized_pnl, unrealized_pnl or \
+                 total_pnl, or a strategy metric: drawdown"
+                    .to_string(),
+                "Expected METRIC,OP,VALUE, e.g. 'drawdown,>,5', got 'drawdown > 5'".to_string(),
+                "1. drawdown > 5".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn thresholds_alert_once_per_breach_across_ticks() {
+        let rule = parse_threshold("drawdown,>,5").unwrap();
+        let mut state = BotState {
+            alert_rules: vec![rule.clone()],
+            ..Default::default()
+        };
+        let report = StatusReport::default();
+
+        let fired: Vec<Vec<(AlertRule, f64)>> = [3.0, 6.0, 8.0, 5.0, 4.0, 9.0, 9.5]
+            .into_iter()
+            .map(|drawdown| {
+                let columns = BTreeMap::from([("drawdown".to_string(), drawdown)]);
+                state.check_alert_rules(&report, &columns)
+            })
+            .collect();
+
+        assert_eq!(
+            fired,
+            vec![
+                vec![],
+                vec![(rule.clone(), 6.0)],
+                vec![],
+                vec![],
+                vec![],
+                vec![(rule.clone(), 9.0)],
+                vec![],
+            ]
+        );
+    }
+
+    static DRIFT_BOT_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Reports a drawdown that grows by two with every tick
+    struct DriftBot;
+
+    #[async_trait]
+    impl TradingBot for DriftBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(DriftBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            DRIFT_BOT_TICKS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+
+        fn status_columns(&self) -> BTreeMap<String, f64> {
+            let drawdown = 2.0 * DRIFT_BOT_TICKS.load(Ordering::SeqCst) as f64;
+            BTreeMap::from([("drawdown".to_string(), drawdown)])
+        }
+    }
+
+    #[tokio::test]
+    async fn a_threshold_breached_over_several_ticks_alerts_once() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let mut handler = start_runner_every::<DriftBot>(&telegram, &state, 1).await;
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/threshold drawdown,>,3"),
+                Command::Threshold("drawdown,>,3".to_string()),
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        // The drawdown is 2 after the first tick and over 3 from the second on
+        for _ in 0..600 {
+            if DRIFT_BOT_TICKS.load(Ordering::SeqCst) >= 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(DRIFT_BOT_TICKS.load(Ordering::SeqCst) >= 4);
+        state.lock().await.is_running = false;
+
+        let alerts: Vec<String> = telegram
+            .sent_texts()
+            .into_iter()
+            .filter(|text| text.contains("Alert: drawdown > 3"))
+            .collect();
+        assert_eq!(alerts.len(), 1, "{:?}", alerts);
+        assert!(alerts[0].contains("(now 4.00)"), "{}", alerts[0]);
+    }
+
+    /// A state with several settings changed away from their defaults
+    fn tweaked_state() -> BotState {
+        let mut state = BotState {
+            is_running: true,
+            kill_switch: true,
+            notification_targets: vec![CHAT, ChatId(7)],
+            staged_symbols: Some(vec![symbol_config("BTCUSDT")]),
+            notification_level: NotificationLevel::Critical,
+            level_schedule: parse_level_schedule("00:00-08:00=none").unwrap(),
+            dry_run: true,
+            summary_mode: true,
+            command_cooldown_secs: 0,
+            alert_rules: vec!["total_pnl < 0".parse().unwrap()],
+            watchdog_intervals: 5,
+            ..Default::default()
+        };
+        state.circuit_breaker.threshold = 4;
+        state
+    }
+
+    #[tokio::test]
+    async fn resetsettings_asks_for_confirmation_first() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(tweaked_state());
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/resetsettings",
+            Command::ResetSettings(String::new()),
+        )
+        .await;
+
+        assert!(texts.last().unwrap().contains("/resetsettings confirm"));
+        let state = state.lock().await;
+        assert!(state.dry_run);
+        assert_eq!(state.notification_level, NotificationLevel::Critical);
+    }
+
+    #[tokio::test]
+    async fn resetsettings_restores_defaults_but_keeps_symbols_and_running_state() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(tweaked_state());
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/resetsettings confirm",
+            Command::ResetSettings("confirm".to_string()),
+        )
+        .await;
+
+        assert_eq!(texts.last().unwrap(), "Runtime settings reset to defaults.");
+        let state = state.lock().await;
+        let defaults = BotState::default();
+        assert_eq!(state.notification_level, defaults.notification_level);
+        assert!(state.level_schedule.is_empty());
+        assert!(!state.dry_run);
+        assert!(!state.summary_mode);
+        assert_eq!(state.command_cooldown_secs, defaults.command_cooldown_secs);
+        assert!(state.alert_rules.is_empty());
+        assert_eq!(state.watchdog_intervals, 0);
+        assert_eq!(state.circuit_breaker.threshold, 0);
+
+        assert!(state.is_running);
+        assert!(state.kill_switch);
+        assert_eq!(state.notification_targets, vec![CHAT, ChatId(7)]);
+        assert_eq!(
+            symbol_names(state.staged_symbols.as_deref().unwrap()),
+            ["BTCUSDT"]
+        );
+    }
+
+    #[test]
+    fn render_account_flags_testnet_and_mainnet() {
+        let mut account = AccountInfo {
+            exchange: "Kraken".to_string(),
+            account_label: String::new(),
+            testnet: false,
+        };
+        assert_eq!(
+            render_account(&account),
+            "Exchange: Kraken\nAccount: (unlabelled)\nEnvironment: MAINNET (live funds)"
+        );
+
+        account.account_label = "hedge".to_string();
+        account.testnet = true;
+        assert_eq!(
+            render_account(&account),
+            "Exchange: Kraken\nAccount: hedge\nEnvironment: TESTNET (no real funds)"
+        );
+
+        assert_eq!(
+            render_account(&AccountInfo::default()),
+            "The strategy does not report its account."
+        );
+    }
+
+    #[tokio::test]
+    async fn account_shows_the_strategy_account() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/account"),
+                Command::Account,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        let reply = texts.last().unwrap();
+        assert!(reply.contains("Exchange: Binance"), "{}", reply);
+        assert!(reply.contains("Account: main"), "{}", reply);
+        assert!(reply.contains("TESTNET"), "{}", reply);
+    }
+
+    #[tokio::test]
+    async fn notifications_are_chunked_at_the_configured_length() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            max_message_length: 300,
+            ..Default::default()
+        });
+
+        let text = "0123456789\n".repeat(100);
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            text.clone(),
+        )
+        .await
+        .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert!(texts.len() >= 4, "{:?}", texts);
+        assert!(texts.iter().all(|text| text.chars().count() <= 300));
+        let joined: String = texts
+            .iter()
+            .map(|text| text.trim_start_matches("<pre>").trim_end_matches("</pre>"))
+            .collect();
+        assert_eq!(joined, text);
+    }
+
+    #[tokio::test]
+    async fn maxlength_rejects_values_outside_the_allowed_range() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+
+        for value in ["100", "5000", "many"] {
+            run_command(
+                &telegram,
+                &state,
+                &format!("/maxlength {}", value),
+                Command::MaxLength(value.to_string()),
+            )
+            .await;
+        }
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/maxlength 300",
+            Command::MaxLength("300".to_string()),
+        )
+        .await;
+
+        assert_eq!(
+            texts[..3],
+            ["Invalid length. Use a value between 256 and 4096."; 3]
+        );
+        assert_eq!(texts[3], "Maximum message length set to 300");
+        assert_eq!(state.lock().await.max_message_length, 300);
+    }
+
+    #[tokio::test]
+    async fn tick_notifications_count_sent_and_filtered_messages() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::Important,
+            notification_targets: vec![CHAT],
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/lastticknotify",
+            Command::LastTickNotify,
+        )
+        .await;
+        assert_eq!(texts, vec!["No tick has completed yet."]);
+
+        state.lock().await.begin_tick_notifications();
+        for (level, message) in [
+            (NotificationLevel::Critical, "sent"),
+            (NotificationLevel::All, "below the level"),
+        ] {
+            send_notification(&telegram.bot, CHAT, &state, level, message.to_string())
+                .await
+                .unwrap();
+        }
+        state.lock().await.quiet_until = Some(utc(13, 0));
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Critical,
+            "while quiet".to_string(),
+        )
+        .await
+        .unwrap();
+        state.lock().await.end_tick_notifications();
+
+        assert_eq!(
+            state.lock().await.last_tick_notifications,
+            Some(TickNotifications {
+                sent: 1,
+                filtered_by_level: 1,
+                filtered_by_quiet: 1,
+            })
+        );
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/lastticknotify",
+            Command::LastTickNotify,
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Notifications in the last tick:\nSent: 1\nFiltered by level: 1\nFiltered by quiet: 1"
+        );
+    }
+
+    #[test]
+    fn budget_blocks_entries_past_it_until_capital_is_released() {
+        let mut state = BotState {
+            session_budget: Some(100.0),
+            ..Default::default()
+        };
+
+        assert!(state.reserve_budget(60.0));
+        assert!(!state.reserve_budget(50.0));
+        assert_eq!(state.deployed_capital, 60.0);
+        assert!(state.reserve_budget(40.0));
+
+        state.release_budget(60.0);
+        assert_eq!(state.deployed_capital, 40.0);
+        assert!(state.reserve_budget(50.0));
+        state.release_budget(500.0);
+        assert_eq!(state.deployed_capital, 0.0);
+    }
+
+    #[tokio::test]
+    async fn check_budget_alerts_when_an_entry_is_blocked() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            session_budget: Some(100.0),
+            ..Default::default()
+        });
+
+        assert!(check_budget(&telegram.bot, CHAT, &state, "BTCUSDT", 80.0).await);
+        assert!(!check_budget(&telegram.bot, CHAT, &state, "ETHUSDT", 30.0).await);
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts.len(), 1, "{:?}", texts);
+        assert!(
+            texts[0].contains(
+                "Entry for ETHUSDT blocked: 30.00 would exceed the session budget of 100.00 \
+                 (80.00 deployed)"
+            ),
+            "{}",
+            texts[0]
+        );
+        assert_eq!(state.lock().await.symbol_notifications["ETHUSDT"], 1);
+    }
+
+    #[tokio::test]
+    async fn startbot_resets_the_deployed_capital() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            session_budget: Some(100.0),
+            deployed_capital: 90.0,
+            ..Default::default()
+        });
+
+        run_command(&telegram, &state, "/startbot", Command::StartBot).await;
+
+        assert_eq!(state.lock().await.deployed_capital, 0.0);
+    }
+
+    #[test]
+    fn status_diff_reports_every_delta() {
+        let snapshot = StatusSnapshot {
+            at: utc(9, 0),
+            report: StatusReport {
+                open_positions: 3,
+                realized_pnl: 10.0,
+                unrealized_pnl: -4.5,
+                ..Default::default()
+            },
+        };
+        let current = StatusReport {
+            open_positions: 1,
+            realized_pnl: 12.25,
+            unrealized_pnl: 1.0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render_status_diff("morning", &snapshot, &current, utc(10, 30)),
+            "Changes since snapshot 'morning' (1h 30m ago):\nOpen positions: 3 -> 1 \
+             (-2)\nRealized PnL: 10.00 -> 12.25 (+2.25)\nUnrealized PnL: -4.50 -> 1.00 \
+             (+5.50)\nTotal PnL: 5.50 -> 13.25 (+7.75)"
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_then_diffstatus_compares_with_the_current_report() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 0));
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let mut handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+
+        for (text, command) in [
+            ("/snapshot", Command::Snapshot(String::new())),
+            (
+                "/diffstatus missing",
+                Command::DiffStatus("missing".to_string()),
+            ),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+        }
+        state
+            .lock()
+            .await
+            .status_snapshots
+            .get_mut("latest")
+            .unwrap()
+            .report
+            .open_positions = 5;
+        clock.set(utc(9, 15));
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/diffstatus"),
+                Command::DiffStatus(String::new()),
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        let replies = &texts[texts.len() - 3..];
+        assert_eq!(replies[0], "Status saved as snapshot 'latest'");
+        assert_eq!(
+            replies[1],
+            "No snapshot named 'missing'. Save one with /snapshot."
+        );
+        assert!(
+            replies[2].starts_with("Changes since snapshot 'latest' (15m 0s ago):"),
+            "{}",
+            replies[2]
+        );
+        assert!(
+            replies[2].contains("Open positions: 5 -> 2 (-3)"),
+            "{}",
+            replies[2]
+        );
+    }
+
+    #[test]
+    fn auto_backup_is_due_once_a_day_after_the_scheduled_time() {
+        let mut state = BotState {
+            time_zone: FixedOffset::east_opt(2 * 3600).unwrap(),
+            ..Default::default()
+        };
+        let at = parse_time("03:00").unwrap();
+
+        // 00:30 UTC is 02:30 local, before the backup time
+        state.schedule_auto_backup(Some(at), utc(0, 30));
+        assert!(!state.take_auto_backup(utc(0, 59)));
+        assert!(state.take_auto_backup(utc(1, 0)));
+        assert!(!state.take_auto_backup(utc(5, 0)), "already done today");
+        let tomorrow = utc(1, 0) + chrono::Duration::days(1);
+        assert!(state.take_auto_backup(tomorrow));
+
+        // A time already passed today first runs tomorrow
+        state.schedule_auto_backup(Some(at), utc(4, 0));
+        assert!(!state.take_auto_backup(utc(4, 1)));
+
+        state.schedule_auto_backup(None, utc(4, 0));
+        assert!(!state.take_auto_backup(tomorrow));
+    }
+
+    #[tokio::test]
+    async fn runner_writes_the_automatic_backup_when_it_is_due() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("auto_backup", &[symbol_config("BTCUSDT")]);
+        let clock = FakeClock::at(utc(2, 0));
+        let state = state_with_config(&path);
+        {
+            let mut state = state.lock().await;
+            state.is_running = true;
+            state.clock = clock.clock();
+        }
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/autobackup 03:00",
+            Command::AutoBackup("03:00".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Config will be backed up daily at 03:00"
+        );
+
+        // The runner checks for a due backup as soon as it starts
+        clock.set(utc(3, 0));
+        let _handler = start_runner::<CustomFormatBot>(&telegram, &state).await;
+        let backups = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let backups = list_backups(&path).await.unwrap();
+                if !backups.is_empty() {
+                    return backups;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("no automatic backup was written");
+        state.lock().await.is_running = false;
+
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&backups[0]).unwrap(),
+            std::fs::read_to_string(&path).unwrap()
+        );
+        assert_eq!(
+            state.lock().await.last_auto_backup,
+            Some(utc(3, 0).date_naive())
+        );
+        remove_config(&path);
+    }
+
+    /// A strategy with one adjustable risk parameter
+    struct RiskBot {
+        max_drawdown: f64,
+    }
+
+    #[async_trait]
+    impl TradingBot for RiskBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(RiskBot { max_drawdown: 5.0 })
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                risk: true,
+                ..Default::default()
+            }
+        }
+
+        fn risk_params(&self) -> Vec<RiskParam> {
+            vec![RiskParam {
+                key: "max_drawdown".to_string(),
+                value: self.max_drawdown,
+                description: "Stop trading past this loss".to_string(),
+            }]
+        }
+
+        async fn set_risk_param(&mut self, key: &str, value: f64) -> Result<(), Self::Error> {
+            if value <= 0.0 {
+                return Err(format!("{} must be positive", key));
+            }
+            self.max_drawdown = value;
+            Ok(())
+        }
+    }
+
+    /// Send each command to a runner for `T` and return the replies
+    async fn run_commands<T: TradingBot>(commands: Vec<(&str, Command)>) -> Vec<String> {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let mut handler = start_runner::<T>(&telegram, &state).await;
+        let before = telegram.sent_texts().len();
+        for (text, command) in commands {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
+                )
+                .await
+                .unwrap();
+        }
+        telegram.sent_texts().split_off(before)
+    }
+
+    #[tokio::test]
+    async fn setrisk_changes_a_declared_parameter() {
+        let replies = run_commands::<RiskBot>(vec![
+            (
+                "/setrisk max_drawdown=7.5",
+                Command::SetRisk("max_drawdown=7.5".to_string()),
+            ),
+            (
+                "/setrisk leverage=3",
+                Command::SetRisk("leverage=3".to_string()),
+            ),
+            (
+                "/setrisk max_drawdown=-1",
+                Command::SetRisk("max_drawdown=-1".to_string()),
+            ),
+            ("/risk", Command::Risk),
+        ])
+        .await;
+
+        assert_eq!(replies[0], "Risk parameter max_drawdown set to 7.5");
+        assert_eq!(
+            replies[1],
+            "Unable to set leverage: Unknown risk parameter 'leverage'"
+        );
+        assert_eq!(
+            replies[2],
+            "Unable to set max_drawdown: max_drawdown must be positive"
+        );
+        assert!(replies[3].contains("max_drawdown"), "{}", replies[3]);
+        assert!(replies[3].contains("7.5"), "{}", replies[3]);
+        assert!(
+            replies[3].contains("Stop trading past this loss"),
+            "{}",
+            replies[3]
+        );
+    }
+
+    #[tokio::test]
+    async fn setrisk_is_not_supported_without_the_capability() {
+        let replies = run_commands::<CustomFormatBot>(vec![
+            (
+                "/setrisk max_drawdown=7",
+                Command::SetRisk("max_drawdown=7".to_string()),
+            ),
+            ("/risk", Command::Risk),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            vec![
+                "Unable to set max_drawdown: The strategy does not support changing risk \
+                 parameters",
+                "The strategy declares no risk parameters.",
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamp_prefix_uses_the_configured_zone() {
+        assert_eq!(
+            timestamp_prefix(utc(9, 5), FixedOffset::east_opt(0).unwrap()),
+            "2024-03-09T09:05:00Z "
+        );
+        assert_eq!(
+            timestamp_prefix(utc(23, 30), FixedOffset::west_opt(5 * 3600).unwrap()),
+            "2024-03-09T18:30:00-05:00 "
+        );
+    }
+
+    #[tokio::test]
+    async fn timestamps_prefix_notifications_only_while_on() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 5));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            time_zone: FixedOffset::east_opt(2 * 3600).unwrap(),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let notify = |message: &str| {
+            send_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                NotificationLevel::Critical,
+                message.to_string(),
+            )
+        };
+
+        notify("plain").await.unwrap();
+        run_command(
+            &telegram,
+            &state,
+            "/timestamps on",
+            Command::Timestamps("on".to_string()),
+        )
+        .await;
+        notify("stamped").await.unwrap();
+        notify(&"y".repeat(TELEGRAM_MAX_MESSAGE_LENGTH))
+            .await
+            .unwrap();
+
+        let texts = telegram.sent_texts();
+        assert_eq!(texts[0], "<pre>plain</pre>");
+        assert_eq!(
+            texts[1],
+            "Notifications now start with the time they were sent"
+        );
+        assert_eq!(texts[2], "<pre>2024-03-09T11:05:00+02:00 stamped</pre>");
+        let chunks = &texts[3..];
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|chunk| {
+            chunk.starts_with("<pre>2024-03-09T11:05:00+02:00 ")
+                && chunk.chars().count() <= TELEGRAM_MAX_MESSAGE_LENGTH
+        }));
+    }
+
+    #[test]
+    fn queue_guards_count_while_alive() {
+        let queue = CommandQueue::default();
+        let first = queue.start();
+        let second = queue.start();
+        assert_eq!(queue.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(queue.in_flight(), 1);
+        drop(second);
+        assert_eq!(queue.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn queue_counts_the_command_being_handled() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(CHAT, "/queue"),
+                Command::Queue,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            telegram.sent_texts(),
+            vec!["Commands in flight: 1 (including this one)\nCommands waiting: 0"]
+        );
+        assert_eq!(handler.command_queue.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn queue_counts_commands_waiting_for_the_handler() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let (handler, _request_rx) = TelegramBotHandler::new();
+        let dispatcher = Arc::new(CommandDispatcher::new(handler));
+
+        let busy = dispatcher.handler().lock().await;
+        let sent: Vec<_> = (0..3)
+            .map(|_| {
+                let dispatcher = Arc::clone(&dispatcher);
+                let bot = telegram.bot.clone();
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    dispatcher
+                        .dispatch(bot, command_message(CHAT, "/queue"), Command::Queue, state)
+                        .await
+                })
+            })
+            .collect();
+        for _ in 0..300 {
+            if dispatcher.queue.waiting() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(dispatcher.queue.waiting(), 3);
+        assert_eq!(dispatcher.queue.in_flight(), 0);
+
+        drop(busy);
+        for task in sent {
+            task.await.unwrap().unwrap();
+        }
+
+        let mut texts = telegram.sent_texts();
+        texts.sort();
+        assert_eq!(
+            texts,
+            vec![
+                "Commands in flight: 1 (including this one)\nCommands waiting: 0",
+                "Commands in flight: 1 (including this one)\nCommands waiting: 1",
+                "Commands in flight: 1 (including this one)\nCommands waiting: 2",
+            ]
+        );
+        assert_eq!(dispatcher.queue.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn configmtime_reports_when_the_file_was_written() {
+        let path = write_config(
+            "configmtime",
+            &[symbol_config("BTCUSDT"), symbol_config("ETHUSDT")],
+        );
+        let written = utc(10, 30);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(written.into())
+            .unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let info = config_file_info(&path).await.unwrap().unwrap();
+        assert_eq!(
+            info,
+            ConfigFileInfo {
+                modified: written,
+                size,
+                symbols: Some(2),
+            }
+        );
+
+        let telegram = FakeTelegram::start().await;
+        let state = state_with_config(&path);
+        state.lock().await.time_zone = FixedOffset::east_opt(2 * 3600).unwrap();
+        let texts = run_command(&telegram, &state, "/configmtime", Command::ConfigMtime).await;
+        assert_eq!(
+            texts,
+            vec![format!(
+                "{}\nModified: 2024-03-09 12:30:00 +02:00\nSize: {} bytes\nSymbols: 2",
+                path.display(),
+                size
+            )]
+        );
+        remove_config(&path);
+    }
+
+    #[tokio::test]
+    async fn configmtime_reports_a_missing_file() {
+        let path = temp_path("configmtime-missing.json");
+        assert_eq!(config_file_info(&path).await.unwrap(), None);
+
+        let telegram = FakeTelegram::start().await;
+        let texts = run_command(
+            &telegram,
+            &state_with_config(&path),
+            "/configmtime",
+            Command::ConfigMtime,
+        )
+        .await;
+        assert_eq!(
+            texts,
+            vec![format!("Config file {} does not exist.", path.display())]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribed_chats_receive_only_critical_notifications() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("subscribe", &[symbol_config("BTCUSDT")]);
+        let state = state_with_config(&path);
+        let member = ChatId(7);
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(member, "/subscribe"),
+                Command::Subscribe,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Important,
+            "routine fill".to_string(),
+        )
+        .await
+        .unwrap();
+        broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "exchange down".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let received = |chat_id: ChatId| -> Vec<String> {
+            telegram
+                .calls()
+                .iter()
+                .filter(|call| call.chat_id() == Some(chat_id))
+                .map(|call| call.text().to_string())
+                .collect()
+        };
+        let member_texts = received(member);
+        assert_eq!(member_texts.len(), 2, "{:?}", member_texts);
+        assert_eq!(
+            member_texts[0],
+            "Subscribed. This chat will receive critical notifications."
+        );
+        assert!(
+            member_texts[1].contains("exchange down"),
+            "{:?}",
+            member_texts
+        );
+        assert_eq!(received(CHAT).len(), 2);
+
+        // The subscription outlives a restart
+        let restarted = state_with_config(&path);
+        restore_subscriptions(&restarted).await.unwrap();
+        assert_eq!(restarted.lock().await.subscribers, vec![member]);
+        assert_eq!(
+            restarted.lock().await.notification_targets,
+            vec![CHAT, member]
+        );
+
+        handler
+            .handle_command(
+                telegram.bot.clone(),
+                command_message(member, "/unsubscribe"),
+                Command::Unsubscribe,
+                Arc::clone(&state),
+            )
+            .await
+            .unwrap();
+        assert_eq!(state.lock().await.notification_targets, vec![CHAT]);
+        let restarted = state_with_config(&path);
+        restore_subscriptions(&restarted).await.unwrap();
+        assert!(restarted.lock().await.subscribers.is_empty());
+        remove_config(&path);
+    }
+
+    /// A config file written before `enabled`, `max_slippage` and
+    /// `size_step` existed, next to one that already has them all
+    const OLD_SCHEMA_CONFIG: &str = r#"[
+        {"symbol": "BTCUSDT", "entry_amount": 10.0, "exit_amount": 10.0,
+         "entry_threshold": 1.0, "exit_threshold": 1.0},
+        {"symbol": "ETHUSDT", "entry_amount": 5.0, "exit_amount": 5.0,
+         "entry_threshold": 2.0, "exit_threshold": 2.0, "enabled": false,
+         "max_slippage": 0.5, "size_step": 0.01}
+    ]"#;
+
+    #[test]
+    fn migrate_config_fills_in_missing_fields_with_defaults() {
+        let migration = migrate_config(OLD_SCHEMA_CONFIG).unwrap();
+
+        assert_eq!(
+            migration.changes,
+            vec!["BTCUSDT: enabled, max_slippage, size_step"]
+        );
+        let btc = &migration.symbols[0];
+        assert!(btc.enabled);
+        assert_eq!((btc.max_slippage, btc.size_step), (None, None));
+        let eth = &migration.symbols[1];
+        assert!(!eth.enabled);
+        assert_eq!((eth.max_slippage, eth.size_step), (Some(0.5), Some(0.01)));
+    }
+
+    #[test]
+    fn migrate_config_names_the_entry_it_cannot_read() {
+        let error = migrate_config(r#"[{"symbol": "BTCUSDT"}]"#).unwrap_err();
+        assert!(describe_error(&error, false).starts_with("Invalid entry 1: missing field"));
+    }
+
+    #[tokio::test]
+    async fn migrate_rewrites_an_old_config_after_backing_it_up() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("migrate", &[]);
+        std::fs::write(&path, OLD_SCHEMA_CONFIG).unwrap();
+        let state = state_with_config(&path);
+
+        let texts = run_command(&telegram, &state, "/migrate", Command::Migrate).await;
+        let backups = list_backups(&path).await.unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&backups[0]).unwrap(),
+            OLD_SCHEMA_CONFIG
+        );
+        assert_eq!(
+            texts.last().unwrap(),
+            &format!(
+                "Migrated 1 symbol(s), adding defaults for:\nBTCUSDT: enabled, max_slippage, \
+                 size_step\nBackup saved to {}",
+                backups[0].display()
+            )
+        );
+        let written: Vec<Value> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written[0]["enabled"], json!(true));
+        assert!(written[0].get("size_step").is_some());
+
+        let texts = run_command(&telegram, &state, "/migrate", Command::Migrate).await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "The config file already uses the latest schema."
+        );
+        assert_eq!(list_backups(&path).await.unwrap().len(), 1);
+        remove_config(&path);
+    }
+
+    #[tokio::test]
+    async fn startbot_waits_out_the_breaker_cooldown() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(10, 0));
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            clock: clock.clock(),
+            ..Default::default()
+        });
+
+        run_command(
+            &telegram,
+            &state,
+            "/breakercooldown 30m",
+            Command::BreakerCooldown("30m".to_string()),
+        )
+        .await;
+        {
+            let mut state = state.lock().await;
+            assert!(state.circuit_breaker.trip(utc(10, 0)));
+            assert!(state
+                .circuit_breaker
+                .acknowledge("@ops".to_string(), utc(10, 5)));
+        }
+
+        clock.set(utc(10, 10));
+        run_command(
+            &telegram,
+            &state,
+            "/breakercooldown",
+            Command::BreakerCooldown(String::new()),
+        )
+        .await;
+        let texts = run_command(&telegram, &state, "/startbot", Command::StartBot).await;
+        assert!(!state.lock().await.is_running);
+
+        clock.set(utc(10, 30));
+        run_command(&telegram, &state, "/startbot", Command::StartBot).await;
+        assert!(state.lock().await.is_running);
+
+        assert_eq!(
+            texts,
+            vec![
+                "Breaker cooldown set to 30m 0s",
+                "Breaker cooldown: 30m 0s (20m 0s left)",
+                "The circuit breaker is cooling down, 20m 0s remaining.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn symbolnoise_ranks_symbols_by_their_notifications() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            notification_level: NotificationLevel::All,
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        for symbol in [
+            "ETHUSDT", "BTCUSDT", "SOLUSDT", "ETHUSDT", "BTCUSDT", "ETHUSDT",
+        ] {
+            send_symbol_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                symbol,
+                NotificationLevel::Important,
+                format!("{} filled", symbol),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            state.lock().await.symbol_noise(),
+            vec![
+                ("ETHUSDT".to_string(), 3),
+                ("BTCUSDT".to_string(), 2),
+                ("SOLUSDT".to_string(), 1),
+            ]
+        );
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbolnoise",
+            Command::SymbolNoise(String::new()),
+        )
+        .await;
+        let table = texts.last().unwrap();
+        let rows: Vec<usize> = ["ETHUSDT", "BTCUSDT", "SOLUSDT"]
+            .iter()
+            .map(|symbol| table.find(symbol).unwrap())
+            .collect();
+        assert!(rows.windows(2).all(|pair| pair[0] < pair[1]), "{}", table);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbolnoise reset",
+            Command::SymbolNoise("reset".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Symbol notification counts reset.");
+        assert!(state.lock().await.symbol_noise().is_empty());
+    }
+
+    #[test]
+    fn symbol_errors_count_consecutive_ticks() {
+        let mut state = BotState {
+            symbol_error_limit: 3,
+            ..Default::default()
+        };
+        let errors = |symbols: &[&str]| -> BTreeMap<String, String> {
+            symbols
+                .iter()
+                .map(|symbol| (symbol.to_string(), "rejected".to_string()))
+                .collect()
+        };
+
+        assert!(state
+            .record_symbol_errors(&errors(&["BTC", "ETH"]))
+            .is_empty());
+        assert!(state.record_symbol_errors(&errors(&["ETH"])).is_empty());
+        assert_eq!(state.symbol_errors, HashMap::from([("ETH".to_string(), 2)]));
+        assert_eq!(
+            state.record_symbol_errors(&errors(&["BTC", "ETH"])),
+            vec![("ETH".to_string(), "rejected".to_string())]
+        );
+        assert_eq!(state.symbol_errors, HashMap::from([("BTC".to_string(), 1)]));
+
+        state.symbol_error_limit = 0;
+        for _ in 0..5 {
+            assert!(state.record_symbol_errors(&errors(&["BTC"])).is_empty());
+        }
+    }
+
+    /// A strategy whose ETHUSDT trades fail on its first two ticks
+    struct SymbolErrorBot {
+        ticks: usize,
+    }
+
+    #[async_trait]
+    impl TradingBot for SymbolErrorBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(SymbolErrorBot { ticks: 0 })
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            self.ticks += 1;
+            Ok(())
+        }
+
+        async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+            Ok(StatusReport::default())
+        }
+
+        async fn reload_symbols(&mut self, _symbols: Vec<SymbolConfig>) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn symbol_errors(&self) -> BTreeMap<String, String> {
+            match self.ticks {
+                1 | 2 => BTreeMap::from([("ETHUSDT".to_string(), "market closed".to_string())]),
+                _ => BTreeMap::new(),
+            }
+        }
+    }
+
+    /// Run `SymbolErrorBot` against a config of BTCUSDT and ETHUSDT until
+    /// the runner disables ETHUSDT, returning the alert it sent
+    async fn disable_failing_symbol_with(
+        name: &str,
+        configure: impl FnOnce(&mut BotState),
+    ) -> (Arc<Mutex<BotState>>, TelegramBotHandler, PathBuf, String) {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(name, &[symbol_config("BTCUSDT"), symbol_config("ETHUSDT")]);
+        let mut state = BotState {
+            is_running: true,
+            config_path: Some(path.to_string_lossy().into_owned()),
+            notification_targets: vec![CHAT],
+            notification_level: NotificationLevel::All,
+            symbol_error_limit: 2,
+            ..Default::default()
+        };
+        configure(&mut state);
+        let state = shared(state);
+        let handler = start_runner_every::<SymbolErrorBot>(&telegram, &state, 1).await;
+
+        let calls = telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("ETHUSDT disabled after"))
+            })
+            .await;
+        state.lock().await.is_running = false;
+        let alert = calls
+            .iter()
+            .map(|call| call.text().to_string())
+            .find(|text| text.contains("ETHUSDT disabled after"))
+            .unwrap();
+        (state, handler, path, alert)
+    }
+
+    #[tokio::test]
+    async fn failing_symbols_are_disabled_in_the_file_after_a_backup() {
+        let (_state, _handler, path, alert) =
+            disable_failing_symbol_with("symbol-errors-file", |_| {}).await;
+
+        assert!(
+            alert
+                .contains("ETHUSDT disabled after 2 consecutive errors. Last error: market closed"),
+            "{}",
+            alert
+        );
+        let backups = list_backups(&path).await.unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(alert.contains(&format!("Backup saved to {}", backups[0].display())));
+        assert!(read_config(&backups[0]).iter().all(|symbol| symbol.enabled));
+        assert_eq!(
+            read_config(&path)
+                .iter()
+                .map(|symbol| symbol.enabled)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        remove_config(&path);
+    }
+
+    #[tokio::test]
+    async fn failing_symbols_are_disabled_in_memory_while_persist_is_off() {
+        let (state, handler, path, alert) =
+            disable_failing_symbol_with("symbol-errors-memory", |state| {
+                state.persist_changes = false;
+            })
+            .await;
+
+        assert!(
+            alert.contains("Kept in memory; the file is unchanged."),
+            "{}",
+            alert
+        );
+        assert!(read_config(&path).iter().all(|symbol| symbol.enabled));
+        assert!(state.lock().await.unsaved_changes);
+        let in_memory = handler
+            .request(BotRequest::GetSymbolOverride)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            in_memory
+                .iter()
+                .map(|symbol| symbol.enabled)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        remove_config(&path);
+    }
+
+    #[tokio::test]
+    async fn failing_symbols_are_disabled_in_the_staged_copy() {
+        let (state, _handler, path, alert) =
+            disable_failing_symbol_with("symbol-errors-staged", |state| {
+                state.staged_symbols =
+                    Some(vec![symbol_config("BTCUSDT"), symbol_config("ETHUSDT")]);
+            })
+            .await;
+
+        assert!(
+            alert.contains("Staged; use /apply to save it."),
+            "{}",
+            alert
+        );
+        assert!(read_config(&path).iter().all(|symbol| symbol.enabled));
+        let state = state.lock().await;
+        let staged = state.staged_symbols.as_ref().unwrap();
+        assert_eq!(
+            staged
+                .iter()
+                .map(|symbol| symbol.enabled)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert_eq!(state.staged_changes, 1);
+        remove_config(&path);
+    }
+
+    /// The /effectivelevel reply at 10:00 for a state `configure` sets up
+    async fn effective_level_reply(configure: impl FnOnce(&mut BotState)) -> String {
+        let telegram = FakeTelegram::start().await;
+        let mut state = BotState {
+            notification_targets: vec![CHAT],
+            notification_level: NotificationLevel::All,
+            clock: FakeClock::at(utc(10, 0)).clock(),
+            ..Default::default()
+        };
+        configure(&mut state);
+        let texts = run_command(
+            &telegram,
+            &shared(state),
+            "/effectivelevel",
+            Command::EffectiveLevel,
+        )
+        .await;
+        texts.last().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn effectivelevel_explains_each_override() {
+        assert_eq!(
+            effective_level_reply(|_| {}).await,
+            "Configured level: All\nEffective level: All\nNo override is active."
+        );
+        assert_eq!(
+            effective_level_reply(|state| state.quiet_until = Some(utc(10, 10))).await,
+            "Configured level: All\nEffective level: None\nBecause /quiet is on for 10m 0s more"
+        );
+        assert_eq!(
+            effective_level_reply(|state| state.warmup_until = Some(utc(10, 5))).await,
+            "Configured level: All\nEffective level: Critical\nBecause warmup allows only \
+             critical notifications for 5m 0s more"
+        );
+
+        let schedule = parse_level_schedule("08:00-12:00=important").unwrap();
+        let window = schedule[0].to_string();
+        assert_eq!(
+            effective_level_reply(|state| state.level_schedule = schedule).await,
+            format!(
+                "Configured level: All\nEffective level: Important\nBecause schedule window {} is \
+                 active",
+                window
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn effectivelevel_names_the_override_that_wins() {
+        // /quiet outranks the warmup, which outranks the schedule
+        assert!(effective_level_reply(|state| {
+            state.quiet_until = Some(utc(10, 10));
+            state.warmup_until = Some(utc(10, 5));
+        })
+        .await
+        .ends_with("Because /quiet is on for 10m 0s more"));
+        assert!(effective_level_reply(|state| {
+            state.warmup_until = Some(utc(10, 5));
+            state.level_schedule = parse_level_schedule("08:00-12:00=important").unwrap();
+        })
+        .await
+        .contains("Effective level: Critical\nBecause warmup"));
+        // A schedule that silences everything is not raised to Critical
+        assert!(effective_level_reply(|state| {
+            state.warmup_until = Some(utc(10, 5));
+            state.level_schedule = parse_level_schedule("08:00-12:00=none").unwrap();
+        })
+        .await
+        .contains("Effective level: None\nBecause schedule window"));
+    }
+
+    #[tokio::test]
+    async fn exchange_reports_the_measured_latency() {
+        let replies = run_commands::<CustomFormatBot>(vec![("/exchange", Command::Exchange)]).await;
+        assert_eq!(replies, vec!["Exchange latency: 42.0 ms"]);
+    }
+
+    #[tokio::test]
+    async fn exchange_reports_why_it_is_unreachable() {
+        let replies = run_commands::<ErrorBot>(vec![("/exchange", Command::Exchange)]).await;
+        assert_eq!(replies, vec!["Exchange is unreachable: connection refused"]);
+    }
+
+    #[test]
+    fn symbols_page_slices_the_requested_page() {
+        let symbols: Vec<SymbolConfig> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|symbol| symbol_config(symbol))
+            .collect();
+
+        let (first, pages) = symbols_page(&symbols, 1, 2).unwrap();
+        assert_eq!((symbol_names(first), pages), (vec!["A", "B"], 3));
+        let (last, _) = symbols_page(&symbols, 3, 2).unwrap();
+        assert_eq!(symbol_names(last), vec!["E"]);
+        let (all, pages) = symbols_page(&symbols, 1, 20).unwrap();
+        assert_eq!((all.len(), pages), (5, 1));
+
+        for page in [0, 4] {
+            assert_eq!(
+                symbols_page(&symbols, page, 2).unwrap_err(),
+                format!("Page {} does not exist. There are 3 pages.", page)
+            );
+        }
+    }
+
+    #[test]
+    fn an_empty_symbol_list_has_one_empty_page() {
+        let (entries, pages) = symbols_page(&[], 1, 20).unwrap();
+        assert_eq!((entries.len(), pages), (0, 1));
+        assert_eq!(
+            symbols_page(&[], 2, 20).unwrap_err(),
+            "Page 2 does not exist. There is 1 page."
+        );
+    }
+
+    #[tokio::test]
+    async fn symbols_shows_one_page_with_navigation_hints() {
+        let telegram = FakeTelegram::start().await;
+        let symbols: Vec<SymbolConfig> = ["AAAUSDT", "BBBUSDT", "CCCUSDT", "DDDUSDT", "EEEUSDT"]
+            .iter()
+            .map(|symbol| symbol_config(symbol))
+            .collect();
+        let path = write_config("symbols-pages", &symbols);
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
+
+        let texts = run_command(&telegram, &state, "/pagesize 2", Command::PageSize(2)).await;
+        assert_eq!(texts.last().unwrap(), "Page size set to 2 symbols");
+        let before = texts.len();
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbols 2",
+            Command::Symbols("2".to_string()),
+        )
+        .await;
+        let [table, footer] = &texts[before..] else {
+            panic!("expected a table and a footer: {:?}", texts);
+        };
+        assert!(
+            table.contains("CCCUSDT") && table.contains("DDDUSDT"),
+            "{}",
+            table
+        );
+        assert!(
+            !table.contains("BBBUSDT") && !table.contains("EEEUSDT"),
+            "{}",
+            table
+        );
+        assert_eq!(
+            footer,
+            "Page 2 of 3\nPrevious: /symbols 1\nNext: /symbols 3"
+        );
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbols 4",
+            Command::Symbols("4".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Page 4 does not exist. There are 3 pages."
+        );
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbols last",
+            Command::Symbols("last".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Usage: /symbols [PAGE]");
+        remove_config(&path);
+    }
+
+    #[tokio::test]
+    async fn pagesize_rejects_sizes_out_of_range() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState::new());
+        state.lock().await.command_cooldown_secs = 0;
+
+        for size in [0, MAX_SYMBOLS_PAGE_SIZE + 1] {
+            let texts = run_command(&telegram, &state, "/pagesize", Command::PageSize(size)).await;
+            assert_eq!(
+                texts.last().unwrap(),
+                "Invalid page size. Use a value between 1 and 200."
+            );
+        }
+        assert_eq!(
+            state.lock().await.symbols_page_size,
+            DEFAULT_SYMBOLS_PAGE_SIZE
+        );
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/pagesize",
+            Command::PageSize(DEFAULT_SYMBOLS_PAGE_SIZE),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Page size already set to 20 symbols");
+    }
+
+    const CLAIM_NOTICE: &str = "No notification target was configured, so this chat will receive \
+                                notifications. Use /unsubscribe to stop them.";
+
+    #[tokio::test]
+    async fn the_first_chat_to_use_the_bot_becomes_the_notification_target() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+
+        let texts = run_command(&telegram, &state, "/help", Command::Help).await;
+        assert_eq!(texts[0], CLAIM_NOTICE);
+        assert_eq!(state.lock().await.notification_targets, vec![CHAT]);
+        let texts = run_command(&telegram, &state, "/help", Command::Help).await;
+        assert_eq!(texts.iter().filter(|text| *text == CLAIM_NOTICE).count(), 1);
+
+        broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "margin call".to_string(),
+        )
+        .await
+        .unwrap();
+        let calls = telegram.calls();
+        let notification = calls.last().unwrap();
+        assert_eq!(notification.chat_id(), Some(CHAT));
+        assert!(notification.text().contains("margin call"));
+    }
+
+    #[tokio::test]
+    async fn an_unsubscribed_chat_is_not_claimed_again() {
+        let telegram = FakeTelegram::start().await;
+        // Without a config path /unsubscribe has nowhere to save the
+        // subscribers, so the test leaves no file behind
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            config_path: None,
+            ..BotState::new()
+        });
+
+        run_command(&telegram, &state, "/help", Command::Help).await;
+        run_command(&telegram, &state, "/unsubscribe", Command::Unsubscribe).await;
+        let texts = run_command(&telegram, &state, "/help", Command::Help).await;
+
+        assert!(state.lock().await.notification_targets.is_empty());
+        assert_eq!(texts.iter().filter(|text| *text == CLAIM_NOTICE).count(), 1);
+    }
+
+    #[test]
+    fn a_target_set_by_the_runner_is_never_replaced_by_a_claim() {
+        let mut state = BotState::new();
+        assert!(state.add_notification_target(CHAT));
+        assert!(!state.add_notification_target(CHAT));
+        state.remove_notification_target(CHAT);
+
+        assert!(!state.claim_default_target(ChatId(7)));
+        assert!(state.notification_targets.is_empty());
+    }
+
+    fn setting_source(state: &BotState, name: &str) -> (String, SettingSource) {
+        let setting = state
+            .effective_settings()
+            .into_iter()
+            .find(|setting| setting.name == name)
+            .unwrap();
+        (setting.value, setting.source)
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn the_environment_overrides_the_settings_file() {
+        let path = temp_path("settings-overlap.json");
+        std::fs::write(
+            &path,
+            r#"{"notification_level": "critical", "dry_run": true, "warmup": "5m"}"#,
+        )
+        .unwrap();
+        let mut state = BotState::new();
+
+        let loaded = state.load_settings_file(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+        let loaded = state
+            .load_settings_env(env(&[
+                ("TELEGRAM_BOT_NOTIFICATION_LEVEL", "all"),
+                ("TELEGRAM_BOT_TOKEN", "not a setting"),
+                ("NOTIFICATION_LEVEL", "none"),
+            ]))
+            .unwrap();
+        assert_eq!(loaded, vec!["notification_level"]);
+
+        assert_eq!(
+            setting_source(&state, "notification_level"),
+            ("All".to_string(), SettingSource::Env)
+        );
+        assert_eq!(
+            setting_source(&state, "dry_run"),
+            ("true".to_string(), SettingSource::File)
+        );
+        assert_eq!(
+            setting_source(&state, "warmup"),
+            ("5m 0s".to_string(), SettingSource::File)
+        );
+        assert_eq!(
+            setting_source(&state, "summary_mode"),
+            ("false".to_string(), SettingSource::Default)
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn invalid_settings_are_not_applied() {
+        let path = temp_path("settings-invalid.json");
+        std::fs::write(
+            &path,
+            r#"{"dry_run": true, "send_timeout_secs": 0, "colour": "blue"}"#,
+        )
+        .unwrap();
+        let mut state = BotState::new();
+
+        let error = state.load_settings_file(&path).unwrap_err().to_string();
+        assert_eq!(error, "Invalid settings file: colour: unknown setting");
+        std::fs::write(&path, r#"{"dry_run": true, "send_timeout_secs": 0}"#).unwrap();
+        let error = state.load_settings_file(&path).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid file settings: send_timeout_secs: '0' is not a number between 1 and 120"
+        );
+        let error = state
+            .load_settings_env(env(&[
+                ("TELEGRAM_BOT_SUMMARY_MODE", "on"),
+                ("TELEGRAM_BOT_STOP_MODE", "sideways"),
+            ]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Invalid env settings: stop_mode: "),
+            "{}",
+            error
+        );
+
+        assert!(!state.dry_run && !state.summary_mode);
+        assert_eq!(setting_source(&state, "dry_run").1, SettingSource::Default);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn commands_take_precedence_over_loaded_settings() {
+        let telegram = FakeTelegram::start().await;
+        let mut state = BotState {
+            command_cooldown_secs: 0,
+            summary_mode: true,
+            ..BotState::new()
+        };
+        state
+            .load_settings_env(env(&[("TELEGRAM_BOT_NOTIFICATION_LEVEL", "critical")]))
+            .unwrap();
+        let state = shared(state);
+
+        // Setting the default value still counts as the command's doing
+        run_command(
+            &telegram,
+            &state,
+            "/notify important",
+            Command::Notify("important".to_string()),
+        )
+        .await;
+        {
+            let state = state.lock().await;
+            assert_eq!(
+                setting_source(&state, "notification_level"),
+                ("Important".to_string(), SettingSource::Runtime)
+            );
+            assert_eq!(
+                setting_source(&state, "summary_mode").1,
+                SettingSource::Application
+            );
+        }
+
+        run_command(
+            &telegram,
+            &state,
+            "/resetsettings confirm",
+            Command::ResetSettings("confirm".to_string()),
+        )
+        .await;
+        let state = state.lock().await;
+        for setting in state.effective_settings() {
+            assert_eq!(setting.source, SettingSource::Default, "{}", setting.name);
+        }
+    }
+
+    #[test]
+    fn notification_history_keeps_the_latest_in_order() {
+        let mut state = BotState::new();
+        for i in 0..MAX_NOTIFICATION_HISTORY + 2 {
+            state.record_notification(utc(12, 0), NotificationLevel::All, &format!("n{}", i));
+            // A broadcast records the same notification once per chat
+            state.record_notification(utc(12, 0), NotificationLevel::All, &format!("n{}", i));
+        }
+
+        assert_eq!(state.notification_history.len(), MAX_NOTIFICATION_HISTORY);
+        let recent: Vec<String> = state
+            .recent_notifications(3)
+            .into_iter()
+            .map(|record| record.message)
+            .collect();
+        assert_eq!(recent, ["n49", "n50", "n51"]);
+        assert_eq!(
+            state.recent_notifications(100).first().unwrap().message,
+            "n2"
+        );
+    }
+
+    #[tokio::test]
+    async fn replaynotify_resends_the_latest_notifications_in_order() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            max_message_length: MIN_MESSAGE_LENGTH,
+            clock: clock.clock(),
+            ..BotState::new()
+        });
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/replaynotify 2",
+            Command::ReplayNotify(2),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "No notifications sent yet.");
+        let long = "x".repeat(MIN_MESSAGE_LENGTH + 10);
+        for (minute, message) in [
+            (1, "first".to_string()),
+            (2, "second".to_string()),
+            (3, long),
+        ] {
+            clock.set(utc(12, minute));
+            broadcast_notification(&telegram.bot, &state, NotificationLevel::All, message)
+                .await
+                .unwrap();
+        }
+
+        let before = telegram.sent_texts().len();
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/replaynotify 2",
+            Command::ReplayNotify(2),
+        )
+        .await;
+        let replayed = &texts[before..];
+        assert_eq!(replayed[0], "(archived 2024-03-09 12:02:00) second");
+        assert!(replayed[1].starts_with("(archived 2024-03-09 12:03:00) xxx"));
+        assert!(replayed.len() > 2, "{:?}", replayed);
+        assert!(replayed.iter().all(|text| text.len() <= MIN_MESSAGE_LENGTH));
+        assert_eq!(
+            replayed[1..].concat().matches('x').count(),
+            MIN_MESSAGE_LENGTH + 10
+        );
+
+        for count in [0, MAX_NOTIFICATION_HISTORY + 1] {
+            let texts = run_command(
+                &telegram,
+                &state,
+                "/replaynotify",
+                Command::ReplayNotify(count),
+            )
+            .await;
+            assert_eq!(
+                texts.last().unwrap(),
+                "Invalid count. Use a value between 1 and 50."
+            );
+        }
+    }
+
+    #[test]
+    fn trading_days_begin_at_the_reset_hour() {
+        assert_eq!(trading_day_start(utc(7, 0), 6), utc(6, 0));
+        assert_eq!(trading_day_start(utc(6, 0), 6), utc(6, 0));
+        assert_eq!(
+            trading_day_start(utc(5, 59), 6),
+            utc(6, 0) - chrono::Duration::days(1)
+        );
+        assert_eq!(trading_day_start(utc(23, 0), 0), utc(0, 0));
+    }
+
+    #[tokio::test]
+    async fn entries_are_blocked_at_the_cap_until_the_day_resets() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(7, 0));
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            max_daily_trades: 2,
+            trade_reset_hour: 6,
+            clock: clock.clock(),
+            ..BotState::new()
+        });
+
+        let mut allowed = Vec::new();
+        for _ in 0..3 {
+            allowed.push(check_trade_cap(&telegram.bot, CHAT, &state, "BTCUSDT").await);
+        }
+        assert_eq!(allowed, [true, true, false]);
+        let alerts: Vec<String> = telegram
+            .sent_texts()
+            .into_iter()
+            .filter(|text| text.contains("Daily trade cap"))
+            .collect();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("Daily trade cap of 2 reached with BTCUSDT"));
+
+        // Still the same trading day just before the reset hour
+        clock.set(utc(6, 0) + chrono::Duration::hours(23) + chrono::Duration::minutes(59));
+        assert!(!check_trade_cap(&telegram.bot, CHAT, &state, "BTCUSDT").await);
+        clock.set(utc(6, 0) + chrono::Duration::days(1));
+        assert!(check_trade_cap(&telegram.bot, CHAT, &state, "BTCUSDT").await);
+        assert_eq!(state.lock().await.daily_trades, 1);
+    }
+
+    #[tokio::test]
+    async fn maxtrades_and_traderesethour_report_the_cap() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(7, 0));
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            clock: clock.clock(),
+            ..BotState::new()
+        });
+        state.lock().await.reserve_trade(utc(7, 0));
+
+        let commands = [
+            (
+                "/maxtrades 3",
+                Command::MaxTrades(3),
+                "Daily trade cap set to 3 (1 entries so far today)",
+            ),
+            (
+                "/maxtrades 3",
+                Command::MaxTrades(3),
+                "Daily trade cap already set to 3",
+            ),
+            (
+                "/maxtrades 0",
+                Command::MaxTrades(0),
+                "Daily trade cap removed.",
+            ),
+            (
+                "/traderesethour 24",
+                Command::TradeResetHour(24),
+                "Invalid hour. Use a value between 0 and 23.",
+            ),
+            (
+                "/traderesethour 6",
+                Command::TradeResetHour(6),
+                "Daily trade count now resets at 06:00 UTC",
+            ),
+        ];
+        for (text, command, reply) in commands {
+            let texts = run_command(&telegram, &state, text, command).await;
+            assert_eq!(texts.last().unwrap(), reply, "{}", text);
+        }
+        assert_eq!(state.lock().await.trade_reset_hour, 6);
+    }
+
+    #[test]
+    fn escape_preview_escapes_for_each_parse_mode() {
+        assert_eq!(
+            escape_preview("BTC_USDT *up* 2.5% [x](y) <b>&"),
+            "MarkdownV2:\nBTC\\_USDT \\*up\\* 2\\.5% \\[x\\]\\(y\\) <b\\>&\n\nHTML:\nBTC_USDT \
+             *up* 2.5% [x](y) &lt;b&gt;&amp;"
+        );
+    }
+
+    #[tokio::test]
+    async fn escape_shows_both_escapings_or_its_usage() {
+        let replies = run_commands::<CustomFormatBot>(vec![
+            ("/escape a-b", Command::Escape("a-b".to_string())),
+            ("/escape", Command::Escape(" ".to_string())),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            ["MarkdownV2:\na\\-b\n\nHTML:\na-b", "Usage: /escape TEXT"]
+        );
+    }
+
+    /// Attempts made by `RetryBot`, `RetryPanicBot` and `RetryStopBot`
+    static RETRY_BOT_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    static RETRY_PANIC_BOT_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    static RETRY_STOP_BOT_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A strategy whose first two attempts fail
+    struct RetryBot;
+
+    #[async_trait]
+    impl TradingBot for RetryBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(RetryBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            if RETRY_BOT_ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err("order rejected".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// A strategy that panics on every attempt
+    struct RetryPanicBot;
+
+    #[async_trait]
+    impl TradingBot for RetryPanicBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(RetryPanicBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            RETRY_PANIC_BOT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            panic!("strategy blew up");
+        }
+    }
+
+    /// A strategy that fails after a graceful stop was asked for mid-tick
+    struct RetryStopBot;
+
+    #[async_trait]
+    impl TradingBot for RetryStopBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(RetryStopBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            RETRY_STOP_BOT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            bot_state.lock().await.is_running = false;
+            Err("order rejected".to_string())
+        }
+    }
+
+    fn retrying_state(retries: u32) -> Arc<Mutex<BotState>> {
+        shared(BotState {
+            is_running: true,
+            notification_targets: vec![CHAT],
+            strategy_retries: retries,
+            strategy_retry_delay: Duration::ZERO,
+            ..BotState::new()
+        })
+    }
+
+    /// Wait until `attempts` reaches `count`, then long enough for any
+    /// further retry to have run
+    async fn wait_for_attempts(attempts: &AtomicUsize, count: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while attempts.load(Ordering::SeqCst) < count {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_are_retried_within_the_tick() {
+        let telegram = FakeTelegram::start().await;
+        let state = retrying_state(2);
+        let _handler = start_runner_every::<RetryBot>(&telegram, &state, 1).await;
+        wait_for_attempts(&RETRY_BOT_ATTEMPTS, 3).await;
+
+        assert_eq!(RETRY_BOT_ATTEMPTS.load(Ordering::SeqCst), 3);
+        assert!(!telegram
+            .sent_texts()
+            .iter()
+            .any(|text| text.contains("order rejected")));
+        assert_eq!(state.lock().await.circuit_breaker.consecutive_failures, 0);
+        state.lock().await.is_running = false;
+    }
 
-                // Remove the symbol
-                let original_len = symbols.len();
-                symbols.retain(|s| s.symbol != symbol);
+    #[tokio::test]
+    async fn panics_are_not_retried() {
+        let telegram = FakeTelegram::start().await;
+        let state = retrying_state(3);
+        let _handler = start_runner_every::<RetryPanicBot>(&telegram, &state, 1).await;
+        telegram
+            .wait_for(|calls| {
+                calls
+                    .iter()
+                    .any(|call| call.text().contains("Strategy panicked"))
+            })
+            .await;
 
-                if symbols.len() == original_len {
-                    bot.send_message(chat_id, format!("Symbol '{}' not found.", symbol))
-                        .await?;
-                    return Ok(());
-                }
+        assert_eq!(RETRY_PANIC_BOT_ATTEMPTS.load(Ordering::SeqCst), 1);
+        state.lock().await.is_running = false;
+    }
 
-                // Write the updated content back to the file
-                if tokio::fs::write(config_path, serde_json::to_string_pretty(&symbols).unwrap())
-                    .await
-                    .is_err()
-                {
-                    bot.send_message(chat_id, "Failed to update symbols configuration.")
-                        .await?;
-                    return Ok(());
-                }
+    #[tokio::test]
+    async fn a_graceful_stop_ends_the_retries() {
+        let telegram = FakeTelegram::start().await;
+        let state = retrying_state(5);
+        let _handler = start_runner_every::<RetryStopBot>(&telegram, &state, 1).await;
+        wait_for_attempts(&RETRY_STOP_BOT_ATTEMPTS, 1).await;
 
-                bot.send_message(
-                    chat_id,
-                    format!("Symbol '{}' removed successfully.", symbol),
-                )
-                .await?;
+        assert_eq!(RETRY_STOP_BOT_ATTEMPTS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn strategy_retries_parse_a_count_and_an_optional_delay() {
+        assert_eq!(parse_strategy_retries("off"), Ok((0, None)));
+        assert_eq!(parse_strategy_retries("3"), Ok((3, None)));
+        assert_eq!(
+            parse_strategy_retries("3 5"),
+            Ok((3, Some(Duration::from_secs(5))))
+        );
+        assert_eq!(
+            parse_strategy_retries("11").unwrap_err(),
+            "Invalid retry count '11'. Use a value between 0 and 10."
+        );
+        assert_eq!(
+            parse_strategy_retries("3 31").unwrap_err(),
+            "Invalid delay '31'. Use 0 to 30 seconds."
+        );
+        assert_eq!(
+            parse_strategy_retries("1 2 3").unwrap_err(),
+            "Usage: /strategyretries COUNT [DELAY_SECS] or off"
+        );
+    }
+
+    fn order(id: &str, symbol: &str, side: &str, price: f64) -> OrderInfo {
+        OrderInfo {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            price,
+            quantity: 0.5,
+            status: "open".to_string(),
+        }
+    }
+
+    /// A strategy with `N` resting orders
+    struct OrderBot<const N: usize> {
+        orders: Vec<OrderInfo>,
+    }
+
+    #[async_trait]
+    impl<const N: usize> TradingBot for OrderBot<N> {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            let orders = [
+                order("o-1", "BTCUSDT", "buy", 60000.0),
+                order("o-2", "ETHUSDT", "sell", 3000.125),
+            ];
+            Ok(OrderBot {
+                orders: orders.into_iter().take(N).collect(),
+            })
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                orders: true,
+                ..Default::default()
             }
-            Err(_) => {
-                bot.send_message(
-                    chat_id,
-                    "Failed to read symbols configuration. Ensure the file exists.",
-                )
-                .await?;
+        }
+
+        async fn get_open_orders(&self) -> Result<Vec<OrderInfo>, Self::Error> {
+            Ok(self.orders.clone())
+        }
+
+        async fn cancel_all_orders(&mut self) -> Result<usize, Self::Error> {
+            Ok(std::mem::take(&mut self.orders).len())
+        }
+    }
+
+    #[test]
+    fn orders_table_lists_each_order() {
+        let table = orders_table(&[order("o-1", "BTCUSDT", "buy", 60000.0)]).to_string();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert!(
+            lines[1].contains("ID") && lines[1].contains("Status"),
+            "{}",
+            table
+        );
+        for cell in ["o-1", "BTCUSDT", "buy", "60000.0000", "0.5000", "open"] {
+            assert!(lines[3].contains(cell), "{} missing from {}", cell, table);
+        }
+    }
+
+    #[tokio::test]
+    async fn orders_shows_the_resting_orders() {
+        let replies = run_commands::<OrderBot<2>>(vec![("/orders", Command::Orders)]).await;
+
+        assert!(replies[0].contains("o-1") && replies[0].contains("BTCUSDT"));
+        assert!(replies[0].contains("ETHUSDT") && replies[0].contains("3000.1250"));
+    }
+
+    #[tokio::test]
+    async fn orders_reports_when_there_are_none() {
+        let replies = run_commands::<OrderBot<0>>(vec![("/orders", Command::Orders)]).await;
+
+        assert_eq!(replies, ["No open orders."]);
+    }
+
+    #[tokio::test]
+    async fn orders_reports_a_strategy_that_cannot_list_them() {
+        let replies = run_commands::<CustomFormatBot>(vec![("/orders", Command::Orders)]).await;
+
+        assert_eq!(
+            replies,
+            [
+                "Unable to retrieve orders from running bot: The strategy does not support \
+                 listing orders"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelall_cancels_every_order_after_confirmation() {
+        let replies = run_commands::<OrderBot<2>>(vec![
+            ("/cancelall", Command::CancelAll(String::new())),
+            (
+                "/cancelall confirm",
+                Command::CancelAll("confirm".to_string()),
+            ),
+            ("/orders", Command::Orders),
+            (
+                "/cancelall confirm",
+                Command::CancelAll("confirm".to_string()),
+            ),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            [
+                "This cancels every open order on the exchange. Run /cancelall confirm to proceed.",
+                "Cancelled 2 orders.",
+                "No open orders.",
+                "No open orders to cancel.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelall_reports_a_strategy_that_cannot_cancel() {
+        let replies = run_commands::<CustomFormatBot>(vec![(
+            "/cancelall confirm",
+            Command::CancelAll("confirm".to_string()),
+        )])
+        .await;
+
+        assert_eq!(
+            replies,
+            ["Unable to cancel orders: The strategy does not support cancelling orders"]
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelall_is_refused_while_the_bot_is_stopped() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/cancelall confirm",
+            Command::CancelAll("confirm".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Bot is not running.");
+    }
+
+    #[test]
+    fn quote_assets_are_read_from_the_symbol() {
+        let cases = [
+            ("BTCUSDT", Some("USDT")),
+            ("ethbusd", Some("BUSD")),
+            ("BTCFDUSD", Some("FDUSD")),
+            ("ETHBTC", Some("BTC")),
+            ("ETH/USD", Some("USD")),
+            ("SOL-EUR", Some("EUR")),
+            ("ABCXYZ", None),
+            ("USDT", None),
+            ("/USDT", None),
+        ];
+        for (symbol, quote) in cases {
+            assert_eq!(quote_asset(symbol).as_deref(), quote, "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn the_quote_filter_matches_the_quote_asset_exactly() {
+        let symbols: Vec<SymbolConfig> = ["BTCUSD", "ETHBUSD", "SOL/USD", "XRPUSDT"]
+            .iter()
+            .map(|symbol| symbol_config(symbol))
+            .collect();
+
+        assert_eq!(
+            symbol_names(&filter_by_quote(symbols.clone(), "usd")),
+            vec!["BTCUSD", "SOL/USD"]
+        );
+        assert_eq!(
+            symbol_names(&filter_by_quote(symbols, "BUSD")),
+            vec!["ETHBUSD"]
+        );
+    }
+
+    #[tokio::test]
+    async fn symbols_shows_only_the_filtered_quote_until_it_is_cleared() {
+        let telegram = FakeTelegram::start().await;
+        let symbols: Vec<SymbolConfig> = ["BTCUSD", "ETHBUSD", "SOLUSDT"]
+            .iter()
+            .map(|symbol| symbol_config(symbol))
+            .collect();
+        let path = write_config("symbols-quote", &symbols);
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/quote usd",
+            Command::Quote("usd".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Quote filter set to USD");
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbols",
+            Command::Symbols(String::new()),
+        )
+        .await;
+        let shown = texts.concat();
+        assert!(
+            shown.contains("BTCUSD") && !shown.contains("ETHBUSD"),
+            "{}",
+            shown
+        );
+        assert!(!shown.contains("SOLUSDT"), "{}", shown);
+        assert_eq!(
+            texts.last().unwrap(),
+            "Showing only symbols quoted in USD (/quote off shows all)"
+        );
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/quote off",
+            Command::Quote("off".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Quote filter removed.");
+        let before = texts.len();
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/symbols",
+            Command::Symbols(String::new()),
+        )
+        .await;
+        let shown = texts[before..].concat();
+        assert!(
+            shown.contains("ETHBUSD") && shown.contains("SOLUSDT"),
+            "{}",
+            shown
+        );
+        remove_config(&path);
+    }
+
+    #[test]
+    fn repeats_are_duplicates_only_within_the_window() {
+        let mut state = BotState {
+            dedup_window: Duration::from_secs(5 * 60),
+            ..BotState::new()
+        };
+
+        assert!(!state.is_duplicate_notification(CHAT, "fill", utc(12, 0)));
+        assert!(state.is_duplicate_notification(CHAT, "fill", utc(12, 4)));
+        assert!(!state.is_duplicate_notification(ChatId(7), "fill", utc(12, 4)));
+        assert!(!state.is_duplicate_notification(CHAT, "other", utc(12, 4)));
+        assert!(!state.is_duplicate_notification(CHAT, "fill", utc(12, 5)));
+
+        state.dedup_window = Duration::ZERO;
+        assert!(!state.is_duplicate_notification(CHAT, "fill", utc(12, 5)));
+    }
+
+    #[tokio::test]
+    async fn dedupwindow_changes_which_repeats_are_dropped() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(12, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::All,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            clock: clock.clock(),
+            ..BotState::new()
+        });
+        let notify = |level: NotificationLevel| {
+            send_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                level,
+                "order filled".to_string(),
+            )
+        };
+        let sent = || {
+            telegram
+                .sent_texts()
+                .iter()
+                .filter(|text| text.contains("order filled"))
+                .count()
+        };
+
+        notify(NotificationLevel::Important).await.unwrap();
+        notify(NotificationLevel::Important).await.unwrap();
+        assert_eq!(sent(), 2);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/dedupwindow 10m",
+            Command::DedupWindow("10m".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Dedup window set to 10m 0s");
+        notify(NotificationLevel::Important).await.unwrap();
+        clock.set(utc(12, 9));
+        notify(NotificationLevel::Important).await.unwrap();
+        assert_eq!(sent(), 3);
+        notify(NotificationLevel::Critical).await.unwrap();
+        assert_eq!(sent(), 4);
+        clock.set(utc(12, 10));
+        notify(NotificationLevel::Important).await.unwrap();
+        assert_eq!(sent(), 5);
+        assert_eq!(state.lock().await.notification_stats.suppressed.dedup, 1);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/dedupwindow 2h",
+            Command::DedupWindow("2h".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            &format!(
+                "Dedup window must be at most {}.",
+                format_duration(MAX_DEDUP_WINDOW)
+            )
+        );
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/dedupwindow off",
+            Command::DedupWindow("off".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Notification deduplication turned off"
+        );
+        notify(NotificationLevel::Important).await.unwrap();
+        assert_eq!(sent(), 6);
+    }
+
+    #[test]
+    fn state_json_includes_the_operational_fields() {
+        let state = BotState {
+            is_running: true,
+            dry_run: true,
+            notification_level: NotificationLevel::Critical,
+            instance_tag: Some("[PROD]".to_string()),
+            ..BotState::new()
+        };
+        let json: Value = serde_json::from_str(&state_json(&state).unwrap()).unwrap();
+
+        assert_eq!(json["is_running"], json!(true));
+        assert_eq!(json["dry_run"], json!(true));
+        assert_eq!(json["notification_level"], json!("Critical"));
+        assert_eq!(json["instance_tag"], json!("[PROD]"));
+        assert_eq!(json["time_zone"], json!("+00:00"));
+        assert!(json.get("events").is_none());
+    }
+
+    #[test]
+    fn sensitive_keys_are_redacted_at_any_depth() {
+        let mut value = json!({
+            "bot_token": "123:abc",
+            "exchange": {"API_KEY": "k", "label": "main"},
+            "accounts": [{"password": "hunter2", "user": "ops"}],
+            "client_secret": {"nested": "value"},
+        });
+        redact_sensitive(&mut value);
+
+        assert_eq!(
+            value,
+            json!({
+                "bot_token": "[redacted]",
+                "exchange": {"API_KEY": "[redacted]", "label": "main"},
+                "accounts": [{"password": "[redacted]", "user": "ops"}],
+                "client_secret": "[redacted]",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn state_sends_the_json_in_a_code_block() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+
+        run_command(&telegram, &state, "/state", Command::State).await;
+        let calls = telegram.calls();
+        let call = calls.last().unwrap();
+        assert!(call.text().starts_with("<pre>{"), "{}", call.text());
+        assert!(
+            call.text()
+                .contains("\"notification_level\": \"Important\""),
+            "{}",
+            call.text()
+        );
+    }
+
+    #[test]
+    fn intervals_accept_seconds_or_durations() {
+        assert_eq!(parse_interval("3600"), Ok(3600));
+        assert_eq!(parse_interval("1h"), Ok(3600));
+        assert_eq!(parse_interval("30m"), Ok(1800));
+        for too_short in ["4", "4s", "0"] {
+            assert_eq!(
+                parse_interval(too_short).unwrap_err(),
+                "Interval must be at least 5 seconds.",
+                "{}",
+                too_short
+            );
+        }
+        assert!(parse_interval("soon").is_err());
+    }
+
+    #[tokio::test]
+    async fn setinterval_treats_seconds_and_durations_alike() {
+        let replies = run_commands::<CustomFormatBot>(vec![
+            ("/setinterval 30m", Command::SetInterval("30m".to_string())),
+            (
+                "/setinterval 1800",
+                Command::SetInterval("1800".to_string()),
+            ),
+            ("/setinterval 2s", Command::SetInterval("2s".to_string())),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            [
+                "Interval set to 30m 0s".to_string(),
+                "Interval already set to 30m 0s".to_string(),
+                "Interval must be at least 5 seconds.".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbol_overrides_list_only_symbols_with_one_active() {
+        let symbols = vec![
+            disabled("ADAUSDT"),
+            SymbolConfig {
+                max_slippage: Some(0.005),
+                size_step: Some(0.01),
+                ..symbol_config("BTCUSDT")
+            },
+            symbol_config("ETHUSDT"),
+            symbol_config("SOLUSDT"),
+        ];
+        let mut state = BotState {
+            symbol_error_limit: 3,
+            ..BotState::new()
+        };
+        state.symbol_errors.insert("SOLUSDT".to_string(), 2);
+        state.symbol_errors.insert("ETHUSDT".to_string(), 0);
+
+        assert_eq!(
+            symbol_overrides(&symbols, &state),
+            vec![
+                ("ADAUSDT".to_string(), vec!["disabled".to_string()]),
+                (
+                    "BTCUSDT".to_string(),
+                    vec![
+                        "max slippage 0.0050".to_string(),
+                        "size step 0.01".to_string()
+                    ]
+                ),
+                (
+                    "SOLUSDT".to_string(),
+                    vec!["2 of 3 errors before auto-disable".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn overrides_shows_a_table_or_says_there_are_none() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "symbols-overrides",
+            &[symbol_config("BTCUSDT"), disabled("ETHUSDT")],
+        );
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
+
+        let texts = run_command(&telegram, &state, "/overrides", Command::Overrides).await;
+        let table = texts.last().unwrap();
+        assert!(
+            table.contains("ETHUSDT") && table.contains("disabled"),
+            "{}",
+            table
+        );
+        assert!(!table.contains("BTCUSDT"), "{}", table);
+
+        write_config("symbols-overrides", &[symbol_config("BTCUSDT")]);
+        let texts = run_command(&telegram, &state, "/overrides", Command::Overrides).await;
+        assert_eq!(texts.last().unwrap(), "No symbol has an override active.");
+        remove_config(&path);
+    }
+
+    #[test]
+    fn endpoints_must_be_absolute_http_or_ws_urls() {
+        for url in [
+            "https://testnet.example.com",
+            "wss://stream.example.com:9443/ws",
+            "HTTP://localhost:8080",
+        ] {
+            assert_eq!(validate_endpoint(url), Ok(()), "{}", url);
+        }
+        for url in [
+            "testnet.example.com",
+            "ftp://example.com",
+            "https://",
+            "https://:443/api",
+            "https://exa mple.com",
+        ] {
+            assert!(validate_endpoint(url).is_err(), "{}", url);
+        }
+    }
+
+    static APPLIED_ENDPOINTS: StdMutex<Vec<String>> = StdMutex::new(Vec::new());
+
+    /// A strategy that records the endpoints it is switched to
+    struct EndpointBot;
+
+    #[async_trait]
+    impl TradingBot for EndpointBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(EndpointBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: Arc<Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                endpoint: true,
+                ..Default::default()
             }
         }
 
-        Ok(())
+        async fn set_endpoint(&mut self, url: &str) -> Result<(), Self::Error> {
+            APPLIED_ENDPOINTS.lock().unwrap().push(url.to_string());
+            Ok(())
+        }
     }
 
-    async fn handle_show_symbols(
-        &self,
-        bot: &Bot,
-        chat_id: ChatId,
-        bot_state: Arc<Mutex<BotState>>,
-    ) -> ResponseResult<()> {
-        let config_path = match bot_state.lock().await.config_path.clone() {
-            Some(path) => PathBuf::from(path),
-            None => {
-                bot.send_message(
-                    chat_id,
-                    "Configuration path is not set. Use /startbot first to initialize.",
+    #[tokio::test]
+    async fn endpoint_applies_valid_urls_only() {
+        let replies = run_commands::<EndpointBot>(vec![
+            (
+                "/endpoint https://testnet.example.com",
+                Command::Endpoint("https://testnet.example.com".to_string()),
+            ),
+            (
+                "/endpoint ftp://example.com",
+                Command::Endpoint("ftp://example.com".to_string()),
+            ),
+            ("/endpoint", Command::Endpoint(String::new())),
+        ])
+        .await;
+
+        assert_eq!(
+            replies,
+            [
+                "Endpoint set to https://testnet.example.com; the strategy has reconnected.",
+                "Invalid endpoint 'ftp://example.com'. Use an http(s):// or ws(s):// URL, e.g. \
+                 https://testnet.example.com",
+                "Usage: /endpoint URL",
+            ]
+        );
+        assert_eq!(
+            *APPLIED_ENDPOINTS.lock().unwrap(),
+            ["https://testnet.example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint_reports_a_strategy_that_cannot_switch() {
+        let replies = run_commands::<CustomFormatBot>(vec![(
+            "/endpoint https://testnet.example.com",
+            Command::Endpoint("https://testnet.example.com".to_string()),
+        )])
+        .await;
+
+        assert_eq!(
+            replies,
+            ["Unable to switch endpoint: The strategy does not support switching endpoints"]
+        );
+    }
+
+    #[test]
+    fn sparkline_scales_values_between_their_min_and_max() {
+        let rising: Vec<f64> = (0..8).map(f64::from).collect();
+        assert_eq!(sparkline(&rising), "▁▂▃▄▅▆▇█");
+        assert_eq!(sparkline(&[-10.0, 10.0, 0.0]), "▁█▅");
+        assert_eq!(sparkline(&[3.0, 3.0]), "▅▅");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn pnl_samples_keep_the_latest() {
+        let mut state = BotState::new();
+        for pnl in 0..MAX_PNL_SAMPLES + 5 {
+            state.record_pnl_sample(pnl as f64);
+        }
+
+        assert_eq!(state.pnl_samples.len(), MAX_PNL_SAMPLES);
+        assert_eq!(state.pnl_samples.front(), Some(&5.0));
+    }
+
+    #[tokio::test]
+    async fn pnlchart_charts_the_last_samples_with_their_range() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+        state.lock().await.record_pnl_sample(1.0);
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/pnlchart",
+            Command::PnlChart(String::new()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Not enough PnL samples yet; they are recorded after each tick."
+        );
+
+        for pnl in [-5.0, 2.0, 9.0] {
+            state.lock().await.record_pnl_sample(pnl);
+        }
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/pnlchart 3",
+            Command::PnlChart("3".to_string()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "PnL over the last 3 ticks:\n▁▅█\nMin: -5.00  Max: 9.00  Now: 9.00"
+        );
+
+        for count in ["1", "61", "many"] {
+            let texts = run_command(
+                &telegram,
+                &state,
+                "/pnlchart",
+                Command::PnlChart(count.to_string()),
+            )
+            .await;
+            assert_eq!(
+                texts.last().unwrap(),
+                "Usage: /pnlchart [N] with N between 2 and 60",
+                "{}",
+                count
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_messages_use_the_restart_level() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_level: NotificationLevel::Important,
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+
+        let restarts = || {
+            telegram
+                .sent_texts()
+                .iter()
+                .filter(|text| text.contains("Restarting strategy"))
+                .count()
+        };
+        send_restart_notification(&telegram.bot, CHAT, &state, "Restarting strategy")
+            .await
+            .unwrap();
+        assert_eq!(restarts(), 1);
+
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/restartlevel all",
+            Command::RestartLevel("all".to_string()),
+        )
+        .await;
+        assert_eq!(texts.last().unwrap(), "Restart level set to All");
+        send_restart_notification(&telegram.bot, CHAT, &state, "Restarting strategy")
+            .await
+            .unwrap();
+        assert_eq!(restarts(), 1);
+
+        state.lock().await.notification_level = NotificationLevel::Critical;
+        run_command(
+            &telegram,
+            &state,
+            "/restartlevel critical",
+            Command::RestartLevel("critical".to_string()),
+        )
+        .await;
+        send_restart_notification(&telegram.bot, CHAT, &state, "Restarting strategy")
+            .await
+            .unwrap();
+        assert_eq!(restarts(), 2);
+    }
+
+    #[tokio::test]
+    async fn restartlevel_rejects_none_and_unknown_levels() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            command_cooldown_secs: 0,
+            ..BotState::new()
+        });
+
+        for level in ["none", "loud"] {
+            let texts = run_command(
+                &telegram,
+                &state,
+                "/restartlevel",
+                Command::RestartLevel(level.to_string()),
+            )
+            .await;
+            assert_eq!(
+                texts.last().unwrap(),
+                "Invalid level. Use: all, important or critical"
+            );
+        }
+        let texts = run_command(
+            &telegram,
+            &state,
+            "/restartlevel",
+            Command::RestartLevel(String::new()),
+        )
+        .await;
+        assert_eq!(
+            texts.last().unwrap(),
+            "Restart messages are sent at level Important"
+        );
+    }
+
+    /// Answer every `GetLastError` on `request_rx` with an error naming
+    /// `runner`, so replies show which runner a command went to
+    fn answer_last_error(
+        mut request_rx: mpsc::UnboundedReceiver<BotRequest>,
+        runner: &'static str,
+    ) {
+        tokio::spawn(async move {
+            while let Some(request) = request_rx.recv().await {
+                if let BotRequest::GetLastError(tx) = request {
+                    let _ = tx.send(Ok(Some(ErrorRecord {
+                        at: utc(9, 0),
+                        message: format!("from {}", runner),
+                    })));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn runners_must_be_registered_once_with_a_name_without_spaces() {
+        let (mut handler, _request_rx) = TelegramBotHandler::new();
+        assert!(handler.register_runner("grid").is_ok());
+        assert_eq!(
+            handler.register_runner("grid").unwrap_err(),
+            "A runner named 'grid' is already registered"
+        );
+        assert!(handler.register_runner("").is_err());
+        assert!(handler.register_runner("two words").is_err());
+
+        assert_eq!(handler.active_runner(), DEFAULT_RUNNER);
+        assert_eq!(
+            handler.use_runner("missing").unwrap_err(),
+            "Unknown runner 'missing'. Registered: default, grid"
+        );
+        assert_eq!(handler.active_runner(), DEFAULT_RUNNER);
+        handler.use_runner("grid").unwrap();
+        assert_eq!(handler.active_runner(), "grid");
+    }
+
+    #[tokio::test]
+    async fn commands_go_to_the_active_runner() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        let (mut handler, default_rx) = TelegramBotHandler::new();
+        answer_last_error(default_rx, DEFAULT_RUNNER);
+        answer_last_error(handler.register_runner("grid").unwrap(), "grid");
+
+        for (text, command) in [
+            ("/lasterror", Command::LastError),
+            ("/use grid", Command::Use("grid".to_string())),
+            ("/lasterror", Command::LastError),
+            ("/use grid", Command::Use("grid".to_string())),
+            ("/use missing", Command::Use("missing".to_string())),
+            ("/lasterror", Command::LastError),
+            ("/active", Command::Active),
+            ("/use default", Command::Use("default".to_string())),
+            ("/lasterror", Command::LastError),
+        ] {
+            handler
+                .handle_command(
+                    telegram.bot.clone(),
+                    command_message(CHAT, text),
+                    command,
+                    Arc::clone(&state),
                 )
-                .await?;
-                return Ok(());
+                .await
+                .unwrap();
+        }
+
+        let replies = telegram.sent_texts();
+        assert_eq!(replies.len(), 9);
+        assert!(replies[0].ends_with("from default"));
+        assert_eq!(replies[1], "Commands now go to grid");
+        assert!(replies[2].ends_with("from grid"));
+        assert_eq!(replies[3], "Already using grid");
+        assert_eq!(
+            replies[4],
+            "Unknown runner 'missing'. Registered: default, grid"
+        );
+        assert!(replies[5].ends_with("from grid"));
+        assert_eq!(replies[6], "Strategy runners:\ndefault\ngrid (active)");
+        assert_eq!(replies[7], "Commands now go to default");
+        assert!(replies[8].ends_with("from default"));
+    }
+
+    #[tokio::test]
+    async fn each_suppression_reason_is_counted() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 0));
+        let state = shared(BotState {
+            notification_level: NotificationLevel::Important,
+            dedup_window: Duration::from_secs(5 * 60),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        let notify = |level: NotificationLevel, text: &'static str| {
+            let state = Arc::clone(&state);
+            let bot = telegram.bot.clone();
+            async move {
+                send_notification(&bot, CHAT, &state, level, text.to_string())
+                    .await
+                    .unwrap()
             }
         };
+        let suppressed = || async { state.lock().await.notification_stats.suppressed };
 
-        // Read the file
-        let file_content = tokio::fs::read_to_string(config_path).await;
+        notify(NotificationLevel::All, "chatter").await;
+        assert_eq!(suppressed().await.level, 1);
 
-        match file_content {
-            Ok(content) => {
-                // Parse the JSON
-                let symbols: Vec<SymbolConfig> = match serde_json::from_str(&content) {
-                    Ok(json) => json,
-                    Err(_) => {
-                        bot.send_message(chat_id, "Failed to parse symbols configuration.")
-                            .await?;
-                        return Ok(());
-                    }
-                };
+        notify(NotificationLevel::Important, "fill").await;
+        notify(NotificationLevel::Important, "fill").await;
+        assert_eq!(suppressed().await.dedup, 1);
 
-                // Create a table
-                let mut table = Table::new();
-                table.add_row(row![
-                    "Symbol",
-                    "Entry Amount",
-                    "Exit Amount",
-                    "Entry Threshold",
-                    "Exit Threshold"
-                ]);
+        state.lock().await.warmup_until = Some(utc(9, 5));
+        notify(NotificationLevel::Important, "warming up").await;
+        assert_eq!(suppressed().await.warmup, 1);
+        state.lock().await.warmup_until = None;
 
-                // Populate the table
-                for symbol in &symbols {
-                    table.add_row(row![
-                        symbol.symbol,
-                        format!("{:.2}", symbol.entry_amount),
-                        format!("{:.2}", symbol.exit_amount),
-                        format!("{:.2}", symbol.entry_threshold),
-                        format!("{:.2}", symbol.exit_threshold)
-                    ]);
-                }
+        state.lock().await.quiet_until = Some(utc(9, 5));
+        notify(NotificationLevel::Important, "quiet").await;
+        assert_eq!(suppressed().await.quiet, 1);
+        state.lock().await.quiet_until = None;
+
+        {
+            let mut state = state.lock().await;
+            state.summary_mode = true;
+            state.begin_tick_notifications();
+        }
+        notify(NotificationLevel::Important, "batched").await;
+        notify(NotificationLevel::Critical, "urgent").await;
+
+        let counts = suppressed().await;
+        assert_eq!(
+            counts,
+            SuppressedCounts {
+                level: 1,
+                quiet: 1,
+                warmup: 1,
+                dedup: 1,
+                coalesced: 1,
+            }
+        );
+        assert!(counts
+            .render()
+            .starts_with("Notifications suppressed this session: 5"));
+        assert!(counts.render().ends_with("Coalesced into summaries: 1"));
+        assert_eq!(telegram.sent_texts().len(), 2);
+    }
+
+    /// Answers chats 1 and 3 as blocked and every other chat normally
+    fn blocked(call: &ApiCall) -> Reply {
+        match call.chat_id() {
+            Some(ChatId(1) | ChatId(3)) if call.is("sendMessage") => {
+                api_error(403, "Forbidden: bot was blocked by the user")
+            }
+            _ => Reply::Ok(default_result(call)),
+        }
+    }
+
+    fn sent_to(telegram: &FakeTelegram, chat_id: ChatId) -> Vec<String> {
+        telegram
+            .calls()
+            .iter()
+            .filter(|call| call.is("sendMessage") && call.chat_id() == Some(chat_id))
+            .map(|call| call.text().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn the_fallback_chat_gets_broadcasts_no_target_could_receive() {
+        let telegram = FakeTelegram::with_responder(blocked).await;
+        let state = shared(BotState {
+            notification_targets: vec![ChatId(1), ChatId(3)],
+            fallback_chat: Some(ChatId(2)),
+            ..Default::default()
+        });
+
+        let removed = broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "margin call".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(removed, 2);
+        assert_eq!(sent_to(&telegram, ChatId(2)), ["<pre>margin call</pre>"]);
+    }
+
+    #[tokio::test]
+    async fn the_fallback_chat_is_untouched_while_a_target_is_reachable() {
+        let telegram = FakeTelegram::with_responder(blocked).await;
+        let state = shared(BotState {
+            notification_targets: vec![ChatId(1), CHAT],
+            fallback_chat: Some(ChatId(2)),
+            ..Default::default()
+        });
+
+        broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "margin call".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sent_to(&telegram, CHAT), ["<pre>margin call</pre>"]);
+        assert!(sent_to(&telegram, ChatId(2)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn single_chat_notifications_do_not_fall_back() {
+        let telegram = FakeTelegram::with_responder(blocked).await;
+        let state = shared(BotState {
+            fallback_chat: Some(ChatId(2)),
+            ..Default::default()
+        });
+
+        let result = send_notification(
+            &telegram.bot,
+            ChatId(1),
+            &state,
+            NotificationLevel::Critical,
+            "margin call".to_string(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(sent_to(&telegram, ChatId(2)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn fallback_sets_and_clears_the_fallback_chat() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            ..Default::default()
+        });
+        for (text, id) in [
+            ("/fallback 7", 7),
+            ("/fallback 7", 7),
+            ("/fallback 0", 0),
+            ("/fallback 0", 0),
+        ] {
+            run_command(&telegram, &state, text, Command::Fallback(id)).await;
+        }
+
+        assert_eq!(
+            telegram.sent_texts(),
+            [
+                "Fallback chat set to 7. It receives broadcasts only when no target can be \
+                 reached.",
+                "Fallback chat already set to 7",
+                "Fallback chat removed.",
+                "No fallback chat is set.",
+            ]
+        );
+        assert_eq!(state.lock().await.fallback_chat, None);
+    }
+
+    const BREAKER_ALERT: &str = "The bot is stopped; use /clearbreaker to acknowledge.</pre>";
+
+    /// A running bot alerting only critical notifications to `targets`,
+    /// whose breaker trips after one failure and then cools down
+    fn breaker_state(targets: Vec<ChatId>) -> Arc<Mutex<BotState>> {
+        let mut state = BotState {
+            is_running: true,
+            notification_targets: targets,
+            notification_level: NotificationLevel::Critical,
+            command_cooldown_secs: 0,
+            ..Default::default()
+        };
+        state.circuit_breaker.threshold = 1;
+        state.circuit_breaker.cooldown = Duration::from_secs(600);
+        shared(state)
+    }
+
+    /// Whether the breaker tripped, the bot runs, the stop was announced and
+    /// the cooldown is active
+    async fn breaker_outcome(state: &Arc<Mutex<BotState>>) -> (bool, bool, bool, bool) {
+        let state = state.lock().await;
+        (
+            state.circuit_breaker.tripped,
+            state.is_running,
+            state.stop_announced,
+            state
+                .circuit_breaker
+                .cooldown_remaining(state.now())
+                .is_some(),
+        )
+    }
+
+    #[tokio::test]
+    async fn a_forced_trip_behaves_like_an_organic_one() {
+        let organic = FakeTelegram::start().await;
+        let organic_state = breaker_state(vec![ChatId(7)]);
+        let _handler = start_runner_every::<ErrorBot>(&organic, &organic_state, 1).await;
+        organic
+            .wait_for(|calls| {
+                calls.iter().any(|call| {
+                    call.chat_id() == Some(ChatId(7)) && call.text().contains("Circuit breaker")
+                })
+            })
+            .await;
+
+        let forced = FakeTelegram::start().await;
+        let forced_state = breaker_state(vec![ChatId(7), CHAT]);
+        run_command(&forced, &forced_state, "/tripbreaker", Command::TripBreaker).await;
+
+        assert_eq!(
+            breaker_outcome(&forced_state).await,
+            breaker_outcome(&organic_state).await
+        );
+        assert_eq!(
+            breaker_outcome(&forced_state).await,
+            (true, false, true, true)
+        );
+        for chat_id in [ChatId(7), CHAT] {
+            let organic_alerts: Vec<_> = sent_to(&organic, chat_id)
+                .into_iter()
+                .filter(|text| text.contains("Circuit breaker"))
+                .collect();
+            assert_eq!(
+                organic_alerts,
+                [format!(
+                    "<pre>Circuit breaker tripped after 1 consecutive failures. {}",
+                    BREAKER_ALERT
+                )]
+            );
+            assert_eq!(
+                sent_to(&forced, chat_id),
+                [format!(
+                    "<pre>Circuit breaker tripped by /tripbreaker. {}",
+                    BREAKER_ALERT
+                )]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tripbreaker_confirms_to_a_caller_outside_the_targets() {
+        let telegram = FakeTelegram::start().await;
+        let state = breaker_state(vec![ChatId(7)]);
+        for _ in 0..2 {
+            run_command(&telegram, &state, "/tripbreaker", Command::TripBreaker).await;
+        }
+
+        assert_eq!(
+            sent_to(&telegram, ChatId(7)),
+            [format!(
+                "<pre>Circuit breaker tripped by /tripbreaker. {}",
+                BREAKER_ALERT
+            )]
+        );
+        assert_eq!(
+            sent_to(&telegram, CHAT),
+            [
+                "Circuit breaker tripped. The notification targets were alerted.",
+                "The circuit breaker is already tripped.",
+            ]
+        );
+    }
+
+    /// The calls made by /startbot after /confirmstyle `style`
+    async fn start_confirmation(style: &str) -> Vec<ApiCall> {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        run_command(
+            &telegram,
+            &state,
+            "/confirmstyle",
+            Command::ConfirmStyle(style.to_string()),
+        )
+        .await;
+        let before = telegram.calls().len();
+        run_command(&telegram, &state, "/startbot", Command::StartBot).await;
+        assert!(state.lock().await.is_running);
+        telegram.calls().split_off(before)
+    }
+
+    #[tokio::test]
+    async fn start_confirmations_follow_the_confirm_style() {
+        let calls = start_confirmation("message").await;
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].is("sendMessage"));
+        assert_eq!(calls[0].text(), "Trading bot started!");
+        assert!(calls[0].body["reply_parameters"].is_null());
+
+        let calls = start_confirmation("reply").await;
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].is("sendMessage"));
+        assert_eq!(calls[0].text(), "Trading bot started!");
+        assert_eq!(calls[0].body["reply_parameters"]["message_id"], 1);
+
+        let calls = start_confirmation("Reaction").await;
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].is("setMessageReaction"));
+        assert_eq!(calls[0].chat_id(), Some(CHAT));
+        assert_eq!(calls[0].body["message_id"], 1);
+        assert_eq!(calls[0].body["reaction"][0]["emoji"], "👍");
+    }
+
+    #[tokio::test]
+    async fn confirmstyle_reports_changes_and_rejects_unknown_styles() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
+        for style in ["", "reply", "reply", "emoji"] {
+            run_command(
+                &telegram,
+                &state,
+                "/confirmstyle",
+                Command::ConfirmStyle(style.to_string()),
+            )
+            .await;
+        }
 
-                // Convert the table to a string
-                let table_string = format!("```\n{}\n```", table.to_string());
+        assert_eq!(
+            telegram.sent_texts(),
+            [
+                "Confirmation style: Message",
+                "Confirmation style set to Reply",
+                "Confirmation style already set to Reply",
+                "Invalid confirmation style 'emoji'. Use: reply, message or reaction",
+            ]
+        );
+        assert_eq!(state.lock().await.confirm_style, ConfirmStyle::Reply);
+    }
 
-                // Send the table as a message
-                bot.send_message(chat_id, table_string)
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await?;
-            }
-            Err(_) => {
-                bot.send_message(
-                    chat_id,
-                    "Failed to read symbols configuration. Ensure the file exists.",
-                )
-                .await?;
-            }
+    #[test]
+    fn the_budget_reflects_recent_sends_and_refills() {
+        let mut limiter = RateLimiter::new(1.0, 3.0);
+        let now = utc(9, 0);
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(CHAT, now), Duration::ZERO);
         }
+        assert_eq!(limiter.tokens(CHAT, now), 0.0);
+        assert!(limiter.is_throttled(CHAT, now));
+        assert_eq!(limiter.tokens(ChatId(7), now), 3.0);
 
-        Ok(())
+        assert_eq!(limiter.reserve(CHAT, now), Duration::from_secs(1));
+        let later = now + chrono::Duration::seconds(3);
+        assert_eq!(limiter.tokens(CHAT, later), 2.0);
+        assert!(!limiter.is_throttled(CHAT, later));
+        assert_eq!(limiter.tokens(CHAT, utc(10, 0)), 3.0);
     }
 
-    /// Initialize and run the trading bot in a separate thread
-    pub async fn init_and_run_bot<T: TradingBot>(
-        bot_state: Arc<Mutex<BotState>>,
-        bot: Bot,
-        chat_id: ChatId,
-        mut request_rx: mpsc::UnboundedReceiver<BotRequest>,
-        interval_seconds: u64,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        // Spawn the bot in a new thread to avoid Send issues
-        std::thread::spawn(move || {
-            tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap()
-                .block_on(async move {
-                    // Try to initialize the bot
-                    let init_result = T::new(interval_seconds).await;
+    #[tokio::test]
+    async fn ratelimit_reports_the_budget_left_after_recent_sends() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 0));
+        let state = shared(BotState {
+            notification_targets: vec![CHAT, ChatId(7)],
+            rate_limiter: RateLimiter::new(1.0, 2.0),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        for text in ["first", "second"] {
+            send_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                NotificationLevel::Critical,
+                text.to_string(),
+            )
+            .await
+            .unwrap();
+        }
 
-                    match init_result {
-                        Ok(mut trading_bot) => {
-                            {
-                                let mut state = bot_state.lock().await;
-                                state.interval_seconds = Some(interval_seconds);
-                            }
+        let texts = run_command(&telegram, &state, "/ratelimit", Command::RateLimit).await;
 
-                            // Send confirmation message
-                            if let Err(e) = bot
-                                .send_message(
-                                    chat_id,
-                                    "Trading bot has initialized and is now running.",
-                                )
-                                .await
-                            {
-                                eprintln!("Error sending message: {}", e);
-                            }
+        let table = &texts[texts.len() - 2];
+        let row = |chat: &str| {
+            table
+                .lines()
+                .find(|line| line.contains(&format!("| {} ", chat)))
+                .unwrap_or_else(|| panic!("no row for {} in {}", chat, table))
+                .to_string()
+        };
+        let throttled = row("42");
+        assert!(
+            throttled.contains("0.0") && throttled.contains("yes"),
+            "{}",
+            throttled
+        );
+        let idle = row("7");
+        assert!(idle.contains("2.0") && idle.contains("no"), "{}", idle);
+        assert_eq!(texts.last().unwrap(), "1 chat(s) throttled.");
+    }
 
-                            let mut check_interval =
-                                tokio::time::interval(Duration::from_secs(interval_seconds));
+    #[tokio::test]
+    async fn throttled_notifications_wait_for_a_token() {
+        let telegram = FakeTelegram::start().await;
+        let clock = FakeClock::at(utc(9, 0));
+        let state = shared(BotState {
+            rate_limiter: RateLimiter::new(2.0, 1.0),
+            clock: clock.clock(),
+            ..Default::default()
+        });
 
-                            // First tick is consumed
-                            check_interval.tick().await;
+        let started = Instant::now();
+        for text in ["first", "second"] {
+            send_notification(
+                &telegram.bot,
+                CHAT,
+                &state,
+                NotificationLevel::Critical,
+                text.to_string(),
+            )
+            .await
+            .unwrap();
+        }
 
-                            loop {
-                                tokio::select! {
-                                    maybe_request = request_rx.recv() => {
-                                        match maybe_request {
-                                            Some(BotRequest::GetStatus(response_tx)) => {
-                                                let status = "Bot is running. Use /update for detailed status.".to_string();
-                                                let _ = response_tx.send(Ok(status));
-                                            }
-                                            None => {
-                                                println!("Request channel closed, shutting down bot runner");
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    _ = check_interval.tick() => {
-                                        let should_run = {
-                                            let state = bot_state.lock().await;
-                                            state.is_running
-                                        };
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert_eq!(
+            telegram.sent_texts(),
+            ["<pre>first</pre>", "<pre>second</pre>"]
+        );
+        assert_eq!(
+            state
+                .lock()
+                .await
+                .notification_stats
+                .sent
+                .iter()
+                .sum::<u64>(),
+            2
+        );
+    }
 
-                                        if !should_run {
-                                            println!("Stop flag detected, shutting down bot");
-                                            if let Err(e) = bot
-                                                .send_message(chat_id, "Trading bot has been stopped.")
-                                                .await
-                                            {
-                                                eprintln!("Error sending stop message: {}", e);
-                                            }
-                                            break;
-                                        }
+    #[tokio::test]
+    async fn throttled_broadcasts_still_remove_blocked_chats() {
+        let telegram = FakeTelegram::with_responder(blocked_and_limited).await;
+        let clock = FakeClock::at(utc(9, 0));
+        let state = shared(BotState {
+            notification_targets: vec![ChatId(1), ChatId(2)],
+            rate_limiter: RateLimiter::new(2.0, 1.0),
+            clock: clock.clock(),
+            ..Default::default()
+        });
+        {
+            let mut state = state.lock().await;
+            state.rate_limiter.reserve(ChatId(1), utc(9, 0));
+            state.rate_limiter.reserve(ChatId(2), utc(9, 0));
+        }
 
-                                        match tokio::time::timeout(
-                                            Duration::from_secs(60),
-                                            trading_bot.execute_strategy(
-                                                bot_state.clone(),
-                                                bot.clone(),
-                                                chat_id,
-                                            ),
-                                        )
-                                        .await
-                                        {
-                                            Ok(Ok(_)) => {}
-                                            Ok(Err(e)) => {
-                                                let error_msg = format!("Strategy execution failed: {}", e);
-                                                eprintln!("{}", &error_msg);
+        let removed = broadcast_notification(
+            &telegram.bot,
+            &state,
+            NotificationLevel::Critical,
+            "filled".to_string(),
+        )
+        .await
+        .unwrap();
 
-                                                if let Err(e) = bot.send_message(chat_id, &error_msg).await {
-                                                    eprintln!("Error sending error message: {}", e);
-                                                }
+        assert_eq!(removed, 1);
+        assert_eq!(state.lock().await.notification_targets, vec![ChatId(2)]);
+        assert_eq!(sent_to(&telegram, ChatId(2)), ["<pre>filled</pre>"]);
+    }
 
-                                                if let Err(e) = bot
-                                                    .send_message(
-                                                        chat_id,
-                                                        "Stopping and restarting the bot due to error...",
-                                                    )
-                                                    .await
-                                                {
-                                                    eprintln!("Error sending restart message: {}", e);
-                                                }
+    #[tokio::test]
+    async fn broadcasts_send_to_at_most_send_concurrency_chats_at_once() {
+        let telegram = FakeTelegram::with_responder(|_| Reply::Hang).await;
+        let state = shared(BotState {
+            notification_targets: (1..=4).map(ChatId).collect(),
+            send_concurrency: 2,
+            send_timeout_secs: 1,
+            ..Default::default()
+        });
 
-                                                {
-                                                    let mut state = bot_state.lock().await;
-                                                    state.is_running = false;
-                                                }
+        let broadcast = tokio::spawn({
+            let bot = telegram.bot.clone();
+            let state = Arc::clone(&state);
+            async move {
+                broadcast_notification(&bot, &state, NotificationLevel::Critical, "fill".into())
+                    .await
+                    .is_err()
+            }
+        });
+        telegram.wait_for(|calls| sends(calls) == 2).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(sends(&telegram.calls()), 2);
 
-                                                tokio::time::sleep(Duration::from_secs(5)).await;
+        assert!(broadcast.await.unwrap());
+        assert_eq!(sends(&telegram.calls()), 4);
+    }
 
-                                                {
-                                                    let mut state = bot_state.lock().await;
-                                                    state.is_running = true;
-                                                }
+    #[tokio::test]
+    async fn lowering_send_concurrency_waits_for_sends_in_flight() {
+        let mut state = BotState {
+            send_concurrency: 3,
+            ..Default::default()
+        };
+        let permits = state.send_permits();
+        let mut held: Vec<_> = (0..3)
+            .map(|_| Arc::clone(&permits).try_acquire_owned().unwrap())
+            .collect();
 
-                                                if let Err(e) = bot
-                                                    .send_message(chat_id, "Bot has been restarted.")
-                                                    .await
-                                                {
-                                                    eprintln!(
-                                                        "Error sending restart confirmation message: {}",
-                                                        e
-                                                    );
-                                                }
+        state.send_concurrency = 1;
+        assert!(Arc::ptr_eq(&state.send_permits(), &permits));
+        held.truncate(1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(permits.available_permits(), 0);
+        held.clear();
+        assert_eq!(permits.available_permits(), 1);
 
-                                                match T::new(interval_seconds).await {
-                                                    Ok(new_bot) => {
-                                                        {
-                                                            let mut state = bot_state.lock().await;
-                                                            state.interval_seconds = Some(interval_seconds);
-                                                        }
+        state.send_concurrency = 4;
+        state.send_permits();
+        assert_eq!(permits.available_permits(), 4);
+    }
 
-                                                        trading_bot = new_bot;
-                                                        check_interval = tokio::time::interval(
-                                                            Duration::from_secs(interval_seconds),
-                                                        );
-                                                        check_interval.tick().await;
-                                                        if let Err(e) = bot
-                                                            .send_message(
-                                                                chat_id,
-                                                                "Trading bot has been re-initialized.",
-                                                            )
-                                                            .await
-                                                        {
-                                                            eprintln!(
-                                                                "Error sending re-initialization message: {}",
-                                                                e
-                                                            );
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        let init_error_msg =
-                                                            format!("Failed to re-initialize bot: {}", e);
-                                                        eprintln!("{}", &init_error_msg);
+    #[tokio::test]
+    async fn sendconcurrency_rejects_out_of_range_limits() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            notification_targets: vec![CHAT],
+            command_cooldown_secs: 0,
+            ..Default::default()
+        });
 
-                                                        if let Err(e) =
-                                                            bot.send_message(chat_id, &init_error_msg).await
-                                                        {
-                                                            eprintln!(
-                                                                "Error sending re-initialization error message: {}",
-                                                                e
-                                                            );
-                                                        }
+        for limit in [0, MAX_SEND_CONCURRENCY + 1, 8, 8] {
+            run_command(
+                &telegram,
+                &state,
+                "/sendconcurrency",
+                Command::SendConcurrency(limit),
+            )
+            .await;
+        }
 
-                                                        let mut state = bot_state.lock().await;
-                                                        state.is_running = false;
-                                                    }
-                                                }
-                                            }
-                                            Err(_) => {
-                                                println!("Strategy execution timed out");
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            // Safely handle error
-                            let error_msg = format!("Failed to initialize bot: {}", e);
-                            eprintln!("{}", &error_msg);
+        let usage = format!(
+            "Usage: /sendconcurrency N, with N between 1 and {}",
+            MAX_SEND_CONCURRENCY
+        );
+        assert_eq!(
+            telegram.sent_texts(),
+            [
+                usage.clone(),
+                usage,
+                "Send concurrency set to 8".to_string(),
+                "Send concurrency already set to 8".to_string(),
+            ]
+        );
+        assert_eq!(state.lock().await.send_concurrency, 8);
+    }
 
-                            if let Err(e) = bot.send_message(chat_id, &error_msg).await {
-                                eprintln!("Error sending initialization error message: {}", e);
-                            }
+    #[test]
+    fn audit_finds_duplicates_ignoring_case_and_conflicting_thresholds() {
+        let mut conflicting = symbol_config("btcusdt");
+        conflicting.exit_threshold = 2.0;
+        let symbols = [
+            symbol_config("BTCUSDT"),
+            symbol_config("ETHUSDT"),
+            conflicting,
+            symbol_config("ETHUSDT"),
+            symbol_config("SOLUSDT"),
+        ];
 
-                            // Reset the running state
-                            let mut state = bot_state.lock().await;
-                            state.is_running = false;
-                        }
-                    }
-                });
-        });
-        // Don't wait for thread completion - just return success
-        Ok(())
+        let findings = audit_symbols(&symbols, None);
+
+        assert_eq!(
+            findings,
+            [
+                AuditFinding {
+                    severity: AuditSeverity::Error,
+                    symbol: "BTCUSDT".to_string(),
+                    detail: "conflicting entry/exit thresholds: 1/1, 1/2".to_string(),
+                },
+                AuditFinding {
+                    severity: AuditSeverity::Warning,
+                    symbol: "BTCUSDT".to_string(),
+                    detail: "listed 2 times (BTCUSDT, btcusdt)".to_string(),
+                },
+                AuditFinding {
+                    severity: AuditSeverity::Warning,
+                    symbol: "ETHUSDT".to_string(),
+                    detail: "listed 2 times (ETHUSDT, ETHUSDT)".to_string(),
+                },
+            ]
+        );
     }
-}
 
-/// Create a helper function for sending messages
-const TELEGRAM_MAX_MESSAGE_LENGTH: usize = 4096;
-const PRE_WRAP_OVERHEAD: usize = "<pre></pre>".len();
+    #[test]
+    fn audit_flags_enabled_allocation_over_the_budget() {
+        let symbols = [
+            symbol_config("BTCUSDT"),
+            symbol_config("ETHUSDT"),
+            disabled("SOLUSDT"),
+        ];
 
-fn split_message_chunks(message: &str, max_len: usize) -> Vec<String> {
-    if message.is_empty() {
-        return Vec::new();
+        assert!(audit_symbols(&symbols, None).is_empty());
+        assert!(audit_symbols(&symbols, Some(20.0)).is_empty());
+        assert_eq!(
+            audit_symbols(&symbols, Some(15.0)),
+            [AuditFinding {
+                severity: AuditSeverity::Error,
+                symbol: "*".to_string(),
+                detail: "enabled entry amounts total 20.00, over the session budget of 15.00"
+                    .to_string(),
+            }]
+        );
     }
 
-    let mut chunks = Vec::new();
-    let mut current = String::new();
-    let mut current_len = 0usize;
+    #[tokio::test]
+    async fn audit_reports_the_findings_or_that_there_are_none() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config(
+            "audit",
+            &[symbol_config("BTCUSDT"), symbol_config("ETHUSDT")],
+        );
+        let state = state_with_config(&path);
+        state.lock().await.command_cooldown_secs = 0;
 
-    for segment in message.split_inclusive('\n') {
-        let seg_len = segment.chars().count();
+        run_command(&telegram, &state, "/audit", Command::Audit).await;
+        state.lock().await.session_budget = Some(15.0);
+        let texts = run_command(&telegram, &state, "/audit", Command::Audit).await;
+        remove_config(&path);
 
-        if current_len + seg_len <= max_len {
-            current.push_str(segment);
-            current_len += seg_len;
-            continue;
-        }
+        assert_eq!(texts[0], "No findings across 2 symbol(s).");
+        assert!(texts[1].contains("Severity"), "{}", texts[1]);
+        assert!(texts[1].contains("ERROR"), "{}", texts[1]);
+        assert!(
+            texts[1].contains("over the session budget of 15.00"),
+            "{}",
+            texts[1]
+        );
+    }
 
-        if !current.is_empty() {
-            chunks.push(std::mem::take(&mut current));
-            current_len = 0;
-        }
+    /// `SymbolConfig` has no `PartialEq`, so compare configs as JSON
+    fn as_json(symbols: &[SymbolConfig]) -> Value {
+        serde_json::to_value(symbols).unwrap()
+    }
 
-        if seg_len <= max_len {
-            current.push_str(segment);
-            current_len = seg_len;
-        } else {
-            let mut buffer = String::new();
-            let mut buffer_len = 0usize;
+    #[test]
+    fn share_snippets_round_trip_and_tolerate_wrapping() {
+        let symbols = vec![symbol_config("BTCUSDT"), disabled("SOLUSDT")];
+        let snippet = encode_share(&symbols, false).unwrap();
+        assert_eq!(as_json(&decode_share(&snippet).unwrap()), as_json(&symbols));
 
-            for ch in segment.chars() {
-                if buffer_len == max_len {
-                    chunks.push(buffer);
-                    buffer = String::new();
-                    buffer_len = 0;
-                }
+        let (head, tail) = snippet.split_at(snippet.len() / 2);
+        assert_eq!(
+            as_json(&decode_share(&format!(" {}\n{} ", head, tail)).unwrap()),
+            as_json(&symbols)
+        );
+    }
 
-                buffer.push(ch);
-                buffer_len += 1;
-            }
+    #[cfg(feature = "compression")]
+    #[test]
+    fn gzipped_share_snippets_round_trip() {
+        let symbols = vec![symbol_config("BTCUSDT"), symbol_config("ETHUSDT")];
+        let snippet = encode_share(&symbols, true).unwrap();
+        assert_ne!(snippet, encode_share(&symbols, false).unwrap());
+        assert_eq!(as_json(&decode_share(&snippet).unwrap()), as_json(&symbols));
+    }
 
-            if !buffer.is_empty() {
-                current = buffer;
-                current_len = buffer_len;
-            }
-        }
+    #[test]
+    fn corrupt_share_snippets_are_rejected() {
+        assert_eq!(decode_share("  ").unwrap_err(), ["Share snippet is empty"]);
+        assert!(decode_share("not base64!").unwrap_err()[0]
+            .starts_with("Share snippet is not valid base64"));
+        assert!(
+            decode_share(&BASE64.encode("{}")).unwrap_err()[0].starts_with("Invalid symbols JSON")
+        );
+
+        let duplicates = [symbol_config("BTCUSDT"), symbol_config("BTCUSDT")];
+        assert_eq!(
+            decode_share(&encode_share(&duplicates, false).unwrap()).unwrap_err(),
+            ["entry 2: duplicate symbol BTCUSDT"]
+        );
     }
 
-    if !current.is_empty() {
-        chunks.push(current);
+    #[tokio::test]
+    async fn a_shared_config_imports_after_a_backup() {
+        let telegram = FakeTelegram::start().await;
+        let shared_symbols = vec![symbol_config("BTCUSDT"), disabled("SOLUSDT")];
+        let source = write_config("share_source", &shared_symbols);
+        let target = write_config("share_target", &[symbol_config("ETHUSDT")]);
+
+        run_command(
+            &telegram,
+            &state_with_config(&source),
+            "/share",
+            Command::Share(String::new()),
+        )
+        .await;
+        let block = telegram.sent_texts().pop().unwrap();
+        let snippet = block
+            .trim_start_matches("```\n")
+            .trim_end_matches("\n```")
+            .to_string();
+
+        let texts = run_command(
+            &telegram,
+            &state_with_config(&target),
+            "/importshare",
+            Command::ImportShare(snippet),
+        )
+        .await;
+        let imported = read_config(&target);
+        let backups = list_backups(&target).await.unwrap();
+        let backed_up = read_config(&backups[0]);
+        remove_config(&source);
+        remove_config(&target);
+
+        let reply = texts.last().unwrap();
+        assert!(
+            reply.starts_with("Imported 2 symbol(s).\nBackup saved to"),
+            "{}",
+            reply
+        );
+        assert_eq!(as_json(&imported), as_json(&shared_symbols));
+        assert_eq!(symbol_names(&backed_up), ["ETHUSDT"]);
     }
 
-    chunks
-}
+    #[tokio::test]
+    async fn importshare_leaves_the_config_alone_on_a_corrupt_snippet() {
+        let telegram = FakeTelegram::start().await;
+        let path = write_config("share_corrupt", &[symbol_config("ETHUSDT")]);
 
-pub async fn send_telegram_notification(
-    bot: &Bot,
-    chat_id: ChatId,
-    level: NotificationLevel,
-    current_level: NotificationLevel,
-    message: String,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Only send if the message level is important enough
-    if level_is_sufficient(level, current_level) {
-        let max_payload_len = TELEGRAM_MAX_MESSAGE_LENGTH.saturating_sub(PRE_WRAP_OVERHEAD);
-        let chunks = split_message_chunks(&message, max_payload_len);
+        let texts = run_command(
+            &telegram,
+            &state_with_config(&path),
+            "/importshare",
+            Command::ImportShare("@@@".to_string()),
+        )
+        .await;
+        let saved = read_config(&path);
+        let backups = list_backups(&path).await.unwrap();
+        remove_config(&path);
 
-        if chunks.is_empty() {
-            return Ok(());
-        }
+        assert!(texts
+            .last()
+            .unwrap()
+            .starts_with("Import failed:\nShare snippet is not valid base64"));
+        assert_eq!(symbol_names(&saved), ["ETHUSDT"]);
+        assert!(backups.is_empty());
+    }
 
-        for chunk in chunks {
-            let mono_message = format!("<pre>{}</pre>", chunk);
-            if let Err(e) = bot
-                .send_message(chat_id, mono_message)
-                .parse_mode(ParseMode::Html)
-                .await
-            {
-                eprintln!("Failed to send Telegram message: {}", e);
-                return Err(Box::new(BotError(format!("Telegram error: {}", e))));
-            }
-        }
+    #[tokio::test]
+    async fn summary_mode_sends_notifications_outside_a_tick_at_once() {
+        let telegram = FakeTelegram::start().await;
+        let state = shared(BotState {
+            summary_mode: true,
+            notification_level: NotificationLevel::All,
+            ..Default::default()
+        });
 
-        Ok(())
-    } else {
-        Ok(())
+        send_notification(
+            &telegram.bot,
+            CHAT,
+            &state,
+            NotificationLevel::Important,
+            "between ticks".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(telegram.sent_texts(), ["<pre>between ticks</pre>"]);
+        assert!(state.lock().await.summary_buffer.is_empty());
     }
-}
 
-/// Helper to check if notification level is sufficient
-fn level_is_sufficient(msg_level: NotificationLevel, current_level: NotificationLevel) -> bool {
-    match current_level {
-        NotificationLevel::None => false,
-        NotificationLevel::Critical => msg_level == NotificationLevel::Critical,
-        NotificationLevel::Important => {
-            matches!(
-                msg_level,
-                NotificationLevel::Critical | NotificationLevel::Important
+    #[tokio::test]
+    async fn a_failed_summary_counts_each_notification_once() {
+        let telegram = FakeTelegram::with_responder(blocked).await;
+        let state = shared(BotState {
+            summary_mode: true,
+            notification_level: NotificationLevel::All,
+            ..Default::default()
+        });
+        state.lock().await.begin_tick_notifications();
+        for text in ["first", "second"] {
+            send_notification(
+                &telegram.bot,
+                ChatId(1),
+                &state,
+                NotificationLevel::Important,
+                text.to_string(),
             )
+            .await
+            .unwrap();
         }
-        NotificationLevel::All => true,
+
+        assert!(flush_notification_summary(&telegram.bot, &state)
+            .await
+            .is_err());
+
+        let stats = state.lock().await.notification_stats.clone();
+        assert_eq!(stats.sent, [0, 0, 0, 0]);
+        assert_eq!(stats.failed, 2);
     }
 }
@@ -0,0 +1,36 @@
+use std::io::{self, Read, Write};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+/// Gzip-compress a payload before sending it as a document
+pub fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Decompress a gzip payload back into its original bytes
+pub fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gunzip_restores_the_gzipped_payload() {
+        let payload = br#"[{"symbol":"BTCUSDT","enabled":true}]"#;
+        let compressed = gzip(payload).unwrap();
+
+        assert_ne!(compressed.as_slice(), payload.as_slice());
+        assert_eq!(gunzip(&compressed).unwrap(), payload);
+    }
+
+    #[test]
+    fn gunzip_rejects_plain_data() {
+        assert!(gunzip(b"not gzip").is_err());
+    }
+}
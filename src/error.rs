@@ -1,18 +1,235 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, time::Duration};
 
-/// Custom error type for the Telegram bot
+use teloxide::{types::ChatId, ApiError, RequestError};
+
+/// Errors raised by the handler and runner while talking to Telegram or
+/// reading and writing configuration files
 #[derive(Debug)]
-pub struct BotError(pub String);
+pub enum BotError {
+    /// A general failure described by a message
+    Message(String),
+    /// A Telegram request did not complete within the configured send timeout
+    Timeout(Duration),
+    /// A Telegram request failed
+    Telegram(RequestError),
+    /// An operation failed because of an underlying error
+    Context {
+        message: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A broadcast could not be delivered to some of its chats
+    Broadcast(Vec<(ChatId, Box<dyn Error + Send + Sync>)>),
+}
+
+impl BotError {
+    /// Wrap `source` with a description of what was being attempted
+    pub fn context(
+        message: impl Into<String>,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        BotError::Context {
+            message: message.into(),
+            source: source.into(),
+        }
+    }
+}
 
 impl fmt::Display for BotError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            BotError::Message(message) => write!(f, "{}", message),
+            BotError::Timeout(timeout) => {
+                write!(f, "Telegram request timed out after {:?}", timeout)
+            }
+            // The underlying error is left to `source()` so it is described once
+            BotError::Telegram(_) => write!(f, "Telegram request failed"),
+            BotError::Context { message, .. } => write!(f, "{}", message),
+            BotError::Broadcast(failures) => {
+                write!(f, "Delivery failed for {} chat(s)", failures.len())?;
+                for (chat_id, e) in failures {
+                    write!(f, "\n{}: {}", chat_id, e)?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
-impl Error for BotError {}
+impl Error for BotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BotError::Telegram(e) => Some(e),
+            BotError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// How a failed Telegram request should be handled
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorClass {
+    /// Telegram asked us to slow down; retry after the given delay
+    RetryableRateLimit { after: Duration },
+    /// A transient network or server problem; retrying may succeed
+    RetryableServer,
+    /// Retrying will not help, e.g. the bot was blocked or the chat is gone
+    Fatal,
+}
+
+impl ErrorClass {
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, ErrorClass::Fatal)
+    }
+}
+
+/// Classify a teloxide `RequestError` to decide whether to retry it
+pub fn classify_error(e: &RequestError) -> ErrorClass {
+    match e {
+        RequestError::RetryAfter(after) => ErrorClass::RetryableRateLimit {
+            after: after.duration(),
+        },
+        RequestError::Network(_) | RequestError::Io(_) | RequestError::InvalidJson { .. } => {
+            ErrorClass::RetryableServer
+        }
+        RequestError::Api(ApiError::Unknown(description)) if is_server_error(description) => {
+            ErrorClass::RetryableServer
+        }
+        RequestError::Api(_) | RequestError::MigrateToChatId(_) => ErrorClass::Fatal,
+    }
+}
+
+/// Describe `e` and its `source()` chain on one line, or with every cause
+/// listed on its own line beneath it when `verbose` is set
+pub fn describe_error(e: &(dyn Error + 'static), verbose: bool) -> String {
+    let separator = if verbose { "\nCaused by: " } else { ": " };
+    let mut text = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        text.push_str(separator);
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+/// Telegram reports 5xx failures as plain descriptions rather than typed
+/// errors
+fn is_server_error(description: &str) -> bool {
+    let description = description.to_lowercase();
+    [
+        "internal server error",
+        "bad gateway",
+        "service unavailable",
+        "gateway timeout",
+    ]
+    .iter()
+    .any(|marker| description.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-// Safely implement Send and Sync for BotError since it only contains a String
-// which is Send + Sync
-unsafe impl Send for BotError {}
-unsafe impl Sync for BotError {}
+    #[test]
+    fn bot_error_displays_its_message() {
+        let error = BotError::Message("strategy failed".to_string());
+        assert_eq!(error.to_string(), "strategy failed");
+    }
+
+    #[test]
+    fn rate_limits_are_retried_after_telegrams_delay() {
+        let error = RequestError::RetryAfter(teloxide::types::Seconds::from_seconds(7));
+        assert_eq!(
+            classify_error(&error),
+            ErrorClass::RetryableRateLimit {
+                after: Duration::from_secs(7)
+            }
+        );
+        assert!(classify_error(&error).is_retryable());
+    }
+
+    #[test]
+    fn server_and_io_failures_are_retryable() {
+        let io = RequestError::Io(std::sync::Arc::new(std::io::Error::from(
+            std::io::ErrorKind::ConnectionReset,
+        )));
+        let bad_gateway = RequestError::Api(ApiError::Unknown("Bad Gateway".to_string()));
+
+        assert_eq!(classify_error(&io), ErrorClass::RetryableServer);
+        assert_eq!(classify_error(&bad_gateway), ErrorClass::RetryableServer);
+    }
+
+    #[test]
+    fn api_rejections_are_fatal() {
+        for error in [
+            RequestError::Api(ApiError::BotBlocked),
+            RequestError::Api(ApiError::MessageTextIsEmpty),
+            RequestError::Api(ApiError::Unknown("Bad Request: odd".to_string())),
+            RequestError::MigrateToChatId(teloxide::types::ChatId(-100)),
+        ] {
+            assert_eq!(classify_error(&error), ErrorClass::Fatal, "{:?}", error);
+            assert!(!classify_error(&error).is_retryable());
+        }
+    }
+
+    #[test]
+    fn broadcast_error_lists_every_failed_chat() {
+        let error = BotError::Broadcast(vec![
+            (ChatId(1), "timed out".into()),
+            (ChatId(2), "bad gateway".into()),
+        ]);
+
+        assert_eq!(
+            error.to_string(),
+            "Delivery failed for 2 chat(s)\n1: timed out\n2: bad gateway"
+        );
+    }
+
+    #[test]
+    fn timeouts_name_the_limit() {
+        let error = BotError::Timeout(Duration::from_secs(3));
+        assert_eq!(error.to_string(), "Telegram request timed out after 3s");
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn context_displays_only_its_own_message() {
+        let error = BotError::context(
+            "Failed to write subscriptions",
+            BotError::Message("disk full".to_string()),
+        );
+        assert_eq!(error.to_string(), "Failed to write subscriptions");
+        assert_eq!(error.source().unwrap().to_string(), "disk full");
+    }
+
+    #[test]
+    fn telegram_errors_leave_the_request_error_to_source() {
+        let error = BotError::Telegram(RequestError::Api(ApiError::BotBlocked));
+        assert_eq!(error.to_string(), "Telegram request failed");
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            RequestError::Api(ApiError::BotBlocked).to_string()
+        );
+    }
+
+    #[test]
+    fn describe_error_names_each_cause_once() {
+        let error = BotError::context(
+            "Failed to back up configuration",
+            BotError::context(
+                "Failed to copy config",
+                BotError::Message("disk full".to_string()),
+            ),
+        );
+
+        assert_eq!(
+            describe_error(&error, false),
+            "Failed to back up configuration: Failed to copy config: disk full"
+        );
+        assert_eq!(
+            describe_error(&error, true),
+            "Failed to back up configuration\nCaused by: Failed to copy config\nCaused by: disk \
+             full"
+        );
+    }
+}
@@ -1,10 +1,32 @@
+pub mod alerts;
 pub mod bot;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod error;
+#[cfg(feature = "tracing")]
+pub mod logging;
+#[cfg(feature = "resources")]
+pub mod resources;
+pub mod schedule;
+#[cfg(feature = "csv")]
+pub mod symbols_csv;
 pub mod traits;
 
+pub use alerts::{AlertField, AlertRule};
+#[allow(deprecated)]
+pub use bot::send_telegram_notification;
 pub use bot::{
-    send_telegram_notification, BotState, Command, NotificationLevel, TelegramBotHandler,
+    botfather_commands, broadcast_notification, check_budget, check_position_cap, check_trade_cap,
+    flush_notification_summary, send_notification, send_symbol_notification, BotEvent, BotState,
+    CircuitBreaker, Clock, Command, CommandDispatcher, ConfigFileInfo, ConfirmStyle,
+    EffectiveSetting, LevelOverride, Migration, NotificationLevel, NotificationRecord,
+    NotificationStats, RateLimiter, Role, SettingSource, StatusSnapshot, StopMode,
+    SuppressedCounts, SymbolColumn, TelegramBotHandler, TickNotifications,
+    MAX_STRATEGY_CONCURRENCY, SETTINGS_ENV_PREFIX,
 };
-pub use error::BotError;
+pub use error::{classify_error, describe_error, BotError, ErrorClass};
 pub use teloxide::{prelude::*, types::ChatId, Bot};
-pub use traits::{SymbolConfig, TradingBot};
+pub use traits::{
+    round_to_step, validate_import_entry, AccountInfo, Capabilities, MarketData, MarketPoint,
+    OrderInfo, PositionInfo, RiskParam, StatusReport, SymbolConfig, TradingBot,
+};
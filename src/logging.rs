@@ -0,0 +1,80 @@
+//! `tracing` setup with a log level that can be changed at runtime
+
+use std::sync::OnceLock;
+
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry,
+};
+
+static LEVEL_HANDLE: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Install a global `tracing` subscriber whose level `/loglevel` can change
+///
+/// Fails if a global subscriber was already set.
+pub fn init_logging(level: LevelFilter) -> Result<(), String> {
+    let (filter, handle) = reload::Layer::new(level);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .try_init()
+        .map_err(|e| format!("Failed to install log subscriber: {}", e))?;
+    let _ = LEVEL_HANDLE.set(handle);
+    Ok(())
+}
+
+/// Parse error/warn/info/debug/trace (or off)
+pub fn parse_log_level(value: &str) -> Result<LevelFilter, String> {
+    match value.trim().to_lowercase().as_str() {
+        "off" => Ok(LevelFilter::OFF),
+        "error" => Ok(LevelFilter::ERROR),
+        "warn" => Ok(LevelFilter::WARN),
+        "info" => Ok(LevelFilter::INFO),
+        "debug" => Ok(LevelFilter::DEBUG),
+        "trace" => Ok(LevelFilter::TRACE),
+        _ => Err(format!(
+            "Invalid log level '{}'. Use: error, warn, info, debug, trace, or off",
+            value.trim()
+        )),
+    }
+}
+
+/// The level currently applied, if `init_logging` has run
+pub fn current_log_level() -> Option<LevelFilter> {
+    LEVEL_HANDLE.get().and_then(|handle| handle.clone_current())
+}
+
+/// Swap the active level filter
+pub fn set_log_level(level: LevelFilter) -> Result<(), String> {
+    let handle = LEVEL_HANDLE
+        .get()
+        .ok_or_else(|| "Logging was not initialized with init_logging.".to_string())?;
+    handle
+        .reload(level)
+        .map_err(|e| format!("Failed to change log level: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_log_level_accepts_known_levels_in_any_case() {
+        assert_eq!(parse_log_level(" Debug "), Ok(LevelFilter::DEBUG));
+        assert_eq!(parse_log_level("off"), Ok(LevelFilter::OFF));
+        assert!(parse_log_level("loud").unwrap_err().contains("'loud'"));
+    }
+
+    // The subscriber is process-wide, so its whole lifecycle is one test
+    #[test]
+    fn level_changes_apply_once_logging_is_initialized() {
+        assert!(set_log_level(LevelFilter::DEBUG).is_err());
+        assert_eq!(current_log_level(), None);
+
+        init_logging(LevelFilter::INFO).unwrap();
+        assert_eq!(current_log_level(), Some(LevelFilter::INFO));
+        set_log_level(LevelFilter::DEBUG).unwrap();
+        assert_eq!(current_log_level(), Some(LevelFilter::DEBUG));
+
+        assert!(init_logging(LevelFilter::WARN).is_err());
+    }
+}
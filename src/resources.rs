@@ -0,0 +1,74 @@
+//! Process resource usage for `/resources`
+
+use std::time::Duration;
+
+/// A snapshot of the process's resource usage
+///
+/// Fields are `None` when the platform does not expose them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceUsage {
+    /// Resident set size in bytes
+    pub rss_bytes: Option<u64>,
+    /// User plus system CPU time consumed so far
+    pub cpu_time: Option<Duration>,
+}
+
+/// Measure the current process
+pub fn current_usage() -> ResourceUsage {
+    ResourceUsage {
+        rss_bytes: rss_bytes(),
+        cpu_time: cpu_time(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn rss_bytes() -> Option<u64> {
+    // The second field of statm is the resident size in pages
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(page_size)
+        .ok()
+        .map(|page_size| pages * page_size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn cpu_time() -> Option<Duration> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec.max(0) as u64)
+            + Duration::from_micros(time.tv_usec.max(0) as u64)
+    };
+    Some(to_duration(usage.ru_utime) + to_duration(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_reports_memory_and_cpu_time() {
+        // Burn a little CPU so the reading is not rounded down to zero
+        let sum: u64 = (0..2_000_000u64).map(std::hint::black_box).sum();
+        assert!(sum > 0);
+
+        let usage = current_usage();
+
+        assert!(usage.rss_bytes.is_some_and(|bytes| bytes > 0));
+        assert!(usage.cpu_time.is_some_and(|time| time > Duration::ZERO));
+    }
+}
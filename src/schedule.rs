@@ -0,0 +1,416 @@
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, NaiveTime};
+use serde::Serialize;
+
+use crate::bot::NotificationLevel;
+
+/// A time-of-day window during which a specific notification level applies
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LevelWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub level: NotificationLevel,
+}
+
+impl LevelWindow {
+    /// Whether `time` falls inside the window; windows whose end is not after
+    /// their start wrap around midnight
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for LevelWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}={:?}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.level
+        )
+    }
+}
+
+/// Parse a schedule such as `00:00-08:00=critical, 08:00-00:00=important`
+pub fn parse_level_schedule(input: &str) -> Result<Vec<LevelWindow>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(parse_level_window)
+        .collect()
+}
+
+fn parse_level_window(entry: &str) -> Result<LevelWindow, String> {
+    let (range, level) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("Expected HH:MM-HH:MM=level, got '{}'", entry))?;
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("Expected HH:MM-HH:MM, got '{}'", range))?;
+
+    Ok(LevelWindow {
+        start: parse_time(start)?,
+        end: parse_time(end)?,
+        level: level.parse()?,
+    })
+}
+
+/// Parse a time of day written as `HH:MM`
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("Invalid time '{}'. Use HH:MM", value.trim()))
+}
+
+/// Resolve the level for `time`, using the first matching window or
+/// `fallback` when none match
+pub fn resolve_level(
+    windows: &[LevelWindow],
+    fallback: &NotificationLevel,
+    time: NaiveTime,
+) -> NotificationLevel {
+    windows
+        .iter()
+        .find(|window| window.contains(time))
+        .map(|window| window.level.clone())
+        .unwrap_or_else(|| fallback.clone())
+}
+
+/// Parse a duration such as `90`, `45s`, `5m`, `2h` or `1h30m`
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return Err("Duration must not be empty".to_string());
+    }
+    if let Ok(secs) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let invalid = || {
+        format!(
+            "Invalid duration '{}'. Use e.g. 45s, 5m, 2h or 1h30m",
+            input
+        )
+    };
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Render a duration compactly, e.g. `1h 5m`, `4m 12s` or `30s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m {}s", minutes, seconds),
+        _ => format!("{}h {}m", hours, minutes),
+    }
+}
+
+/// A daily time window without a level, e.g. the trading window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Whether `time` falls inside the window, wrapping around midnight like
+    /// `LevelWindow::contains`
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Parse a window such as `09:30-16:00`
+pub fn parse_time_window(input: &str) -> Result<TimeWindow, String> {
+    let (start, end) = input
+        .split_once('-')
+        .ok_or_else(|| format!("Expected HH:MM-HH:MM, got '{}'", input.trim()))?;
+    Ok(TimeWindow {
+        start: parse_time(start)?,
+        end: parse_time(end)?,
+    })
+}
+
+/// Describe the trading window, level windows and the next level change as
+/// seen from `now`
+pub fn render_schedule_info(
+    now: DateTime<FixedOffset>,
+    trading_window: Option<&TimeWindow>,
+    level_windows: &[LevelWindow],
+    fallback: &NotificationLevel,
+) -> String {
+    let time = now.time();
+    let mut lines = vec![
+        format!("Time zone: UTC{}", now.offset()),
+        format!("Current time: {}", now.format("%Y-%m-%d %H:%M")),
+        String::new(),
+    ];
+
+    lines.push(match trading_window {
+        Some(window) if window.contains(time) => {
+            format!(
+                "Trading window: {} (open, closes at {})",
+                window,
+                window.end.format("%H:%M")
+            )
+        }
+        Some(window) => format!(
+            "Trading window: {} (closed, opens at {})",
+            window,
+            window.start.format("%H:%M")
+        ),
+        None => "Trading window: none (trades whenever running)".to_string(),
+    });
+
+    let current = resolve_level(level_windows, fallback, time);
+    lines.push(String::new());
+    if level_windows.is_empty() {
+        lines.push(format!(
+            "Level windows: none (level is {:?} all day)",
+            current
+        ));
+        return lines.join("\n");
+    }
+
+    lines.push("Level windows:".to_string());
+    for window in level_windows {
+        let marker = if window.contains(time) {
+            " (active)"
+        } else {
+            ""
+        };
+        lines.push(format!("{}{}", window, marker));
+    }
+    lines.push(format!("Outside these windows: {:?}", fallback));
+
+    match next_level_change(level_windows, fallback, time) {
+        Some((at, level)) => lines.push(format!(
+            "Next change: {:?} -> {:?} at {}",
+            current,
+            level,
+            at.format("%H:%M")
+        )),
+        None => lines.push(format!("Level stays {:?} all day", current)),
+    }
+    lines.join("\n")
+}
+
+/// The first window boundary after `time` where the resolved level changes
+fn next_level_change(
+    windows: &[LevelWindow],
+    fallback: &NotificationLevel,
+    time: NaiveTime,
+) -> Option<(NaiveTime, NotificationLevel)> {
+    let current = resolve_level(windows, fallback, time);
+    let mut boundaries: Vec<NaiveTime> = windows
+        .iter()
+        .flat_map(|window| [window.start, window.end])
+        .collect();
+    // Order by how far ahead each boundary is, wrapping past midnight
+    boundaries.sort_by_key(|boundary| (*boundary - time).num_seconds().rem_euclid(86_400));
+    boundaries
+        .into_iter()
+        .filter(|boundary| *boundary != time)
+        .map(|boundary| (boundary, resolve_level(windows, fallback, boundary)))
+        .find(|(_, level)| *level != current)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        parse_time(value).unwrap()
+    }
+
+    #[test]
+    fn level_schedule_parses_every_window() {
+        let windows = parse_level_schedule("00:00-08:00=critical, 22:00-02:00=none,").unwrap();
+
+        assert_eq!(
+            windows,
+            vec![
+                LevelWindow {
+                    start: time("00:00"),
+                    end: time("08:00"),
+                    level: NotificationLevel::Critical,
+                },
+                LevelWindow {
+                    start: time("22:00"),
+                    end: time("02:00"),
+                    level: NotificationLevel::None,
+                },
+            ]
+        );
+        assert_eq!(windows[1].to_string(), "22:00-02:00=None");
+    }
+
+    #[test]
+    fn level_schedule_rejects_malformed_windows() {
+        assert!(parse_level_schedule("08:00=critical").is_err());
+        assert!(parse_level_schedule("08:00-25:00=critical").is_err());
+        assert!(parse_level_schedule("08:00-09:00").is_err());
+        assert!(parse_level_schedule("08:00-09:00=loud").is_err());
+    }
+
+    #[test]
+    fn windows_wrap_around_midnight() {
+        let windows = parse_level_schedule("22:00-02:00=none, 08:00-09:00=critical").unwrap();
+        let fallback = NotificationLevel::Important;
+
+        assert_eq!(
+            resolve_level(&windows, &fallback, time("23:30")),
+            NotificationLevel::None
+        );
+        assert_eq!(
+            resolve_level(&windows, &fallback, time("01:59")),
+            NotificationLevel::None
+        );
+        assert_eq!(
+            resolve_level(&windows, &fallback, time("02:00")),
+            NotificationLevel::Important
+        );
+        assert_eq!(
+            resolve_level(&windows, &fallback, time("08:00")),
+            NotificationLevel::Critical
+        );
+        assert_eq!(
+            resolve_level(&windows, &fallback, time("09:00")),
+            NotificationLevel::Important
+        );
+    }
+
+    #[test]
+    fn parse_duration_accepts_units_and_bare_seconds() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 1H30m "), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        for invalid in ["", "5x", "m", "10m5"] {
+            assert!(parse_duration(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn format_duration_keeps_the_two_largest_units() {
+        assert_eq!(format_duration(Duration::from_secs(30)), "30s");
+        assert_eq!(format_duration(Duration::from_secs(252)), "4m 12s");
+        assert_eq!(format_duration(Duration::from_secs(3930)), "1h 5m");
+    }
+
+    #[test]
+    fn schedule_info_marks_the_active_window_and_next_change() {
+        let windows = parse_level_schedule("22:00-02:00=none, 08:00-09:00=critical").unwrap();
+        let now = FixedOffset::east_opt(7200)
+            .unwrap()
+            .with_ymd_and_hms(2024, 1, 2, 8, 30, 0)
+            .unwrap();
+
+        let trading = parse_time_window("09:30-16:00").unwrap();
+
+        let info =
+            render_schedule_info(now, Some(&trading), &windows, &NotificationLevel::Important);
+
+        assert!(info.contains("Time zone: UTC+02:00"), "{}", info);
+        assert!(
+            info.contains("Trading window: 09:30-16:00 (closed, opens at 09:30)"),
+            "{}",
+            info
+        );
+        assert!(info.contains("Current time: 2024-01-02 08:30"), "{}", info);
+        assert!(info.contains("22:00-02:00=None\n"), "{}", info);
+        assert!(info.contains("08:00-09:00=Critical (active)"), "{}", info);
+        assert!(
+            info.contains("Outside these windows: Important"),
+            "{}",
+            info
+        );
+        assert!(
+            info.contains("Next change: Critical -> Important at 09:00"),
+            "{}",
+            info
+        );
+    }
+
+    #[test]
+    fn schedule_info_without_windows_reports_a_fixed_level() {
+        let now = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2024, 1, 2, 12, 0, 0)
+            .unwrap();
+
+        let info = render_schedule_info(now, None, &[], &NotificationLevel::All);
+
+        assert!(info.contains("Time zone: UTC+00:00"), "{}", info);
+        assert!(
+            info.contains("Trading window: none (trades whenever running)"),
+            "{}",
+            info
+        );
+        assert!(
+            info.ends_with("Level windows: none (level is All all day)"),
+            "{}",
+            info
+        );
+    }
+
+    #[test]
+    fn trading_windows_wrap_around_midnight() {
+        let window = parse_time_window("22:00-02:00").unwrap();
+
+        assert!(window.contains(time("23:30")));
+        assert!(window.contains(time("01:59")));
+        assert!(!window.contains(time("02:00")));
+        assert!(!window.contains(time("12:00")));
+        assert!(parse_time_window("09:30").is_err());
+    }
+}
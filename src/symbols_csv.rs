@@ -0,0 +1,126 @@
+//! CSV import and export of the symbols configuration
+
+use std::collections::HashSet;
+
+use crate::traits::{validate_import_entry, SymbolConfig};
+
+/// Parse a symbols CSV whose header row names `SymbolConfig` fields
+///
+/// Every row is parsed and validated before anything is returned, so the
+/// errors cover the whole document and carry their line numbers.
+pub fn parse_symbols_csv(content: &str) -> Result<Vec<SymbolConfig>, Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| vec![format!("Invalid header row: {}", e)])?
+        .clone();
+
+    let mut symbols = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                errors.push(format!("line {}: {}", line, describe_error(&e)));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+
+        match record.deserialize::<SymbolConfig>(Some(&headers)) {
+            Ok(symbol) => match validate_import_entry(&symbol, &mut seen) {
+                Ok(()) => symbols.push(symbol),
+                Err(reason) => errors.push(format!("line {}: {}", line, reason)),
+            },
+            Err(e) => errors.push(format!("line {}: {}", line, describe_error(&e))),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(symbols)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Render symbols as CSV with the header row `parse_symbols_csv` expects
+pub fn symbols_to_csv(symbols: &[SymbolConfig]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for symbol in symbols {
+        writer
+            .serialize(symbol)
+            .map_err(|e| format!("Failed to write CSV: {}", e))?;
+    }
+    let content = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {}", e))?;
+    String::from_utf8(content).map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+/// Describe a CSV error without the position prefix the crate adds
+fn describe_error(e: &csv::Error) -> String {
+    match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("expected {} fields, found {}", expected_len, len),
+        _ => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "symbol,entry_amount,exit_amount,entry_threshold,exit_threshold,enabled";
+
+    #[test]
+    fn malformed_rows_are_reported_with_their_line() {
+        let content = format!(
+            "{}\nBTCUSDT,10,10,1,1,true\nETHUSDT,ten,10,1,1,true\nSOLUSDT,10\n",
+            HEADER
+        );
+
+        let errors = parse_symbols_csv(&content).unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("line 3:"), "{}", errors[0]);
+        assert_eq!(errors[1], "line 4: expected 6 fields, found 2");
+    }
+
+    #[test]
+    fn duplicate_symbols_are_rejected() {
+        let content = format!(
+            "{}\nBTCUSDT,10,10,1,1,true\nBTCUSDT,5,5,1,1,false\n",
+            HEADER
+        );
+
+        assert_eq!(
+            parse_symbols_csv(&content).unwrap_err(),
+            ["line 3: duplicate symbol BTCUSDT"]
+        );
+    }
+
+    #[test]
+    fn exported_symbols_parse_back_unchanged() {
+        let content = format!(
+            "{}\nBTCUSDT,10,5,1,-1,true\nETHUSDT,2.5,2.5,0.5,0.5,false\n",
+            HEADER
+        );
+        let symbols = parse_symbols_csv(&content).unwrap();
+
+        let exported = symbols_to_csv(&symbols).unwrap();
+        let reparsed = parse_symbols_csv(&exported).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&reparsed).unwrap(),
+            serde_json::to_value(&symbols).unwrap()
+        );
+        assert_eq!(reparsed.len(), 2);
+    }
+}
@@ -1,6 +1,11 @@
-use std::error::Error;
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use teloxide::{types::ChatId, Bot};
 
@@ -14,20 +19,199 @@ pub struct SymbolConfig {
     pub exit_amount: f64,
     pub entry_threshold: f64,
     pub exit_threshold: f64,
+    /// Disabled symbols stay in the configuration but are not traded
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Largest acceptable slippage for this symbol's orders; strategies
+    /// should reject trades that would exceed it
+    #[serde(default)]
+    pub max_slippage: Option<f64>,
+    /// The exchange's lot size; strategies should pass order sizes through
+    /// `round_to_step` so they are not rejected
+    #[serde(default)]
+    pub size_step: Option<f64>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl SymbolConfig {
+    /// Check that the symbol is named and its amounts are usable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.symbol.trim().is_empty() {
+            return Err("symbol must not be empty".to_string());
+        }
+        for (name, value) in [
+            ("entry_amount", self.entry_amount),
+            ("exit_amount", self.exit_amount),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{} must be a non-negative number", name));
+            }
+        }
+        for (name, value) in [
+            ("entry_threshold", self.entry_threshold),
+            ("exit_threshold", self.exit_threshold),
+        ] {
+            if !value.is_finite() {
+                return Err(format!("{} must be a number", name));
+            }
+        }
+        if let Some(max_slippage) = self.max_slippage {
+            if !max_slippage.is_finite() || max_slippage < 0.0 {
+                return Err("max_slippage must be a non-negative number".to_string());
+            }
+        }
+        if let Some(size_step) = self.size_step {
+            if !size_step.is_finite() || size_step <= 0.0 {
+                return Err("size_step must be a positive number".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate one entry of an imported symbols configuration, rejecting a
+/// symbol that already appeared earlier in the same import
+pub fn validate_import_entry(
+    symbol: &SymbolConfig,
+    seen: &mut HashSet<String>,
+) -> Result<(), String> {
+    symbol.validate()?;
+    if !seen.insert(symbol.symbol.clone()) {
+        return Err(format!("duplicate symbol {}", symbol.symbol));
+    }
+    Ok(())
+}
+
+/// Round `amount` down to a whole number of `step`s, e.g. `0.1234` with a
+/// step of `0.01` becomes `0.12`.
+///
+/// Rounding down never orders more than was intended. Amounts that are a
+/// multiple of `step` up to floating point noise are kept as they are. A
+/// `step` that is not positive leaves `amount` unchanged.
+pub fn round_to_step(amount: f64, step: f64) -> f64 {
+    if !step.is_finite() || step <= 0.0 {
+        return amount;
+    }
+    let steps = amount / step;
+    let rounded = steps.round();
+    if (steps - rounded).abs() < 1e-9 {
+        rounded * step
+    } else {
+        steps.floor() * step
+    }
+}
+
+/// A recorded market data point, as stored in replay datasets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketPoint {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub price: f64,
+    #[serde(default)]
+    pub volume: f64,
+}
+
+/// A recorded dataset replayed through the strategy by `/replay`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarketData {
+    pub points: Vec<MarketPoint>,
+}
+
+/// What a strategy supports, so dashboards can hide commands that would do
+/// nothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// `get_positions` reports real positions
+    pub positions: bool,
+    /// `get_status` reports realized and unrealized PnL
+    pub pnl: bool,
+    /// The strategy reads tunable parameters from the symbols configuration
+    pub params: bool,
+    /// The strategy handles commands beyond the built-in set
+    pub custom_commands: bool,
+    /// `set_risk_param` applies the parameters listed by `risk_params`
+    pub risk: bool,
+    /// `get_open_orders` lists the orders resting on the exchange and
+    /// `cancel_all_orders` cancels them
+    pub orders: bool,
+    /// `set_endpoint` switches the exchange API endpoint
+    pub endpoint: bool,
+}
+
+/// Which exchange account a strategy trades, so operators of several bots
+/// can tell them apart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub exchange: String,
+    pub account_label: String,
+    /// Whether orders go to the exchange's test environment
+    pub testnet: bool,
+}
+
+/// A global risk setting of a strategy, such as maximum drawdown or leverage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskParam {
+    pub key: String,
+    pub value: f64,
+    /// What the parameter controls, shown by `/risk`
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Snapshot of a strategy's state as reported to `/status` and `/update`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Free-form headline supplied by the strategy
+    pub summary: String,
+    pub open_positions: usize,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// An open position held by the strategy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionInfo {
+    pub symbol: String,
+    pub size: f64,
+    pub entry_price: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// An order resting on the exchange, as listed by `/orders`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderInfo {
+    pub id: String,
+    pub symbol: String,
+    /// e.g. `buy` or `sell`
+    pub side: String,
+    pub price: f64,
+    pub quantity: f64,
+    /// The exchange's order status, e.g. `open` or `partially_filled`
+    pub status: String,
 }
 
 /// Trait that any trading bot must implement to work with the Telegram
 /// interface
 #[async_trait]
 pub trait TradingBot: Send + Sync {
-    /// Associated error type for operations
-    type Error: std::fmt::Display + Send + Sync + 'static;
+    /// Associated error type for operations. Errors implementing
+    /// `std::error::Error` keep their `source()` chain in the runner's
+    /// reports; `String` works too.
+    ///
+    /// This used to require only `Display`. A type that implements nothing
+    /// more needs an `std::error::Error` impl, or its values can be mapped
+    /// to `BotError::Message(error.to_string())`.
+    type Error: std::fmt::Display + Into<Box<dyn Error + Send + Sync>> + Send + Sync + 'static;
 
     /// Initializes a new trading bot instance.
     ///
     /// # Arguments
     ///
-    /// * `interval_seconds` - The interval in seconds between strategy executions
+    /// * `interval_seconds` - The interval in seconds between strategy
+    ///   executions
     ///
     /// # Returns
     ///
@@ -37,8 +221,38 @@ pub trait TradingBot: Send + Sync {
     where
         Self: Sized;
 
+    /// Confirms the strategy is able to trade, e.g. that API credentials are
+    /// present, before the runner starts executing it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` when the strategy is ready
+    /// * `Err(Self::Error)` describing why the strategy cannot start
+    async fn readiness_check(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Measures how long the exchange API takes to answer a lightweight
+    /// request, for `/exchange`.
+    ///
+    /// The default times `readiness_check`; override it with a real round
+    /// trip to the exchange.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Duration)` with the measured latency
+    /// * `Err(Self::Error)` if the exchange is unreachable
+    async fn ping_exchange(&self) -> Result<Duration, Self::Error> {
+        let started = std::time::Instant::now();
+        self.readiness_check().await?;
+        Ok(started.elapsed())
+    }
+
     /// Executes the core trading strategy logic.
     ///
+    /// Strategies that process symbols in parallel should limit themselves to
+    /// `BotState::strategy_concurrency` symbols at a time.
+    ///
     /// # Arguments
     ///
     /// * `bot_state` - Shared mutable state wrapper controlling bot execution
@@ -55,6 +269,172 @@ pub trait TradingBot: Send + Sync {
         telegram_bot: Bot,
         chat_id: ChatId,
     ) -> Result<(), Self::Error>;
+
+    /// Runs one tick against recorded market data instead of live prices.
+    ///
+    /// Called by `/replay` with `BotState::dry_run` set, so no orders may be
+    /// placed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(String))` describing what the strategy would have done
+    /// * `Ok(None)` if the strategy does not support replays (the default)
+    /// * `Err(Self::Error)` if the replayed tick fails
+    async fn execute_strategy_with_data(
+        &mut self,
+        _data: &MarketData,
+        _bot_state: std::sync::Arc<tokio::sync::Mutex<BotState>>,
+        _telegram_bot: Bot,
+        _chat_id: ChatId,
+    ) -> Result<Option<String>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Collects the current status of the strategy.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(StatusReport)` describing the strategy's current state
+    /// * `Err(Self::Error)` if the status could not be gathered
+    async fn get_status(&self) -> Result<StatusReport, Self::Error> {
+        Ok(StatusReport {
+            summary: "Bot is running. Use /update for detailed status.".to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Applies symbol edits that were kept in memory instead of being written
+    /// to the configuration file (see `/persist`).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the strategy uses the new symbols
+    /// * `Err(Self::Error)` if the symbols could not be applied
+    async fn reload_symbols(&mut self, _symbols: Vec<SymbolConfig>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Describes which optional features the strategy implements.
+    ///
+    /// The default declares none of them.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Identifies the exchange account the strategy trades.
+    ///
+    /// The default reports an empty exchange, which `/account` shows as
+    /// unknown.
+    fn account_info(&self) -> AccountInfo {
+        AccountInfo::default()
+    }
+
+    /// Reports the strategy's numeric metrics by name, e.g. drawdown or
+    /// exposure, for `/addrule` to watch.
+    ///
+    /// The default reports none.
+    fn status_columns(&self) -> BTreeMap<String, f64> {
+        BTreeMap::new()
+    }
+
+    /// Reports the symbols whose trades failed during the last
+    /// `execute_strategy` call, with the error. The runner disables a symbol
+    /// that errors on `symbol_error_limit` ticks in a row.
+    ///
+    /// The default reports none.
+    fn symbol_errors(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// Lists the strategy's global risk parameters and their current values.
+    ///
+    /// `/setrisk` only accepts the keys listed here. The default declares
+    /// none.
+    fn risk_params(&self) -> Vec<RiskParam> {
+        Vec::new()
+    }
+
+    /// Changes one of the risk parameters declared by `risk_params`.
+    ///
+    /// Only called when `capabilities` declares `risk`; otherwise `/setrisk`
+    /// reports that the strategy does not support it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - A key returned by `risk_params`
+    /// * `value` - The new value
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the strategy uses the new value
+    /// * `Err(Self::Error)` if the value is rejected
+    async fn set_risk_param(&mut self, _key: &str, _value: f64) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Points the strategy at another exchange API endpoint, e.g. to switch
+    /// between testnet and mainnet, reconnecting before it returns.
+    ///
+    /// Only called when `capabilities` declares `endpoint`; otherwise
+    /// `/endpoint` reports that the strategy does not support it. It only
+    /// passes URLs that `validate_endpoint` accepts.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the strategy is connected to `url`
+    /// * `Err(Self::Error)` if the endpoint cannot be used
+    async fn set_endpoint(&mut self, _url: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Lists the positions currently held by the strategy.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<PositionInfo>)` with one entry per open position
+    /// * `Err(Self::Error)` if positions could not be fetched
+    async fn get_positions(&self) -> Result<Vec<PositionInfo>, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    /// Lists the orders the strategy has resting on the exchange.
+    ///
+    /// Only called when `capabilities` declares `orders`; otherwise `/orders`
+    /// reports that the strategy does not support it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<OrderInfo>)` with one entry per open order
+    /// * `Err(Self::Error)` if orders could not be fetched
+    async fn get_open_orders(&self) -> Result<Vec<OrderInfo>, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    /// Cancels every order returned by `get_open_orders`, for `/cancelall`.
+    ///
+    /// Only called when `capabilities` declares `orders`; otherwise
+    /// `/cancelall` reports that the strategy does not support it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` with the number of orders cancelled
+    /// * `Err(Self::Error)` if the orders could not be cancelled
+    async fn cancel_all_orders(&mut self) -> Result<usize, Self::Error> {
+        Ok(0)
+    }
+
+    /// Renders a `StatusReport` into the text sent to Telegram.
+    ///
+    /// The default sends the report's `summary` as it is. Override this to
+    /// take full control of the layout; chunking and delivery are still
+    /// handled by the crate.
+    ///
+    /// # Arguments
+    ///
+    /// * `report` - The report returned by `get_status`
+    fn format_status(&self, report: &StatusReport) -> String {
+        report.summary.clone()
+    }
 }
 
 /// Configuration manager trait for handling symbol configurations
@@ -108,3 +488,115 @@ pub trait ConfigManager {
     /// * `Err(Self::Error)` if the operation fails
     async fn remove_symbol(&self, symbol_name: &str) -> Result<bool, Self::Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DefaultBot;
+
+    #[async_trait]
+    impl TradingBot for DefaultBot {
+        type Error = String;
+
+        async fn new(_interval_seconds: u64) -> Result<Self, Self::Error> {
+            Ok(DefaultBot)
+        }
+
+        async fn execute_strategy(
+            &mut self,
+            _bot_state: std::sync::Arc<tokio::sync::Mutex<BotState>>,
+            _telegram_bot: Bot,
+            _chat_id: ChatId,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn default_format_status_sends_the_summary_unchanged() {
+        let report = StatusReport {
+            summary: "Running".to_string(),
+            open_positions: 3,
+            realized_pnl: 1.5,
+            unrealized_pnl: -0.25,
+        };
+        assert_eq!(DefaultBot.format_status(&report), "Running");
+    }
+
+    #[tokio::test]
+    async fn default_ping_times_the_readiness_check() {
+        let latency = DefaultBot.ping_exchange().await.unwrap();
+        assert!(latency < Duration::from_secs(1), "{:?}", latency);
+    }
+
+    #[test]
+    fn max_slippage_defaults_to_none_and_must_not_be_negative() {
+        let mut symbol: SymbolConfig = serde_json::from_str(
+            r#"{"symbol":"BTCUSDT","entry_amount":10.0,"exit_amount":10.0,
+                "entry_threshold":1.0,"exit_threshold":1.0}"#,
+        )
+        .unwrap();
+        assert_eq!(symbol.max_slippage, None);
+        assert!(symbol.validate().is_ok());
+
+        symbol.max_slippage = Some(-0.01);
+        assert_eq!(
+            symbol.validate(),
+            Err("max_slippage must be a non-negative number".to_string())
+        );
+        symbol.max_slippage = Some(0.005);
+        assert!(symbol.validate().is_ok());
+    }
+
+    #[test]
+    fn round_to_step_rounds_down_to_whole_steps() {
+        let cases = [
+            (0.1234, 0.01, 0.12),
+            (0.1299, 0.01, 0.12),
+            (1.0, 0.1, 1.0),
+            (0.3, 0.1, 0.3),
+            (7.0, 5.0, 5.0),
+            (4.0, 5.0, 0.0),
+            (123.456, 0.001, 123.456),
+        ];
+        for (amount, step, rounded) in cases {
+            let result = round_to_step(amount, step);
+            assert!(
+                (result - rounded).abs() < 1e-9,
+                "round_to_step({}, {}) = {}, expected {}",
+                amount,
+                step,
+                result,
+                rounded
+            );
+        }
+    }
+
+    #[test]
+    fn round_to_step_ignores_steps_that_are_not_positive() {
+        for step in [0.0, -0.01, f64::NAN, f64::INFINITY] {
+            assert_eq!(round_to_step(0.1234, step), 0.1234);
+        }
+    }
+
+    #[test]
+    fn size_step_defaults_to_none_and_must_be_positive() {
+        let mut symbol: SymbolConfig = serde_json::from_str(
+            r#"{"symbol":"BTCUSDT","entry_amount":10.0,"exit_amount":10.0,
+                "entry_threshold":1.0,"exit_threshold":1.0}"#,
+        )
+        .unwrap();
+        assert_eq!(symbol.size_step, None);
+
+        for step in [0.0, -0.5, f64::NAN] {
+            symbol.size_step = Some(step);
+            assert_eq!(
+                symbol.validate(),
+                Err("size_step must be a positive number".to_string())
+            );
+        }
+        symbol.size_step = Some(0.001);
+        assert!(symbol.validate().is_ok());
+    }
+}